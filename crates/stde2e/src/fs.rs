@@ -1,34 +1,99 @@
 use std::{
-    fs::{File as StdFile, OpenOptions as StdOpenOptions},
-    os::fd::AsRawFd,
+    fs::{File as StdFile, Metadata, OpenOptions as StdOpenOptions},
+    io::{Seek, SeekFrom},
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    path::Path,
 };
 
-use trace2e_client::p2m::local_enroll;
+use trace2e_client::{
+    p2m::{close, dup, local_enroll, truncate},
+    primitives::OpenMode,
+};
+
+use crate::io::mediated_io;
 
-pub struct File;
+/// Traced counterpart of [`std::fs::File`], whose reads and writes are always mediated.
+///
+/// The file is unenrolled when dropped.
+#[derive(Debug)]
+pub struct File(StdFile);
 
 impl File {
-    pub fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<StdFile> {
+    /// Enrolls a file opened at the given path.
+    fn enroll(path: &Path, file: StdFile, mode: OpenMode) -> std::io::Result<File> {
+        local_enroll(path, file.as_raw_fd(), mode)?;
+        Ok(File(file))
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
         let path_ref = path.as_ref();
         let file = StdFile::open(path_ref)?;
-        local_enroll(path_ref.display().to_string(), file.as_raw_fd());
-        Ok(file)
+        File::enroll(path_ref, file, OpenMode::default())
     }
-    pub fn create<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<StdFile> {
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
         let path_ref = path.as_ref();
         let file = StdFile::create(path_ref)?;
-        local_enroll(path_ref.display().to_string(), file.as_raw_fd());
-        Ok(file)
+        let mode = OpenMode { write: true, truncate: true, ..Default::default() };
+        File::enroll(path_ref, file, mode)
     }
-    pub fn create_new<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<StdFile> {
+    pub fn create_new<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
         let path_ref = path.as_ref();
         let file = StdFile::create_new(path_ref)?;
-        local_enroll(path_ref.display().to_string(), file.as_raw_fd());
-        Ok(file)
+        let mode = OpenMode { write: true, create_new: true, ..Default::default() };
+        File::enroll(path_ref, file, mode)
     }
     pub fn options() -> OpenOptions {
         OpenOptions::new()
     }
+    /// Duplicates the file handle, the duplicate is enrolled with the same resource.
+    pub fn try_clone(&self) -> std::io::Result<File> {
+        let clone = self.0.try_clone()?;
+        dup(self.0.as_raw_fd(), clone.as_raw_fd())?;
+        Ok(File(clone))
+    }
+    /// Truncates or extends the file, truncating it to zero discards its provenance.
+    pub fn set_len(&self, size: u64) -> std::io::Result<()> {
+        self.0.set_len(size)?;
+        if size == 0 {
+            truncate(self.0.as_raw_fd())?;
+        }
+        Ok(())
+    }
+    pub fn metadata(&self) -> std::io::Result<Metadata> {
+        self.0.metadata()
+    }
+    pub fn sync_all(&self) -> std::io::Result<()> {
+        self.0.sync_all()
+    }
+    pub fn sync_data(&self) -> std::io::Result<()> {
+        self.0.sync_data()
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        close(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for File {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+mediated_io!(File);
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.0.seek(pos)
+    }
 }
 
 pub struct OpenOptions {
@@ -89,10 +154,15 @@ impl OpenOptions {
         self.create_new = create_new;
         self
     }
-    pub fn open<P: AsRef<std::path::Path>>(&self, path: P) -> std::io::Result<std::fs::File> {
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> std::io::Result<File> {
         let path_ref = path.as_ref();
         let file = self.options.open(path_ref)?;
-        local_enroll(path_ref.display().to_string(), file.as_raw_fd());
-        Ok(file)
+        let mode = OpenMode {
+            write: self.write,
+            append: self.append,
+            truncate: self.truncate,
+            create_new: self.create_new,
+        };
+        File::enroll(path_ref, file, mode)
     }
 }
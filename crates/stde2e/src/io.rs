@@ -1,234 +1,307 @@
-use trace2e_client::p2m::{io_report, io_request};
+//! I/O traits and helpers of the traced types.
+//!
+//! The traced types of this crate implement the traits of `std::io`, re-exported here, and
+//! mediate each of their reads and writes, whichever trait is in scope.
+use std::{
+    net::SocketAddr,
+    os::fd::{AsRawFd, BorrowedFd, RawFd},
+};
+
+use trace2e_client::p2m::{
+    clear_flow, datagram_io_request, declare_flow, io_report, io_request, io_request_batch,
+};
 use trace2e_client::primitives::Flow;
+use tracing::warn;
+
+pub use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+
+/// Reports a completed operation, with the offset of the transferred region if the file
+/// descriptor is seekable.
+fn report(
+    fd: RawFd,
+    grant_id: u128,
+    flow: Flow,
+    result: bool,
+    bytes: usize,
+) -> std::io::Result<()> {
+    Ok(io_report(fd, grant_id, flow.into(), result, bytes as u64, transfer_offset(fd, bytes))?)
+}
 
-pub struct BufReader<R>(std::io::BufReader<R>);
-
-pub trait Read: std::io::Read {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        std::io::Read::read(self, buf)
-    }
-
-    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
-        std::io::Read::read_vectored(self, bufs)
-    }
-
-    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
-        std::io::Read::read_to_end(self, buf)
-    }
-
-    fn read_to_string(&mut self, buf: &mut String) -> std::io::Result<usize> {
-        std::io::Read::read_to_string(self, buf)
-    }
-
-    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
-        std::io::Read::read_exact(self, buf)
-    }
-
-    fn by_ref(&mut self) -> &mut Self
-    where
-        Self: Sized,
-    {
-        std::io::Read::by_ref(self)
-    }
-
-    fn bytes(self) -> std::io::Bytes<Self>
-    where
-        Self: Sized,
-    {
-        std::io::Read::bytes(self)
-    }
-
-    fn chain<R: std::io::Read>(self, next: R) -> std::io::Chain<Self, R>
-    where
-        Self: Sized,
-    {
-        std::io::Read::chain(self, next)
-    }
+/// Returns the offset of the last `bytes` bytes transferred on a file descriptor, or None if
+/// the file descriptor is not seekable (e.g., a socket).
+///
+/// The offset is computed after the operation, so that writes in append mode are located.
+fn transfer_offset(fd: RawFd, bytes: usize) -> Option<u64> {
+    // SAFETY: the file descriptor is held open by the reader or writer during the call
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    rustix::fs::tell(fd).ok()?.checked_sub(bytes as u64)
+}
 
-    fn take(self, limit: u64) -> std::io::Take<Self>
-    where
-        Self: Sized,
-    {
-        std::io::Read::take(self, limit)
-    }
+/// Scope in which the writes on a file descriptor derive from declared sources only.
+///
+/// The declaration is cleared when the scope is dropped.
+pub struct FlowScope {
+    fd: RawFd,
 }
 
-impl<R: std::io::Read + std::os::fd::AsRawFd> Read for R {
-    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Input.into()) {
-            let result = std::io::Read::read(self, buf);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
+impl Drop for FlowScope {
+    fn drop(&mut self) {
+        clear_flow(self.fd);
     }
+}
 
-    fn read_vectored(&mut self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Input.into()) {
-            let result = std::io::Read::read_vectored(self, bufs);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
-    }
+/// Declares that the data written on `destination` derives from `sources` only, instead of
+/// everything the process has read, until the returned scope is dropped.
+pub fn derive_from(
+    destination: &impl AsRawFd,
+    sources: &[&dyn AsRawFd],
+) -> std::io::Result<FlowScope> {
+    let fd = destination.as_raw_fd();
+    declare_flow(fd, sources.iter().map(|source| source.as_raw_fd()).collect())?;
+    Ok(FlowScope { fd })
+}
 
-    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Input.into()) {
-            let result = std::io::Read::read_to_end(self, buf);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
-    }
+/// Grant of an operation on a file descriptor, obtained along with others by [`request_all`].
+///
+/// The operation is reported with [`Grant::report`], a grant dropped without being reported
+/// is released as a failed operation.
+pub struct Grant {
+    fd: RawFd,
+    flow: Flow,
+    grant_id: Option<u128>,
+}
 
-    fn read_to_string(&mut self, buf: &mut String) -> std::io::Result<usize> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Input.into()) {
-            let result = std::io::Read::read_to_string(self, buf);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+impl Grant {
+    /// Reports the outcome of the granted operation and the number of bytes it transferred.
+    pub fn report(mut self, result: bool, bytes: usize) -> std::io::Result<()> {
+        match self.grant_id.take() {
+            Some(grant_id) => report(self.fd, grant_id, self.flow, result, bytes),
+            None => Ok(()),
         }
     }
+}
 
-    fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Input.into()) {
-            let result = std::io::Read::read_exact(self, buf);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+impl Drop for Grant {
+    fn drop(&mut self) {
+        if let Some(grant_id) = self.grant_id.take()
+            && let Err(error) = io_report(self.fd, grant_id, self.flow.into(), false, 0, None)
+        {
+            warn!(fd = self.fd, %error, "[trace2e] Unused grant not released");
         }
     }
 }
 
-pub trait Write: std::io::Write {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        std::io::Write::write(self, buf)
-    }
-    fn flush(&mut self) -> std::io::Result<()> {
-        std::io::Write::flush(self)
-    }
-
-    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
-        std::io::Write::write_vectored(self, bufs)
-    }
-
-    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
-        std::io::Write::write_all(self, buf)
-    }
-
-    fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> std::io::Result<()> {
-        std::io::Write::write_fmt(self, fmt)
-    }
-
-    fn by_ref(&mut self) -> &mut Self
-    where
-        Self: Sized,
-    {
-        std::io::Write::by_ref(self)
-    }
+/// Requests the grants of operations on several file descriptors at once.
+///
+/// Returns the grant of each operation in request order, or an error of the kind matching
+/// the refusal, which embeds its [`P2mError`](trace2e_client::error::P2mError). Operations
+/// that conflict with each other, such as a read and a write of the same file, are not granted
+/// together.
+pub fn request_all(requests: &[(&dyn AsRawFd, Flow)]) -> Vec<std::io::Result<Grant>> {
+    let batch: Vec<(RawFd, i32)> =
+        requests.iter().map(|(fd, flow)| (fd.as_raw_fd(), (*flow).into())).collect();
+    io_request_batch(&batch)
+        .into_iter()
+        .zip(requests)
+        .map(|(result, &(fd, flow))| match result {
+            Ok(grant_id) => Ok(Grant { fd: fd.as_raw_fd(), flow, grant_id: Some(grant_id) }),
+            Err(error) => Err(error.into()),
+        })
+        .collect()
 }
 
-impl<W: std::io::Write + std::os::fd::AsRawFd> Write for W {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Output.into()) {
-            let result = std::io::Write::write(self, buf);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
-    }
-
-    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Output.into()) {
-            let result = std::io::Write::write_vectored(self, bufs);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
-    }
-
-    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Output.into()) {
-            let result = std::io::Write::write_all(self, buf);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
+/// Writes a whole buffer to each of several writers, requesting the grants of all the writes
+/// at once.
+///
+/// Returns the outcome of each write, in order. A write that is not granted is not attempted.
+pub fn write_all_each<W: AsRawFd>(writers: &mut [W], buf: &[u8]) -> Vec<std::io::Result<()>> {
+    let requests: Vec<(&dyn AsRawFd, Flow)> =
+        writers.iter().map(|writer| (writer as &dyn AsRawFd, Flow::Output)).collect();
+    let grants = request_all(&requests);
+    writers
+        .iter()
+        .zip(grants)
+        .map(|(writer, grant)| {
+            let grant = grant?;
+            // Granted already, the write is not mediated again
+            let result = write_all_unmediated(writer.as_raw_fd(), buf);
+            let bytes = if result.is_ok() { buf.len() } else { 0 };
+            if let Err(error) = grant.report(result.is_ok(), bytes) {
+                warn!(fd = writer.as_raw_fd(), %error, "[trace2e] Write not reported");
+            }
             result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
-    }
+        })
+        .collect()
+}
 
-    fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> std::io::Result<()> {
-        if let Ok(grant_id) = io_request(self.as_raw_fd(), Flow::Output.into()) {
-            let result = std::io::Write::write_fmt(self, fmt);
-            io_report(self.as_raw_fd(), grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+/// Writes a whole buffer to a file descriptor.
+fn write_all_unmediated(fd: RawFd, mut buf: &[u8]) -> std::io::Result<()> {
+    // SAFETY: the file descriptor is held open by the writer during the call
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    while !buf.is_empty() {
+        match rustix::io::write(fd, buf) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(bytes) => buf = &buf[bytes..],
+            Err(rustix::io::Errno::INTR) => (),
+            Err(error) => return Err(error.into()),
         }
     }
+    Ok(())
 }
 
-pub trait BufRead: std::io::BufRead {
-    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
-        std::io::BufRead::fill_buf(self)
-    }
-
-    fn consume(&mut self, amt: usize) {
-        std::io::BufRead::consume(self, amt)
-    }
-
-    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> std::io::Result<usize> {
-        std::io::BufRead::read_until(self, byte, buf)
-    }
-
-    fn read_line(&mut self, buf: &mut String) -> std::io::Result<usize> {
-        std::io::BufRead::read_line(self, buf)
-    }
-
-    fn split(self, byte: u8) -> std::io::Split<Self>
-    where
-        Self: Sized,
-    {
-        std::io::BufRead::split(self, byte)
-    }
+/// Implements `Read` and `Write` for a traced type wrapping a `std` I/O object, so that each of
+/// its reads and writes is mediated, and whole-buffer operations are granted once.
+macro_rules! mediated_io {
+    ($traced:ty) => {
+        const _: () = {
+            use std::{
+                io::{IoSlice, IoSliceMut, Read, Write},
+                os::fd::AsRawFd,
+            };
+            use trace2e_client::primitives::Flow;
+
+            use $crate::io::mediate;
+
+            impl Read for $traced {
+                fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                    mediate(self.as_raw_fd(), Flow::Input, || self.0.read(buf), |bytes| *bytes)
+                }
+
+                fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> std::io::Result<usize> {
+                    mediate(
+                        self.as_raw_fd(),
+                        Flow::Input,
+                        || self.0.read_vectored(bufs),
+                        |bytes| *bytes,
+                    )
+                }
+
+                fn read_to_end(&mut self, buf: &mut Vec<u8>) -> std::io::Result<usize> {
+                    mediate(
+                        self.as_raw_fd(),
+                        Flow::Input,
+                        || self.0.read_to_end(buf),
+                        |bytes| *bytes,
+                    )
+                }
+
+                fn read_to_string(&mut self, buf: &mut String) -> std::io::Result<usize> {
+                    mediate(
+                        self.as_raw_fd(),
+                        Flow::Input,
+                        || self.0.read_to_string(buf),
+                        |bytes| *bytes,
+                    )
+                }
+
+                fn read_exact(&mut self, buf: &mut [u8]) -> std::io::Result<()> {
+                    let len = buf.len();
+                    mediate(self.as_raw_fd(), Flow::Input, || self.0.read_exact(buf), |_| len)
+                }
+            }
+
+            impl Write for $traced {
+                fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                    mediate(self.as_raw_fd(), Flow::Output, || self.0.write(buf), |bytes| *bytes)
+                }
+
+                fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> std::io::Result<usize> {
+                    mediate(
+                        self.as_raw_fd(),
+                        Flow::Output,
+                        || self.0.write_vectored(bufs),
+                        |bytes| *bytes,
+                    )
+                }
+
+                fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+                    mediate(self.as_raw_fd(), Flow::Output, || self.0.write_all(buf), |_| buf.len())
+                }
+
+                fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> std::io::Result<()> {
+                    // Formatted first, so that the write is granted once
+                    self.write_all(std::fmt::format(fmt).as_bytes())
+                }
+
+                fn flush(&mut self) -> std::io::Result<()> {
+                    self.0.flush()
+                }
+            }
+        };
+    };
+}
+pub(crate) use mediated_io;
+
+/// Performs an operation on a file descriptor once granted, and reports its outcome along with
+/// the number of bytes it transferred.
+///
+/// The traced types of this crate mediate each of their reads and writes with it, so that
+/// whole-buffer operations such as `read_to_end` or `write_all` are granted once.
+pub(crate) fn mediate<T>(
+    fd: RawFd,
+    flow: Flow,
+    operation: impl FnOnce() -> std::io::Result<T>,
+    bytes: impl FnOnce(&T) -> usize,
+) -> std::io::Result<T> {
+    let grant_id = io_request(fd, flow.into())?;
+    perform(fd, grant_id, flow, operation, bytes)
+}
 
-    fn lines(self) -> std::io::Lines<Self>
-    where
-        Self: Sized,
-    {
-        std::io::BufRead::lines(self)
-    }
+/// Sends or receives a datagram on a socket once granted for its peer, and reports its outcome
+/// along with the number of bytes it transferred.
+pub(crate) fn mediate_datagram<T>(
+    fd: RawFd,
+    peer: SocketAddr,
+    flow: Flow,
+    operation: impl FnOnce() -> std::io::Result<T>,
+    bytes: impl FnOnce(&T) -> usize,
+) -> std::io::Result<T> {
+    let grant_id = datagram_io_request(fd, peer.to_string(), flow.into())?;
+    perform(fd, grant_id, flow, operation, bytes)
 }
 
-impl<T: std::io::BufRead + std::os::fd::AsRawFd> BufRead for std::io::Take<T> {
-    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
-        let reader_raw_fd = self.get_ref().as_raw_fd();
-        if let Ok(grant_id) = io_request(reader_raw_fd, Flow::Input.into()) {
-            let result = std::io::BufRead::fill_buf(self);
-            io_report(reader_raw_fd, grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+/// Requests the grant of a datagram already received on a socket from a peer, and reports it.
+///
+/// The peer of a datagram on an unconnected socket is only known once the datagram is
+/// received, so the grant is requested afterwards, for the peer it actually came from. If the
+/// grant is refused, the received data is zeroed before the error is returned.
+pub(crate) fn mediate_received(
+    fd: RawFd,
+    peer: SocketAddr,
+    data: &mut [u8],
+) -> std::io::Result<()> {
+    let grant_id = match datagram_io_request(fd, peer.to_string(), Flow::Input.into()) {
+        Ok(grant_id) => grant_id,
+        Err(error) => {
+            data.fill(0);
+            return Err(error.into());
         }
-    }
+    };
+    report_performed(fd, grant_id, Flow::Input, true, data.len());
+    Ok(())
 }
 
-impl<R: ?Sized + std::io::Read + std::os::fd::AsRawFd> BufRead for std::io::BufReader<R> {
-    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
-        let reader_raw_fd = self.get_ref().as_raw_fd();
-        if let Ok(grant_id) = io_request(reader_raw_fd, Flow::Input.into()) {
-            let result = std::io::BufRead::fill_buf(self);
-            io_report(reader_raw_fd, grant_id, result.is_ok())?;
-            result
-        } else {
-            Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
-        }
+/// Performs a granted operation, and reports its outcome.
+fn perform<T>(
+    fd: RawFd,
+    grant_id: u128,
+    flow: Flow,
+    operation: impl FnOnce() -> std::io::Result<T>,
+    bytes: impl FnOnce(&T) -> usize,
+) -> std::io::Result<T> {
+    let result = operation();
+    let transferred = result.as_ref().map_or(0, bytes);
+    report_performed(fd, grant_id, flow, result.is_ok(), transferred);
+    result
+}
+
+/// Reports an operation that was performed already.
+///
+/// The data is transferred already, so that a report that cannot be sent is not surfaced as
+/// the outcome of the operation, which a retry would duplicate. It is logged, and the next
+/// request or flush fails if the middleware is still unreachable.
+fn report_performed(fd: RawFd, grant_id: u128, flow: Flow, result: bool, bytes: usize) {
+    if let Err(error) = report(fd, grant_id, flow, result, bytes) {
+        warn!(fd, %error, "[trace2e] Operation not reported");
     }
 }
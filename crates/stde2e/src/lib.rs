@@ -1,3 +1,4 @@
 pub mod fs;
 pub mod io;
 pub mod net;
+pub mod os;
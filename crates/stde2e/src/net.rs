@@ -1,10 +1,20 @@
 use std::{
-    net::{SocketAddr, TcpListener as StdTcpListener, TcpStream as StdTcpStream},
-    os::fd::AsRawFd,
+    net::{
+        Shutdown, SocketAddr, TcpListener as StdTcpListener, TcpStream as StdTcpStream,
+        ToSocketAddrs, UdpSocket as StdUdpSocket,
+    },
+    os::fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+    time::Duration,
 };
 
-use trace2e_client::p2m::remote_enroll;
+use trace2e_client::{
+    p2m::{close, datagram_enroll, dup, remote_enroll},
+    primitives::Flow,
+};
+
+use crate::io::{mediate_datagram, mediate_received, mediated_io};
 
+#[derive(Debug)]
 pub struct TcpListener(StdTcpListener);
 
 impl TcpListener {
@@ -26,25 +36,12 @@ impl TcpListener {
         }
     }
 
-    pub fn accept(&self) -> std::io::Result<(StdTcpStream, SocketAddr)> {
+    pub fn accept(&self) -> std::io::Result<(TcpStream, SocketAddr)> {
         let (tcp_stream, socket) = self.0.accept()?;
-        remote_enroll(
-            tcp_stream.as_raw_fd(),
-            tcp_stream.local_addr()?.to_string(),
-            tcp_stream.peer_addr()?.to_string(),
-        );
-        Ok((tcp_stream, socket))
+        Ok((TcpStream::enroll(tcp_stream)?, socket))
     }
-    pub fn incoming(&self) -> impl Iterator<Item = std::io::Result<StdTcpStream>> + '_ {
-        self.0.incoming().map(|stream_result| {
-            stream_result.inspect(|stream| {
-                remote_enroll(
-                    stream.as_raw_fd(),
-                    stream.local_addr().unwrap().to_string(),
-                    stream.peer_addr().unwrap().to_string(),
-                );
-            })
-        })
+    pub fn incoming(&self) -> impl Iterator<Item = std::io::Result<TcpStream>> + '_ {
+        self.0.incoming().map(|stream_result| TcpStream::enroll(stream_result?))
     }
 
     pub fn set_ttl(&self, ttl: u32) -> std::io::Result<()> {
@@ -64,28 +61,204 @@ impl TcpListener {
     }
 }
 
-pub struct TcpStream;
+/// Traced counterpart of [`std::net::TcpStream`], whose reads and writes are always mediated.
+///
+/// The stream is unenrolled when dropped.
+#[derive(Debug)]
+pub struct TcpStream(StdTcpStream);
 
 impl TcpStream {
-    pub fn connect<A: std::net::ToSocketAddrs>(addr: A) -> std::io::Result<StdTcpStream> {
-        let tcp_stream = StdTcpStream::connect(addr)?;
-        remote_enroll(
-            tcp_stream.as_raw_fd(),
-            tcp_stream.local_addr()?.to_string(),
-            tcp_stream.peer_addr()?.to_string(),
-        );
-        Ok(tcp_stream)
-    }
-    pub fn connect_timeout<A>(
-        addr: &SocketAddr,
-        timeout: std::time::Duration,
-    ) -> std::io::Result<StdTcpStream> {
-        let tcp_stream = StdTcpStream::connect_timeout(addr, timeout)?;
+    /// Enrolls a connected stream.
+    fn enroll(tcp_stream: StdTcpStream) -> std::io::Result<TcpStream> {
         remote_enroll(
             tcp_stream.as_raw_fd(),
             tcp_stream.local_addr()?.to_string(),
             tcp_stream.peer_addr()?.to_string(),
-        );
-        Ok(tcp_stream)
+        )?;
+        Ok(TcpStream(tcp_stream))
+    }
+
+    pub fn connect<A: std::net::ToSocketAddrs>(addr: A) -> std::io::Result<TcpStream> {
+        TcpStream::enroll(StdTcpStream::connect(addr)?)
+    }
+    pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> std::io::Result<TcpStream> {
+        TcpStream::enroll(StdTcpStream::connect_timeout(addr, timeout)?)
+    }
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+    pub fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        self.0.shutdown(how)
+    }
+    pub fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.0.set_nodelay(nodelay)
+    }
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.0.set_read_timeout(timeout)
+    }
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.0.set_write_timeout(timeout)
+    }
+    pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+    /// Duplicates the stream handle, the duplicate is enrolled with the same resource.
+    pub fn try_clone(&self) -> std::io::Result<TcpStream> {
+        let clone = self.0.try_clone()?;
+        dup(self.0.as_raw_fd(), clone.as_raw_fd())?;
+        Ok(TcpStream(clone))
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        close(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for TcpStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+mediated_io!(TcpStream);
+
+/// Traced counterpart of [`std::net::UdpSocket`], whose datagrams are always mediated.
+///
+/// Each datagram is granted for the peer it is sent to or received from. A datagram is
+/// received before it is granted, for the peer it actually came from, so that no reservation
+/// is held while waiting for it. If the grant is refused, the received data is zeroed before
+/// the error is returned.
+///
+/// The socket is unenrolled when dropped.
+#[derive(Debug)]
+pub struct UdpSocket(StdUdpSocket);
+
+impl UdpSocket {
+    /// Enrolls a bound socket, or enrolls it again once its local address changed.
+    fn enroll(udp_socket: StdUdpSocket) -> std::io::Result<UdpSocket> {
+        datagram_enroll(udp_socket.as_raw_fd(), udp_socket.local_addr()?.to_string())?;
+        Ok(UdpSocket(udp_socket))
+    }
+
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> std::io::Result<UdpSocket> {
+        UdpSocket::enroll(StdUdpSocket::bind(addr)?)
+    }
+    /// Connects the socket to a peer, the local address of the socket is enrolled again since
+    /// connecting may bind it to a specific interface.
+    pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> std::io::Result<()> {
+        self.0.connect(addr)?;
+        datagram_enroll(self.0.as_raw_fd(), self.0.local_addr()?.to_string())?;
+        Ok(())
+    }
+    pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> std::io::Result<usize> {
+        // The datagram is sent to the first address, like std does
+        let Some(peer) = addr.to_socket_addrs()?.next() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "no addresses to send data to",
+            ));
+        };
+        mediate_datagram(self.as_raw_fd(), peer, Flow::Output, || self.0.send_to(buf, peer), |b| *b)
+    }
+    /// Receives a datagram, which is granted for the peer it came from once received.
+    ///
+    /// If the datagram is refused, it is zeroed in the buffer and the error is returned.
+    pub fn recv_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+        let (bytes, peer) = self.0.recv_from(buf)?;
+        mediate_received(self.as_raw_fd(), peer, &mut buf[..bytes])?;
+        Ok((bytes, peer))
+    }
+    /// Peeks at a datagram, which is granted for the peer it came from once peeked at.
+    ///
+    /// If the datagram is refused, it is zeroed in the buffer and the error is returned.
+    pub fn peek_from(&self, buf: &mut [u8]) -> std::io::Result<(usize, SocketAddr)> {
+        let (bytes, peer) = self.0.peek_from(buf)?;
+        mediate_received(self.as_raw_fd(), peer, &mut buf[..bytes])?;
+        Ok((bytes, peer))
+    }
+    /// Sends a datagram to the peer the socket is connected to.
+    pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
+        let peer = self.0.peer_addr()?;
+        mediate_datagram(self.as_raw_fd(), peer, Flow::Output, || self.0.send(buf), |b| *b)
+    }
+    /// Receives a datagram from the peer the socket is connected to.
+    pub fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let peer = self.0.peer_addr()?;
+        mediate_datagram(self.as_raw_fd(), peer, Flow::Input, || self.0.recv(buf), |b| *b)
+    }
+    pub fn peek(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let peer = self.0.peer_addr()?;
+        mediate_datagram(self.as_raw_fd(), peer, Flow::Input, || self.0.peek(buf), |b| *b)
+    }
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.0.set_read_timeout(timeout)
+    }
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.0.set_write_timeout(timeout)
+    }
+    pub fn read_timeout(&self) -> std::io::Result<Option<Duration>> {
+        self.0.read_timeout()
+    }
+    pub fn write_timeout(&self) -> std::io::Result<Option<Duration>> {
+        self.0.write_timeout()
+    }
+    pub fn set_broadcast(&self, broadcast: bool) -> std::io::Result<()> {
+        self.0.set_broadcast(broadcast)
+    }
+    pub fn broadcast(&self) -> std::io::Result<bool> {
+        self.0.broadcast()
+    }
+    pub fn set_ttl(&self, ttl: u32) -> std::io::Result<()> {
+        self.0.set_ttl(ttl)
+    }
+    pub fn ttl(&self) -> std::io::Result<u32> {
+        self.0.ttl()
+    }
+    pub fn take_error(&self) -> std::io::Result<Option<std::io::Error>> {
+        self.0.take_error()
+    }
+    pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+    /// Duplicates the socket handle, the duplicate is enrolled with the same resource.
+    pub fn try_clone(&self) -> std::io::Result<UdpSocket> {
+        let clone = self.0.try_clone()?;
+        dup(self.0.as_raw_fd(), clone.as_raw_fd())?;
+        Ok(UdpSocket(clone))
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        close(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for UdpSocket {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
     }
 }
@@ -0,0 +1,2 @@
+//! OS-specific traced types.
+pub mod unix;
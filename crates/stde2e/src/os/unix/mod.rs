@@ -0,0 +1,2 @@
+//! Unix-specific traced types.
+pub mod net;
@@ -0,0 +1,154 @@
+//! Traced counterparts of the Unix domain sockets of `std::os::unix::net`.
+//!
+//! Connected streams are enrolled with the path of the listening socket and the pid of the
+//! peer process, as reported by the kernel. The flows between the processes of a node
+//! are tracked by the middleware of the node alone.
+use std::{
+    net::Shutdown,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, RawFd},
+        unix::net::{SocketAddr, UnixListener as StdUnixListener, UnixStream as StdUnixStream},
+    },
+    path::Path,
+    time::Duration,
+};
+
+use rustix::net::sockopt::socket_peercred;
+use trace2e_client::p2m::{close, dup, unix_enroll};
+
+use crate::io::mediated_io;
+
+#[derive(Debug)]
+pub struct UnixListener(StdUnixListener);
+
+impl UnixListener {
+    pub fn bind<P: AsRef<Path>>(path: P) -> std::io::Result<UnixListener> {
+        Ok(UnixListener(StdUnixListener::bind(path)?))
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<UnixListener> {
+        Ok(UnixListener(self.0.try_clone()?))
+    }
+
+    pub fn accept(&self) -> std::io::Result<(UnixStream, SocketAddr)> {
+        let (unix_stream, addr) = self.0.accept()?;
+        let listener_addr = unix_stream.local_addr()?;
+        Ok((UnixStream::enroll(unix_stream, &listener_addr)?, addr))
+    }
+
+    pub fn incoming(&self) -> impl Iterator<Item = std::io::Result<UnixStream>> + '_ {
+        self.0.incoming().map(|stream_result| {
+            let unix_stream = stream_result?;
+            let listener_addr = unix_stream.local_addr()?;
+            UnixStream::enroll(unix_stream, &listener_addr)
+        })
+    }
+
+    pub fn take_error(&self) -> std::io::Result<Option<std::io::Error>> {
+        self.0.take_error()
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+/// Traced counterpart of [`std::os::unix::net::UnixStream`], whose reads and writes are always
+/// mediated.
+///
+/// The stream is unenrolled when dropped.
+#[derive(Debug)]
+pub struct UnixStream(StdUnixStream);
+
+impl UnixStream {
+    /// Enrolls a connected stream, given the address of the listening socket, which names
+    /// the connection on both ends.
+    fn enroll(
+        unix_stream: StdUnixStream,
+        listener_addr: &SocketAddr,
+    ) -> std::io::Result<UnixStream> {
+        let path =
+            listener_addr.as_pathname().map(|path| path.display().to_string()).unwrap_or_default();
+        let peer = socket_peercred(&unix_stream)?;
+        unix_enroll(unix_stream.as_raw_fd(), path, peer.pid.as_raw_nonzero().get())?;
+        Ok(UnixStream(unix_stream))
+    }
+
+    pub fn connect<P: AsRef<Path>>(path: P) -> std::io::Result<UnixStream> {
+        let unix_stream = StdUnixStream::connect(path)?;
+        let listener_addr = unix_stream.peer_addr()?;
+        UnixStream::enroll(unix_stream, &listener_addr)
+    }
+    /// Creates an unnamed pair of connected streams, both enrolled.
+    pub fn pair() -> std::io::Result<(UnixStream, UnixStream)> {
+        let (first, second) = StdUnixStream::pair()?;
+        let listener_addr = first.local_addr()?;
+        Ok((
+            UnixStream::enroll(first, &listener_addr)?,
+            UnixStream::enroll(second, &listener_addr)?,
+        ))
+    }
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.peer_addr()
+    }
+    pub fn shutdown(&self, how: Shutdown) -> std::io::Result<()> {
+        self.0.shutdown(how)
+    }
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.0.set_read_timeout(timeout)
+    }
+    pub fn set_write_timeout(&self, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.0.set_write_timeout(timeout)
+    }
+    pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        self.0.set_nonblocking(nonblocking)
+    }
+    pub fn take_error(&self) -> std::io::Result<Option<std::io::Error>> {
+        self.0.take_error()
+    }
+    /// Duplicates the stream handle, the duplicate is enrolled with the same resource.
+    pub fn try_clone(&self) -> std::io::Result<UnixStream> {
+        let clone = self.0.try_clone()?;
+        dup(self.0.as_raw_fd(), clone.as_raw_fd())?;
+        Ok(UnixStream(clone))
+    }
+}
+
+impl Drop for UnixStream {
+    fn drop(&mut self) {
+        close(self.0.as_raw_fd());
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.as_raw_fd()
+    }
+}
+
+impl AsFd for UnixStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+mediated_io!(UnixStream);
@@ -0,0 +1,43 @@
+//! Helpers shared by the integration tests, which name the files of the working directory.
+#![allow(dead_code)]
+use trace2e_client::o2m;
+use trace2e_core::{
+    traceability::infrastructure::naming::Resource,
+    transport::grpc::proto::messages::ResourceVolume,
+};
+
+/// Returns the absolute path of a file of the working directory, under which the middleware
+/// names it.
+pub fn absolute(name: &str) -> String {
+    std::env::current_dir().unwrap().join(name).display().to_string()
+}
+
+/// Returns a file name unique to this run, for files whose policy outlives it.
+pub fn unique(name: &str) -> String {
+    format!("{name}_{}.txt", std::process::id())
+}
+
+/// Returns the resource of a file of the working directory.
+pub fn file(name: &str) -> Resource {
+    Resource::new_file(absolute(name))
+}
+
+/// Returns the volumes transferred from and into a file of the working directory.
+pub fn volume(name: &str) -> ResourceVolume {
+    o2m::get_volumes(vec![file(name)]).unwrap().remove(0)
+}
+
+/// Returns the resources a file of the working directory derives from, on any node.
+pub fn references(name: &str) -> Vec<Resource> {
+    o2m::get_references(file(name))
+        .unwrap()
+        .into_iter()
+        .flat_map(|references| references.resources)
+        .map(Resource::from)
+        .collect()
+}
+
+/// Returns whether a file of the working directory derives from another one.
+pub fn derives_from(destination: &str, source: &str) -> bool {
+    references(destination).contains(&file(source))
+}
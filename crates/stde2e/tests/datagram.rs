@@ -0,0 +1,40 @@
+use stde2e::net::UdpSocket;
+use trace2e_client::{o2m, p2m};
+use trace2e_core::traceability::infrastructure::naming::Resource;
+
+#[test]
+fn stde2e_net_datagram_instantiation() -> std::io::Result<()> {
+    let receiver = UdpSocket::bind("[::1]:0")?;
+    let sender = UdpSocket::bind("[::1]:0")?;
+    let (receiver_addr, sender_addr) = (receiver.local_addr()?, sender.local_addr()?);
+
+    assert_eq!(sender.send_to(b"test", receiver_addr)?, 4);
+    let mut buf = [0; 16];
+    let (bytes, peer) = receiver.recv_from(&mut buf)?;
+    assert_eq!((&buf[..bytes], peer), (&b"test"[..], sender_addr));
+
+    // Each datagram is recorded on the socket pair it was exchanged on, once reported
+    p2m::flush().unwrap();
+    let volume = |local: std::net::SocketAddr, peer: std::net::SocketAddr| {
+        let datagram = Resource::new_datagram(local.to_string(), Some(peer.to_string()));
+        o2m::get_volumes(vec![datagram]).unwrap()[0].clone()
+    };
+    assert_eq!(volume(sender_addr, receiver_addr).bytes_in, 4);
+    assert_eq!(volume(receiver_addr, sender_addr).bytes_out, 4);
+    Ok(())
+}
+
+#[test]
+fn stde2e_net_datagram_connected() -> std::io::Result<()> {
+    let receiver = UdpSocket::bind("[::1]:0")?;
+    let sender = UdpSocket::bind("[::1]:0")?;
+    sender.connect(receiver.local_addr()?)?;
+    receiver.connect(sender.local_addr()?)?;
+
+    // Connected sockets exchange datagrams with their peer only
+    assert_eq!(sender.send(b"test")?, 4);
+    let mut buf = [0; 16];
+    assert_eq!(receiver.recv(&mut buf)?, 4);
+    assert_eq!(&buf[..4], b"test");
+    Ok(())
+}
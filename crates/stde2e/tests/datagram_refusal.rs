@@ -0,0 +1,35 @@
+//! The refused datagram taints the whole test process, so it is received apart from the other
+//! datagram tests.
+mod common;
+
+use stde2e::{
+    fs::File,
+    io::{Read, Write},
+    net::UdpSocket,
+};
+use trace2e_client::{o2m, p2m};
+
+use common::{file, unique};
+
+#[test]
+fn stde2e_net_datagram_refused() -> std::io::Result<()> {
+    // The secret is deleted for good, its name is unique to this run
+    let name = unique("datagram_secret");
+    File::create(&name)?.write_all(b"secret")?;
+    let mut secret = Vec::new();
+    File::open(&name)?.read_to_end(&mut secret)?;
+
+    let receiver = UdpSocket::bind("[::1]:0")?;
+    let sender = UdpSocket::bind("[::1]:0")?;
+    assert_eq!(sender.send_to(&secret, receiver.local_addr()?)?, 6);
+    p2m::flush().unwrap();
+
+    // The datagram is refused once received, since its source is deleted, and never reaches
+    // the process
+    o2m::set_deleted(file(&name)).unwrap();
+    let mut buf = [0; 16];
+    let error = receiver.recv_from(&mut buf).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+    assert_eq!(buf, [0; 16]);
+    std::fs::remove_file(name)
+}
@@ -0,0 +1,57 @@
+//! A declared flow is checked against everything the test process has read, so it runs apart
+//! from the other file tests.
+mod common;
+
+use stde2e::{
+    fs::File,
+    io::{Read, Write, derive_from},
+};
+use trace2e_client::{o2m, p2m};
+
+use common::{derives_from, file, unique};
+
+#[test]
+fn stde2e_file_declared_flow() -> std::io::Result<()> {
+    // The policy of the secret outlives the run, its name is unique to it
+    let public = unique("declared_public");
+    let secret = unique("declared_secret");
+    let destination = unique("declared_destination");
+    for path in [&public, &secret] {
+        File::create(path)?.write_all(path.as_bytes())?;
+    }
+
+    let mut buf = String::new();
+    let mut public_file = File::open(&public)?;
+    public_file.read_to_string(&mut buf)?;
+    File::open(&secret)?.read_to_string(&mut buf)?;
+
+    // Leaving out the secret, or every source, would strip its taint, which its policy forbids
+    // by default
+    let mut f = File::create(&destination)?;
+    for sources in [&[&public_file as &dyn std::os::fd::AsRawFd][..], &[]] {
+        let _scope = derive_from(&f, sources)?;
+        let error = f.write_all(b"public").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    // Only the declared source flows into the destination once the secret may be declassified
+    o2m::set_declassification(file(&secret), true).unwrap();
+    {
+        let _scope = derive_from(&f, &[&public_file])?;
+        f.write_all(b"public")?;
+    }
+    p2m::flush().unwrap();
+    assert!(derives_from(&destination, &public));
+    assert!(!derives_from(&destination, &secret));
+
+    // Out of the scope, the destination derives from the whole process again
+    f.write_all(b"secret")?;
+    // The report of the write is pipelined
+    p2m::flush().unwrap();
+    assert!(derives_from(&destination, &secret));
+
+    for path in [public, secret, destination] {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
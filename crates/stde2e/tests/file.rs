@@ -1,12 +1,113 @@
+mod common;
+
 use std::time::Instant;
 
-use stde2e::{fs::File, io::Write};
+use stde2e::{
+    fs::File,
+    io::{Write, request_all, write_all_each},
+};
+use trace2e_client::{o2m, p2m, primitives::Flow};
+
+use common::{file, references, volume};
 
 #[test]
 fn stde2e_file_create() {
     let time = Instant::now();
     let mut f = File::create("test1.txt").unwrap();
-    f.write(b"test").unwrap();
+    f.write_all(b"test").unwrap();
 
     println!("Elapsed time {:?}", time.elapsed());
 }
+
+#[test]
+fn stde2e_file_clone_and_close() {
+    let f = File::create("test2.txt").unwrap();
+    let mut clone = f.try_clone().unwrap();
+    drop(f);
+
+    // The duplicate stays enrolled after the original is closed
+    clone.write_all(b"test").unwrap();
+    drop(clone);
+}
+
+#[test]
+fn stde2e_file_transfer_volumes() {
+    let before = volume("test3.txt").bytes_in;
+
+    let mut f = File::create("test3.txt").unwrap();
+    let word = "world";
+    f.write_all(b"hello").unwrap();
+    write!(f, "{word}").unwrap();
+    drop(f);
+
+    // Byte counts are reported along with each operation
+    assert_eq!(volume("test3.txt").bytes_in - before, 10);
+}
+
+#[test]
+fn stde2e_file_truncate() {
+    let overwrites = || o2m::get_lineage_events(file("test4.txt")).unwrap().len();
+    let only_itself = || references("test4.txt").iter().all(|source| *source == file("test4.txt"));
+
+    let mut f = File::create("test4.txt").unwrap();
+    f.write_all(b"hello").unwrap();
+    p2m::flush().unwrap();
+    assert!(!only_itself());
+    let before = overwrites();
+
+    // Truncating the file to zero discards its provenance and records an overwrite, extending
+    // it does not
+    File::set_len(&f, 0).unwrap();
+    assert!(only_itself());
+    File::set_len(&f, 16).unwrap();
+    assert_eq!(overwrites() - before, 1);
+
+    // So does a truncating open
+    f.write_all(b"hello").unwrap();
+    drop(f);
+    assert!(!only_itself());
+    drop(File::create("test4.txt").unwrap());
+    assert!(only_itself());
+    assert_eq!(overwrites() - before, 2);
+}
+
+#[test]
+fn stde2e_file_batch() {
+    let names = ["test8.txt", "test9.txt"];
+    let volumes = || names.map(volume);
+    let mut files = names.map(|name| File::create(name).unwrap());
+    let before = volumes();
+
+    // The writes are granted together, and reported on their own
+    assert!(write_all_each(&mut files, b"hello").iter().all(|result| result.is_ok()));
+    p2m::flush().unwrap();
+    let after = volumes();
+    assert_eq!(after.iter().zip(&before).map(|(a, b)| a.bytes_in - b.bytes_in).sum::<u64>(), 10);
+
+    // Both reads flow into the process at once, and share its reservation
+    let grants = request_all(&[(&files[0], Flow::Input), (&files[1], Flow::Input)]);
+    for grant in grants {
+        grant.unwrap().report(true, 5).unwrap();
+    }
+    p2m::flush().unwrap();
+    for (read, after) in volumes().iter().zip(after) {
+        assert_eq!(read.bytes_out - after.bytes_out, 5);
+    }
+}
+
+#[test]
+fn stde2e_file_generic_io() {
+    let mut f = File::create("test10.txt").unwrap();
+    f.write_all(b"hello").unwrap();
+    drop(f);
+    let (source, destination) = (volume("test10.txt"), volume("test11.txt"));
+
+    // Generic code of std reads and writes through the traced types, which mediate them
+    let mut reader = std::io::BufReader::new(File::open("test10.txt").unwrap());
+    let mut writer = File::create("test11.txt").unwrap();
+    assert_eq!(std::io::copy(&mut reader, &mut writer).unwrap(), 5);
+    drop(reader);
+    drop(writer);
+    assert_eq!(volume("test10.txt").bytes_out - source.bytes_out, 5);
+    assert_eq!(volume("test11.txt").bytes_in - destination.bytes_in, 5);
+}
@@ -10,7 +10,7 @@ async fn stde2e_net_stream_instantiation() -> std::io::Result<()> {
     let listener = TcpListener::bind("127.0.0.1:8081")?;
     Handle::current().spawn(async move {
         let (mut server, _) = listener.accept().unwrap();
-        server.write("test".as_bytes()).unwrap();
+        server.write_all("test".as_bytes()).unwrap();
     });
 
     // Give the server a brief moment to start
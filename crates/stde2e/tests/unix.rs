@@ -0,0 +1,42 @@
+use stde2e::{
+    io::{Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+};
+use trace2e_client::o2m;
+use trace2e_core::traceability::infrastructure::naming::Resource;
+
+#[test]
+fn stde2e_unix_stream_instantiation() -> std::io::Result<()> {
+    let path = std::env::temp_dir().join(format!("stde2e-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    let server = std::thread::spawn(move || {
+        let (mut server, _) = listener.accept().unwrap();
+        server.write_all("test".as_bytes()).unwrap();
+    });
+
+    let mut client = UnixStream::connect(&path)?;
+    server.join().unwrap();
+
+    let mut buf = String::new();
+    assert_eq!(client.read_to_string(&mut buf).unwrap(), 4);
+    assert_eq!(buf, "test");
+
+    // Both ends are held by this process, the transfer is recorded on the end it read from
+    let socket = Resource::new_unix_socket(path.display().to_string(), std::process::id() as i32);
+    let volume = &o2m::get_volumes(vec![socket]).unwrap()[0];
+    assert_eq!((volume.bytes_in, volume.bytes_out), (4, 4));
+    std::fs::remove_file(&path)
+}
+
+#[test]
+fn stde2e_unix_stream_pair() -> std::io::Result<()> {
+    let (mut first, mut second) = UnixStream::pair()?;
+    first.write_all(b"test")?;
+    drop(first);
+
+    let mut buf = Vec::new();
+    assert_eq!(second.read_to_end(&mut buf)?, 4);
+    assert_eq!(buf, b"test");
+    Ok(())
+}
@@ -0,0 +1,210 @@
+use std::{
+    io::SeekFrom,
+    os::fd::{AsRawFd, RawFd},
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    fs::{File as TokioFile, OpenOptions as TokioOpenOptions},
+    io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf},
+};
+use trace2e_client::{p2m::P2mClient, primitives::OpenMode};
+
+use crate::io::{Client, Traced};
+
+/// Traced counterpart of [`tokio::fs::File`].
+///
+/// The file is unenrolled when dropped, or when closed with [`File::close`]. Its writes are
+/// buffered, and reported once flushed.
+pub struct File(Traced<TokioFile>);
+
+impl File {
+    /// Enrolls a file opened at the given path through a client.
+    async fn enroll(
+        path: &Path,
+        file: TokioFile,
+        mode: OpenMode,
+        client: Client,
+    ) -> std::io::Result<File> {
+        let (path, fd) = (path.to_path_buf(), file.as_raw_fd());
+        client.run(move |client| async move { client.local_enroll(path, fd, mode).await }).await?;
+        Ok(File(Traced::new(file, fd, client).buffered()))
+    }
+
+    pub async fn open<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
+        let path_ref = path.as_ref();
+        let file = TokioFile::open(path_ref).await?;
+        File::enroll(path_ref, file, OpenMode::default(), Client::Process).await
+    }
+    pub async fn create<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
+        let path_ref = path.as_ref();
+        let file = TokioFile::create(path_ref).await?;
+        let mode = OpenMode { write: true, truncate: true, ..Default::default() };
+        File::enroll(path_ref, file, mode, Client::Process).await
+    }
+    pub async fn create_new<P: AsRef<Path>>(path: P) -> std::io::Result<File> {
+        let path_ref = path.as_ref();
+        let file = TokioFile::create_new(path_ref).await?;
+        let mode = OpenMode { write: true, create_new: true, ..Default::default() };
+        File::enroll(path_ref, file, mode, Client::Process).await
+    }
+    pub fn options() -> OpenOptions {
+        OpenOptions::new()
+    }
+    /// Duplicates the file handle, the duplicate is enrolled with the same resource.
+    pub async fn try_clone(&self) -> std::io::Result<File> {
+        let clone = self.0.get_ref().try_clone().await?;
+        let (fd, new_fd, client) = (self.0.fd(), clone.as_raw_fd(), self.0.client().clone());
+        client.run(move |client| async move { client.dup(fd, new_fd).await }).await?;
+        Ok(File(Traced::new(clone, new_fd, client).buffered()))
+    }
+    /// Truncates or extends the file, truncating it to zero discards its provenance.
+    pub async fn set_len(&self, size: u64) -> std::io::Result<()> {
+        self.0.get_ref().set_len(size).await?;
+        if size == 0 {
+            let fd = self.0.fd();
+            self.0.client().run(move |client| async move { client.truncate(fd).await }).await?;
+        }
+        Ok(())
+    }
+    pub async fn metadata(&self) -> std::io::Result<std::fs::Metadata> {
+        self.0.get_ref().metadata().await
+    }
+    pub async fn sync_all(&self) -> std::io::Result<()> {
+        self.0.get_ref().sync_all().await
+    }
+    pub async fn sync_data(&self) -> std::io::Result<()> {
+        self.0.get_ref().sync_data().await
+    }
+    /// Unenrolls and closes the file handle, once its pending writes are flushed.
+    pub async fn close(mut self) -> std::io::Result<()> {
+        let flushed = tokio::io::AsyncWriteExt::flush(&mut self).await;
+        self.0.close().await;
+        flushed
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.fd()
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        self.get_mut().0.poll_read(cx, buf, |_, _| Poll::Ready(Ok(())))
+    }
+}
+
+impl AsyncWrite for File {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf, |_, _| Poll::Ready(Ok(())))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().0.poll_shutdown(cx)
+    }
+}
+
+impl AsyncSeek for File {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        Pin::new(self.get_mut().0.get_mut()).start_seek(position)
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        Pin::new(self.get_mut().0.get_mut()).poll_complete(cx)
+    }
+}
+
+pub struct OpenOptions {
+    options: TokioOpenOptions,
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    client: Option<P2mClient>,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OpenOptions {
+    pub fn new() -> Self {
+        OpenOptions {
+            options: TokioOpenOptions::new(),
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            client: None,
+        }
+    }
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.options.read(read);
+        self.read = read;
+        self
+    }
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.options.write(write);
+        self.write = write;
+        self
+    }
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.options.append(append);
+        self.append = append;
+        self
+    }
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.options.truncate(truncate);
+        self.truncate = truncate;
+        self
+    }
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.options.create(create);
+        self.create = create;
+        self
+    }
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.options.create_new(create_new);
+        self.create_new = create_new;
+        self
+    }
+    /// Mediates the operations of the opened file through a client owned by the caller, rather
+    /// than through the process-wide client.
+    pub fn client(&mut self, client: P2mClient) -> &mut Self {
+        self.client = Some(client);
+        self
+    }
+    pub async fn open<P: AsRef<Path>>(&self, path: P) -> std::io::Result<File> {
+        let path_ref = path.as_ref();
+        let file = self.options.open(path_ref).await?;
+        let mode = OpenMode {
+            write: self.write,
+            append: self.append,
+            truncate: self.truncate,
+            create_new: self.create_new,
+        };
+        File::enroll(path_ref, file, mode, Client::new(self.client.clone())).await
+    }
+}
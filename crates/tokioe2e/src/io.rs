@@ -0,0 +1,359 @@
+//! Mediation of the asynchronous I/O operations of the traced types.
+use std::{
+    future::Future,
+    io,
+    os::fd::RawFd,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    runtime::Handle,
+    sync::oneshot,
+    task::JoinHandle,
+};
+use trace2e_client::{
+    error::P2mError,
+    p2m::{self, P2mClient},
+    primitives::Flow,
+};
+use tracing::warn;
+
+/// Client through which the operations of a traced object are mediated.
+#[derive(Clone)]
+pub(crate) enum Client {
+    /// The process-wide client, whose operations run on a runtime of their own
+    Process,
+    /// A client owned by the caller, whose operations run on the runtime the object was
+    /// created on
+    Owned(P2mClient, Handle),
+}
+
+impl Client {
+    /// Returns the client owned by the caller if any, the process-wide client otherwise.
+    ///
+    /// Must be called from the runtime the operations of an owned client run on.
+    pub(crate) fn new(client: Option<P2mClient>) -> Self {
+        match client {
+            Some(client) => Self::Owned(client, Handle::current()),
+            None => Self::Process,
+        }
+    }
+
+    /// Spawns an operation of the client.
+    fn spawn<F>(&self, operation: impl FnOnce(P2mClient) -> F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match self {
+            Self::Process => p2m::spawn(operation),
+            Self::Owned(client, runtime) => runtime.spawn(operation(client.clone())),
+        }
+    }
+
+    /// Runs an operation of the client and waits until it completes.
+    pub(crate) async fn run<F>(&self, operation: impl FnOnce(P2mClient) -> F) -> F::Output
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        self.spawn(operation).await.expect("trace2e client operation panicked")
+    }
+
+    /// Reports the outcome of a granted operation on the current stream without blocking.
+    fn io_report_now(
+        &self,
+        fd: RawFd,
+        grant_id: u128,
+        flow: Flow,
+        result: bool,
+        bytes: u64,
+    ) -> Result<(), P2mError> {
+        // Buffered by tokio, the transferred region is not located
+        match self {
+            Self::Process => p2m::io_report_now(fd, grant_id, flow.into(), result, bytes, None),
+            Self::Owned(client, _) => {
+                client.io_report_now(fd, grant_id, flow.into(), result, bytes, None)
+            }
+        }
+    }
+}
+
+/// Stage of the current operation in one direction.
+enum Stage {
+    Idle,
+    /// Waiting for the grant of the operation
+    Requesting(oneshot::Receiver<Result<u128, P2mError>>),
+    /// The operation is granted, and performed once the I/O object is ready
+    Granted(u128),
+    /// The operation returned the number of bytes it buffered, and is reported once they are
+    /// written
+    Buffered(u128, usize),
+}
+
+/// Mediation of the operations of a file descriptor in one direction.
+///
+/// Operations are granted one at a time: the grant is requested when the operation is first
+/// polled, and the outcome is reported as soon as it is performed, so that the report is sent
+/// before any operation the process starts afterward. The writes of objects that buffer them,
+/// such as files, are only performed once flushed, and reported then.
+struct Mediation {
+    fd: RawFd,
+    flow: Flow,
+    client: Client,
+    stage: Stage,
+}
+
+impl Mediation {
+    fn new(fd: RawFd, flow: Flow, client: Client) -> Self {
+        Self { fd, flow, client, stage: Stage::Idle }
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(self.stage, Stage::Idle)
+    }
+
+    /// Requests the grant of an operation.
+    ///
+    /// If the operation is cancelled before it is granted, the grant is released as a failed
+    /// operation.
+    fn request(&self) -> oneshot::Receiver<Result<u128, P2mError>> {
+        let (fd, flow) = (self.fd, self.flow.into());
+        let (sender, receiver) = oneshot::channel();
+        self.client.spawn(move |client| async move {
+            if let Err(Ok(grant_id)) = sender.send(client.io_request(fd, flow).await)
+                && let Err(error) = client.io_report(fd, grant_id, flow, false, 0, None).await
+            {
+                warn!(fd, %error, "[trace2e] Grant of a cancelled operation not released");
+            }
+        });
+        receiver
+    }
+
+    /// Reports a performed operation.
+    ///
+    /// The data is transferred already, so that a report that cannot be sent is not surfaced.
+    fn report(&self, grant_id: u128, result: bool, bytes: usize) {
+        if let Err(error) =
+            self.client.io_report_now(self.fd, grant_id, self.flow, result, bytes as u64)
+        {
+            warn!(fd = self.fd, %error, "[trace2e] Operation not reported");
+        }
+    }
+
+    /// Polls an operation, which returns the number of bytes it transferred, once granted.
+    ///
+    /// If the operation is `buffered`, it is reported once [settled](Mediation::poll_settle).
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+        buffered: bool,
+        mut operation: impl FnMut(&mut Context<'_>) -> Poll<io::Result<usize>>,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            match &mut self.stage {
+                Stage::Idle => self.stage = Stage::Requesting(self.request()),
+                Stage::Requesting(receiver) => {
+                    let grant = ready!(Pin::new(receiver).poll(cx))
+                        .expect("trace2e client operation panicked");
+                    match grant {
+                        Ok(grant_id) => self.stage = Stage::Granted(grant_id),
+                        Err(error) => {
+                            self.stage = Stage::Idle;
+                            return Poll::Ready(Err(error.into()));
+                        }
+                    }
+                }
+                Stage::Granted(grant_id) => {
+                    let grant_id = *grant_id;
+                    let result = ready!(operation(cx));
+                    match result {
+                        Ok(bytes) if buffered => self.stage = Stage::Buffered(grant_id, bytes),
+                        _ => {
+                            self.stage = Stage::Idle;
+                            self.report(grant_id, result.is_ok(), *result.as_ref().unwrap_or(&0));
+                        }
+                    }
+                    return Poll::Ready(result);
+                }
+                Stage::Buffered(..) => unreachable!("buffered operation not settled"),
+            }
+        }
+    }
+
+    /// Flushes the data buffered by the last operation, if any, and reports it.
+    fn poll_settle<T: AsyncWrite + Unpin>(
+        &mut self,
+        cx: &mut Context<'_>,
+        io: &mut T,
+    ) -> Poll<io::Result<()>> {
+        if let Stage::Buffered(grant_id, bytes) = self.stage {
+            let flushed = ready!(Pin::new(io).poll_flush(cx));
+            self.stage = Stage::Idle;
+            self.report(grant_id, flushed.is_ok(), if flushed.is_ok() { bytes } else { 0 });
+            return Poll::Ready(flushed);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Returns the grant of the pending operation, if any, which must be reported along with
+    /// the outcome of the operation.
+    ///
+    /// A buffered operation is reported as performed, as its data is still written once the
+    /// object is dropped.
+    fn release(&mut self) -> Option<(u128, bool, usize)> {
+        match std::mem::replace(&mut self.stage, Stage::Idle) {
+            Stage::Granted(grant_id) => Some((grant_id, false, 0)),
+            Stage::Buffered(grant_id, bytes) => Some((grant_id, true, bytes)),
+            _ => None,
+        }
+    }
+}
+
+/// I/O object whose reads and writes are mediated, and which is unenrolled when dropped.
+pub(crate) struct Traced<T: Send + 'static> {
+    /// Taken once the object is unenrolled
+    io: Option<T>,
+    fd: RawFd,
+    client: Client,
+    /// Whether the object buffers its writes until flushed
+    buffered: bool,
+    input: Mediation,
+    output: Mediation,
+}
+
+impl<T: Send + 'static> Traced<T> {
+    /// Wraps an I/O object whose file descriptor is enrolled through a client.
+    pub(crate) fn new(io: T, fd: RawFd, client: Client) -> Self {
+        Self {
+            io: Some(io),
+            fd,
+            input: Mediation::new(fd, Flow::Input, client.clone()),
+            output: Mediation::new(fd, Flow::Output, client.clone()),
+            client,
+            buffered: false,
+        }
+    }
+
+    /// Reports the writes of an object that buffers them once they are flushed.
+    pub(crate) fn buffered(mut self) -> Self {
+        self.buffered = true;
+        self
+    }
+
+    pub(crate) fn get_ref(&self) -> &T {
+        self.io.as_ref().expect("traced I/O object used after being closed")
+    }
+
+    /// Returns the object for operations that transfer no data, such as seeks.
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.io.as_mut().expect("traced I/O object used after being closed")
+    }
+
+    pub(crate) fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    pub(crate) fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Releases the pending grants and unenrolls the file descriptor, which is kept open until
+    /// then, so that it is not reused meanwhile.
+    fn unenroll(&mut self) -> Option<JoinHandle<()>> {
+        let io = self.io.take()?;
+        let fd = self.fd;
+        let grants = [(Flow::Input, self.input.release()), (Flow::Output, self.output.release())];
+        Some(self.client.spawn(move |client| async move {
+            for (flow, (grant_id, result, bytes)) in
+                grants.into_iter().filter_map(|(flow, grant)| Some((flow, grant?)))
+            {
+                if let Err(error) =
+                    client.io_report(fd, grant_id, flow.into(), result, bytes as u64, None).await
+                {
+                    warn!(fd, %error, "[trace2e] Pending grant not released");
+                }
+            }
+            client.close(fd).await;
+            drop(io);
+        }))
+    }
+
+    /// Unenrolls and closes the I/O object.
+    pub(crate) async fn close(mut self) {
+        if let Some(unenrolled) = self.unenroll()
+            && let Err(error) = unenrolled.await
+        {
+            warn!(fd = self.fd, %error, "[trace2e] File descriptor not unenrolled");
+        }
+    }
+
+    /// Polls a read, once the object is `ready` to be read from.
+    pub(crate) fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+        ready: impl FnOnce(&T, &mut Context<'_>) -> Poll<io::Result<()>>,
+    ) -> Poll<io::Result<()>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        let io = self.io.as_mut().expect("traced I/O object used after being closed");
+        // Buffered writes are performed before the object is read from
+        ready!(self.output.poll_settle(cx, io))?;
+        // The grant is not held while waiting for data
+        if self.input.is_idle() {
+            ready!(ready(io, cx))?;
+        }
+        let bytes = self.input.poll(cx, false, |cx| {
+            let filled = buf.filled().len();
+            ready!(Pin::new(&mut *io).poll_read(cx, buf))?;
+            Poll::Ready(Ok(buf.filled().len() - filled))
+        });
+        bytes.map_ok(|_| ())
+    }
+
+    /// Polls a write, once the object is `ready` to be written to.
+    pub(crate) fn poll_write(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        ready: impl FnOnce(&T, &mut Context<'_>) -> Poll<io::Result<()>>,
+    ) -> Poll<io::Result<usize>>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        let io = self.io.as_mut().expect("traced I/O object used after being closed");
+        ready!(self.output.poll_settle(cx, io))?;
+        if self.output.is_idle() {
+            ready!(ready(io, cx))?;
+        }
+        self.output.poll(cx, self.buffered, |cx| Pin::new(&mut *io).poll_write(cx, buf))
+    }
+
+    pub(crate) fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        let io = self.io.as_mut().expect("traced I/O object used after being closed");
+        ready!(self.output.poll_settle(cx, io))?;
+        Pin::new(io).poll_flush(cx)
+    }
+
+    pub(crate) fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        let io = self.io.as_mut().expect("traced I/O object used after being closed");
+        ready!(self.output.poll_settle(cx, io))?;
+        Pin::new(io).poll_shutdown(cx)
+    }
+}
+
+impl<T: Send + 'static> Drop for Traced<T> {
+    fn drop(&mut self) {
+        self.unenroll();
+    }
+}
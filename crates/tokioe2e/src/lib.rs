@@ -0,0 +1,14 @@
+//! Tokio I/O types providing TracE2E integration using `trace2e_client`.
+//!
+//! The traced types wrap their `tokio` counterparts, they are enrolled with the middleware when
+//! opened, and each of their reads and writes is granted by the middleware beforehand and
+//! reported afterward, like those of `stde2e`. Operations that are not granted fail with
+//! I/O errors of the kind matching the refusal, which embed its reason.
+//!
+//! The operations of the process-wide middleware client run on a runtime of their own, so that
+//! the traced types may be used on any runtime, including current-thread ones. Applications that
+//! own a [`P2mClient`](trace2e_client::p2m::P2mClient) may open the traced types with it
+//! instead, their operations then run on the runtime the types are opened on.
+pub mod fs;
+mod io;
+pub mod net;
@@ -0,0 +1,134 @@
+use std::{
+    net::SocketAddr,
+    os::fd::{AsRawFd, RawFd},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener as TokioTcpListener, TcpStream as TokioTcpStream, ToSocketAddrs},
+};
+
+use trace2e_client::p2m::P2mClient;
+
+use crate::io::{Client, Traced};
+
+/// Listener whose accepted streams are traced.
+pub struct TcpListener(TokioTcpListener, Client);
+
+impl TcpListener {
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> std::io::Result<TcpListener> {
+        Ok(TcpListener(TokioTcpListener::bind(addr).await?, Client::Process))
+    }
+
+    /// Binds a listener whose accepted streams are mediated through a client owned by the
+    /// caller, rather than through the process-wide client.
+    pub async fn bind_with<A: ToSocketAddrs>(
+        addr: A,
+        client: P2mClient,
+    ) -> std::io::Result<TcpListener> {
+        Ok(TcpListener(TokioTcpListener::bind(addr).await?, Client::new(Some(client))))
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    pub async fn accept(&self) -> std::io::Result<(TcpStream, SocketAddr)> {
+        let (tcp_stream, socket) = self.0.accept().await?;
+        Ok((TcpStream::enroll(tcp_stream, self.1.clone()).await?, socket))
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> std::io::Result<()> {
+        self.0.set_ttl(ttl)
+    }
+
+    pub fn ttl(&self) -> std::io::Result<u32> {
+        self.0.ttl()
+    }
+}
+
+/// Traced counterpart of [`tokio::net::TcpStream`].
+///
+/// The stream is unenrolled when dropped, or when closed with [`TcpStream::close`].
+pub struct TcpStream(Traced<TokioTcpStream>);
+
+impl TcpStream {
+    /// Enrolls a connected stream through a client.
+    async fn enroll(tcp_stream: TokioTcpStream, client: Client) -> std::io::Result<TcpStream> {
+        let fd = tcp_stream.as_raw_fd();
+        let local_socket = tcp_stream.local_addr()?.to_string();
+        let peer_socket = tcp_stream.peer_addr()?.to_string();
+        client
+            .run(move |client| async move {
+                client.remote_enroll(fd, local_socket, peer_socket).await
+            })
+            .await?;
+        Ok(TcpStream(Traced::new(tcp_stream, fd, client)))
+    }
+
+    pub async fn connect<A: ToSocketAddrs>(addr: A) -> std::io::Result<TcpStream> {
+        TcpStream::enroll(TokioTcpStream::connect(addr).await?, Client::Process).await
+    }
+
+    /// Connects a stream mediated through a client owned by the caller, rather than through
+    /// the process-wide client.
+    pub async fn connect_with<A: ToSocketAddrs>(
+        addr: A,
+        client: P2mClient,
+    ) -> std::io::Result<TcpStream> {
+        TcpStream::enroll(TokioTcpStream::connect(addr).await?, Client::new(Some(client))).await
+    }
+
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.get_ref().local_addr()
+    }
+
+    pub fn peer_addr(&self) -> std::io::Result<SocketAddr> {
+        self.0.get_ref().peer_addr()
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> std::io::Result<()> {
+        self.0.get_ref().set_nodelay(nodelay)
+    }
+
+    /// Unenrolls and closes the stream handle.
+    pub async fn close(self) {
+        self.0.close().await;
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.fd()
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        self.get_mut().0.poll_read(cx, buf, TokioTcpStream::poll_read_ready)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.get_mut().0.poll_write(cx, buf, TokioTcpStream::poll_write_ready)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().0.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.get_mut().0.poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,90 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokioe2e::fs::File;
+use trace2e_client::{o2m, p2m::P2mClient};
+use trace2e_core::traceability::infrastructure::naming::Resource;
+
+fn absolute(name: &str) -> String {
+    std::env::current_dir().unwrap().join(name).display().to_string()
+}
+
+#[tokio::test]
+async fn tokioe2e_file_create() {
+    let mut f = File::create("test1.txt").await.unwrap();
+    f.write_all(b"test").await.unwrap();
+    f.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn tokioe2e_file_clone_and_close() {
+    let f = File::create("test2.txt").await.unwrap();
+    let mut clone = f.try_clone().await.unwrap();
+    f.close().await.unwrap();
+
+    // The duplicate stays enrolled after the original is closed
+    clone.write_all(b"test").await.unwrap();
+    clone.close().await.unwrap();
+}
+
+// The operator API blocks in place, which requires a multi-thread runtime
+#[tokio::test(flavor = "multi_thread")]
+async fn tokioe2e_file_transfer_volumes() {
+    let path = absolute("test3.txt");
+    let bytes_in = || o2m::get_volumes(vec![Resource::new_file(path.clone())]).unwrap()[0].bytes_in;
+
+    let mut f = File::create("test3.txt").await.unwrap();
+    let before = bytes_in();
+    f.write_all(b"hello").await.unwrap();
+    f.write_all(b"world").await.unwrap();
+    f.close().await.unwrap();
+
+    // Byte counts are reported along with each operation
+    assert_eq!(bytes_in() - before, 10);
+
+    let mut f = File::open("test3.txt").await.unwrap();
+    let mut buf = String::new();
+    assert_eq!(f.read_to_string(&mut buf).await.unwrap(), 10);
+    assert_eq!(buf, "helloworld");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokioe2e_file_truncate() {
+    let path = absolute("test4.txt");
+    let overwrites = || o2m::get_lineage_events(Resource::new_file(path.clone())).unwrap().len();
+
+    let mut f = File::create("test4.txt").await.unwrap();
+    f.write_all(b"hello").await.unwrap();
+    f.flush().await.unwrap();
+    let before = overwrites();
+
+    // Truncating the file to zero records an overwrite, extending it does not
+    f.set_len(0).await.unwrap();
+    f.set_len(16).await.unwrap();
+    assert_eq!(overwrites() - before, 1);
+    f.close().await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokioe2e_file_owned_client() {
+    let path = absolute("test5.txt");
+    let bytes_in = || o2m::get_volumes(vec![Resource::new_file(path.clone())]).unwrap()[0].bytes_in;
+
+    let client = P2mClient::from_env();
+    let mut f = File::options()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .client(client.clone())
+        .open("test5.txt")
+        .await
+        .unwrap();
+    let before = bytes_in();
+
+    // The write is buffered by tokio, it is reported once flushed
+    f.write_all(b"hello").await.unwrap();
+    client.flush().await.unwrap();
+    assert_eq!(bytes_in(), before);
+    f.flush().await.unwrap();
+    client.flush().await.unwrap();
+    assert_eq!(bytes_in() - before, 5);
+    f.close().await.unwrap();
+}
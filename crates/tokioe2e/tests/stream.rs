@@ -0,0 +1,60 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokioe2e::net::{TcpListener, TcpStream};
+use trace2e_client::p2m::P2mClient;
+
+#[tokio::test]
+async fn tokioe2e_net_stream_instantiation() -> std::io::Result<()> {
+    let listener = TcpListener::bind("[::1]:0").await?;
+    let addr = listener.local_addr()?;
+    // Both ends are enrolled before any data is sent
+    let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    let (mut client, (mut server, _)) = (client?, server?);
+    let server = tokio::spawn(async move {
+        server.write_all("test".as_bytes()).await.unwrap();
+        server.close().await;
+    });
+
+    let mut buf = String::new();
+    assert_eq!(client.read_to_string(&mut buf).await?, 4);
+    assert_eq!(buf, "test");
+    server.await.unwrap();
+    client.close().await;
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn tokioe2e_net_stream_current_thread() -> std::io::Result<()> {
+    let listener = TcpListener::bind("[::1]:0").await?;
+    let addr = listener.local_addr()?;
+    let (client, server) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    let (mut client, (mut server, _)) = (client?, server?);
+
+    // Reads wait for the data before being granted, so that both ends may be driven at once
+    let mut buf = [0; 4];
+    let (read, written) = tokio::join!(server.read_exact(&mut buf), client.write_all(b"ping"));
+    read?;
+    written?;
+    assert_eq!(&buf, b"ping");
+    Ok(())
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn tokioe2e_net_stream_owned_client() -> std::io::Result<()> {
+    // The operations of the owned client run on the runtime of the test
+    let p2m = P2mClient::from_env();
+    let listener = TcpListener::bind_with("[::1]:0", p2m.clone()).await?;
+    let addr = listener.local_addr()?;
+    let (client, server) =
+        tokio::join!(TcpStream::connect_with(addr, p2m.clone()), listener.accept());
+    let (mut client, (mut server, _)) = (client?, server?);
+
+    let mut buf = [0; 4];
+    let (read, written) = tokio::join!(server.read_exact(&mut buf), client.write_all(b"ping"));
+    read?;
+    written?;
+    assert_eq!(&buf, b"ping");
+    client.close().await;
+    server.close().await;
+    p2m.flush().await?;
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Cost of file I/O mediated by an in-process middleware, either with unary P2M calls (a
+//! request and a report per operation) or through the P2M client (pipelined reports over the
+//! stream of the process).
+use std::fs::File;
+use std::os::{fd::AsRawFd, unix::fs::FileExt};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, Server};
+use trace2e_client::client::P2mClient;
+use trace2e_core::{
+    traceability::init_middleware,
+    transport::{
+        grpc::{
+            P2mHandler, connect, p2m_uds_incoming,
+            proto::{
+                messages::{IoInfo, IoResult, LocalCt},
+                p2m_client,
+                p2m_server::P2mServer,
+                primitives::Flow,
+            },
+        },
+        nop::M2mNop,
+    },
+};
+
+const SIZES: [usize; 3] = [100, 10_000, 1_000_000];
+
+fn make_data(size: usize) -> Vec<u8> {
+    (0..size).map(|i| (i % 256) as u8).collect()
+}
+
+/// Starts an in-process middleware listening on a Unix domain socket, and returns a unary
+/// client and a P2M client of it.
+fn start_middleware(rt: &Runtime) -> (p2m_client::P2mClient<Channel>, P2mClient) {
+    let socket = std::env::temp_dir().join(format!("trace2e_bench_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket);
+    let url = format!("unix://{}", socket.display());
+    rt.block_on(async {
+        let (_, p2m_service, _) = init_middleware("127.0.0.1".to_string(), None, 0, M2mNop, false);
+        let incoming = p2m_uds_incoming(&socket).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+                .serve_with_incoming(incoming),
+        );
+        let unary = p2m_client::P2mClient::new(connect(&url).await.unwrap());
+        (unary, P2mClient::new(url))
+    })
+}
+
+fn bench_mediated(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (mut unary, client) = start_middleware(&rt);
+    let pid = std::process::id() as i32;
+
+    for (name, flow) in [("write", Flow::Output), ("read", Flow::Input)] {
+        let mut group = c.benchmark_group(format!("p2m_{name}"));
+
+        for &size in &SIZES {
+            let path = format!("/tmp/bench_stdio_p2m_{size}");
+            let f = File::options()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)
+                .unwrap();
+            f.write_all_at(&make_data(size), 0).unwrap();
+            let fd = f.as_raw_fd();
+            rt.block_on(unary.p2m_local_enroll(LocalCt {
+                process_id: pid,
+                file_descriptor: fd,
+                path: path.clone(),
+                mode: None,
+            }))
+            .unwrap();
+            rt.block_on(client.local_enroll(&path, fd, Default::default())).unwrap();
+            let mut buf = make_data(size);
+            let io = |buf: &mut [u8]| match flow {
+                Flow::Output => f.write_all_at(buf, 0).unwrap(),
+                _ => f.read_exact_at(buf, 0).unwrap(),
+            };
+
+            group.bench_function(BenchmarkId::new("unary", size), |b| {
+                b.iter(|| {
+                    let io_request =
+                        IoInfo { process_id: pid, file_descriptor: fd, flow: flow as i32 };
+                    let grant = rt.block_on(unary.p2m_io_request(io_request)).unwrap().into_inner();
+                    io(&mut buf);
+                    let io_report = IoResult {
+                        process_id: pid,
+                        file_descriptor: fd,
+                        grant_id: grant.id,
+                        result: true,
+                        bytes: size as u64,
+                        offset: Some(0),
+                        flow: flow as i32,
+                    };
+                    rt.block_on(unary.p2m_io_report(io_report)).unwrap();
+                });
+            });
+
+            group.bench_function(BenchmarkId::new("client", size), |b| {
+                b.iter(|| {
+                    let grant_id = rt.block_on(client.io_request(fd, flow as i32)).unwrap();
+                    io(&mut buf);
+                    rt.block_on(client.io_report(
+                        fd,
+                        grant_id,
+                        flow as i32,
+                        true,
+                        size as u64,
+                        Some(0),
+                    ))
+                    .unwrap();
+                });
+            });
+            rt.block_on(client.flush()).unwrap();
+        }
+
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_mediated);
+criterion_main!(benches);
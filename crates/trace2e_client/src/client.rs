@@ -0,0 +1,1139 @@
+//! Asynchronous Process-to-Middleware client.
+//!
+//! A [`P2mClient`] carries all the operations of a process over a single long-lived
+//! bidirectional stream, which is opened on first use. Each request is tagged with a sequence
+//! number, and the caller waits until the reply with the same sequence number is received,
+//! except for I/O reports: those are pipelined, the process does not wait for their
+//! acknowledgement. A refused report is surfaced by the next I/O request on the same file
+//! descriptor, or by [`P2mClient::flush`].
+//!
+//! Since the middleware handles the requests of a stream in order, any request that waits for
+//! its reply also waits for the previous reports to be handled. Processes that exit without
+//! closing their resources should flush the client beforehand, so that no report is lost.
+//!
+//! The client is a cheap handle, clones share the same stream. The replies are dispatched by a
+//! task spawned on the runtime of the caller, which may be a current-thread runtime, and which
+//! must have its time driver enabled. The stream is closed once every handle is dropped.
+//!
+//! A client acts on behalf of the process that created it. A child forked from the process
+//! inherits neither its stream nor the runtime driving it, it gets its own client with
+//! [`P2mClient::forked`].
+//!
+//! ## Failure Modes
+//!
+//! Once lost, the stream is opened again by the next operation, at most once per
+//! [`RECONNECT_INTERVAL`]. While the middleware cannot be reached, operations are handled
+//! according to the [`FailureMode`] of the client:
+//! - **Closed** (default): I/O requests are denied, and the operations that would loosen the
+//!   tracking of the process (truncations, flow declarations and sessions) fail.
+//! - **Open**: operations proceed without mediation, a warning is logged once per outage.
+//! - **Buffered**: operations proceed without mediation, and are replayed in order once the
+//!   middleware is reachable again, so that the provenance of the resources catches up. So are
+//!   the reports of the operations granted before the outage.
+//!
+//! Operations refused by the middleware fail in every mode, those refused on replay are
+//! surfaced like refused reports. The client keeps track of the
+//! enrolled file descriptors, of their flow declarations and of the session of the process,
+//! and restores them on each new stream: descriptors opened while the middleware was
+//! unreachable, or before it restarted, are mediated once it is back.
+//! [`P2mClient::health_check`] tells whether the middleware is reachable.
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::canonicalize,
+    path::Path,
+    process::id,
+    str::FromStr,
+    sync::{
+        Arc, Mutex, MutexGuard, Weak,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::timeout,
+};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tracing::warn;
+
+use trace2e_core::transport::grpc::{DEFAULT_P2M_SOCKET, connect, proto};
+
+use crate::error::P2mError;
+use proto::{
+    messages::{
+        P2mStreamRequest, P2mStreamResponse, p2m_stream_request::Request,
+        p2m_stream_response::Response,
+    },
+    primitives::Flow,
+};
+
+/// Minimum delay between two attempts to reach the middleware.
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum number of operations buffered while the middleware is unreachable, the following
+/// ones are handled as in open mode.
+pub const MAX_BUFFERED_OPERATIONS: usize = 4096;
+
+/// Maximum delay to open a stream to the middleware.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Behaviour of the client while the middleware cannot be reached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailureMode {
+    /// I/O requests are denied
+    #[default]
+    Closed,
+    /// Operations proceed without mediation, and a warning is logged
+    Open,
+    /// Operations proceed without mediation, and are replayed once the middleware is back
+    Buffered,
+}
+
+impl FromStr for FailureMode {
+    type Err = String;
+
+    fn from_str(mode: &str) -> Result<Self, Self::Err> {
+        match mode.to_ascii_lowercase().as_str() {
+            "closed" | "fail-closed" => Ok(FailureMode::Closed),
+            "open" | "fail-open" => Ok(FailureMode::Open),
+            "buffered" => Ok(FailureMode::Buffered),
+            _ => Err(format!("unknown failure mode: {mode}")),
+        }
+    }
+}
+
+/// Health of the connection to the middleware, as returned by [`P2mClient::health_check`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    /// Whether the middleware answered the check
+    pub reachable: bool,
+    /// Failure mode applied while the middleware is unreachable
+    pub mode: FailureMode,
+    /// Number of operations waiting to be replayed
+    pub buffered: usize,
+}
+
+/// Returns the grant handed out for an operation that is not mediated by the middleware.
+///
+/// The grants issued by the middleware are random, so that they cannot be mistaken for these.
+pub(crate) fn unmediated_grant(flow: i32) -> u128 {
+    if flow == Flow::Output as i32 { u128::MAX } else { u128::MAX - 1 }
+}
+
+/// Returns the flow of an operation that was not mediated, given its grant.
+pub(crate) fn unmediated_flow(grant_id: u128) -> Option<i32> {
+    match grant_id {
+        u128::MAX => Some(Flow::Output as i32),
+        grant_id if grant_id == u128::MAX - 1 => Some(Flow::Input as i32),
+        _ => None,
+    }
+}
+
+/// Returns the refusal carried by an unexpected reply of the middleware.
+fn refusal(response: Response) -> P2mError {
+    match response {
+        Response::Error(error) => error.into(),
+        _ => P2mError::InvalidResponse,
+    }
+}
+
+/// Party waiting for the reply to a request sent on the stream.
+enum Waiter {
+    /// A caller waiting until the reply is received
+    Reply(oneshot::Sender<Response>),
+    /// A pipelined report on the given file descriptor
+    Report(i32),
+    /// A restored or replayed operation, whose outcome is not awaited
+    Replay,
+}
+
+/// Operation performed while the middleware was unreachable, replayed in buffered mode.
+#[derive(Clone)]
+enum Operation {
+    Request(Request),
+    /// Report of a granted I/O operation, replayed as a pipelined report
+    Report {
+        fd: i32,
+        request: Request,
+    },
+    /// Unmediated I/O operation, replayed as a request followed by its report
+    Transfer {
+        fd: i32,
+        flow: i32,
+        /// Peer socket of the datagram, for operations on datagram sockets
+        peer_socket: Option<String>,
+        result: bool,
+        bytes: u64,
+        offset: Option<u64>,
+    },
+}
+
+/// Tracking state of a file descriptor, restored on each new stream.
+#[derive(Clone)]
+struct Descriptor {
+    /// Enrollment of the descriptor, which does not discard the content of the resource
+    enrollment: Request,
+    /// Descriptors declared as the sources of its writes, if any
+    sources: Option<Vec<i32>>,
+}
+
+/// Stream to the middleware.
+struct Stream {
+    id: u64,
+    requests: mpsc::UnboundedSender<P2mStreamRequest>,
+    /// Whether the stream ended, in which case no reply will be received anymore
+    ended: Arc<AtomicBool>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Current stream, None while the middleware is unreachable
+    stream: Option<Stream>,
+    /// Instant of the last failed attempt to reach the middleware
+    failed_at: Option<Instant>,
+    /// Whether the current outage was logged
+    outage_logged: bool,
+    /// Waiters by sequence number, along with the stream their request was sent on
+    waiters: HashMap<u64, (u64, Waiter)>,
+    /// File descriptors with a refused report not yet surfaced to the process
+    refused_reports: HashMap<i32, P2mError>,
+    descriptors: HashMap<i32, Descriptor>,
+    /// Name of the session of the process, if any
+    session: Option<String>,
+    /// Operations waiting to be replayed, in buffered mode
+    buffered: VecDeque<Operation>,
+    /// Peer sockets of the unmediated datagram operations not yet reported, by descriptor
+    unmediated_peers: HashMap<i32, String>,
+}
+
+struct Inner {
+    url: String,
+    pid: i32,
+    mode: Mutex<FailureMode>,
+    state: Mutex<State>,
+    /// Held while a stream is being opened, so that a single task reconnects at a time
+    connecting: tokio::sync::Mutex<()>,
+    sequence: AtomicU64,
+    stream_id: AtomicU64,
+}
+
+/// Asynchronous client of the local middleware, on behalf of the current process.
+#[derive(Clone)]
+pub struct P2mClient {
+    inner: Arc<Inner>,
+}
+
+impl P2mClient {
+    /// Creates a client of the middleware at the given URL (`unix://<path>` for a Unix domain
+    /// socket, `http://<address>` for TCP), which connects on first use.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                url: url.into(),
+                pid: id() as i32,
+                mode: Mutex::new(FailureMode::default()),
+                state: Default::default(),
+                connecting: Default::default(),
+                sequence: AtomicU64::new(0),
+                stream_id: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Creates a client configured by the environment: the middleware is reached at
+    /// `TRACE2E_MIDDLEWARE_URL`, or at the default local socket, and the failure mode is read
+    /// from `TRACE2E_FAILURE_MODE` (`closed`, `open` or `buffered`).
+    pub fn from_env() -> Self {
+        let url = std::env::var("TRACE2E_MIDDLEWARE_URL")
+            .unwrap_or_else(|_| format!("unix://{DEFAULT_P2M_SOCKET}"));
+        let mode = std::env::var("TRACE2E_FAILURE_MODE")
+            .map_or(Ok(FailureMode::default()), |mode| mode.parse::<FailureMode>());
+        Self::new(url).with_failure_mode(mode.unwrap_or_else(|e| {
+            warn!("[trace2e] {e}, failing closed");
+            FailureMode::Closed
+        }))
+    }
+
+    /// Sets the behaviour of the client while the middleware cannot be reached.
+    pub fn with_failure_mode(self, mode: FailureMode) -> Self {
+        self.set_failure_mode(mode);
+        self
+    }
+
+    /// Returns the failure mode of the client.
+    pub fn failure_mode(&self) -> FailureMode {
+        *self.inner.mode.lock().unwrap()
+    }
+
+    /// Sets the failure mode of the client, shared by its clones.
+    pub fn set_failure_mode(&self, mode: FailureMode) {
+        *self.inner.mode.lock().unwrap() = mode;
+    }
+
+    /// Returns a client acting on behalf of the current process, forked from the one of this
+    /// client.
+    ///
+    /// The new client opens its own stream, with the same URL and failure mode. The file
+    /// descriptors inherited by the child are enrolled again on its behalf on this stream, but
+    /// not their flow declarations, nor the session of the parent.
+    pub fn forked(&self) -> Self {
+        let child = Self::new(self.inner.url.clone()).with_failure_mode(self.failure_mode());
+        let pid = child.inner.pid;
+        let descriptors = self
+            .state()
+            .descriptors
+            .iter()
+            .map(|(&fd, descriptor)| {
+                let mut enrollment = descriptor.enrollment.clone();
+                match &mut enrollment {
+                    Request::LocalEnroll(local_ct) => local_ct.process_id = pid,
+                    Request::RemoteEnroll(remote_ct) => remote_ct.process_id = pid,
+                    Request::UnixEnroll(unix_ct) => unix_ct.process_id = pid,
+                    Request::DatagramEnroll(datagram_ct) => datagram_ct.process_id = pid,
+                    _ => (),
+                }
+                (fd, Descriptor { enrollment, sources: None })
+            })
+            .collect();
+        child.state().descriptors = descriptors;
+        child
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.inner.state.lock().unwrap()
+    }
+
+    /// Opens a stream to the middleware.
+    async fn open_stream(
+        &self,
+    ) -> Option<(mpsc::UnboundedSender<P2mStreamRequest>, tonic::Streaming<P2mStreamResponse>)>
+    {
+        let channel = timeout(CONNECT_TIMEOUT, connect(&self.inner.url)).await.ok()?.ok()?;
+        let mut client = proto::p2m_client::P2mClient::new(channel);
+        let (requests, outbound) = mpsc::unbounded_channel();
+        let response =
+            timeout(CONNECT_TIMEOUT, client.p2m_stream(UnboundedReceiverStream::new(outbound)))
+                .await
+                .ok()?
+                .ok()?;
+        Some((requests, response.into_inner()))
+    }
+
+    /// Makes sure a stream to the middleware is open, opening a new one unless the last
+    /// attempt failed less than [`RECONNECT_INTERVAL`] ago, or if forced.
+    ///
+    /// Returns false if the middleware is unreachable.
+    async fn connect_if_needed(&self, force: bool) -> bool {
+        let may_connect = |state: &State| {
+            state.stream.is_none()
+                && (force
+                    || state
+                        .failed_at
+                        .is_none_or(|failed_at| failed_at.elapsed() >= RECONNECT_INTERVAL))
+        };
+        if !may_connect(&self.state()) {
+            return self.state().stream.is_some();
+        }
+        let _connecting = self.inner.connecting.lock().await;
+        // Another task may have reconnected, or failed to, in the meantime
+        if !may_connect(&self.state()) {
+            return self.state().stream.is_some();
+        }
+
+        let Some((requests, inbound)) = self.open_stream().await else {
+            self.state().failed_at = Some(Instant::now());
+            return false;
+        };
+        let stream = Stream {
+            id: self.inner.stream_id.fetch_add(1, Ordering::Relaxed),
+            requests,
+            ended: Default::default(),
+        };
+        tokio::spawn(drive(Arc::downgrade(&self.inner), inbound, stream.id, stream.ended.clone()));
+
+        // The state of the process is restored before any other request is sent on the stream
+        let restored = self.restore(&stream).await;
+        let mut state = self.state();
+        if !restored || stream.ended.load(Ordering::Relaxed) {
+            state.failed_at = Some(Instant::now());
+            return false;
+        }
+        state.stream = Some(stream);
+        state.failed_at = None;
+        state.outage_logged = false;
+        true
+    }
+
+    /// Sends a request on the given stream, registering its waiter beforehand so that the
+    /// reply cannot be received before it.
+    fn send_on(
+        &self,
+        stream_id: u64,
+        requests: &mpsc::UnboundedSender<P2mStreamRequest>,
+        request: Request,
+        waiter: Waiter,
+    ) -> bool {
+        let mut state = self.state();
+        let sequence = self.inner.sequence.fetch_add(1, Ordering::Relaxed);
+        state.waiters.insert(sequence, (stream_id, waiter));
+        if requests.send(P2mStreamRequest { sequence, request: Some(request) }).is_err() {
+            state.waiters.remove(&sequence);
+            return false;
+        }
+        true
+    }
+
+    /// Sends a request on the given stream and waits until its reply is received.
+    async fn call_on(&self, stream: &Stream, request: Request) -> Option<Response> {
+        let (sender, receiver) = oneshot::channel();
+        if !self.send_on(stream.id, &stream.requests, request, Waiter::Reply(sender)) {
+            return None;
+        }
+        receiver.await.ok()
+    }
+
+    /// Sends a request on the current stream, opening it if needed.
+    ///
+    /// Returns false if the middleware is unreachable.
+    async fn send(&self, request: Request, waiter: Waiter) -> bool {
+        if !self.connect_if_needed(false).await {
+            return false;
+        }
+        let Some((stream_id, requests)) =
+            self.state().stream.as_ref().map(|stream| (stream.id, stream.requests.clone()))
+        else {
+            return false;
+        };
+        self.send_on(stream_id, &requests, request, waiter)
+    }
+
+    /// Sends a request on the current stream and waits until its reply is received.
+    ///
+    /// Returns None if the middleware is unreachable.
+    async fn call(&self, request: Request) -> Option<Response> {
+        let (sender, receiver) = oneshot::channel();
+        if !self.send(request, Waiter::Reply(sender)).await {
+            return None;
+        }
+        receiver.await.ok()
+    }
+
+    /// Restores the state of the process on a new stream: enrolls the known descriptors again,
+    /// along with their flow declarations and the session of the process, then replays the
+    /// buffered operations.
+    ///
+    /// Returns false if the stream ended meanwhile, the operations not replayed are kept.
+    async fn restore(&self, stream: &Stream) -> bool {
+        let pid = self.inner.pid;
+        let (descriptors, session) = {
+            let state = self.state();
+            (state.descriptors.clone(), state.session.clone())
+        };
+        let mut requests = Vec::new();
+        for (fd, descriptor) in descriptors {
+            requests.push(descriptor.enrollment);
+            requests.push(match descriptor.sources {
+                Some(sources) => Request::DeclareFlow(proto::messages::DeclareFlowCt {
+                    process_id: pid,
+                    file_descriptor: fd,
+                    sources,
+                }),
+                None => Request::ClearFlow(proto::messages::ClearFlowCt {
+                    process_id: pid,
+                    file_descriptor: fd,
+                }),
+            });
+        }
+        requests.push(match session {
+            Some(name) => {
+                Request::OpenSession(proto::messages::OpenSessionCt { process_id: pid, name })
+            }
+            None => Request::EndSession(proto::messages::EndSessionCt { process_id: pid }),
+        });
+        // The requests of a stream are handled in order, so that their replies are not awaited
+        let replay = |request| self.send_on(stream.id, &stream.requests, request, Waiter::Replay);
+        if !requests.into_iter().all(&replay) {
+            return false;
+        }
+
+        // Operations are removed once replayed, so that none is lost if the stream ends
+        loop {
+            let Some(operation) = self.state().buffered.front().cloned() else {
+                return true;
+            };
+            let replayed = match operation {
+                Operation::Request(request) => replay(request),
+                Operation::Report { fd, request } => {
+                    self.send_on(stream.id, &stream.requests, request, Waiter::Report(fd))
+                }
+                Operation::Transfer { fd, flow, peer_socket, result, bytes, offset } => {
+                    let request = match peer_socket {
+                        Some(peer_socket) => {
+                            Request::DatagramIoRequest(proto::messages::DatagramIoInfo {
+                                process_id: pid,
+                                file_descriptor: fd,
+                                peer_socket,
+                                flow,
+                            })
+                        }
+                        None => Request::IoRequest(proto::messages::IoInfo {
+                            process_id: pid,
+                            file_descriptor: fd,
+                            flow,
+                        }),
+                    };
+                    match self.call_on(stream, request).await {
+                        Some(Response::Grant(grant)) => {
+                            replay(Request::IoReport(proto::messages::IoResult {
+                                process_id: pid,
+                                file_descriptor: fd,
+                                grant_id: grant.id,
+                                result,
+                                bytes,
+                                offset,
+                                flow,
+                            }))
+                        }
+                        Some(response) => {
+                            warn!(fd, "[trace2e] Unmediated I/O operation refused on replay");
+                            self.state().refused_reports.insert(fd, refusal(response));
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            };
+            if !replayed {
+                return false;
+            }
+            self.state().buffered.pop_front();
+        }
+    }
+
+    /// Handles an operation that could not be sent to the middleware.
+    ///
+    /// In closed mode, fails unless the operation is `tracked`, i.e. restored on the next
+    /// stream anyway. Otherwise, the operation proceeds, and it is buffered in buffered mode.
+    fn unreachable(&self, operation: Option<Operation>, tracked: bool) -> Result<(), P2mError> {
+        let mode = self.failure_mode();
+        let mut state = self.state();
+        if !state.outage_logged {
+            state.outage_logged = true;
+            warn!(?mode, "[trace2e] Middleware unreachable");
+        }
+        match (mode, operation) {
+            (FailureMode::Closed, _) if !tracked => Err(P2mError::Unreachable),
+            (FailureMode::Buffered, Some(operation)) => {
+                if state.buffered.len() < MAX_BUFFERED_OPERATIONS {
+                    state.buffered.push_back(operation);
+                } else {
+                    warn!("[trace2e] Buffer full, operation not replayed");
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Enrolls a file descriptor, which is enrolled again with `enrollment` on the next
+    /// streams.
+    async fn enroll(&self, fd: i32, request: Request, enrollment: Request) -> Result<(), P2mError> {
+        let response = self.call(request.clone()).await;
+        if let Some(response @ Response::Error(_)) = response {
+            return Err(refusal(response));
+        }
+        self.state().descriptors.insert(fd, Descriptor { enrollment, sources: None });
+        match response {
+            Some(_) => Ok(()),
+            None => self.unreachable(Some(Operation::Request(request)), true),
+        }
+    }
+
+    /// Enrolls a file descriptor opened on a local file.
+    pub async fn local_enroll(
+        &self,
+        path: impl AsRef<Path>,
+        fd: i32,
+        mode: proto::primitives::OpenMode,
+    ) -> std::io::Result<()> {
+        let path = canonicalize(path.as_ref())?.to_string_lossy().into_owned();
+        let local_ct = |mode| {
+            Request::LocalEnroll(proto::messages::LocalCt {
+                process_id: self.inner.pid,
+                file_descriptor: fd,
+                path: path.clone(),
+                mode: Some(mode),
+            })
+        };
+        // Enrolling the descriptor again must not discard what was written since it was opened
+        let enrollment =
+            local_ct(proto::primitives::OpenMode { truncate: false, create_new: false, ..mode });
+        Ok(self.enroll(fd, local_ct(mode), enrollment).await?)
+    }
+
+    /// Enrolls a file descriptor connected to a remote peer.
+    pub async fn remote_enroll(
+        &self,
+        fd: i32,
+        local_socket: String,
+        peer_socket: String,
+    ) -> Result<(), P2mError> {
+        let request = Request::RemoteEnroll(proto::messages::RemoteCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            local_socket,
+            peer_socket,
+        });
+        self.enroll(fd, request.clone(), request).await
+    }
+
+    /// Enrolls a file descriptor connected to a peer process through a Unix domain socket.
+    ///
+    /// The path is the one of the listening socket, empty for unnamed sockets, and the peer
+    /// pid is the one reported by the kernel for the connection.
+    pub async fn unix_enroll(&self, fd: i32, path: String, peer_pid: i32) -> Result<(), P2mError> {
+        let request = Request::UnixEnroll(proto::messages::UnixCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            path,
+            peer_pid,
+        });
+        self.enroll(fd, request.clone(), request).await
+    }
+
+    /// Requests the grant of an I/O operation on a file descriptor.
+    ///
+    /// If the middleware is unreachable, the operation is denied in closed mode, and granted
+    /// without mediation otherwise.
+    pub async fn io_request(&self, fd: i32, flow: i32) -> Result<u128, P2mError> {
+        let request = Request::IoRequest(proto::messages::IoInfo {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            flow,
+        });
+
+        // The replies to the previous reports on the stream are received before this one
+        let grant_id = match self.call(request).await {
+            Some(Response::Grant(grant)) => {
+                grant.id.parse::<u128>().map_err(|_| P2mError::InvalidResponse)?
+            }
+            Some(response) => return Err(refusal(response)),
+            None => return self.unmediated(flow),
+        };
+        self.surface_refused_report(fd, grant_id, flow).await
+    }
+
+    /// Enrolls a datagram socket bound to a local socket.
+    ///
+    /// The peer of each datagram is given along with its request, see
+    /// [`P2mClient::datagram_io_request`].
+    pub async fn datagram_enroll(&self, fd: i32, local_socket: String) -> Result<(), P2mError> {
+        let request = Request::DatagramEnroll(proto::messages::DatagramCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            local_socket,
+        });
+        self.enroll(fd, request.clone(), request).await
+    }
+
+    /// Requests the grant of sending a datagram to, or receiving a datagram from, a peer
+    /// socket on a datagram socket.
+    ///
+    /// The operation is reported with [`P2mClient::io_report`], like any other. If the
+    /// middleware is unreachable, the operation is denied in closed mode, and granted without
+    /// mediation otherwise.
+    pub async fn datagram_io_request(
+        &self,
+        fd: i32,
+        peer_socket: String,
+        flow: i32,
+    ) -> Result<u128, P2mError> {
+        let request = Request::DatagramIoRequest(proto::messages::DatagramIoInfo {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            peer_socket: peer_socket.clone(),
+            flow,
+        });
+
+        let grant_id = match self.call(request).await {
+            Some(Response::Grant(grant)) => {
+                grant.id.parse::<u128>().map_err(|_| P2mError::InvalidResponse)?
+            }
+            Some(response) => return Err(refusal(response)),
+            None => {
+                let grant_id = self.unmediated(flow)?;
+                // The peer is replayed along with the report of the operation
+                self.state().unmediated_peers.insert(fd, peer_socket);
+                return Ok(grant_id);
+            }
+        };
+        self.surface_refused_report(fd, grant_id, flow).await
+    }
+
+    /// Requests the grants of several I/O operations at once, given as `(fd, flow)` pairs.
+    ///
+    /// The middleware evaluates each operation on its own, but queries the policies of the
+    /// streams connected to a same remote node together. Returns the outcome of each
+    /// operation, in request order, every granted operation must be reported with
+    /// [`P2mClient::io_report`].
+    pub async fn io_request_batch(&self, requests: &[(i32, i32)]) -> Vec<Result<u128, P2mError>> {
+        use proto::messages::io_batch_result::Result as BatchResult;
+
+        let request = Request::IoRequestBatch(proto::messages::IoBatch {
+            process_id: self.inner.pid,
+            requests: requests
+                .iter()
+                .map(|&(fd, flow)| proto::messages::IoBatchItem { file_descriptor: fd, flow })
+                .collect(),
+        });
+
+        let results = match self.call(request).await {
+            Some(Response::Grants(grants)) if grants.results.len() == requests.len() => {
+                grants.results
+            }
+            Some(response) => {
+                let error = refusal(response);
+                return requests.iter().map(|_| Err(error.clone())).collect();
+            }
+            None => return requests.iter().map(|&(_, flow)| self.unmediated(flow)).collect(),
+        };
+        let mut grants = Vec::with_capacity(requests.len());
+        for (&(fd, flow), result) in requests.iter().zip(results) {
+            grants.push(match result.result {
+                Some(BatchResult::Grant(grant)) => match grant.id.parse() {
+                    Ok(grant_id) => self.surface_refused_report(fd, grant_id, flow).await,
+                    Err(_) => Err(P2mError::InvalidResponse),
+                },
+                Some(BatchResult::Error(error)) => Err(error.into()),
+                None => Err(P2mError::InvalidResponse),
+            });
+        }
+        grants
+    }
+
+    /// Handles an I/O request that could not be sent to the middleware, the operation is
+    /// buffered once reported.
+    fn unmediated(&self, flow: i32) -> Result<u128, P2mError> {
+        self.unreachable(None, false)?;
+        Ok(unmediated_grant(flow))
+    }
+
+    /// Returns the grant, unless a previous report on the same file descriptor was refused, in
+    /// which case the grant is released and the refusal is surfaced instead.
+    async fn surface_refused_report(
+        &self,
+        fd: i32,
+        grant_id: u128,
+        flow: i32,
+    ) -> Result<u128, P2mError> {
+        let refused = self.state().refused_reports.remove(&fd);
+        if let Some(error) = refused {
+            // Release the grant, the operation is not performed
+            self.io_report(fd, grant_id, flow, false, 0, None).await?;
+            return Err(P2mError::ReportRefused(Box::new(error)));
+        }
+        Ok(grant_id)
+    }
+
+    /// Reports the outcome of a granted operation without waiting for its acknowledgement.
+    ///
+    /// The operation is reported in the direction it was granted for. A refused report is
+    /// surfaced later. In buffered mode, reports that cannot be sent and operations that were
+    /// not mediated are buffered, they are ignored otherwise.
+    pub async fn io_report(
+        &self,
+        fd: i32,
+        grant_id: u128,
+        flow: i32,
+        result: bool,
+        bytes: u64,
+        offset: Option<u64>,
+    ) -> Result<(), P2mError> {
+        if let Some(flow) = unmediated_flow(grant_id) {
+            let peer_socket = self.state().unmediated_peers.remove(&fd);
+            let transfer = Operation::Transfer { fd, flow, peer_socket, result, bytes, offset };
+            return self.unreachable(Some(transfer), true);
+        }
+        let request = Self::report(self.inner.pid, fd, grant_id, flow, result, bytes, offset);
+
+        if self.send(request, Waiter::Report(fd)).await {
+            Ok(())
+        } else {
+            let request = Self::report(self.inner.pid, fd, grant_id, flow, result, bytes, offset);
+            self.unreachable(Some(Operation::Report { fd, request }), false)
+        }
+    }
+
+    /// Reports the outcome of a granted operation on the current stream, before returning.
+    ///
+    /// Unlike [`P2mClient::io_report`], the report is ordered before any operation the process
+    /// starts afterward, even from another task, but the stream is not opened again if it was
+    /// lost since the grant: the report is then handled as if the middleware were unreachable.
+    pub fn io_report_now(
+        &self,
+        fd: i32,
+        grant_id: u128,
+        flow: i32,
+        result: bool,
+        bytes: u64,
+        offset: Option<u64>,
+    ) -> Result<(), P2mError> {
+        if let Some(flow) = unmediated_flow(grant_id) {
+            let peer_socket = self.state().unmediated_peers.remove(&fd);
+            let transfer = Operation::Transfer { fd, flow, peer_socket, result, bytes, offset };
+            return self.unreachable(Some(transfer), true);
+        }
+        let request = Self::report(self.inner.pid, fd, grant_id, flow, result, bytes, offset);
+
+        let stream =
+            self.state().stream.as_ref().map(|stream| (stream.id, stream.requests.clone()));
+        let sent = stream.is_some_and(|(stream_id, requests)| {
+            self.send_on(stream_id, &requests, request, Waiter::Report(fd))
+        });
+        if sent {
+            Ok(())
+        } else {
+            let request = Self::report(self.inner.pid, fd, grant_id, flow, result, bytes, offset);
+            self.unreachable(Some(Operation::Report { fd, request }), false)
+        }
+    }
+
+    fn report(
+        process_id: i32,
+        fd: i32,
+        grant_id: u128,
+        flow: i32,
+        result: bool,
+        bytes: u64,
+        offset: Option<u64>,
+    ) -> Request {
+        Request::IoReport(proto::messages::IoResult {
+            process_id,
+            file_descriptor: fd,
+            grant_id: grant_id.to_string(),
+            result,
+            bytes,
+            offset,
+            flow,
+        })
+    }
+
+    /// Waits until all the previous requests of the process are handled by the middleware.
+    ///
+    /// Fails if any pipelined report was refused since the last call, or if the middleware is
+    /// unreachable.
+    pub async fn flush(&self) -> Result<(), P2mError> {
+        let response = self.call(Request::Flush(proto::messages::FlushCt {})).await;
+        let refused = std::mem::take(&mut self.state().refused_reports);
+        match response {
+            Some(Response::Ack(_)) => match refused.into_values().next() {
+                Some(error) => Err(P2mError::ReportRefused(Box::new(error))),
+                None => Ok(()),
+            },
+            Some(response) => Err(refusal(response)),
+            None => Err(P2mError::Unreachable),
+        }
+    }
+
+    /// Checks whether the middleware is reachable, attempting to reconnect right away if not.
+    ///
+    /// Like [`P2mClient::flush`], the check waits until the previous requests of the process
+    /// are handled.
+    pub async fn health_check(&self) -> Health {
+        let reachable = self.connect_if_needed(true).await
+            && matches!(
+                self.call(Request::Flush(proto::messages::FlushCt {})).await,
+                Some(Response::Ack(_))
+            );
+        Health { reachable, mode: self.failure_mode(), buffered: self.state().buffered.len() }
+    }
+
+    /// Declares that the writes on a file descriptor derive from the given sources only.
+    pub async fn declare_flow(&self, fd: i32, sources: Vec<i32>) -> Result<(), P2mError> {
+        let request = Request::DeclareFlow(proto::messages::DeclareFlowCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            sources: sources.clone(),
+        });
+
+        match self.call(request.clone()).await {
+            Some(Response::Ack(_)) => (),
+            Some(response) => return Err(refusal(response)),
+            None => self.unreachable(Some(Operation::Request(request)), false)?,
+        }
+        if let Some(descriptor) = self.state().descriptors.get_mut(&fd) {
+            descriptor.sources = Some(sources);
+        }
+        Ok(())
+    }
+
+    /// Clears the flow declaration of a file descriptor.
+    pub async fn clear_flow(&self, fd: i32) {
+        if let Some(descriptor) = self.state().descriptors.get_mut(&fd) {
+            descriptor.sources = None;
+        }
+        let request = Request::ClearFlow(proto::messages::ClearFlowCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+        });
+        if self.call(request.clone()).await.is_none() {
+            let _ = self.unreachable(Some(Operation::Request(request)), true);
+        }
+    }
+
+    /// Opens a session, which groups the following operations of the process.
+    pub async fn open_session(&self, name: impl Into<String>) -> Result<(), P2mError> {
+        let name = name.into();
+        let request = Request::OpenSession(proto::messages::OpenSessionCt {
+            process_id: self.inner.pid,
+            name: name.clone(),
+        });
+
+        match self.call(request.clone()).await {
+            Some(Response::Ack(_)) => (),
+            Some(response) => return Err(refusal(response)),
+            None => self.unreachable(Some(Operation::Request(request)), false)?,
+        }
+        self.state().session = Some(name);
+        Ok(())
+    }
+
+    /// Ends the session of the process.
+    pub async fn end_session(&self) -> Result<(), P2mError> {
+        self.state().session = None;
+        let request =
+            Request::EndSession(proto::messages::EndSessionCt { process_id: self.inner.pid });
+
+        match self.call(request.clone()).await {
+            Some(Response::Ack(_)) => Ok(()),
+            Some(response) => Err(refusal(response)),
+            None => self.unreachable(Some(Operation::Request(request)), true),
+        }
+    }
+
+    /// Unenrolls a file descriptor, before it is closed.
+    pub async fn close(&self, fd: i32) {
+        self.state().descriptors.remove(&fd);
+        let request = Request::Close(proto::messages::CloseCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+        });
+        if self.call(request.clone()).await.is_none() {
+            let _ = self.unreachable(Some(Operation::Request(request)), true);
+        }
+        // Reports on a closed file descriptor are not surfaced to its next owner
+        let mut state = self.state();
+        state.refused_reports.remove(&fd);
+        state.unmediated_peers.remove(&fd);
+    }
+
+    /// Truncates the provenance of a file descriptor, which fails in closed mode if the
+    /// middleware is unreachable.
+    pub async fn truncate(&self, fd: i32) -> Result<(), P2mError> {
+        let request = Request::Truncate(proto::messages::TruncateCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+        });
+        match self.call(request.clone()).await {
+            Some(Response::Ack(_)) => Ok(()),
+            Some(response) => Err(refusal(response)),
+            None => self.unreachable(Some(Operation::Request(request)), false),
+        }
+    }
+
+    /// Enrolls a duplicate of a file descriptor with the same resource.
+    pub async fn dup(&self, fd: i32, new_fd: i32) -> Result<(), P2mError> {
+        {
+            let mut state = self.state();
+            if let Some(mut descriptor) = state.descriptors.get(&fd).cloned() {
+                match &mut descriptor.enrollment {
+                    Request::LocalEnroll(local_ct) => local_ct.file_descriptor = new_fd,
+                    Request::RemoteEnroll(remote_ct) => remote_ct.file_descriptor = new_fd,
+                    Request::UnixEnroll(unix_ct) => unix_ct.file_descriptor = new_fd,
+                    Request::DatagramEnroll(datagram_ct) => datagram_ct.file_descriptor = new_fd,
+                    _ => (),
+                }
+                // Flow declarations are not inherited by the duplicate
+                descriptor.sources = None;
+                state.descriptors.insert(new_fd, descriptor);
+            }
+        }
+        let request = Request::Dup(proto::messages::DupCt {
+            process_id: self.inner.pid,
+            file_descriptor: fd,
+            new_file_descriptor: new_fd,
+        });
+        match self.call(request.clone()).await {
+            Some(Response::Ack(_)) => Ok(()),
+            Some(response) => Err(refusal(response)),
+            None => self.unreachable(Some(Operation::Request(request)), true),
+        }
+    }
+}
+
+/// Dispatches the replies received on a stream to their waiters until the stream ends, or
+/// until every handle of the client is dropped.
+async fn drive(
+    inner: Weak<Inner>,
+    mut inbound: tonic::Streaming<P2mStreamResponse>,
+    id: u64,
+    ended: Arc<AtomicBool>,
+) {
+    while let Ok(Some(reply)) = inbound.message().await {
+        let (Some(inner), Some(response)) = (inner.upgrade(), reply.response) else {
+            continue;
+        };
+        let mut state = inner.state.lock().unwrap();
+        match state.waiters.remove(&reply.sequence).map(|(_, waiter)| waiter) {
+            Some(Waiter::Reply(sender)) => {
+                let _ = sender.send(response);
+            }
+            Some(Waiter::Report(fd)) => {
+                if let Response::Error(error) = response {
+                    state.refused_reports.insert(fd, error.into());
+                }
+            }
+            _ => (),
+        }
+    }
+    ended.store(true, Ordering::Relaxed);
+    let Some(inner) = inner.upgrade() else {
+        return;
+    };
+    // Dropping the waiters wakes up the callers
+    let mut state = inner.state.lock().unwrap();
+    if state.stream.as_ref().is_some_and(|stream| stream.id == id) {
+        state.stream = None;
+    }
+    state.waiters.retain(|_, (stream_id, _)| *stream_id != id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TraceabilityError;
+    use std::os::fd::AsRawFd;
+    use tonic::codegen::Service;
+    use tonic::transport::Server;
+    use trace2e_core::{
+        traceability::{
+            api::{O2mRequest, O2mResponse},
+            infrastructure::naming::Resource,
+            init_middleware,
+        },
+        transport::{
+            grpc::{P2mHandler, p2m_uds_incoming, proto::p2m_server::P2mServer},
+            nop::M2mNop,
+        },
+    };
+
+    #[tokio::test]
+    async fn client_current_thread_runtime() {
+        let tmp = std::env::temp_dir();
+        let socket = tmp.join(format!("trace2e_client_async_{}.sock", id()));
+        let path = tmp.join(format!("trace2e_client_async_{}.txt", id()));
+        let _ = std::fs::remove_file(&socket);
+        let client = P2mClient::new(format!("unix://{}", socket.display()));
+
+        let file = std::fs::File::create(&path).unwrap();
+        let fd = file.as_raw_fd();
+        let output = Flow::Output as i32;
+
+        // The middleware is not started yet
+        client.local_enroll(&path, fd, Default::default()).await.unwrap();
+        assert_eq!(client.io_request(fd, output).await, Err(P2mError::Unreachable));
+
+        let (_, p2m_service, _) = init_middleware("127.0.0.1".to_string(), None, 0, M2mNop, false);
+        let incoming = p2m_uds_incoming(&socket).unwrap();
+        let server = tokio::spawn(
+            Server::builder()
+                .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+                .serve_with_incoming(incoming),
+        );
+        assert!(client.health_check().await.reachable);
+
+        // Clones share the stream, and the enrollment was restored on it
+        let grant_id = client.clone().io_request(fd, output).await.unwrap();
+        assert!(unmediated_flow(grant_id).is_none());
+        client.io_report(fd, grant_id, output, true, 4, Some(0)).await.unwrap();
+        client.flush().await.unwrap();
+        client.close(fd).await;
+        let error = client.io_request(fd, output).await.unwrap_err();
+        assert_eq!(
+            error.traceability_error(),
+            Some(&TraceabilityError::UndeclaredResource(id() as i32, fd))
+        );
+
+        server.abort();
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&socket);
+    }
+
+    #[tokio::test]
+    async fn client_buffered_reports() {
+        let tmp = std::env::temp_dir();
+        let socket = tmp.join(format!("trace2e_client_buffered_{}.sock", id()));
+        let path = tmp.join(format!("trace2e_client_buffered_{}.txt", id()));
+        let _ = std::fs::remove_file(&socket);
+        let client = P2mClient::new(format!("unix://{}", socket.display()))
+            .with_failure_mode(FailureMode::Buffered);
+        let (_, p2m_service, mut o2m_service) =
+            init_middleware("127.0.0.1".to_string(), None, 0, M2mNop, false);
+        // Serves the middleware on a new socket, the previous one being unreachable
+        let serve = || {
+            let _ = std::fs::remove_file(&socket);
+            let incoming = p2m_uds_incoming(&socket).unwrap();
+            tokio::spawn(
+                Server::builder()
+                    .add_service(P2mServer::new(P2mHandler::new(p2m_service.clone())))
+                    .serve_with_incoming(incoming),
+            )
+        };
+        // Loses the stream to the middleware, which cannot be reached again until served anew
+        let outage = || {
+            std::fs::remove_file(&socket).unwrap();
+            client.state().stream = None;
+        };
+
+        let file = std::fs::File::create(&path).unwrap();
+        let fd = file.as_raw_fd();
+        let output = Flow::Output as i32;
+        let mut servers = vec![serve()];
+        client.local_enroll(&path, fd, Default::default()).await.unwrap();
+
+        // The report of an operation granted before the outage is buffered, then replayed
+        let grant_id = client.io_request(fd, output).await.unwrap();
+        assert!(unmediated_flow(grant_id).is_none());
+        outage();
+        client.io_report(fd, grant_id, output, true, 4, Some(0)).await.unwrap();
+        assert_eq!(client.health_check().await.buffered, 1);
+        servers.push(serve());
+        assert_eq!(
+            client.health_check().await,
+            Health { reachable: true, mode: FailureMode::Buffered, buffered: 0 }
+        );
+        client.flush().await.unwrap();
+        // The replayed report released the flow
+        let grant_id = client.io_request(fd, output).await.unwrap();
+        client.io_report(fd, grant_id, output, true, 4, Some(0)).await.unwrap();
+        client.flush().await.unwrap();
+
+        // An unmediated operation refused on replay is surfaced to the process
+        outage();
+        let grant_id = client.io_request(fd, output).await.unwrap();
+        assert_eq!(grant_id, unmediated_grant(output));
+        client.io_report(fd, grant_id, output, true, 4, Some(0)).await.unwrap();
+        let deleted = Resource::new_file(path.to_string_lossy().into_owned());
+        assert_eq!(
+            o2m_service.call(O2mRequest::SetDeleted(deleted)).await.unwrap(),
+            O2mResponse::Ack
+        );
+        servers.push(serve());
+        assert!(client.health_check().await.reachable);
+        assert!(matches!(client.flush().await, Err(P2mError::ReportRefused(_))));
+
+        servers.iter().for_each(|server| server.abort());
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&socket);
+    }
+}
@@ -0,0 +1,132 @@
+//! Errors of the operations mediated by the trace2e middleware.
+//!
+//! The middleware refuses a request with a gRPC status code reflecting the class of the
+//! refusal, along with the traceability error that caused it. Both are decoded into a
+//! [`P2mError`], which converts into an [`std::io::Error`] of the matching kind, so that
+//! callers of I/O wrappers can retrieve it with [`std::io::Error::get_ref`].
+use std::{fmt, io};
+
+use tonic::Code;
+pub use trace2e_core::traceability::error::TraceabilityError;
+use trace2e_core::transport::grpc::proto::messages::P2mStreamError;
+
+/// Error of a Process-to-Middleware operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum P2mError {
+    /// The middleware refused the operation because of a traceability error
+    Refused { code: Code, error: Box<TraceabilityError> },
+    /// The middleware refused the operation without telling why
+    Status { code: Code, message: String },
+    /// A previous pipelined report on the same file descriptor was refused
+    ReportRefused(Box<P2mError>),
+    /// The middleware cannot be reached, and the process fails closed
+    Unreachable,
+    /// The middleware sent a reply that does not match the request
+    InvalidResponse,
+}
+
+impl P2mError {
+    /// Returns the gRPC status code of the refusal.
+    pub fn code(&self) -> Code {
+        match self {
+            P2mError::Refused { code, .. } | P2mError::Status { code, .. } => *code,
+            P2mError::ReportRefused(error) => error.code(),
+            P2mError::Unreachable => Code::Unavailable,
+            P2mError::InvalidResponse => Code::Internal,
+        }
+    }
+
+    /// Returns the traceability error that caused the refusal, if known.
+    pub fn traceability_error(&self) -> Option<&TraceabilityError> {
+        match self {
+            P2mError::Refused { error, .. } => Some(error),
+            P2mError::ReportRefused(error) => error.traceability_error(),
+            _ => None,
+        }
+    }
+
+    /// Returns the kind of I/O error matching the refusal.
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            P2mError::Unreachable => io::ErrorKind::NotConnected,
+            P2mError::InvalidResponse => io::ErrorKind::InvalidData,
+            P2mError::ReportRefused(error) => error.kind(),
+            _ => match self.code() {
+                Code::PermissionDenied => io::ErrorKind::PermissionDenied,
+                Code::NotFound => io::ErrorKind::NotFound,
+                Code::AlreadyExists => io::ErrorKind::AlreadyExists,
+                Code::InvalidArgument | Code::FailedPrecondition => io::ErrorKind::InvalidInput,
+                Code::DeadlineExceeded => io::ErrorKind::TimedOut,
+                Code::Aborted | Code::ResourceExhausted => io::ErrorKind::ResourceBusy,
+                Code::Unavailable => io::ErrorKind::NotConnected,
+                _ => io::ErrorKind::Other,
+            },
+        }
+    }
+}
+
+impl fmt::Display for P2mError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            P2mError::Refused { error, .. } => write!(f, "{error}"),
+            P2mError::Status { code, message } => write!(f, "{message} ({code:?})"),
+            P2mError::ReportRefused(_) => write!(f, "previous I/O report refused"),
+            P2mError::Unreachable => write!(f, "trace2e middleware unreachable"),
+            P2mError::InvalidResponse => write!(f, "invalid response from the trace2e middleware"),
+        }
+    }
+}
+
+impl std::error::Error for P2mError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            P2mError::ReportRefused(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// Decodes the error reported by the middleware on a P2M stream.
+impl From<P2mStreamError> for P2mError {
+    fn from(error: P2mStreamError) -> Self {
+        let code = Code::from_i32(error.code);
+        match error.details.and_then(|details| TraceabilityError::try_from(details).ok()) {
+            Some(error) => P2mError::Refused { code, error: Box::new(error) },
+            None => P2mError::Status { code, message: error.message },
+        }
+    }
+}
+
+/// Embeds the error as the source of an I/O error of the matching kind.
+impl From<P2mError> for io::Error {
+    fn from(error: P2mError) -> Self {
+        io::Error::new(error.kind(), error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use trace2e_core::traceability::infrastructure::naming::Resource;
+
+    use super::*;
+
+    #[test]
+    fn client_io_error_kind() {
+        let busy = P2mError::Refused {
+            code: Code::Aborted,
+            error: Box::new(TraceabilityError::UnavailableDestination(Resource::new_file(
+                "/tmp/test.txt".to_string(),
+            ))),
+        };
+        let error = io::Error::from(busy.clone());
+        assert_eq!(error.kind(), io::ErrorKind::ResourceBusy);
+        assert_eq!(error.get_ref().and_then(|e| e.downcast_ref::<P2mError>()), Some(&busy));
+
+        let violation = P2mError::Refused {
+            code: Code::PermissionDenied,
+            error: Box::new(TraceabilityError::DirectPolicyViolation),
+        };
+        assert_eq!(io::Error::from(violation).kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(io::Error::from(P2mError::Unreachable).kind(), io::ErrorKind::NotConnected);
+    }
+}
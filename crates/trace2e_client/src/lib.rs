@@ -1,3 +1,5 @@
+pub mod client;
+pub mod error;
 pub mod p2m;
 
 #[cfg(feature = "o2m")]
@@ -1,24 +1,53 @@
-use once_cell::{sync::Lazy, unsync::OnceCell};
+use once_cell::unsync::OnceCell;
+use std::{process::id, sync::Mutex};
 use tokio::{
-    runtime::Handle,
+    runtime::{Handle, Runtime},
     task::{self, block_in_place},
 };
 use tonic::transport::Channel;
 use trace2e_core::traceability::{infrastructure::naming, services::consent};
-use trace2e_core::transport::grpc::proto;
+use trace2e_core::transport::grpc::{DEFAULT_O2M_SOCKET, connect, proto};
 
-static TOKIO_RUNTIME: Lazy<tokio::runtime::Runtime> =
-    Lazy::new(|| tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap());
-
-// Get the gRPC URL from environment variable or use default
+// Get the gRPC URL of the O2M API from environment variables or use default. The O2M API is
+// served on its own Unix domain socket, and over TCP only by a middleware started with
+// `--o2m-tcp`.
 fn get_grpc_url() -> String {
-    std::env::var("TRACE2E_MIDDLEWARE_URL").unwrap_or_else(|_| "http://[::1]:50051".to_string())
+    std::env::var("TRACE2E_O2M_URL").unwrap_or_else(|_| format!("unix://{DEFAULT_O2M_SOCKET}"))
+}
+
+/// Runtime and client of a process.
+#[derive(Clone)]
+struct Process {
+    pid: u32,
+    runtime: &'static Runtime,
+    client: proto::o2m_client::O2mClient<Channel>,
+}
+
+static PROCESS: Mutex<Option<Process>> = Mutex::new(None);
+
+/// Returns the runtime and the global client of the current process, created on first use.
+///
+/// A forked child connects again on its own runtime, since the threads of the runtime of its
+/// parent, and the connection they drive, do not exist in the child. Those of the parent are
+/// leaked, as they cannot be shut down from the child.
+fn process() -> Process {
+    let mut process = PROCESS.lock().unwrap();
+    if let Some(current) = process.as_ref().filter(|current| current.pid == id()) {
+        return current.clone();
+    }
+    let runtime: &'static Runtime = Box::leak(Box::new(
+        tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap(),
+    ));
+    let client =
+        runtime.block_on(connect(&get_grpc_url())).map(proto::o2m_client::O2mClient::new).unwrap();
+    let current = Process { pid: id(), runtime, client };
+    *process = Some(current.clone());
+    current
 }
 
-static O2M_CLIENT: Lazy<proto::o2m_client::O2mClient<Channel>> = Lazy::new(|| {
-    let rt = &*TOKIO_RUNTIME;
-    rt.block_on(proto::o2m_client::O2mClient::connect(get_grpc_url())).unwrap()
-});
+fn runtime() -> &'static Runtime {
+    process().runtime
+}
 
 #[allow(clippy::result_large_err)]
 fn get_o2m_client() -> proto::o2m_client::O2mClient<Channel> {
@@ -33,7 +62,8 @@ fn get_o2m_client() -> proto::o2m_client::O2mClient<Channel> {
             cell.get_or_init(|| {
                 block_in_place(|| {
                     Handle::current()
-                        .block_on(proto::o2m_client::O2mClient::connect(get_grpc_url()))
+                        .block_on(connect(&get_grpc_url()))
+                        .map(proto::o2m_client::O2mClient::new)
                         .unwrap()
                 })
             })
@@ -41,7 +71,7 @@ fn get_o2m_client() -> proto::o2m_client::O2mClient<Channel> {
         })
     } else {
         // Use the global client
-        O2M_CLIENT.clone()
+        process().client
     }
 }
 
@@ -64,7 +94,7 @@ pub fn get_policies(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_get_policies(request)) {
+        match runtime().block_on(client.o2m_get_policies(request)) {
             Ok(response) => Ok(response.into_inner().policies),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -92,7 +122,7 @@ pub fn set_policy(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_set_policy(request)) {
+        match runtime().block_on(client.o2m_set_policy(request)) {
             Ok(_) => Ok(()),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -120,7 +150,7 @@ pub fn set_confidentiality(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_set_confidentiality(request)) {
+        match runtime().block_on(client.o2m_set_confidentiality(request)) {
             Ok(_) => Ok(()),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -148,7 +178,35 @@ pub fn set_integrity(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_set_integrity(request)) {
+        match runtime().block_on(client.o2m_set_integrity(request)) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn set_declassification(
+    resource: naming::Resource,
+    declassification: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let proto_resource: proto::primitives::Resource = resource.into();
+    let request = tonic::Request::new(proto::messages::SetDeclassificationRequest {
+        resource: Some(proto_resource),
+        declassification,
+    });
+
+    if let Ok(handle) = Handle::try_current() {
+        match block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_set_declassification(request))
+        }) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_set_declassification(request)) {
             Ok(_) => Ok(()),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -171,7 +229,7 @@ pub fn set_deleted(resource: naming::Resource) -> Result<(), Box<dyn std::error:
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_set_deleted(request)) {
+        match runtime().block_on(client.o2m_set_deleted(request)) {
             Ok(_) => Ok(()),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -198,7 +256,7 @@ pub fn enforce_consent(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_enforce_consent(request)) {
+        match runtime().block_on(client.o2m_enforce_consent(request)) {
             Ok(response) => Ok(response.into_inner()),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -229,7 +287,7 @@ pub fn set_consent_decision(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_set_consent_decision(request)) {
+        match runtime().block_on(client.o2m_set_consent_decision(request)) {
             Ok(_) => Ok(()),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
@@ -255,9 +313,172 @@ pub fn get_references(
         }
     } else {
         let mut client = get_o2m_client();
-        match TOKIO_RUNTIME.block_on(client.o2m_get_references(request)) {
+        match runtime().block_on(client.o2m_get_references(request)) {
             Ok(response) => Ok(response.into_inner().references),
             Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
         }
     }
 }
+
+#[allow(clippy::result_large_err)]
+pub fn watch_deadlocks() -> Result<
+    tonic::codec::Streaming<proto::messages::DeadlockNotification>,
+    Box<dyn std::error::Error>,
+> {
+    let request = tonic::Request::new(proto::messages::WatchDeadlocksRequest {});
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_watch_deadlocks(request))
+        }) {
+            Ok(response) => Ok(response.into_inner()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_watch_deadlocks(request)) {
+            Ok(response) => Ok(response.into_inner()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn list_reservations() -> Result<Vec<proto::messages::Reservation>, Box<dyn std::error::Error>>
+{
+    let request = tonic::Request::new(proto::messages::ListReservationsRequest {});
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_list_reservations(request))
+        }) {
+            Ok(response) => Ok(response.into_inner().reservations),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_list_reservations(request)) {
+            Ok(response) => Ok(response.into_inner().reservations),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn list_pending_grants()
+-> Result<Vec<proto::messages::PendingGrant>, Box<dyn std::error::Error>> {
+    let request = tonic::Request::new(proto::messages::ListPendingGrantsRequest {});
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_list_pending_grants(request))
+        }) {
+            Ok(response) => Ok(response.into_inner().grants),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_list_pending_grants(request)) {
+            Ok(response) => Ok(response.into_inner().grants),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn get_queue_depths() -> Result<Vec<proto::messages::QueueDepth>, Box<dyn std::error::Error>> {
+    let request = tonic::Request::new(proto::messages::GetQueueDepthsRequest {});
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_get_queue_depths(request))
+        }) {
+            Ok(response) => Ok(response.into_inner().depths),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_get_queue_depths(request)) {
+            Ok(response) => Ok(response.into_inner().depths),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn get_volumes(
+    resources: Vec<naming::Resource>,
+) -> Result<Vec<proto::messages::ResourceVolume>, Box<dyn std::error::Error>> {
+    let proto_resources: Vec<proto::primitives::Resource> =
+        resources.into_iter().map(|r| r.into()).collect();
+    let request =
+        tonic::Request::new(proto::messages::GetVolumesRequest { resources: proto_resources });
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_get_volumes(request))
+        }) {
+            Ok(response) => Ok(response.into_inner().volumes),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_get_volumes(request)) {
+            Ok(response) => Ok(response.into_inner().volumes),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn get_lineage_events(
+    resource: naming::Resource,
+) -> Result<Vec<proto::messages::LineageEvent>, Box<dyn std::error::Error>> {
+    let request = tonic::Request::new(proto::messages::GetLineageEventsRequest {
+        resource: Some(resource.into()),
+    });
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_get_lineage_events(request))
+        }) {
+            Ok(response) => Ok(response.into_inner().events),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_get_lineage_events(request)) {
+            Ok(response) => Ok(response.into_inner().events),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn force_release(destination: naming::Resource) -> Result<(), Box<dyn std::error::Error>> {
+    let request = tonic::Request::new(proto::messages::ForceReleaseRequest {
+        destination: Some(destination.into()),
+    });
+
+    if let Ok(handle) = Handle::try_current() {
+        match task::block_in_place(move || {
+            let mut client = get_o2m_client();
+            handle.block_on(client.o2m_force_release(request))
+        }) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    } else {
+        let mut client = get_o2m_client();
+        match runtime().block_on(client.o2m_force_release(request)) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e) as Box<dyn std::error::Error>),
+        }
+    }
+}
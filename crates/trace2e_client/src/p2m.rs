@@ -1,138 +1,297 @@
-use once_cell::{sync::Lazy, unsync::OnceCell};
-use std::{fs::canonicalize, path::Path, process::id};
-use tokio::{
-    runtime::Handle,
-    task::{self, block_in_place},
+//! Synchronous Process-to-Middleware client.
+//!
+//! Each function runs the matching operation of a process-wide [`P2mClient`], configured by
+//! the environment (see [`P2mClient::from_env`]), on a runtime owned by this module, and blocks
+//! the calling thread until it completes. A forked child gets its own client and runtime on
+//! first use, see [`P2mClient::forked`]. No runtime of the caller is nested, so that these
+//! functions may be called from any thread, although asynchronous applications should rather
+//! own a [`P2mClient`] and await its operations.
+//!
+//! See the [`client`](crate::client) module for the semantics of the operations, and for the
+//! behaviour of the client while the middleware is unreachable.
+use std::{
+    future::Future,
+    path::Path,
+    process::id,
+    sync::{Mutex, mpsc},
 };
-use tonic::transport::Channel;
 
+use tokio::runtime::Runtime;
 use trace2e_core::transport::grpc::proto;
 
-static TOKIO_RUNTIME: Lazy<tokio::runtime::Runtime> =
-    Lazy::new(|| tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap());
+pub use crate::client::{
+    FailureMode, Health, MAX_BUFFERED_OPERATIONS, P2mClient, RECONNECT_INTERVAL,
+};
+use crate::error::P2mError;
 
-// Get the gRPC URL from environment variable or use default
-fn get_grpc_url() -> String {
-    std::env::var("TRACE2E_MIDDLEWARE_URL").unwrap_or_else(|_| "http://[::1]:50051".to_string())
+/// Runtime and client of a process.
+#[derive(Clone, Copy)]
+struct Process {
+    pid: u32,
+    runtime: &'static Runtime,
+    client: &'static P2mClient,
 }
 
-static P2M_CLIENT: Lazy<proto::p2m_client::P2mClient<Channel>> = Lazy::new(|| {
-    let rt = &*TOKIO_RUNTIME;
-    rt.block_on(proto::p2m_client::P2mClient::connect(get_grpc_url())).unwrap()
-});
+static PROCESS: Mutex<Option<Process>> = Mutex::new(None);
 
-// Gets an appropriate P2M client for the current runtime context
-fn get_p2m_client() -> proto::p2m_client::P2mClient<Channel> {
-    if Handle::try_current().is_ok() {
-        // We're already in a Tokio runtime, use thread-local client for this runtime
-        thread_local! {
-            static RUNTIME_CLIENT: OnceCell<proto::p2m_client::P2mClient<Channel>> =
-                const { OnceCell::new() };
+/// Returns the runtime and the client of the current process, created on first use.
+///
+/// A forked child gets its own runtime and client, since the threads of the runtime of its
+/// parent, and the stream they drive, do not exist in the child. Those of the parent are
+/// leaked, as they cannot be shut down from the child.
+fn process() -> Process {
+    let mut process = PROCESS.lock().unwrap();
+    match *process {
+        Some(current) if current.pid == id() => current,
+        parent => {
+            let client = match parent {
+                Some(parent) => parent.client.forked(),
+                None => P2mClient::from_env(),
+            };
+            let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+            let current = Process {
+                pid: id(),
+                runtime: Box::leak(Box::new(runtime)),
+                client: Box::leak(Box::new(client)),
+            };
+            *process = Some(current);
+            current
         }
-
-        RUNTIME_CLIENT.with(|cell| {
-            cell.get_or_init(|| {
-                block_in_place(|| {
-                    Handle::current()
-                        .block_on(proto::p2m_client::P2mClient::connect(get_grpc_url()))
-                        .unwrap()
-                })
-            })
-            .clone()
-        })
-    } else {
-        // Use the global client
-        P2M_CLIENT.clone()
     }
 }
 
-pub fn local_enroll(path: impl AsRef<Path>, fd: i32) {
-    let request = tonic::Request::new(proto::messages::LocalCt {
-        process_id: id() as i32,
-        file_descriptor: fd,
-        path: canonicalize(path.as_ref()).unwrap().to_str().unwrap().to_string(),
+/// Returns the client of the current process.
+fn client() -> &'static P2mClient {
+    process().client
+}
+
+/// Runs an operation of the process-wide client and blocks until it completes.
+fn block_on<T: Send + 'static>(operation: impl Future<Output = T> + Send + 'static) -> T {
+    let (sender, receiver) = mpsc::sync_channel(1);
+    process().runtime.spawn(async move {
+        let _ = sender.send(operation.await);
     });
+    receiver.recv().expect("trace2e client operation panicked")
+}
 
-    if let Ok(handle) = Handle::try_current() {
-        task::block_in_place(move || {
-            let mut client = get_p2m_client();
-            let _ = handle.block_on(client.p2m_local_enroll(request));
-        });
-    } else {
-        let mut client = get_p2m_client();
-        let _ = TOKIO_RUNTIME.block_on(client.p2m_local_enroll(request));
-    }
+/// Spawns an operation of the process-wide client on the runtime of this module.
+///
+/// Asynchronous wrappers await the returned handle rather than the operation itself, so that
+/// the stream of the process does not depend on the runtime of their caller, which may be shut
+/// down long before the process exits.
+pub fn spawn<F>(operation: impl FnOnce(P2mClient) -> F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let Process { runtime, client, .. } = process();
+    runtime.spawn(operation(client.clone()))
 }
 
-pub fn remote_enroll(fd: i32, local_socket: String, peer_socket: String) {
-    let request = tonic::Request::new(proto::messages::RemoteCt {
-        process_id: id() as i32,
-        file_descriptor: fd,
-        local_socket,
-        peer_socket,
-    });
+/// Returns the failure mode of the process.
+pub fn failure_mode() -> FailureMode {
+    client().failure_mode()
+}
 
-    if let Ok(handle) = Handle::try_current() {
-        block_in_place(move || {
-            let mut client = get_p2m_client();
-            let _ = handle.block_on(client.p2m_remote_enroll(request));
-        });
-    } else {
-        let mut client = get_p2m_client();
-        let _ = TOKIO_RUNTIME.block_on(client.p2m_remote_enroll(request));
-    }
+/// Sets the failure mode of the process, overriding the `TRACE2E_FAILURE_MODE` variable.
+pub fn set_failure_mode(mode: FailureMode) {
+    client().set_failure_mode(mode);
 }
 
-#[allow(clippy::result_large_err)]
-pub fn io_request(fd: i32, flow: i32) -> Result<u128, Box<dyn std::error::Error>> {
-    let request = tonic::Request::new(proto::messages::IoInfo {
-        process_id: id() as i32,
-        file_descriptor: fd,
-        flow,
-    });
+pub fn local_enroll(
+    path: impl AsRef<Path>,
+    fd: i32,
+    mode: proto::primitives::OpenMode,
+) -> std::io::Result<()> {
+    let path = path.as_ref().to_path_buf();
+    block_on(client().local_enroll(path, fd, mode))
+}
 
-    let result = if let Ok(handle) = Handle::try_current() {
-        match task::block_in_place(move || {
-            let mut client = get_p2m_client();
-            handle.block_on(client.p2m_io_request(request))
-        }) {
-            Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(Box::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied))),
-        }
-    } else {
-        let mut client = get_p2m_client();
-        match TOKIO_RUNTIME.block_on(client.p2m_io_request(request)) {
-            Ok(response) => Ok(response.into_inner().id),
-            Err(_) => Err(Box::new(std::io::Error::from(std::io::ErrorKind::PermissionDenied))),
-        }
+pub fn remote_enroll(fd: i32, local_socket: String, peer_socket: String) -> Result<(), P2mError> {
+    block_on(client().remote_enroll(fd, local_socket, peer_socket))
+}
+
+/// Enrolls a file descriptor connected to a peer process through a Unix domain socket.
+pub fn unix_enroll(fd: i32, path: String, peer_pid: i32) -> Result<(), P2mError> {
+    block_on(client().unix_enroll(fd, path, peer_pid))
+}
+
+/// Requests the grant of an I/O operation on a file descriptor.
+///
+/// If the middleware is unreachable, the operation is denied in closed mode, and granted
+/// without mediation otherwise.
+pub fn io_request(fd: i32, flow: i32) -> Result<u128, P2mError> {
+    block_on(client().io_request(fd, flow))
+}
+
+/// Enrolls a datagram socket bound to a local socket.
+pub fn datagram_enroll(fd: i32, local_socket: String) -> Result<(), P2mError> {
+    block_on(client().datagram_enroll(fd, local_socket))
+}
+
+/// Requests the grant of sending a datagram to, or receiving a datagram from, a peer socket
+/// on a datagram socket.
+///
+/// The operation is reported with [`io_report`], like any other.
+pub fn datagram_io_request(fd: i32, peer_socket: String, flow: i32) -> Result<u128, P2mError> {
+    block_on(client().datagram_io_request(fd, peer_socket, flow))
+}
+
+/// Requests the grants of several I/O operations at once, given as `(fd, flow)` pairs.
+///
+/// Returns the outcome of each operation, in request order, every granted operation must be
+/// reported with [`io_report`].
+pub fn io_request_batch(requests: &[(i32, i32)]) -> Vec<Result<u128, P2mError>> {
+    let requests = requests.to_vec();
+    block_on(async move { client().io_request_batch(&requests).await })
+}
+
+/// Reports the outcome of a granted operation without waiting for its acknowledgement.
+pub fn io_report(
+    fd: i32,
+    grant_id: u128,
+    flow: i32,
+    result: bool,
+    bytes: u64,
+    offset: Option<u64>,
+) -> Result<(), P2mError> {
+    block_on(client().io_report(fd, grant_id, flow, result, bytes, offset))
+}
+
+/// Reports the outcome of a granted operation on the current stream without blocking.
+///
+/// See [`P2mClient::io_report_now`], asynchronous wrappers use it so that the report is sent
+/// before their operation completes.
+pub fn io_report_now(
+    fd: i32,
+    grant_id: u128,
+    flow: i32,
+    result: bool,
+    bytes: u64,
+    offset: Option<u64>,
+) -> Result<(), P2mError> {
+    client().io_report_now(fd, grant_id, flow, result, bytes, offset)
+}
+
+/// Waits until all the previous requests of the process are handled by the middleware.
+///
+/// Fails if any pipelined report was refused since the last call, or if the middleware is
+/// unreachable.
+pub fn flush() -> Result<(), P2mError> {
+    block_on(client().flush())
+}
+
+/// Checks whether the middleware is reachable, attempting to reconnect right away if not.
+pub fn health_check() -> Health {
+    block_on(client().health_check())
+}
+
+pub fn declare_flow(fd: i32, sources: Vec<i32>) -> Result<(), P2mError> {
+    block_on(client().declare_flow(fd, sources))
+}
+
+pub fn clear_flow(fd: i32) {
+    block_on(client().clear_flow(fd))
+}
+
+pub fn open_session(name: impl Into<String>) -> Result<(), P2mError> {
+    block_on(client().open_session(name.into()))
+}
+
+pub fn end_session() -> Result<(), P2mError> {
+    block_on(client().end_session())
+}
+
+pub fn close(fd: i32) {
+    block_on(client().close(fd))
+}
+
+pub fn truncate(fd: i32) -> Result<(), P2mError> {
+    block_on(client().truncate(fd))
+}
+
+pub fn dup(fd: i32, new_fd: i32) -> Result<(), P2mError> {
+    block_on(client().dup(fd, new_fd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{unmediated_flow, unmediated_grant};
+    use proto::primitives::Flow;
+    use std::{os::fd::AsRawFd, process::id};
+    use tonic::transport::Server;
+    use trace2e_core::{
+        traceability::init_middleware,
+        transport::{
+            grpc::{P2mHandler, p2m_uds_incoming, proto::p2m_server::P2mServer},
+            nop::M2mNop,
+        },
     };
-    result
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)
-        .map(|id| id.parse::<u128>().unwrap())
-}
-
-#[allow(clippy::result_large_err)]
-pub fn io_report(fd: i32, grant_id: u128, result: bool) -> std::io::Result<()> {
-    let request = tonic::Request::new(proto::messages::IoResult {
-        process_id: id() as i32,
-        file_descriptor: fd,
-        grant_id: grant_id.to_string(),
-        result,
-    });
 
-    if let Ok(handle) = Handle::try_current() {
-        match block_in_place(move || {
-            let mut client = get_p2m_client();
-            handle.block_on(client.p2m_io_report(request))
-        }) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(std::io::Error::from(std::io::ErrorKind::Other)),
-        }
-    } else {
-        let mut client = get_p2m_client();
-        match TOKIO_RUNTIME.block_on(client.p2m_io_report(request)) {
-            Ok(_) => Ok(()),
-            Err(_) => Err(std::io::Error::from(std::io::ErrorKind::Other)),
-        }
+    #[test]
+    fn client_failure_modes() {
+        let tmp = std::env::temp_dir();
+        let socket = tmp.join(format!("trace2e_client_failure_modes_{}.sock", id()));
+        let path = tmp.join(format!("trace2e_client_failure_modes_{}.txt", id()));
+        let _ = std::fs::remove_file(&socket);
+        // SAFETY: this is the only test of the crate reading the environment
+        unsafe {
+            std::env::set_var("TRACE2E_MIDDLEWARE_URL", format!("unix://{}", socket.display()))
+        };
+
+        let file = std::fs::File::create(&path).unwrap();
+        let fd = file.as_raw_fd();
+        let output = Flow::Output as i32;
+
+        // The middleware is unreachable, the enrollment is kept for the next stream
+        set_failure_mode(FailureMode::Closed);
+        assert!(local_enroll(&path, fd, proto::primitives::OpenMode::default()).is_ok());
+        assert!(io_request(fd, output).is_err());
+        assert_eq!(truncate(fd), Err(P2mError::Unreachable));
+        assert!(!health_check().reachable);
+
+        set_failure_mode(FailureMode::Open);
+        let grant_id = io_request(fd, output).unwrap();
+        assert_eq!(grant_id, unmediated_grant(output));
+        assert!(io_report(fd, grant_id, output, true, 4, None).is_ok());
+        assert_eq!(health_check().buffered, 0);
+
+        set_failure_mode(FailureMode::Buffered);
+        let grant_id = io_request(fd, output).unwrap();
+        assert!(io_report(fd, grant_id, output, true, 4, None).is_ok());
+        assert!(open_session("offline").is_ok());
+        assert_eq!(
+            health_check(),
+            Health { reachable: false, mode: FailureMode::Buffered, buffered: 2 }
+        );
+
+        // Once the middleware is reachable, the buffered operations are replayed
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let (_, p2m_service, _) =
+                init_middleware("127.0.0.1".to_string(), None, 0, M2mNop, false);
+            let incoming = p2m_uds_incoming(&socket).unwrap();
+            tokio::spawn(
+                Server::builder()
+                    .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+                    .serve_with_incoming(incoming),
+            );
+        });
+        assert_eq!(
+            health_check(),
+            Health { reachable: true, mode: FailureMode::Buffered, buffered: 0 }
+        );
+        // The descriptor enrolled while the middleware was unreachable is mediated
+        set_failure_mode(FailureMode::Closed);
+        let grant_id = io_request(fd, output).unwrap();
+        assert!(unmediated_flow(grant_id).is_none());
+        assert!(io_report(fd, grant_id, output, true, 4, None).is_ok());
+        assert!(flush().is_ok());
+        assert!(end_session().is_ok());
+        close(fd);
+
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&socket);
     }
 }
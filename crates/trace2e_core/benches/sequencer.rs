@@ -0,0 +1,112 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures::future::join_all;
+use tower::Service;
+use trace2e_core::traceability::{
+    api::types::{SequencerRequest, SequencerResponse},
+    infrastructure::naming::Resource,
+    services::sequencer::SequencerService,
+};
+
+const ACTIVE_FLOWS: [usize; 4] = [0, 1_000, 5_000, 10_000];
+
+/// Sequencer with `n` active flows reading from the same process
+fn build_sequencer(rt: &tokio::runtime::Runtime, n: usize) -> SequencerService {
+    let mut sequencer = SequencerService::default();
+    let process = Resource::new_process_mock(0);
+    rt.block_on(async {
+        for i in 0..n {
+            sequencer
+                .call(SequencerRequest::ReserveFlow {
+                    source: process.clone(),
+                    destination: Resource::new_file(format!("/tmp/bench_sequencer/active_{i}")),
+                })
+                .await
+                .unwrap();
+        }
+    });
+    sequencer
+}
+
+fn reserve_release(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("sequencer_reserve_release");
+
+    for n in ACTIVE_FLOWS {
+        let sequencer = build_sequencer(&rt, n);
+        let process = Resource::new_process_mock(0);
+        let file = Resource::new_file("/tmp/bench_sequencer/file".to_string());
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{n}_active_flows")),
+            &n,
+            |b, _| {
+                b.iter(|| {
+                    let mut sequencer = sequencer.clone();
+                    rt.block_on(async {
+                        assert_eq!(
+                            sequencer
+                                .call(SequencerRequest::ReserveFlow {
+                                    source: process.clone(),
+                                    destination: file.clone(),
+                                })
+                                .await
+                                .unwrap(),
+                            SequencerResponse::FlowReserved
+                        );
+                        // Partial release, the process is still read by the active flows
+                        sequencer
+                            .call(SequencerRequest::ReleaseFlow { destination: file.clone() })
+                            .await
+                            .unwrap();
+                    })
+                });
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn concurrent_flows(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("sequencer_concurrent_flows");
+
+    for n in ACTIVE_FLOWS.into_iter().filter(|n| *n > 0) {
+        let flows: Vec<(Resource, Resource)> = (0..n)
+            .map(|i| {
+                (
+                    Resource::new_process_mock(i as i32),
+                    Resource::new_file(format!("/tmp/bench_sequencer/concurrent_{i}")),
+                )
+            })
+            .collect();
+        group.throughput(Throughput::Elements(n as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(format!("{n}_flows")), &n, |b, _| {
+            b.iter(|| {
+                let sequencer = SequencerService::default();
+                rt.block_on(async {
+                    // Reserve all flows concurrently, then release them concurrently
+                    join_all(flows.iter().map(|(source, destination)| {
+                        let mut sequencer = sequencer.clone();
+                        tokio::spawn(sequencer.call(SequencerRequest::ReserveFlow {
+                            source: source.clone(),
+                            destination: destination.clone(),
+                        }))
+                    }))
+                    .await;
+                    join_all(flows.iter().map(|(_, destination)| {
+                        let mut sequencer = sequencer.clone();
+                        tokio::spawn(sequencer.call(SequencerRequest::ReleaseFlow {
+                            destination: destination.clone(),
+                        }))
+                    }))
+                    .await;
+                })
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, reserve_release, concurrent_flows);
+criterion_main!(benches);
@@ -1,3 +1,5 @@
+//! Cost of standard file I/O without mediation, see the benchmarks of `trace2e_client` for the
+//! cost of mediated I/O.
 use std::fs::File;
 use std::io::{Read, Write};
 
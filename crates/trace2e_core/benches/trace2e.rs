@@ -10,7 +10,11 @@ use trace2e_core::{
             p2m::P2mApiService,
             types::{P2mRequest, P2mResponse},
         },
-        infrastructure::naming::{LocalizedResource, Resource},
+        infrastructure::{
+            naming::{LocalizedResource, Resource},
+            process::ProcessRegistry,
+            validation::ResourceValidator,
+        },
         services::{
             compliance::ComplianceService,
             consent::ConsentService,
@@ -86,7 +90,10 @@ fn local_io(c: &mut Criterion) {
                                 pid: 1,
                                 fd: 3,
                                 grant_id,
+                                output: false,
                                 result: true,
+                                bytes: 0,
+                                offset: None,
                             })
                             .await
                             .unwrap();
@@ -177,9 +184,17 @@ fn distributed_io(c: &mut Criterion) {
                         else {
                             panic!("Expected Grant");
                         };
-                        svc.call(P2mRequest::IoReport { pid: 1, fd: 3, grant_id, result: true })
-                            .await
-                            .unwrap();
+                        svc.call(P2mRequest::IoReport {
+                            pid: 1,
+                            fd: 3,
+                            grant_id,
+                            output: false,
+                            result: true,
+                            bytes: 0,
+                            offset: None,
+                        })
+                        .await
+                        .unwrap();
                     })
                 },
                 criterion::BatchSize::SmallInput,
@@ -245,6 +260,9 @@ fn grpc_local(c: &mut Criterion) {
                         file_descriptor: 3,
                         grant_id: grant.id,
                         result: true,
+                        bytes: 0,
+                        offset: None,
+                        flow: proto::primitives::Flow::Input as i32,
                     }))
                     .await
                     .unwrap()
@@ -317,6 +335,9 @@ fn grpc_distributed(c: &mut Criterion) {
                         file_descriptor: 3,
                         grant_id: grant.id,
                         result: true,
+                        bytes: 0,
+                        offset: None,
+                        flow: proto::primitives::Flow::Input as i32,
                     }))
                     .await
                     .unwrap()
@@ -327,5 +348,31 @@ fn grpc_distributed(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, local_io, distributed_io, grpc_local, grpc_distributed);
+fn process_lookup(c: &mut Criterion) {
+    let pid = std::process::id() as i32;
+    let mut group = c.benchmark_group("process_lookup");
+
+    // Baseline: full process table refresh, as done before the process registry
+    group.bench_function("sysinfo_refresh_all", |b| {
+        b.iter(|| {
+            let mut system = sysinfo::System::new();
+            system.refresh_all();
+            system.process(sysinfo::Pid::from(pid as usize)).map(|p| p.start_time())
+        })
+    });
+    group.bench_function("registry_uncached", |b| {
+        b.iter_batched(
+            ProcessRegistry::new,
+            |registry| registry.get(pid),
+            criterion::BatchSize::SmallInput,
+        )
+    });
+    group.bench_function("new_process", |b| b.iter(|| Resource::new_process(pid)));
+    group
+        .bench_function("is_valid_process", |b| b.iter(|| ResourceValidator.is_valid_process(pid)));
+
+    group.finish();
+}
+
+criterion_group!(benches, local_io, distributed_io, grpc_local, grpc_distributed, process_lookup);
 criterion_main!(benches);
@@ -1,9 +1,20 @@
-use std::time::Duration;
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
 
 use tower::{Service, ServiceBuilder, timeout::TimeoutLayer};
 
 use crate::{
-    traceability::init_middleware,
+    traceability::{
+        api::{O2mRequest, O2mResponse, P2mRequest, P2mResponse, p2m::PendingGrant},
+        infrastructure::naming::{LocalizedResource, Resource},
+        init_middleware,
+        services::{
+            compliance::ConfidentialityPolicy,
+            provenance::{LineageEvent, ResourceVolume},
+        },
+    },
     transport::{loopback::spawn_loopback_middlewares, nop::M2mNop},
 };
 
@@ -64,3 +75,314 @@ async fn integration_spawn_loopback_middlewares() {
     write!(p2m_1, stream1);
     read!(p2m_2, stream2);
 }
+
+#[tokio::test]
+async fn integration_io_request_batch() {
+    // flowchart LR
+    //     s1337on1@{ shape: h-cyl}
+    //     s1338on2@{ shape: h-cyl}
+    //     s1339on1@{ shape: h-cyl}
+    //     s1340on2@{ shape: h-cyl}
+    //     s1337on1["socket1337 on Node1"] --- s1338on2["socket1338 on Node2"]
+    //     s1339on1["socket1339 on Node1"] --- s1340on2["socket1340 on Node2"]
+
+    //     F1on1["File1 opened by Process1@Node1"] -- 1 --> P1on1["Process1 on Node1"]
+    //     policy0(["Set Secret"]) -. 2 .- F1on1
+    //     P1on1 -- 3 --x s1337on1
+    //     P1on1 -- 3 --x s1339on1
+    //     policy1(["Set Public"]) -. 4 .- F1on1
+    //     P1on1 -- 5 --> s1337on1
+    //     P1on1 -- 5 --> s1339on1
+    //     s1338on2 -- 6 --> P2on2["Process2 on Node2"]
+    //     s1340on2 -- 7 --> P2on2
+    crate::trace2e_tracing::init();
+    let ips = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+    let mut middlewares = spawn_loopback_middlewares(ips.clone()).await.into_iter();
+
+    let (mut p2m_1, mut o2m_1) = middlewares.next().unwrap();
+    let (mut p2m_2, mut o2m_2) = middlewares.next().unwrap();
+
+    let file = FileMapping::new(1, 3, "/tmp/test.txt", "10.0.0.1".to_string());
+    let stream1_1 = StreamMapping::new(1, 4, "10.0.0.1:1337", "10.0.0.2:1338");
+    let stream1_2 = StreamMapping::new(1, 5, "10.0.0.1:1339", "10.0.0.2:1340");
+    let stream2_1 = StreamMapping::new(2, 3, "10.0.0.2:1338", "10.0.0.1:1337");
+    let stream2_2 = StreamMapping::new(2, 4, "10.0.0.2:1340", "10.0.0.1:1339");
+
+    local_enroll!(p2m_1, file);
+    remote_enroll!(p2m_1, stream1_1);
+    remote_enroll!(p2m_1, stream1_2);
+    remote_enroll!(p2m_2, stream2_1);
+    remote_enroll!(p2m_2, stream2_2);
+
+    read!(p2m_1, file);
+    let batch = P2mRequest::IoRequestBatch {
+        pid: 1,
+        requests: vec![(stream1_1.fd(), true), (stream1_2.fd(), true)],
+    };
+
+    // Both writes are refused, and the streams reserved on Node2 are released
+    set_confidentiality!(o2m_1, file.file(), ConfidentialityPolicy::Secret);
+    let P2mResponse::Grants(results) = p2m_1.call(batch.clone()).await.unwrap() else {
+        panic!("Expected P2mResponse::Grants");
+    };
+    assert!(results.iter().all(|result| result.is_err()));
+    assert_eq!(
+        o2m_2.call(O2mRequest::ListReservations).await.unwrap(),
+        O2mResponse::Reservations(vec![])
+    );
+    // The release of a refused write records no flow on Node2
+    assert_provenance!(o2m_2, stream2_1.stream(), HashSet::new());
+
+    // Both writes are granted, and the streams stay reserved on Node2 until reported
+    set_confidentiality!(o2m_1, file.file(), ConfidentialityPolicy::Public);
+    let P2mResponse::Grants(results) = p2m_1.call(batch).await.unwrap() else {
+        panic!("Expected P2mResponse::Grants");
+    };
+    let O2mResponse::Reservations(reservations) =
+        o2m_2.call(O2mRequest::ListReservations).await.unwrap()
+    else {
+        panic!("Expected O2mResponse::Reservations");
+    };
+    assert_eq!(
+        reservations.into_iter().collect::<HashSet<_>>(),
+        HashSet::from([(Resource::None, stream2_1.stream()), (Resource::None, stream2_2.stream())])
+    );
+    let (flow_id1, flow_id2) = (results[0].as_ref().unwrap(), results[1].as_ref().unwrap());
+    io_report!(p2m_1, stream1_1, *flow_id1, true, true);
+    io_report!(p2m_1, stream1_2, *flow_id2, true, true);
+
+    read!(p2m_2, stream2_1);
+    read!(p2m_2, stream2_2);
+    let O2mResponse::References(references) =
+        o2m_2.call(O2mRequest::GetReferences(stream2_1.process())).await.unwrap()
+    else {
+        panic!("Expected O2mResponse::References");
+    };
+    assert!(references.contains(&LocalizedResource::new("10.0.0.1".to_string(), file.file())));
+}
+
+#[tokio::test]
+async fn integration_io_request_batch_reads() {
+    // flowchart LR
+    //     F1["File1 opened by Process1"] -- 1 --> P1["Process1"]
+    //     F2["File2 opened by Process1"] -- 1 --> P1
+    crate::trace2e_tracing::init();
+    let ips = vec!["10.0.0.1".to_string()];
+    let (mut p2m, mut o2m) = spawn_loopback_middlewares(ips).await.into_iter().next().unwrap();
+
+    let file1 = FileMapping::new(1, 3, "/tmp/test1.txt", "10.0.0.1".to_string());
+    let file2 = FileMapping::new(1, 4, "/tmp/test2.txt", "10.0.0.1".to_string());
+    local_enroll!(p2m, file1);
+    local_enroll!(p2m, file2);
+
+    // Both reads are granted, the process is reserved once as their destination
+    let P2mResponse::Grants(results) = p2m
+        .call(P2mRequest::IoRequestBatch {
+            pid: 1,
+            requests: vec![(file1.fd(), false), (file2.fd(), false)],
+        })
+        .await
+        .unwrap()
+    else {
+        panic!("Expected P2mResponse::Grants");
+    };
+    let (flow_id1, flow_id2) = (*results[0].as_ref().unwrap(), *results[1].as_ref().unwrap());
+    let O2mResponse::Reservations(reservations) =
+        o2m.call(O2mRequest::ListReservations).await.unwrap()
+    else {
+        panic!("Expected O2mResponse::Reservations");
+    };
+    assert_eq!(
+        reservations.into_iter().collect::<HashSet<_>>(),
+        HashSet::from([(file1.file(), file1.process()), (file2.file(), file1.process())])
+    );
+
+    // The process keeps its reservation until its last read is reported
+    io_report!(p2m, file1, flow_id1, false, true);
+    assert_eq!(
+        o2m.call(O2mRequest::ListReservations).await.unwrap(),
+        O2mResponse::Reservations(vec![(file2.file(), file1.process())])
+    );
+    io_report!(p2m, file2, flow_id2, false, true);
+    assert_eq!(
+        o2m.call(O2mRequest::ListReservations).await.unwrap(),
+        O2mResponse::Reservations(vec![])
+    );
+    let O2mResponse::References(references) =
+        o2m.call(O2mRequest::GetReferences(file1.process())).await.unwrap()
+    else {
+        panic!("Expected O2mResponse::References");
+    };
+    assert!(references.contains(&LocalizedResource::new("10.0.0.1".to_string(), file1.file())));
+    assert!(references.contains(&LocalizedResource::new("10.0.0.1".to_string(), file2.file())));
+}
+
+#[tokio::test]
+async fn integration_o2m_sequencer_inspection() {
+    crate::trace2e_tracing::init();
+    let (_, mut p2m_service, mut o2m_service) =
+        init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let file = FileMapping::new(1, 3, "/tmp/test.txt", "10.0.0.1".to_string());
+    local_enroll!(p2m_service, file);
+
+    // Grant a write, but never report it
+    let grant_id = write_request!(p2m_service, file);
+
+    assert_eq!(
+        o2m_service.call(O2mRequest::ListReservations).await.unwrap(),
+        O2mResponse::Reservations(vec![(file.process(), file.file())])
+    );
+    assert_eq!(
+        o2m_service.call(O2mRequest::ListPendingGrants).await.unwrap(),
+        O2mResponse::PendingGrants(vec![PendingGrant {
+            grant_id,
+            pid: file.pid(),
+            source: file.process(),
+            destination: file.file(),
+            expires_in: None,
+        }])
+    );
+    assert_eq!(
+        o2m_service.call(O2mRequest::GetQueueDepths).await.unwrap(),
+        O2mResponse::QueueDepths(Default::default())
+    );
+
+    // Release the stuck flow
+    assert_eq!(
+        o2m_service.call(O2mRequest::ForceRelease(file.file())).await.unwrap(),
+        O2mResponse::Ack
+    );
+    assert_eq!(
+        o2m_service.call(O2mRequest::ListReservations).await.unwrap(),
+        O2mResponse::Reservations(vec![])
+    );
+    assert_eq!(
+        o2m_service.call(O2mRequest::ListPendingGrants).await.unwrap(),
+        O2mResponse::PendingGrants(vec![])
+    );
+
+    // The write carried out under the revoked grant is still recorded when reported
+    assert_eq!(
+        p2m_service
+            .call(P2mRequest::IoReport {
+                pid: 1,
+                fd: 3,
+                grant_id,
+                output: true,
+                result: true,
+                bytes: 16,
+                offset: Some(0)
+            })
+            .await
+            .unwrap(),
+        P2mResponse::Ack
+    );
+    assert_eq!(
+        o2m_service.call(O2mRequest::GetVolumes(HashSet::from([file.file()]))).await.unwrap(),
+        O2mResponse::Volumes(HashMap::from([(
+            file.file(),
+            ResourceVolume { bytes_in: 16, bytes_out: 0, transfers_in: 1, transfers_out: 0 }
+        )]))
+    );
+    assert_provenance!(
+        o2m_service,
+        file.file(),
+        HashSet::from([file.localized_file(), file.localized_process()])
+    );
+
+    // The file can be written again
+    write!(p2m_service, file);
+}
+
+#[tokio::test]
+async fn integration_o2m_transfer_volumes() {
+    crate::trace2e_tracing::init();
+    let (_, mut p2m_service, mut o2m_service) =
+        init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let file = FileMapping::new(1, 3, "/tmp/test.txt", "10.0.0.1".to_string());
+    local_enroll!(p2m_service, file);
+
+    // Write 4096 bytes at the start of the file, then read back a 16 bytes probe
+    for (output, result, bytes, offset) in
+        [(true, true, 4096, Some(0)), (false, true, 16, Some(128)), (false, false, 0, None)]
+    {
+        let grant_id = if output {
+            write_request!(p2m_service, file)
+        } else {
+            read_request!(p2m_service, file)
+        };
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid: 1,
+                    fd: 3,
+                    grant_id,
+                    output,
+                    result,
+                    bytes,
+                    offset
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+    }
+
+    // Failed operations are not accounted
+    assert_eq!(
+        o2m_service
+            .call(O2mRequest::GetVolumes(HashSet::from([file.file(), file.process()])))
+            .await
+            .unwrap(),
+        O2mResponse::Volumes(HashMap::from([
+            (
+                file.file(),
+                ResourceVolume { bytes_in: 4096, bytes_out: 16, transfers_in: 1, transfers_out: 1 }
+            ),
+            (
+                file.process(),
+                ResourceVolume { bytes_in: 16, bytes_out: 4096, transfers_in: 1, transfers_out: 1 }
+            ),
+        ]))
+    );
+}
+
+#[tokio::test]
+async fn integration_o2m_lineage_events() {
+    crate::trace2e_tracing::init();
+    let (_, mut p2m_service, mut o2m_service) =
+        init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let file = FileMapping::new(1, 3, "/tmp/test.txt", "10.0.0.1".to_string());
+    local_enroll!(p2m_service, file);
+    write!(p2m_service, file);
+    assert_eq!(
+        o2m_service.call(O2mRequest::GetReferences(file.file())).await.unwrap(),
+        O2mResponse::References(HashSet::from([file.localized_file(), file.localized_process()]))
+    );
+
+    // Truncating the file discards its content, and the provenance inherited from the process
+    assert_eq!(
+        p2m_service.call(P2mRequest::Truncate { pid: 1, fd: 3 }).await.unwrap(),
+        P2mResponse::Ack
+    );
+    assert_eq!(
+        o2m_service.call(O2mRequest::GetReferences(file.file())).await.unwrap(),
+        O2mResponse::References(HashSet::from([file.localized_file()]))
+    );
+
+    // The overwrite is kept in the lineage of the file
+    let O2mResponse::LineageEvents(events) =
+        o2m_service.call(O2mRequest::GetLineageEvents(file.file())).await.unwrap()
+    else {
+        panic!("Expected O2mResponse::LineageEvents");
+    };
+    assert!(matches!(
+        events.as_slice(),
+        [LineageEvent::Overwrite { process, previous, .. }]
+            if *process == file.process() && *previous
+                    == HashSet::from([file.localized_file(), file.localized_process()])
+    ));
+}
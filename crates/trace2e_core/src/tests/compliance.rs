@@ -6,7 +6,7 @@ use std::{
 use tower::{Service, ServiceBuilder, timeout::TimeoutLayer};
 
 use crate::{
-    traceability::api::types::O2mRequest,
+    traceability::api::types::{O2mRequest, OpenMode, P2mRequest, P2mResponse},
     traceability::services::compliance::{ConfidentialityPolicy, DeletionPolicy, Policy},
     transport::loopback::spawn_loopback_middlewares,
 };
@@ -266,3 +266,165 @@ async fn integration_o2m_remote_delete_policy_enforcement() {
         )])
     );
 }
+
+#[tokio::test]
+async fn integration_declared_flow_confidentiality() {
+    // flowchart LR
+    //     s1337on1["socket1337 on Node1"] --- s1338on2["socket1338 on Node2"]
+    //     F1["File1 opened by Process1@Node1"] -- 1 --> P1on1["Process1 on Node1"]
+    //     F2["File2 opened by Process1@Node1"] -- 2 --> P1on1
+    //     policy0(["Set Private"]) -. 3 .- F1
+    //     P1on1 -- 4 --x s1337on1
+    //     decl(["Declare File2 as source"]) -. 5 .- s1337on1
+    //     P1on1 -- 6 --x s1337on1
+    //     policy1(["Allow declassification"]) -. 7 .- F1
+    //     P1on1 -- 8 --> s1337on1
+    //     s1338on2 -- 9 --> P2on2["Process2 on Node2"]
+    //     clear(["Clear declaration"]) -. 10 .- s1337on1
+    //     P1on1 -- 11 --x s1337on1
+
+    //     s1337on1@{ shape: h-cyl}
+    //     s1338on2@{ shape: h-cyl}
+    crate::trace2e_tracing::init();
+    let ips = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+    let mut middlewares =
+        spawn_loopback_middlewares(ips.clone()).await.into_iter().map(|(p2m, o2m)| {
+            (
+                ServiceBuilder::new()
+                    .layer(TimeoutLayer::new(Duration::from_millis(1)))
+                    .service(p2m),
+                o2m,
+            )
+        });
+
+    let (mut p2m_1, mut o2m_1) = middlewares.next().unwrap();
+    let (mut p2m_2, mut o2m_2) = middlewares.next().unwrap();
+
+    let secret = FileMapping::new(1, 4, "/tmp/secret.txt", "10.0.0.1".to_string());
+    let public = FileMapping::new(1, 5, "/tmp/public.txt", "10.0.0.1".to_string());
+    let stream1_2 = StreamMapping::new(1, 3, "10.0.0.1:1337", "10.0.0.2:1338");
+    let stream2_1 = StreamMapping::new(2, 3, "10.0.0.2:1338", "10.0.0.1:1337");
+
+    local_enroll!(p2m_1, secret);
+    local_enroll!(p2m_1, public);
+    remote_enroll!(p2m_1, stream1_2);
+    remote_enroll!(p2m_2, stream2_1);
+
+    // Reading the secret taints the whole process, its writes to the network are refused
+    read!(p2m_1, secret);
+    read!(p2m_1, public);
+    set_confidentiality!(o2m_1, secret.file(), ConfidentialityPolicy::Secret);
+    assert_eq!(write_request!(p2m_1, stream1_2), u128::MAX);
+
+    // Sources must be enrolled descriptors of the process
+    assert!(p2m_1.call(P2mRequest::DeclareFlow { pid: 1, fd: 3, sources: vec![6] }).await.is_err());
+
+    // Writes declared to derive from the public file only are refused, since they would
+    // strip the taint of the secret
+    assert_eq!(
+        p2m_1
+            .call(P2mRequest::DeclareFlow { pid: 1, fd: 3, sources: vec![public.fd()] })
+            .await
+            .unwrap(),
+        P2mResponse::Ack
+    );
+    assert_eq!(write_request!(p2m_1, stream1_2), u128::MAX);
+
+    // They are granted once the policy of the secret allows its declassification
+    set_declassification!(o2m_1, secret.file(), true);
+    write!(p2m_1, stream1_2);
+    read!(p2m_2, stream2_1);
+    assert_provenance!(
+        o2m_2,
+        stream2_1.stream(),
+        HashSet::from([public.localized_file(), public.localized_process()])
+    );
+
+    // The declaration does not outlive its scope
+    assert_eq!(
+        p2m_1.call(P2mRequest::ClearFlow { pid: 1, fd: 3 }).await.unwrap(),
+        P2mResponse::Ack
+    );
+    assert_eq!(write_request!(p2m_1, stream1_2), u128::MAX);
+    assert_provenance!(o2m_1, public.file(), HashSet::from([public.localized_file()]));
+}
+
+#[tokio::test]
+async fn integration_grant_cache_invalidation() {
+    // flowchart LR
+    //     A["File1 opened by Process1@Node1"] -- 1 --> P1on1["Process1 on Node1"]
+    //     P1on1 -- 2, 3 --> B["File2 opened by Process1@Node1"]
+    //     policy0(["Set Private"]) -. 4 .- A
+    //     P1on1 -- 5 --x B
+    //     C["File3 opened by Process2@Node1"] -- 6, 7 --> P2on1["Process2 on Node1"]
+    //     policy1(["Broadcast Deletion"]) -. 8 .- C
+    //     C -- 9 --x P2on1
+    crate::trace2e_tracing::init();
+    let ips = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+    let mut middlewares =
+        spawn_loopback_middlewares(ips.clone()).await.into_iter().map(|(p2m, o2m)| {
+            (
+                ServiceBuilder::new()
+                    .layer(TimeoutLayer::new(Duration::from_millis(10)))
+                    .service(p2m),
+                o2m,
+            )
+        });
+
+    let (mut p2m_1, mut o2m_1) = middlewares.next().unwrap();
+
+    let source = FileMapping::new(1, 3, "/tmp/cache_source.txt", "10.0.0.1".to_string());
+    let destination = FileMapping::new(1, 4, "/tmp/cache_destination.txt", "10.0.0.1".to_string());
+    let deleted = FileMapping::new(2, 3, "/tmp/cache_deleted.txt", "10.0.0.1".to_string());
+
+    local_enroll!(p2m_1, source);
+    local_enroll!(p2m_1, destination);
+    local_enroll!(p2m_1, deleted);
+
+    // Repeated writes on the same descriptor, the second one reuses the first evaluation
+    read!(p2m_1, source);
+    write!(p2m_1, destination);
+    write!(p2m_1, destination);
+
+    // A policy update through the O2M API invalidates the cached grant
+    set_confidentiality!(o2m_1, source.file(), ConfidentialityPolicy::Secret);
+    assert_eq!(write_request!(p2m_1, destination), u128::MAX);
+
+    // A policy update through the M2M API as well
+    read!(p2m_1, deleted);
+    read!(p2m_1, deleted);
+    broadcast_deletion!(o2m_1, deleted.file());
+    assert_eq!(read_request!(p2m_1, deleted), u128::MAX);
+}
+
+#[tokio::test]
+async fn integration_truncate_integrity() {
+    crate::trace2e_tracing::init();
+    let (mut p2m_1, mut o2m_1) =
+        spawn_loopback_middlewares(vec!["10.0.0.1".to_string()]).await.into_iter().next().unwrap();
+
+    let file = FileMapping::new(1, 3, "/tmp/truncate_integrity.txt", "10.0.0.1".to_string());
+    local_enroll!(p2m_1, file);
+    write!(p2m_1, file);
+    let written = HashSet::from([file.localized_file(), file.localized_process()]);
+
+    // Discarding the content of a file is a write, refused if the process is not trusted enough
+    set_integrity!(o2m_1, file.file(), 5);
+    assert!(p2m_1.call(P2mRequest::Truncate { pid: 1, fd: 3 }).await.is_err());
+    assert!(
+        p2m_1
+            .call(P2mRequest::LocalEnroll {
+                pid: 1,
+                fd: 4,
+                path: file.file_path(),
+                mode: OpenMode { write: true, truncate: true, ..Default::default() },
+            })
+            .await
+            .is_err()
+    );
+    assert_provenance!(o2m_1, file.file(), written);
+
+    set_integrity!(o2m_1, file.file(), 0);
+    assert_eq!(p2m_1.call(P2mRequest::Truncate { pid: 1, fd: 3 }).await.unwrap(), P2mResponse::Ack);
+    assert_provenance!(o2m_1, file.file(), HashSet::from([file.localized_file()]));
+}
@@ -100,6 +100,7 @@ macro_rules! local_enroll {
                 pid: $mapping.pid(),
                 fd: $mapping.fd(),
                 path: $mapping.file_path(),
+                mode: crate::traceability::api::OpenMode::default(),
             })
             .await
             .unwrap(),
@@ -165,7 +166,7 @@ macro_rules! read_request {
 }
 
 macro_rules! io_report {
-    ($p2m:expr, $mapping:expr, $flow_id:expr, $result:expr) => {
+    ($p2m:expr, $mapping:expr, $flow_id:expr, $output:expr, $result:expr) => {
         // If flow_id is u128::MAX, it means there was a policy violation or an error, do not report
         // flow_id
         assert_ne!($flow_id, u128::MAX);
@@ -174,7 +175,10 @@ macro_rules! io_report {
                 pid: $mapping.pid(),
                 fd: $mapping.fd(),
                 grant_id: $flow_id,
+                output: $output,
                 result: $result,
+                bytes: 0,
+                offset: None,
             })
             .await
             .unwrap(),
@@ -186,14 +190,14 @@ macro_rules! io_report {
 macro_rules! read {
     ($p2m:expr, $mapping:expr) => {
         let flow_id = read_request!($p2m, $mapping);
-        io_report!($p2m, $mapping, flow_id, true);
+        io_report!($p2m, $mapping, flow_id, false, true);
     };
 }
 
 macro_rules! write {
     ($p2m:expr, $mapping:expr) => {
         let flow_id = write_request!($p2m, $mapping);
-        io_report!($p2m, $mapping, flow_id, true);
+        io_report!($p2m, $mapping, flow_id, true, true);
     };
 }
 
@@ -247,6 +251,20 @@ macro_rules! set_integrity {
     };
 }
 
+macro_rules! set_declassification {
+    ($o2m:expr, $resource:expr, $declassification:expr) => {
+        assert_eq!(
+            $o2m.call(crate::traceability::api::O2mRequest::SetDeclassification {
+                resource: $resource,
+                declassification: $declassification
+            })
+            .await
+            .unwrap(),
+            crate::traceability::api::O2mResponse::Ack
+        )
+    };
+}
+
 macro_rules! set_deleted {
     ($o2m:expr, $resource:expr) => {
         assert_eq!(
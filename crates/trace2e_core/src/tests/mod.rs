@@ -6,3 +6,4 @@ mod compliance;
 mod consent;
 mod deletion;
 mod provenance;
+mod transport;
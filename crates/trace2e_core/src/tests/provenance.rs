@@ -3,7 +3,12 @@ use std::{collections::HashSet, time::Duration};
 use tower::{Service, ServiceBuilder, timeout::TimeoutLayer};
 
 use crate::{
-    traceability::{infrastructure::naming::LocalizedResource, init_middleware},
+    traceability::{
+        api::{P2mRequest, P2mResponse, p2m::SessionPolicy},
+        error::TraceabilityError,
+        infrastructure::naming::{LocalizedResource, Resource},
+        init_middleware,
+    },
     transport::{
         loopback::{spawn_loopback_middlewares, spawn_loopback_middlewares_with_delay},
         nop::M2mNop,
@@ -289,3 +294,174 @@ async fn integration_o2m_remote_provenance_complex_with_entropy() {
         ])
     );
 }
+
+#[tokio::test]
+async fn integration_o2m_session_provenance() {
+    // flowchart LR
+    //     open1(["Open session 1"]) -. 1 .- P1[Process1]
+    //     F1(Secret) -->|2| S1["Session1 of Process1"]
+    //     S1 -->|3| F2(Output1)
+    //     end1(["End session 1"]) -. 4 .- P1
+    //     P1 -->|5| F3(Output2)
+    crate::trace2e_tracing::init();
+    for (policy, declassification) in
+        [(SessionPolicy::Retain, true), (SessionPolicy::Drop, false), (SessionPolicy::Drop, true)]
+    {
+        let (_, p2m_service, mut o2m_service) =
+            init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+        let mut p2m_service = p2m_service.with_session_policy(policy);
+
+        let secret = FileMapping::new(1, 3, "/tmp/secret.txt", "10.0.0.1".to_string());
+        let output1 = FileMapping::new(1, 4, "/tmp/output1.txt", "10.0.0.1".to_string());
+        let output2 = FileMapping::new(1, 5, "/tmp/output2.txt", "10.0.0.1".to_string());
+        let Resource::Process(process) = secret.process() else { unreachable!() };
+        let session = LocalizedResource::new(
+            "10.0.0.1".to_string(),
+            Resource::new_session(process, "request-1".to_string()),
+        );
+
+        local_enroll!(p2m_service, secret);
+        local_enroll!(p2m_service, output1);
+        local_enroll!(p2m_service, output2);
+        set_declassification!(o2m_service, secret.file(), declassification);
+
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::OpenSession { pid: 1, name: "request-1".to_string() })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::OpenSession { pid: 1, name: "request-2".to_string() })
+                .await
+                .unwrap_err(),
+            TraceabilityError::SessionAlreadyOpen(1)
+        );
+
+        // The session is a distinct activity, derived from the process
+        read!(p2m_service, secret);
+        write!(p2m_service, output1);
+        assert_provenance!(
+            o2m_service,
+            output1.file(),
+            HashSet::from([
+                output1.localized_file(),
+                secret.localized_file(),
+                session.clone(),
+                secret.localized_process()
+            ])
+        );
+
+        // The session cannot end while one of its operations is pending, and stays open
+        let flow_id = write_request!(p2m_service, output1);
+        assert_eq!(
+            p2m_service.call(P2mRequest::EndSession { pid: 1 }).await.unwrap_err(),
+            TraceabilityError::SessionBusy(1)
+        );
+        io_report!(p2m_service, output1, flow_id, true, true);
+
+        assert_eq!(
+            p2m_service.call(P2mRequest::EndSession { pid: 1 }).await.unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service.call(P2mRequest::EndSession { pid: 1 }).await.unwrap_err(),
+            TraceabilityError::NoOpenSession(1)
+        );
+
+        // The taint of the session is dropped only if the session policy permits it, and if
+        // the policy of the secret allows its declassification
+        write!(p2m_service, output2);
+        let mut expected = HashSet::from([output2.localized_file(), secret.localized_process()]);
+        if policy == SessionPolicy::Retain || !declassification {
+            expected.extend([secret.localized_file(), session]);
+        }
+        assert_provenance!(o2m_service, output2.file(), expected);
+    }
+}
+
+#[tokio::test]
+async fn integration_o2m_remote_provenance_datagram() {
+    // flowchart LR
+    //     s8125on1["datagram socket8125 on Node1"] -.- s8126on2["datagram socket8126 on Node2"]
+    //     s8125on1@{ shape: h-cyl}
+    //     s8126on2@{ shape: h-cyl}
+
+    //     P1on1["Process1 on Node1"] -- 1 --> s8125on1
+    //     s8126on2 -- 2 --> P2on2["Process2 on Node2"]
+    crate::trace2e_tracing::init();
+    let ips = vec!["10.0.0.1".to_string(), "10.0.0.2".to_string()];
+    let mut middlewares = spawn_loopback_middlewares(ips.clone()).await.into_iter();
+
+    let (mut p2m_1, _) = middlewares.next().unwrap();
+    let (mut p2m_2, mut o2m_2) = middlewares.next().unwrap();
+
+    // The socket of node 1 is bound to the unspecified address, named after the node
+    for (p2m, pid, local_socket) in
+        [(&mut p2m_1, 1, "0.0.0.0:8125"), (&mut p2m_2, 2, "10.0.0.2:8126")]
+    {
+        assert_eq!(
+            p2m.call(P2mRequest::DatagramEnroll {
+                pid,
+                fd: 3,
+                local_socket: local_socket.to_string()
+            })
+            .await
+            .unwrap(),
+            P2mResponse::Ack
+        );
+    }
+
+    // The peer of each datagram is given with its request
+    assert_eq!(
+        p2m_1.call(P2mRequest::IoRequest { pid: 1, fd: 3, output: true }).await.unwrap_err(),
+        TraceabilityError::InvalidDatagram("datagram://10.0.0.1:8125::::".to_string())
+    );
+    for (p2m, pid, peer_socket, output) in
+        [(&mut p2m_1, 1, "10.0.0.2:8126", true), (&mut p2m_2, 2, "10.0.0.1:8125", false)]
+    {
+        let P2mResponse::Grant(grant_id) = p2m
+            .call(P2mRequest::DatagramIoRequest {
+                pid,
+                fd: 3,
+                peer_socket: peer_socket.to_string(),
+                output,
+            })
+            .await
+            .unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+        assert_eq!(
+            p2m.call(P2mRequest::IoReport {
+                pid,
+                fd: 3,
+                grant_id,
+                output,
+                result: true,
+                bytes: 0,
+                offset: None
+            })
+            .await
+            .unwrap(),
+            P2mResponse::Ack
+        );
+    }
+
+    let process_1 = LocalizedResource::new("10.0.0.1".to_string(), Resource::new_process(1));
+    assert_provenance!(
+        o2m_2,
+        Resource::new_datagram("10.0.0.2:8126".to_string(), Some("10.0.0.1:8125".to_string())),
+        HashSet::from([process_1.clone()])
+    );
+    assert_provenance!(
+        o2m_2,
+        Resource::new_process(2),
+        HashSet::from([
+            process_1,
+            LocalizedResource::new("10.0.0.2".to_string(), Resource::new_process(2))
+        ])
+    );
+}
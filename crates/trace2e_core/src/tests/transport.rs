@@ -0,0 +1,501 @@
+use std::time::Duration;
+
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use tonic::{Code, Status, transport::Server};
+
+use crate::{
+    traceability::{error::TraceabilityError, infrastructure::naming::Resource, init_middleware},
+    transport::{
+        grpc::{
+            O2mHandler, P2mHandler, connect, o2m_uds_incoming, p2m_uds_incoming,
+            proto::{
+                messages::{
+                    FlushCt, IoInfo, IoResult, LocalCt, OpenSessionCt, P2mStreamRequest,
+                    SetConfidentialityRequest, p2m_stream_request, p2m_stream_response,
+                },
+                o2m_client::O2mClient,
+                o2m_server::O2mServer,
+                p2m_client::P2mClient,
+                p2m_server::P2mServer,
+                primitives::{Confidentiality, Flow},
+            },
+        },
+        nop::M2mNop,
+    },
+};
+
+#[tokio::test]
+async fn integration_grpc_p2m_uds_peer_credentials() {
+    crate::trace2e_tracing::init();
+    let pid = std::process::id() as i32;
+    let socket = std::env::temp_dir().join(format!("trace2e_p2m_{pid}.sock"));
+    let (_, p2m_service, _) = init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let incoming = p2m_uds_incoming(&socket).unwrap();
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+            .serve_with_incoming(incoming),
+    );
+
+    let mut client =
+        P2mClient::new(connect(&format!("unix://{}", socket.display())).await.unwrap());
+
+    // The declared process matches the peer credentials
+    assert!(
+        client
+            .p2m_local_enroll(LocalCt {
+                process_id: pid,
+                file_descriptor: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: None,
+            })
+            .await
+            .is_ok()
+    );
+
+    // A process cannot act on behalf of another one
+    assert_eq!(
+        client
+            .p2m_local_enroll(LocalCt {
+                process_id: 1,
+                file_descriptor: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: None,
+            })
+            .await
+            .unwrap_err()
+            .code(),
+        Code::PermissionDenied
+    );
+
+    server.abort();
+    std::fs::remove_file(socket).unwrap();
+}
+
+#[tokio::test]
+async fn integration_grpc_uds_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let pid = std::process::id();
+    let p2m_socket = std::env::temp_dir().join(format!("trace2e_p2m_mode_{pid}.sock"));
+    let o2m_socket = std::env::temp_dir().join(format!("trace2e_o2m_mode_{pid}.sock"));
+    let _p2m = p2m_uds_incoming(&p2m_socket).unwrap();
+    let _o2m = o2m_uds_incoming(&o2m_socket).unwrap();
+
+    // Every local process may reach the P2M socket, only the middleware user the O2M one
+    let mode = |socket| std::fs::metadata(socket).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode(&p2m_socket), 0o666);
+    assert_eq!(mode(&o2m_socket), 0o600);
+
+    std::fs::remove_file(p2m_socket).unwrap();
+    std::fs::remove_file(o2m_socket).unwrap();
+}
+
+#[tokio::test]
+async fn integration_grpc_p2m_stream() {
+    crate::trace2e_tracing::init();
+    use p2m_stream_request::Request;
+    use p2m_stream_response::Response;
+
+    let pid = std::process::id() as i32;
+    let socket = std::env::temp_dir().join(format!("trace2e_p2m_stream_{pid}.sock"));
+    let (_, p2m_service, _) = init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let incoming = p2m_uds_incoming(&socket).unwrap();
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+            .serve_with_incoming(incoming),
+    );
+
+    let mut client =
+        P2mClient::new(connect(&format!("unix://{}", socket.display())).await.unwrap());
+    let (requests, outbound) = tokio::sync::mpsc::unbounded_channel();
+    let mut replies =
+        client.p2m_stream(UnboundedReceiverStream::new(outbound)).await.unwrap().into_inner();
+    let send = |sequence: u64, request: Request| {
+        requests.send(P2mStreamRequest { sequence, request: Some(request) }).unwrap()
+    };
+
+    send(
+        0,
+        Request::LocalEnroll(LocalCt {
+            process_id: pid,
+            file_descriptor: 3,
+            path: "/tmp/test.txt".to_string(),
+            mode: None,
+        }),
+    );
+    send(
+        1,
+        Request::IoRequest(IoInfo {
+            process_id: pid,
+            file_descriptor: 3,
+            flow: Flow::Output as i32,
+        }),
+    );
+    let reply = replies.message().await.unwrap().unwrap();
+    assert_eq!(reply.sequence, 0);
+    assert!(matches!(reply.response, Some(Response::Ack(_))));
+    let reply = replies.message().await.unwrap().unwrap();
+    assert_eq!(reply.sequence, 1);
+    let Some(Response::Grant(grant)) = reply.response else { panic!("Expected a grant") };
+
+    // Reports are pipelined, a process cannot act on behalf of another one
+    let report = |process_id: i32| {
+        Request::IoReport(IoResult {
+            process_id,
+            file_descriptor: 3,
+            grant_id: grant.id.clone(),
+            flow: Flow::Output as i32,
+            result: true,
+            bytes: 0,
+            offset: None,
+        })
+    };
+    send(2, report(1));
+    send(3, report(pid));
+    send(4, Request::Flush(FlushCt {}));
+    let reply = replies.message().await.unwrap().unwrap();
+    assert_eq!(reply.sequence, 2);
+    let Some(Response::Error(error)) = reply.response else { panic!("Expected an error") };
+    assert_eq!(error.code, Code::PermissionDenied as i32);
+    for sequence in [3, 4] {
+        let reply = replies.message().await.unwrap().unwrap();
+        assert_eq!(reply.sequence, sequence);
+        assert!(matches!(reply.response, Some(Response::Ack(_))));
+    }
+
+    drop(requests);
+    assert!(replies.message().await.unwrap().is_none());
+    server.abort();
+    std::fs::remove_file(socket).unwrap();
+}
+
+#[tokio::test]
+async fn integration_grpc_p2m_stream_flush() {
+    crate::trace2e_tracing::init();
+    use p2m_stream_request::Request;
+    use p2m_stream_response::Response;
+
+    let pid = std::process::id() as i32;
+    let socket = std::env::temp_dir().join(format!("trace2e_p2m_stream_flush_{pid}.sock"));
+    // Conflicting requests wait for the release of their resources
+    let (_, p2m_service, _) = init_middleware("10.0.0.1".to_string(), Some(1), 0, M2mNop, false);
+
+    let incoming = p2m_uds_incoming(&socket).unwrap();
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+            .serve_with_incoming(incoming),
+    );
+
+    let mut client =
+        P2mClient::new(connect(&format!("unix://{}", socket.display())).await.unwrap());
+    let (requests, outbound) = tokio::sync::mpsc::unbounded_channel();
+    let mut replies =
+        client.p2m_stream(UnboundedReceiverStream::new(outbound)).await.unwrap().into_inner();
+    let send = |sequence: u64, request: Request| {
+        requests.send(P2mStreamRequest { sequence, request: Some(request) }).unwrap()
+    };
+    let write = Request::IoRequest(IoInfo {
+        process_id: pid,
+        file_descriptor: 3,
+        flow: Flow::Output as i32,
+    });
+
+    send(
+        0,
+        Request::LocalEnroll(LocalCt {
+            process_id: pid,
+            file_descriptor: 3,
+            path: "/tmp/flush.txt".to_string(),
+            mode: None,
+        }),
+    );
+    send(1, write.clone());
+    assert_eq!(replies.message().await.unwrap().unwrap().sequence, 0);
+    let reply = replies.message().await.unwrap().unwrap();
+    assert_eq!(reply.sequence, 1);
+    let Some(Response::Grant(grant)) = reply.response else { panic!("Expected a grant") };
+
+    // The second write waits for the report of the first one, and so does the flush
+    send(2, write);
+    send(3, Request::Flush(FlushCt {}));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), replies.message()).await.is_err(),
+        "No request is handled before the first write is reported"
+    );
+
+    // The report is handled right away, the flush once the second write is granted
+    send(
+        4,
+        Request::IoReport(IoResult {
+            process_id: pid,
+            file_descriptor: 3,
+            grant_id: grant.id,
+            flow: Flow::Output as i32,
+            result: true,
+            bytes: 0,
+            offset: None,
+        }),
+    );
+    let mut sequences = Vec::new();
+    for _ in 0..3 {
+        sequences.push(replies.message().await.unwrap().unwrap().sequence);
+    }
+    assert_eq!(sequences, [4, 2, 3]);
+
+    drop(requests);
+    server.abort();
+    std::fs::remove_file(socket).unwrap();
+}
+
+#[test]
+fn integration_grpc_typed_errors_round_trip() {
+    let file = Resource::new_file("/tmp/test.txt".to_string());
+    let stream = Resource::new_stream("10.0.0.1:1337".to_string(), "10.0.0.2:1338".to_string());
+    let unix_socket = Resource::new_unix_socket("/run/test.sock".to_string(), 1);
+    for (error, code) in [
+        (TraceabilityError::UndeclaredResource(1, 3), Code::NotFound),
+        (TraceabilityError::GrantFdMismatch(u128::MAX, 3), Code::InvalidArgument),
+        (TraceabilityError::GrantFlowMismatch(u128::MAX), Code::InvalidArgument),
+        (TraceabilityError::SessionAlreadyOpen(1), Code::AlreadyExists),
+        (
+            TraceabilityError::UnavailableSourceAndDestination(file.clone(), stream.clone()),
+            Code::Aborted,
+        ),
+        (TraceabilityError::DeadlockDetected(stream, file), Code::Aborted),
+        (TraceabilityError::UnavailableDestination(unix_socket), Code::Aborted),
+        (
+            TraceabilityError::InvalidUnixSocket("/run/test.sock".to_string(), 0),
+            Code::InvalidArgument,
+        ),
+        (TraceabilityError::InvalidDatagram("bad_socket".to_string()), Code::InvalidArgument),
+        (TraceabilityError::DirectPolicyViolation, Code::PermissionDenied),
+        (TraceabilityError::ConsentRequestTimeout, Code::DeadlineExceeded),
+        (
+            TraceabilityError::TransportFailedToContactRemote("10.0.0.2".to_string()),
+            Code::Unavailable,
+        ),
+    ] {
+        let status = Status::from(error.clone());
+        assert_eq!(status.code(), code);
+        assert_eq!(status.message(), error.to_string());
+        assert_eq!(TraceabilityError::try_from(&status), Ok(error));
+    }
+    // Statuses not raised by the middleware carry no traceability error
+    assert!(TraceabilityError::try_from(&Status::internal("internal")).is_err());
+}
+
+#[tokio::test]
+async fn integration_grpc_p2m_stream_typed_errors() {
+    crate::trace2e_tracing::init();
+    use p2m_stream_request::Request;
+    use p2m_stream_response::Response;
+
+    let pid = std::process::id() as i32;
+    let socket = std::env::temp_dir().join(format!("trace2e_p2m_typed_errors_{pid}.sock"));
+    let (_, p2m_service, _) = init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let incoming = p2m_uds_incoming(&socket).unwrap();
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+            .serve_with_incoming(incoming),
+    );
+
+    let mut client =
+        P2mClient::new(connect(&format!("unix://{}", socket.display())).await.unwrap());
+    let (requests, outbound) = tokio::sync::mpsc::unbounded_channel();
+    let mut replies =
+        client.p2m_stream(UnboundedReceiverStream::new(outbound)).await.unwrap().into_inner();
+    let send = |sequence: u64, request: Request| {
+        requests.send(P2mStreamRequest { sequence, request: Some(request) }).unwrap()
+    };
+
+    let open_session = || Request::OpenSession(OpenSessionCt { process_id: pid, name: "s".into() });
+    send(0, Request::IoRequest(IoInfo { process_id: pid, file_descriptor: 7, flow: 0 }));
+    send(1, open_session());
+    send(2, open_session());
+    // I/O requests are handled concurrently, their replies may come out of order
+    let mut errors = std::collections::BTreeMap::new();
+    for _ in 0..3 {
+        let reply = replies.message().await.unwrap().unwrap();
+        if let Some(Response::Error(error)) = reply.response {
+            errors.insert(reply.sequence, error);
+        }
+    }
+    assert_eq!(errors.keys().copied().collect::<Vec<_>>(), [0, 2]);
+    assert_eq!(errors[&0].code, Code::NotFound as i32);
+    assert_eq!(
+        TraceabilityError::try_from(errors[&0].details.clone().unwrap()),
+        Ok(TraceabilityError::UndeclaredResource(pid, 7))
+    );
+    assert_eq!(errors[&2].code, Code::AlreadyExists as i32);
+    assert_eq!(
+        TraceabilityError::try_from(errors[&2].details.clone().unwrap()),
+        Ok(TraceabilityError::SessionAlreadyOpen(pid))
+    );
+
+    drop(requests);
+    server.abort();
+    std::fs::remove_file(socket).unwrap();
+}
+
+#[tokio::test]
+async fn integration_grpc_p2m_sequencer_errors() {
+    crate::trace2e_tracing::init();
+    let pid = std::process::id() as i32;
+    let socket = std::env::temp_dir().join(format!("trace2e_p2m_sequencer_errors_{pid}.sock"));
+    let (_, p2m_service, _) = init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let incoming = p2m_uds_incoming(&socket).unwrap();
+    let server = tokio::spawn(
+        Server::builder()
+            .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+            .serve_with_incoming(incoming),
+    );
+
+    let mut client =
+        P2mClient::new(connect(&format!("unix://{}", socket.display())).await.unwrap());
+    for fd in [3, 4] {
+        client
+            .p2m_local_enroll(LocalCt {
+                process_id: pid,
+                file_descriptor: fd,
+                path: "/tmp/test_sequencer_errors.txt".to_string(),
+                mode: None,
+            })
+            .await
+            .unwrap();
+    }
+    let grant_id = client
+        .p2m_io_request(IoInfo { process_id: pid, file_descriptor: 3, flow: Flow::Output.into() })
+        .await
+        .unwrap()
+        .into_inner()
+        .id;
+
+    // The read of the file being written waits for the write, without retries it then gives up
+    let mut reader = client.clone();
+    let read = tokio::spawn(async move {
+        reader
+            .p2m_io_request(IoInfo {
+                process_id: pid,
+                file_descriptor: 4,
+                flow: Flow::Input.into(),
+            })
+            .await
+    });
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    client
+        .p2m_io_report(IoResult {
+            process_id: pid,
+            file_descriptor: 3,
+            grant_id,
+            flow: Flow::Output.into(),
+            result: true,
+            bytes: 0,
+            offset: None,
+        })
+        .await
+        .unwrap();
+
+    // The sequencer error reaches the process unchanged
+    let status = read.await.unwrap().unwrap_err();
+    assert_eq!(status.code(), Code::ResourceExhausted);
+    assert_eq!(
+        TraceabilityError::try_from(&status),
+        Ok(TraceabilityError::ReachedMaxRetriesWaitingQueue)
+    );
+
+    server.abort();
+    std::fs::remove_file(socket).unwrap();
+}
+
+#[tokio::test]
+async fn integration_grpc_grant_cache_invalidation() {
+    crate::trace2e_tracing::init();
+    let pid = std::process::id() as i32;
+    let p2m_socket = std::env::temp_dir().join(format!("trace2e_p2m_grant_cache_{pid}.sock"));
+    let o2m_socket = std::env::temp_dir().join(format!("trace2e_o2m_grant_cache_{pid}.sock"));
+    let (_, p2m_service, o2m_service) =
+        init_middleware("10.0.0.1".to_string(), None, 0, M2mNop, false);
+
+    let p2m_incoming = p2m_uds_incoming(&p2m_socket).unwrap();
+    let o2m_incoming = o2m_uds_incoming(&o2m_socket).unwrap();
+    let servers = [
+        tokio::spawn(
+            Server::builder()
+                .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
+                .serve_with_incoming(p2m_incoming),
+        ),
+        tokio::spawn(
+            Server::builder()
+                .add_service(O2mServer::new(O2mHandler::new(o2m_service)))
+                .serve_with_incoming(o2m_incoming),
+        ),
+    ];
+
+    let mut p2m =
+        P2mClient::new(connect(&format!("unix://{}", p2m_socket.display())).await.unwrap());
+    let mut o2m =
+        O2mClient::new(connect(&format!("unix://{}", o2m_socket.display())).await.unwrap());
+
+    let source = format!("/tmp/grpc_cache_source_{pid}.txt");
+    let destination = format!("/tmp/grpc_cache_destination_{pid}.txt");
+    for (fd, path) in [(3, &source), (4, &destination)] {
+        p2m.p2m_local_enroll(LocalCt {
+            process_id: pid,
+            file_descriptor: fd,
+            path: path.clone(),
+            mode: None,
+        })
+        .await
+        .unwrap();
+    }
+
+    // Performs a granted operation, or returns the status of the refused request
+    let mut operation = async |fd: i32, flow: Flow| {
+        let grant_id = p2m
+            .p2m_io_request(IoInfo { process_id: pid, file_descriptor: fd, flow: flow.into() })
+            .await?
+            .into_inner()
+            .id;
+        p2m.p2m_io_report(IoResult {
+            process_id: pid,
+            file_descriptor: fd,
+            grant_id,
+            flow: flow.into(),
+            result: true,
+            bytes: 0,
+            offset: None,
+        })
+        .await
+        .map(|_| ())
+    };
+
+    // Repeated writes on the same descriptor, the second one reuses the first evaluation
+    operation(3, Flow::Input).await.unwrap();
+    operation(4, Flow::Output).await.unwrap();
+    operation(4, Flow::Output).await.unwrap();
+
+    // A policy update through the O2M API invalidates the cached grant of the P2M API
+    o2m.o2m_set_confidentiality(SetConfidentialityRequest {
+        resource: Some(Resource::new_file(source.clone()).into()),
+        confidentiality: Confidentiality::Secret.into(),
+    })
+    .await
+    .unwrap();
+    let status = operation(4, Flow::Output).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    assert_eq!(TraceabilityError::try_from(&status), Ok(TraceabilityError::DirectPolicyViolation));
+
+    for server in servers {
+        server.abort();
+    }
+    std::fs::remove_file(p2m_socket).unwrap();
+    std::fs::remove_file(o2m_socket).unwrap();
+}
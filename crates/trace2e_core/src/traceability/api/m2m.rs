@@ -29,7 +29,12 @@
 //! The service handles these conditions gracefully and provides appropriate error
 //! responses for downstream handling.
 
-use std::{collections::HashSet, future::Future, pin::Pin, task::Poll};
+use std::{
+    collections::{HashMap, HashSet},
+    future::Future,
+    pin::Pin,
+    task::Poll,
+};
 
 use tower::Service;
 
@@ -132,6 +137,45 @@ where
                         _ => Err(TraceabilityError::InternalTrace2eError),
                     }
                 }
+                M2mRequest::GetDestinationPolicies(destinations) => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        destinations = %DisplayableResource::from(&destinations),
+                        "[m2m] GetDestinationPolicies"
+                    );
+                    let mut policies = HashMap::new();
+                    for destination in destinations {
+                        // the destinations that are not local are left out
+                        if *destination.node_id() != provenance.node_id() {
+                            continue;
+                        }
+                        let resource = destination.resource().to_owned();
+                        if !matches!(
+                            sequencer
+                                .call(SequencerRequest::ReserveFlow {
+                                    source: Resource::None, // placeholder for remote source resource
+                                    destination: resource.clone(),
+                                })
+                                .await,
+                            Ok(SequencerResponse::FlowReserved)
+                        ) {
+                            continue;
+                        }
+                        match compliance.call(ComplianceRequest::GetPolicy(resource.clone())).await
+                        {
+                            Ok(ComplianceResponse::Policy(policy)) => {
+                                policies.insert(destination, policy);
+                            }
+                            _ => {
+                                // the reservation is not reported to the requester
+                                sequencer
+                                    .call(SequencerRequest::ReleaseFlow { destination: resource })
+                                    .await?;
+                            }
+                        }
+                    }
+                    Ok(M2mResponse::DestinationPolicies(policies))
+                }
                 M2mRequest::CheckSourceCompliance { sources, destination } => {
                     info!(
                         node_id = %provenance.node_id(),
@@ -191,6 +235,23 @@ where
                         _ => Err(TraceabilityError::InternalTrace2eError),
                     }
                 }
+                M2mRequest::ReleaseFlow(destination) => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        destination = %destination,
+                        "[m2m] ReleaseFlow"
+                    );
+                    // check if the destination is local
+                    let destination = if *destination.node_id() == provenance.node_id() {
+                        destination.resource().to_owned()
+                    } else {
+                        return Err(TraceabilityError::NotLocalResource);
+                    };
+                    match sequencer.call(SequencerRequest::ReleaseFlow { destination }).await? {
+                        SequencerResponse::FlowReleased { .. } => Ok(M2mResponse::Ack),
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
                 M2mRequest::BroadcastDeletion(resource) => {
                     info!(
                         node_id = %provenance.node_id(),
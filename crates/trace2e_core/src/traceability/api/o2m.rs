@@ -7,9 +7,10 @@
 //!
 //! ## Service Architecture
 //!
-//! The `O2mApiService` coordinates between two core services:
+//! The `O2mApiService` coordinates between the core services:
 //! - **Provenance Service**: For querying resource lineage and ancestry data
 //! - **Compliance Service**: For policy management and configuration updates
+//! - **Sequencer Service**: For monitoring the flow reservations
 //!
 //! ## Supported Operations
 //!
@@ -18,11 +19,20 @@
 //! capability is only available when using loopback transport layer for M2M communication.
 //!
 //! **Provenance Analysis**: Query complete resource lineage to understand data flows
-//! and dependencies for audit and compliance purposes.
+//! and dependencies for audit and compliance purposes, the volume of data read from
+//! and written to resources, and the overwrites that reset their provenance.
 //!
 //! **Consent Management**: Enforce consent for data flows on a resource by taking
 //! ownership of the resource. Set consent decision for a specific data flow operation.
 //!
+//! **Deadlock Monitoring**: Subscribe to the deadlocks detected by the sequencer waiting
+//! queue, each event describing the aborted request and the circular wait it closed.
+//!
+//! **Sequencer Inspection**: List the active flow reservations, the grants pending a report
+//! and the depth of the waiting queues, to diagnose stuck I/O operations. A stuck
+//! reservation can be forcibly released, revoking the grants pending on it. For a remote
+//! stream destination, only the local reservation is released.
+//!
 //! *Note: the notification channel for consent requests is returned but not handled by
 //! this service so for now, this will be probably implemented as websocket.*
 //!
@@ -39,9 +49,10 @@ use tracing::info;
 use crate::traceability::{
     api::{
         M2mRequest, M2mResponse,
+        p2m::PendingGrants,
         types::{
             ComplianceRequest, ComplianceResponse, O2mRequest, O2mResponse, ProvenanceRequest,
-            ProvenanceResponse,
+            ProvenanceResponse, SequencerRequest, SequencerResponse,
         },
     },
     error::TraceabilityError,
@@ -55,7 +66,9 @@ use crate::traceability::{
 /// providing policy management capabilities and resource reference queries.
 /// It coordinates between provenance and compliance services to serve external requests.
 #[derive(Debug, Clone)]
-pub struct O2mApiService<P, C, Consent, M> {
+pub struct O2mApiService<S, P, C, Consent, M> {
+    /// Service for managing flows sequencing
+    sequencer: S,
     /// Service for tracking resources provenance
     provenance: P,
     /// Service for policy management and compliance checking
@@ -64,17 +77,30 @@ pub struct O2mApiService<P, C, Consent, M> {
     consent: Consent,
     /// Client service for Middleware-to-Middleware communication
     m2m: M,
+    /// Grants pending a report in the P2M service
+    pending_grants: PendingGrants,
 }
 
-impl<P, C, Consent, M> O2mApiService<P, C, Consent, M> {
-    /// Creates a new O2M API service with the provided provenance and compliance services
-    pub fn new(provenance: P, compliance: C, consent: Consent, m2m: M) -> Self {
-        Self { provenance, compliance, consent, m2m }
+impl<S, P, C, Consent, M> O2mApiService<S, P, C, Consent, M> {
+    /// Creates a new O2M API service with the provided sequencer, provenance and compliance services
+    pub fn new(sequencer: S, provenance: P, compliance: C, consent: Consent, m2m: M) -> Self {
+        Self { sequencer, provenance, compliance, consent, m2m, pending_grants: Default::default() }
+    }
+
+    /// Sets the grants pending a report to inspect, shared with the P2M service
+    pub fn with_pending_grants(mut self, pending_grants: PendingGrants) -> Self {
+        self.pending_grants = pending_grants;
+        self
     }
 }
 
-impl<P, C, Consent, M> Service<O2mRequest> for O2mApiService<P, C, Consent, M>
+impl<S, P, C, Consent, M> Service<O2mRequest> for O2mApiService<S, P, C, Consent, M>
 where
+    S: Service<SequencerRequest, Response = SequencerResponse, Error = TraceabilityError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
     P: Service<ProvenanceRequest, Response = ProvenanceResponse, Error = TraceabilityError>
         + Clone
         + Send
@@ -106,6 +132,8 @@ where
     }
 
     fn call(&mut self, request: O2mRequest) -> Self::Future {
+        let mut sequencer = self.sequencer.clone();
+        let pending_grants = self.pending_grants.clone();
         let mut provenance = self.provenance.clone();
         let mut compliance = self.compliance.clone();
         let mut consent = self.consent.clone();
@@ -164,6 +192,21 @@ where
                         _ => Err(TraceabilityError::InternalTrace2eError),
                     }
                 }
+                O2mRequest::SetDeclassification { resource, declassification } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        resource = %resource,
+                        declassification = ?declassification,
+                        "[o2m] SetDeclassification"
+                    );
+                    match compliance
+                        .call(ComplianceRequest::SetDeclassification { resource, declassification })
+                        .await?
+                    {
+                        ComplianceResponse::PolicyUpdated => Ok(O2mResponse::Ack),
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
                 O2mRequest::SetDeleted(resource) => {
                     info!(node_id = %provenance.node_id(), resource = %resource, "[o2m] SetDeleted");
                     match compliance.call(ComplianceRequest::SetDeleted(resource)).await? {
@@ -203,6 +246,66 @@ where
                         _ => Err(TraceabilityError::InternalTrace2eError),
                     }
                 }
+                O2mRequest::WatchDeadlocks => {
+                    info!(node_id = %provenance.node_id(), "[o2m] WatchDeadlocks");
+                    match sequencer.call(SequencerRequest::WatchDeadlocks).await? {
+                        SequencerResponse::DeadlockNotifications(notifications) => {
+                            Ok(O2mResponse::DeadlockNotifications(notifications))
+                        }
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
+                O2mRequest::ListReservations => {
+                    info!(node_id = %provenance.node_id(), "[o2m] ListReservations");
+                    match sequencer.call(SequencerRequest::ListReservations).await? {
+                        SequencerResponse::Reservations(reservations) => {
+                            Ok(O2mResponse::Reservations(reservations))
+                        }
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
+                O2mRequest::ListPendingGrants => {
+                    info!(node_id = %provenance.node_id(), "[o2m] ListPendingGrants");
+                    Ok(O2mResponse::PendingGrants(pending_grants.list()))
+                }
+                O2mRequest::GetQueueDepths => {
+                    info!(node_id = %provenance.node_id(), "[o2m] GetQueueDepths");
+                    match sequencer.call(SequencerRequest::GetQueueDepths).await? {
+                        SequencerResponse::QueueDepths(depths) => {
+                            Ok(O2mResponse::QueueDepths(depths))
+                        }
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
+                O2mRequest::GetVolumes(resources) => {
+                    info!(node_id = %provenance.node_id(), "[o2m] GetVolumes");
+                    match provenance.call(ProvenanceRequest::GetVolumes(resources)).await? {
+                        ProvenanceResponse::Volumes(volumes) => Ok(O2mResponse::Volumes(volumes)),
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
+                O2mRequest::GetLineageEvents(resource) => {
+                    info!(node_id = %provenance.node_id(), resource = %resource, "[o2m] GetLineageEvents");
+                    match provenance.call(ProvenanceRequest::GetLineageEvents(resource)).await? {
+                        ProvenanceResponse::LineageEvents(events) => {
+                            Ok(O2mResponse::LineageEvents(events))
+                        }
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
+                O2mRequest::ForceRelease(destination) => {
+                    let revoked = pending_grants.revoke(&destination);
+                    info!(
+                        node_id = %provenance.node_id(),
+                        destination = %destination,
+                        revoked_grants = ?revoked,
+                        "[o2m] ForceRelease"
+                    );
+                    match sequencer.call(SequencerRequest::ReleaseFlow { destination }).await? {
+                        SequencerResponse::FlowReleased { .. } => Ok(O2mResponse::Ack),
+                        _ => Err(TraceabilityError::InternalTrace2eError),
+                    }
+                }
                 O2mRequest::GetReferences(resource) => {
                     info!(node_id = %provenance.node_id(), resource = %resource, "[o2m] GetReferences");
                     match provenance.call(ProvenanceRequest::GetReferences(resource)).await? {
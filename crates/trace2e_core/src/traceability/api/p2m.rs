@@ -18,6 +18,115 @@
 //! - **Resource Map**: Associates process/file descriptor pairs with source/destination resources
 //! - **Flow Map**: Tracks active flows by grant ID for operation completion reporting
 //!
+//! ## Grant Leases
+//!
+//! Grants can be issued with a lease duration (see `with_grant_lease`). A background reaper
+//! (see `spawn_grant_reaper`) reclaims the sequencer reservations of grants that expired or
+//! whose owning process exited before reporting, so that a crashed process cannot block a
+//! resource forever. Expiry only stops the flow from holding its resources: an operation
+//! carried out under an expired, reclaimed or revoked grant is still recorded when reported.
+//!
+//! ## Grant Tokens
+//!
+//! Grant identifiers are unguessable tokens drawn from the operating system random source.
+//! Each token is bound to the process, file descriptor and direction it was issued for, and
+//! a report carrying a token issued to another process or descriptor, or reporting an
+//! operation in the other direction, is rejected without consuming the grant.
+//!
+//! ## Transfer Volumes
+//!
+//! Reports carry the number of bytes transferred and, for seekable resources, the offset of
+//! the transferred region. Successful reports are recorded on the provenance edge of the flow,
+//! and accumulated in per-resource volume counters queried through the O2M API.
+//!
+//! ## Overwrites
+//!
+//! Files are enrolled with the mode in which they were opened. A file opened with truncation
+//! (or created from scratch), or explicitly truncated with `Truncate`, has its provenance reset
+//! so that discarded content no longer taints the new one. Discarding the content of a file is
+//! a write to it: the reset is refused unless a write of the process to the file is compliant
+//! (and consented to). The reset is recorded as a lineage event of the file.
+//!
+//! ## Declared Flows
+//!
+//! By default, the data written by a process derives from everything the process has read,
+//! so that a long-lived process reading a single secret taints all its outputs. A process may
+//! declare the actual dependencies of the writes on a descriptor with `DeclareFlow`: until
+//! `ClearFlow`, compliance is evaluated and provenance updated from the declared descriptors
+//! and the process identity only, fd-to-fd. Declarations are dropped along with the descriptor
+//! they were made on. A declaration cannot be used to strip taint: the writes are refused
+//! unless the declared descriptors cover the provenance of the process, or the policies of all
+//! the resources they leave out allow their declassification.
+//!
+//! ## Sessions
+//!
+//! Long-running workers handle unrelated requests back to back. A process may open a named
+//! session with `OpenSession`: until `EndSession`, its I/O operations are tracked as those of a
+//! distinct session activity, which starts from the provenance of the process. When the
+//! session ends, its taint is merged back into the process, unless the session policy of the
+//! middleware (see `with_session_policy`) permits to drop it and the policies of all the
+//! dropped sources allow their declassification.
+//!
+//! ## Grant Cache
+//!
+//! Processes typically read and write their resources in small chunks. Once a flow on a
+//! descriptor is found compliant, the following requests on the same `(pid, fd, direction)`
+//! are granted without a new evaluation, as long as the flow is unchanged and the policy epoch
+//! (bumped by any policy update or consent decision, through O2M or M2M alike) and the
+//! provenance epoch of its sources are unchanged. Cached grants still reserve the flow in the
+//! sequencer. Flows involving remote nodes, whose policies are not tracked locally, are always
+//! evaluated. The cache can be disabled with `with_grant_cache`.
+//!
+//! ## Batched Requests
+//!
+//! A process about to operate on several descriptors may request all the grants at once with
+//! `IoRequestBatch`. The flows are reserved in request order, then the policies of the remote
+//! stream destinations are queried with a single M2M request per remote node, and each flow is
+//! evaluated and granted on its own. A flow conflicting with a previous flow of the batch is
+//! refused, since it would wait for the process itself.
+//!
+//! ## Unix Sockets
+//!
+//! Unix domain sockets are enrolled with `UnixEnroll`, along with the pid of the peer process
+//! reported by the kernel. A write on a Unix socket is a flow from the process to the end of
+//! the connection held by the peer, named from the pid of the writer, so that the peer reads
+//! what was written: reservations, compliance and provenance are handled locally, without
+//! M2M. Unix sockets have no provenance of their own, and several connections between the
+//! same processes over the same path are tracked as one.
+//!
+//! ## Datagrams
+//!
+//! Datagram sockets (e.g., UDP) are connectionless, they are enrolled with `DatagramEnroll`
+//! along with their local socket only. Each datagram is requested with `DatagramIoRequest`,
+//! which carries the peer socket it is sent to or received from, and reported with `IoReport`.
+//! A datagram is tracked like a write or read on a stream connected to that peer: the policy
+//! of the destination is queried from the middleware of the peer node, which then records the
+//! provenance of the datagram. A socket bound to an unspecified address is enrolled with the
+//! address of the node, so that its datagrams are named as their peers receive them. This only
+//! holds for the peers that reach the node at its node id: a peer reaching it at another
+//! address (e.g., over the loopback interface or another interface of a multi-homed node) names
+//! the datagrams differently, and their provenance is not matched. Such sockets should be bound
+//! to the address their peers use.
+//!
+//! ## File Descriptor Lifecycle
+//!
+//! Processes unenroll their file descriptors with `Close` and declare duplicates with `Dup`,
+//! so that the resource map follows the descriptor table of the process.
+//!
+//! ## Process Termination
+//!
+//! A process watcher (see `spawn_process_watcher`) polls the enrolled processes and releases
+//! the state of those that exited: file descriptor mappings, pending grants and provenance,
+//! which is archived under the `(pid, starttime)` identity of the process.
+//!
+//! ## Process Hierarchy
+//!
+//! Processes are tracked from their first contact with the middleware. A descendant of a known
+//! process inherits the provenance of its nearest known ancestor and the enrollments of the
+//! descriptors it inherited from it, so that forking, even through intermediate processes,
+//! cannot be used to launder data. A program executed by a known process is recorded as a new
+//! activity derived from the previous program.
+//!
 //! ## Operation Workflow
 //!
 //! 1. **Enrollment**: Processes register their files and streams before use
@@ -32,12 +141,20 @@
 //! enforcement and provenance tracking across the network.
 
 use std::{
-    collections::HashSet, future::Future, pin::Pin, sync::Arc, task::Poll, time::SystemTime,
+    collections::{HashMap, HashSet},
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::Poll,
+    time::{Duration, Instant},
 };
 
-use dashmap::DashMap;
+use dashmap::{DashMap, mapref::entry::Entry};
+use rustix::rand::{GetRandomFlags, getrandom};
+use tokio::task::JoinHandle;
 use tower::{Service, ServiceExt};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::traceability::{
     api::types::{
@@ -46,15 +163,171 @@ use crate::traceability::{
     },
     error::TraceabilityError,
     infrastructure::{
-        naming::{NodeId, Resource},
+        naming::{DisplayableResource, LocalizedResource, NodeId, Process, Resource},
+        process::ProcessRegistry,
         validation::ResourceValidator,
     },
+    services::compliance::Policy,
 };
 
 /// Maps (process_id, file_descriptor) to (source_resource, destination_resource) pairs
 type ResourceMap = DashMap<(i32, i32), (Resource, Resource)>;
-/// Maps flow_id to (source_resource, destination_resource) pairs for active flows
-type FlowMap = DashMap<u128, (Resource, Resource)>;
+/// Maps process_id to the process resource last seen by the middleware for this pid
+type ProcessMap = DashMap<i32, Resource>;
+/// Maps grant tokens to the lease of the corresponding flow
+type FlowMap = DashMap<u128, GrantLease>;
+/// Maps (process_id, file_descriptor) to the resources declared as sources of its writes
+type DeclarationMap = DashMap<(i32, i32), HashSet<Resource>>;
+/// Maps process_id to the resource of its open session
+type SessionMap = DashMap<i32, Resource>;
+/// Maps (process_id, file_descriptor, output) to the last flow found compliant on it
+type GrantCache = DashMap<(i32, i32, bool), CachedGrant>;
+
+/// Fate of the taint of a process session when it ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionPolicy {
+    /// The taint of the session is merged back into the process
+    #[default]
+    Retain,
+    /// The taint of the session is dropped, the process is left as before the session.
+    ///
+    /// The taint is still merged back if the policy of any resource it would drop from the
+    /// process forbids its declassification, which is the default.
+    Drop,
+}
+
+/// Lease held by a process on an active flow, from its grant until its report.
+///
+/// The lease is bound to the process and file descriptor it was issued for, so that its
+/// token cannot be reported by another process or on another file descriptor.
+#[derive(Debug, Clone)]
+struct GrantLease {
+    /// Process identifier that received the grant
+    pid: i32,
+    /// File descriptor the grant was issued for
+    fd: i32,
+    /// Direction of the granted flow: true for output (write), false for input (read)
+    output: bool,
+    /// Source resource of the granted flow
+    source: Resource,
+    /// Destination resource of the granted flow
+    destination: Resource,
+    /// Resources declared as the sources of the flow, the whole source otherwise
+    declared: Option<HashSet<Resource>>,
+    /// Instant after which the grant is no longer valid, if leases are enabled
+    expires_at: Option<Instant>,
+}
+
+/// Flow found compliant, along with the epochs of the state it was evaluated on.
+#[derive(Debug, Clone, PartialEq)]
+struct CachedGrant {
+    source: Resource,
+    destination: Resource,
+    declared: Option<HashSet<Resource>>,
+    /// Policy epoch at evaluation time
+    policy_epoch: u64,
+    /// Provenance epoch of the sources of the flow at evaluation time
+    provenance_epoch: u64,
+}
+
+impl GrantLease {
+    /// Returns true if the lease has expired at the given instant.
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Checks that the flow can be held along with flows already reserved by the same process.
+    ///
+    /// The sequencer would make a flow conflicting with another flow of its own process wait
+    /// for the process itself, so such flows are refused upfront. Flows into the process
+    /// itself are compatible, as they share a single reservation of the process.
+    fn check_conflicts<'a>(
+        &self,
+        reserved: impl Iterator<Item = &'a GrantLease> + Clone,
+    ) -> Result<(), TraceabilityError> {
+        let source_busy = reserved.clone().any(|flow| flow.destination == self.source);
+        let destination_busy = reserved.into_iter().any(|flow| {
+            (flow.destination == self.destination && !self.destination.is_process())
+                || flow.source == self.destination
+        });
+        match (source_busy, destination_busy) {
+            (false, false) => Ok(()),
+            (true, false) => Err(TraceabilityError::UnavailableSource(self.source.clone())),
+            (false, true) => {
+                Err(TraceabilityError::UnavailableDestination(self.destination.clone()))
+            }
+            (true, true) => Err(TraceabilityError::UnavailableSourceAndDestination(
+                self.source.clone(),
+                self.destination.clone(),
+            )),
+        }
+    }
+}
+
+/// Grant issued to a process and not reported yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingGrant {
+    /// Identifier of the grant
+    pub grant_id: u128,
+    /// Process identifier that received the grant
+    pub pid: i32,
+    /// Source resource of the granted flow
+    pub source: Resource,
+    /// Destination resource of the granted flow
+    pub destination: Resource,
+    /// Remaining lease duration, None if the grant never expires
+    pub expires_in: Option<Duration>,
+}
+
+/// Shared handle on the grants pending a report in a P2M service.
+///
+/// Lets operators inspect the active flows of the processes, and revoke them.
+#[derive(Debug, Clone, Default)]
+pub struct PendingGrants {
+    flow_map: Arc<FlowMap>,
+    reclaimed: Arc<FlowMap>,
+}
+
+impl PendingGrants {
+    /// Lists the grants pending a report.
+    pub fn list(&self) -> Vec<PendingGrant> {
+        let now = Instant::now();
+        self.flow_map
+            .iter()
+            .map(|entry| PendingGrant {
+                grant_id: *entry.key(),
+                pid: entry.pid,
+                source: entry.source.clone(),
+                destination: entry.destination.clone(),
+                expires_in: entry
+                    .expires_at
+                    .map(|expires_at| expires_at.saturating_duration_since(now)),
+            })
+            .collect()
+    }
+
+    /// Revokes the grants pending a report on a destination.
+    ///
+    /// Revoked grants no longer hold their flow, their reservations are not released. The
+    /// operations carried out under them are still recorded when reported.
+    ///
+    /// # Returns
+    /// The identifiers of the revoked grants
+    pub fn revoke(&self, destination: &Resource) -> Vec<u128> {
+        let revoked: Vec<u128> = self
+            .flow_map
+            .iter()
+            .filter(|entry| &entry.destination == destination)
+            .map(|entry| *entry.key())
+            .collect();
+        revoked.iter().for_each(|grant_id| {
+            if let Some((grant_id, lease)) = self.flow_map.remove(grant_id) {
+                self.reclaimed.insert(grant_id, lease);
+            }
+        });
+        revoked
+    }
+}
 
 /// P2M (Process-to-Middleware) API Service.
 ///
@@ -92,8 +365,18 @@ type FlowMap = DashMap<u128, (Resource, Resource)>;
 pub struct P2mApiService<S, P, C, M> {
     /// Maps (process_id, file_descriptor) to (source_resource, destination_resource) pairs
     resource_map: Arc<ResourceMap>,
-    /// Maps flow_id to (source_resource, destination_resource) pairs for active flows
+    /// Maps process_id to the process resource last seen by the middleware for this pid
+    processes: Arc<ProcessMap>,
+    /// Maps flow_id to the lease of the corresponding active flow
     flow_map: Arc<FlowMap>,
+    /// Maps flow_id to the lease of a flow reclaimed or revoked before its report
+    reclaimed: Arc<FlowMap>,
+    /// Maps (process_id, file_descriptor) to the resources declared as sources of its writes
+    declarations: Arc<DeclarationMap>,
+    /// Maps process_id to the resource of its open session
+    sessions: Arc<SessionMap>,
+    /// Maps (process_id, file_descriptor, output) to the last flow found compliant on it
+    grant_cache: Arc<GrantCache>,
     /// Service for managing flows sequencing
     sequencer: S,
     /// Service for tracking resources provenance
@@ -104,6 +387,12 @@ pub struct P2mApiService<S, P, C, M> {
     m2m: M,
     /// Whether to perform resource validation on incoming requests
     enable_resource_validation: bool,
+    /// Lease duration of issued grants, grants never expire if None
+    grant_lease: Option<Duration>,
+    /// Fate of the taint of the sessions when they end
+    session_policy: SessionPolicy,
+    /// Whether to reuse the outcome of past compliance evaluations
+    enable_grant_cache: bool,
 }
 
 impl<S, P, C, M> P2mApiService<S, P, C, M> {
@@ -121,12 +410,20 @@ impl<S, P, C, M> P2mApiService<S, P, C, M> {
     pub fn new(sequencer: S, provenance: P, compliance: C, m2m: M) -> Self {
         Self {
             resource_map: Arc::new(ResourceMap::new()),
+            processes: Arc::new(ProcessMap::new()),
             flow_map: Arc::new(FlowMap::new()),
+            reclaimed: Arc::new(FlowMap::new()),
+            declarations: Arc::new(DeclarationMap::new()),
+            sessions: Arc::new(SessionMap::new()),
+            grant_cache: Arc::new(GrantCache::new()),
             sequencer,
             provenance,
             compliance,
             m2m,
             enable_resource_validation: false,
+            grant_lease: None,
+            session_policy: SessionPolicy::default(),
+            enable_grant_cache: true,
         }
     }
 
@@ -161,12 +458,50 @@ impl<S, P, C, M> P2mApiService<S, P, C, M> {
         self
     }
 
+    /// Sets the lease duration of the grants issued by this service.
+    ///
+    /// A grant not reported before its lease expires has its flow reservation reclaimed, the
+    /// operation is still recorded when reported. Leases are disabled by default, and a `None`
+    /// or zero duration disables them.
+    pub fn with_grant_lease(mut self, lease: Option<Duration>) -> Self {
+        self.grant_lease = lease.filter(|lease| !lease.is_zero());
+        self
+    }
+
+    /// Sets the fate of the taint of the process sessions when they end.
+    ///
+    /// By default, the taint of a session is retained by its process.
+    pub fn with_session_policy(mut self, policy: SessionPolicy) -> Self {
+        self.session_policy = policy;
+        self
+    }
+
+    /// Enables or disables the reuse of past compliance evaluations for repeated requests on
+    /// the same descriptor. The cache is enabled by default.
+    pub fn with_grant_cache(mut self, enable: bool) -> Self {
+        self.enable_grant_cache = enable;
+        if !enable {
+            self.grant_cache.clear();
+        }
+        self
+    }
+
+    /// Returns a shared handle on the grants pending a report.
+    pub fn pending_grants(&self) -> PendingGrants {
+        PendingGrants { flow_map: self.flow_map.clone(), reclaimed: self.reclaimed.clone() }
+    }
+
     /// Validates a P2M request according to resource requirements.
     ///
     /// Applies the same validation rules as the ResourceValidator:
     /// - `RemoteEnroll`: Validates both process and stream resources
-    /// - `LocalEnroll`, `IoRequest`: Validates process resources only  
+    /// - `UnixEnroll`: Validates both process and peer process resources
+    /// - `DatagramEnroll`, `DatagramIoRequest`: Validate both process and socket address
+    /// - `LocalEnroll`, `IoRequest`, `Truncate`, `DeclareFlow`, `OpenSession`, `Dup`: Validates
+    ///   process resources only
     /// - `IoReport`: Passes through without validation (grant ID is validated later)
+    /// - `ClearFlow`, `EndSession`, `Close`: Pass through without validation (the process may be
+    ///   exiting)
     ///
     /// # Arguments
     /// * `request` - The P2M request to validate
@@ -177,6 +512,8 @@ impl<S, P, C, M> P2mApiService<S, P, C, M> {
     /// # Errors
     /// - `InvalidProcess`: When the process ID is not found or accessible
     /// - `InvalidStream`: When socket addresses are malformed or incompatible
+    /// - `InvalidUnixSocket`: When the peer process of a Unix socket cannot be tracked
+    /// - `InvalidDatagram`: When the socket address of a datagram socket is malformed
     fn validate_request(request: &P2mRequest) -> Result<&P2mRequest, TraceabilityError> {
         // Use the same validation logic as the ResourceValidator
         match request {
@@ -194,19 +531,55 @@ impl<S, P, C, M> P2mApiService<S, P, C, M> {
                     Err(TraceabilityError::InvalidProcess(*pid))
                 }
             }
-            P2mRequest::LocalEnroll { pid, .. } | P2mRequest::IoRequest { pid, .. } => {
+            P2mRequest::UnixEnroll { pid, path, peer_pid, .. } => {
+                if ResourceValidator.is_valid_process(*pid) {
+                    if ResourceValidator.is_valid_unix_socket(*peer_pid) {
+                        Ok(request)
+                    } else {
+                        Err(TraceabilityError::InvalidUnixSocket(path.clone(), *peer_pid))
+                    }
+                } else {
+                    Err(TraceabilityError::InvalidProcess(*pid))
+                }
+            }
+            P2mRequest::DatagramEnroll { pid, local_socket: socket, .. }
+            | P2mRequest::DatagramIoRequest { pid, peer_socket: socket, .. } => {
+                if ResourceValidator.is_valid_process(*pid) {
+                    if ResourceValidator.is_valid_datagram(socket) {
+                        Ok(request)
+                    } else {
+                        Err(TraceabilityError::InvalidDatagram(socket.clone()))
+                    }
+                } else {
+                    Err(TraceabilityError::InvalidProcess(*pid))
+                }
+            }
+            P2mRequest::LocalEnroll { pid, .. }
+            | P2mRequest::IoRequest { pid, .. }
+            | P2mRequest::IoRequestBatch { pid, .. }
+            | P2mRequest::Truncate { pid, .. }
+            | P2mRequest::DeclareFlow { pid, .. }
+            | P2mRequest::OpenSession { pid, .. }
+            | P2mRequest::Dup { pid, .. } => {
                 if ResourceValidator.is_valid_process(*pid) {
                     Ok(request)
                 } else {
                     Err(TraceabilityError::InvalidProcess(*pid))
                 }
             }
-            P2mRequest::IoReport { .. } => Ok(request),
+            P2mRequest::IoReport { .. }
+            | P2mRequest::ClearFlow { .. }
+            | P2mRequest::EndSession { .. }
+            | P2mRequest::Close { .. } => Ok(request),
         }
     }
 
-    fn flow_id() -> u128 {
-        SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap().as_nanos()
+    /// Generates an unguessable grant token from the operating system random source.
+    fn grant_token() -> Result<u128, TraceabilityError> {
+        let mut token = [0u8; 16];
+        getrandom(&mut token, GetRandomFlags::empty())
+            .map_err(|_| TraceabilityError::InternalTrace2eError)?;
+        Ok(u128::from_ne_bytes(token))
     }
 }
 
@@ -243,13 +616,20 @@ where
     }
 
     fn call(&mut self, request: P2mRequest) -> Self::Future {
+        let mut this = self.clone();
         let resource_map = self.resource_map.clone();
+        let processes = self.processes.clone();
         let flow_map = self.flow_map.clone();
+        let reclaimed = self.reclaimed.clone();
+        let declarations = self.declarations.clone();
+        let sessions = self.sessions.clone();
+        let grant_cache = self.grant_cache.clone();
         let mut sequencer = self.sequencer.clone();
         let mut provenance = self.provenance.clone();
         let mut compliance = self.compliance.clone();
         let mut m2m = self.m2m.clone();
         let enable_validation = self.enable_resource_validation;
+        let session_policy = self.session_policy;
 
         Box::pin(async move {
             // Perform resource validation if enabled
@@ -258,16 +638,26 @@ where
             }
 
             match request {
-                P2mRequest::LocalEnroll { pid, fd, path } => {
+                P2mRequest::LocalEnroll { pid, fd, path, mode } => {
                     info!(
                         node_id = %provenance.node_id(),
                         pid = %pid,
                         fd = %fd,
                         path = %path,
+                        mode = ?mode,
                         "[p2m] LocalEnroll"
                     );
-                    resource_map
-                        .insert((pid, fd), (Resource::new_process(pid), Resource::new_file(path)));
+                    let process =
+                        Self::track_process(&processes, &resource_map, &mut provenance, pid)
+                            .await?;
+                    resource_map.insert((pid, fd), (process, Resource::new_file(path)));
+                    if mode.overwrites()
+                        && let Err(e) = this.overwrite(pid, fd).await
+                    {
+                        // The file cannot be used without the reset of its provenance
+                        resource_map.remove(&(pid, fd));
+                        return Err(e);
+                    }
                     Ok(P2mResponse::Ack)
                 }
                 P2mRequest::RemoteEnroll { pid, fd, local_socket, peer_socket } => {
@@ -279,258 +669,1239 @@ where
                         peer_socket = %peer_socket,
                         "[p2m] RemoteEnroll"
                     );
+                    let process =
+                        Self::track_process(&processes, &resource_map, &mut provenance, pid)
+                            .await?;
                     resource_map.insert(
                         (pid, fd),
-                        (
-                            Resource::new_process(pid),
-                            Resource::new_stream(local_socket, peer_socket),
-                        ),
+                        (process, Resource::new_stream(local_socket, peer_socket)),
+                    );
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::UnixEnroll { pid, fd, path, peer_pid } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        path = %path,
+                        peer_pid = %peer_pid,
+                        "[p2m] UnixEnroll"
+                    );
+                    let process =
+                        Self::track_process(&processes, &resource_map, &mut provenance, pid)
+                            .await?;
+                    resource_map
+                        .insert((pid, fd), (process, Resource::new_unix_socket(path, peer_pid)));
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::DatagramEnroll { pid, fd, local_socket } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        local_socket = %local_socket,
+                        "[p2m] DatagramEnroll"
                     );
+                    let process =
+                        Self::track_process(&processes, &resource_map, &mut provenance, pid)
+                            .await?;
+                    let local_socket = Self::node_socket(&provenance.node_id(), local_socket);
+                    resource_map
+                        .insert((pid, fd), (process, Resource::new_datagram(local_socket, None)));
                     Ok(P2mResponse::Ack)
                 }
                 P2mRequest::IoRequest { pid, fd, output } => {
-                    if let Some(resource) = resource_map.get(&(pid, fd)) {
-                        let (source, destination) = if output {
-                            (resource.0.to_owned(), resource.1.to_owned())
-                        } else {
-                            (resource.1.to_owned(), resource.0.to_owned())
-                        };
+                    this.io_request(pid, fd, None, output).await.map(P2mResponse::Grant)
+                }
+                P2mRequest::DatagramIoRequest { pid, fd, peer_socket, output } => this
+                    .io_request(pid, fd, Some(peer_socket), output)
+                    .await
+                    .map(P2mResponse::Grant),
+                P2mRequest::IoRequestBatch { pid, requests } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        requests = ?requests,
+                        "[p2m] IoRequestBatch"
+                    );
+                    Ok(P2mResponse::Grants(this.io_request_batch(pid, requests).await))
+                }
+                P2mRequest::IoReport { pid, fd, grant_id, output, result, bytes, offset } => {
+                    // The grant is only consumed by the process, descriptor and direction it was
+                    // issued for
+                    let owned = |_: &u128, lease: &GrantLease| {
+                        lease.pid == pid && lease.fd == fd && lease.output == output
+                    };
+                    if let Some((_, lease)) = flow_map.remove_if(&grant_id, owned) {
                         info!(
                             node_id = %provenance.node_id(),
-                            source = %source,
-                            destination = %destination,
-                            "[p2m] IoRequest"
+                            source = %lease.source,
+                            destination = %lease.destination,
+                            bytes = %bytes,
+                            offset = ?offset,
+                            "[p2m] IoReport"
                         );
-                        match sequencer
-                            .call(SequencerRequest::ReserveFlow {
-                                source: source.clone(),
-                                destination: destination.clone(),
+                        // The flow is released whatever the outcome of its recording, so that
+                        // a failed report does not leave its reservation behind
+                        let recorded = Self::record_report(
+                            &mut provenance,
+                            &mut m2m,
+                            &lease,
+                            result,
+                            bytes,
+                            offset,
+                        )
+                        .await;
+                        sequencer
+                            .call(SequencerRequest::LeaveFlow {
+                                source: lease.source,
+                                destination: lease.destination,
                             })
-                            .await
-                        {
-                            Ok(SequencerResponse::FlowReserved) => {
-                                let localized_destination =
-                                    destination.clone().into_localized(provenance.node_id());
-                                let destination_policy = if localized_destination
-                                    .resource()
-                                    .is_stream()
-                                {
-                                    debug!(
-                                        node_id = %provenance.node_id(),
-                                        destination = %localized_destination,
-                                        "[p2m] Querying destination policy for remote stream"
-                                    );
-                                    match m2m
-                                        .ready()
-                                        .await?
-                                        .call(M2mRequest::GetDestinationPolicy(
-                                            localized_destination.clone(),
-                                        ))
-                                        .await
-                                    {
-                                        Ok(M2mResponse::DestinationPolicy(policy)) => Some(policy),
-                                        _ => None, // anyway, errors are handled later, but this may be improved
-                                    }
-                                } else {
-                                    None
-                                };
-                                let flow_id = match provenance
-                                    .call(ProvenanceRequest::GetReferences(source.clone()))
-                                    .await
-                                {
-                                    Ok(ProvenanceResponse::Provenance(references)) => {
-                                        let (local_references, remote_references): (
-                                            HashSet<_>,
-                                            HashSet<_>,
-                                        ) = references
-                                            .into_iter()
-                                            .partition(|r| *r.node_id() == provenance.node_id());
-                                        match compliance
-                                            .call(ComplianceRequest::EvalCompliance {
-                                                sources: local_references
-                                                    .iter()
-                                                    .map(|r| r.resource().to_owned())
-                                                    .collect(),
-                                                destination: localized_destination.clone(),
-                                                destination_policy: destination_policy.clone(),
-                                            })
-                                            .await
-                                        {
-                                            Ok(ComplianceResponse::Grant) => {
-                                                // Local compliance check passed. If we have no remote references, we can
-                                                // grant the flow, otherwise we need to check the compliance of the remote nodes.
-                                                // Destination policy is required for remote sources compliance checking.
-                                                if remote_references.is_empty() {
-                                                    debug!(
-                                                        node_id = %provenance.node_id(),
-                                                        "[p2m] Local compliance check passed, granting flow"
-                                                    );
-                                                    Ok(Self::flow_id())
-                                                } else {
-                                                    // It the destination is not a stream, so it is a local resource, we can get the policy from the compliance service
-                                                    // This could have been done earlier, but we do it here, to make this call only when it is really needed.
-                                                    let destination_policy = if let Some(policy) =
-                                                        destination_policy
-                                                    {
-                                                        policy
-                                                    } else if !destination.is_stream() {
-                                                        match compliance.call(ComplianceRequest::GetPolicy(destination.clone())).await {
-                                                            Ok(ComplianceResponse::Policy(policy)) => policy,
-                                                            _ => return Err(TraceabilityError::InternalTrace2eError),
-                                                        }
-                                                    } else {
-                                                        return Err(
-                                                            TraceabilityError::InternalTrace2eError,
-                                                        );
-                                                    };
-                                                    debug!(
-                                                        node_id = %provenance.node_id(),
-                                                        "[p2m] Querying remote sources compliance"
-                                                    );
-                                                    match m2m
-                                                        .ready()
-                                                        .await?
-                                                        .call(M2mRequest::CheckSourceCompliance {
-                                                            sources: remote_references,
-                                                            destination: (
-                                                                localized_destination,
-                                                                destination_policy,
-                                                            ),
-                                                        })
-                                                        .await
-                                                    {
-                                                        Ok(M2mResponse::Ack) => {
-                                                            // Remote sources compliance check passed
-                                                            // Flow can be granted, return the flow id
-                                                            Ok(Self::flow_id())
-                                                        }
-                                                        Err(e) => Err(e),
-                                                        _ => Err(
-                                                            TraceabilityError::InternalTrace2eError,
-                                                        ),
-                                                    }
-                                                }
-                                            }
-                                            Err(e) => Err(e),
-                                            _ => Err(TraceabilityError::InternalTrace2eError),
-                                        }
-                                    }
-                                    Err(e) => Err(e),
-                                    _ => Err(TraceabilityError::InternalTrace2eError),
-                                };
-                                match flow_id {
-                                    Ok(flow_id) => {
-                                        // Compliance check passed, flow can be granted, return the flow id
-                                        flow_map.insert(flow_id, (source, destination));
-                                        Ok(P2mResponse::Grant(flow_id))
-                                    }
-                                    Err(e) => {
-                                        debug!(
-                                            node_id = %provenance.node_id(),
-                                            error = ?e,
-                                            "[p2m] Compliance check failed, releasing flow"
-                                        );
-                                        // release the flow, and then forward the error
-                                        sequencer
-                                            .call(SequencerRequest::ReleaseFlow { destination })
-                                            .await?;
-                                        Err(e)
-                                    }
-                                }
-                            }
-                            _ => Err(TraceabilityError::InternalTrace2eError),
+                            .await?;
+                        recorded.map(|()| P2mResponse::Ack)
+                    } else if let Some((_, lease)) = reclaimed.remove_if(&grant_id, owned) {
+                        // The reservation is already released, but the operation was carried
+                        // out and its provenance must not be lost
+                        warn!(
+                            node_id = %provenance.node_id(),
+                            grant_id = %grant_id,
+                            source = %lease.source,
+                            destination = %lease.destination,
+                            bytes = %bytes,
+                            offset = ?offset,
+                            "[p2m] IoReport on a reclaimed grant"
+                        );
+                        Self::record_report(
+                            &mut provenance,
+                            &mut m2m,
+                            &lease,
+                            result,
+                            bytes,
+                            offset,
+                        )
+                        .await
+                        .map(|()| P2mResponse::Ack)
+                    } else if let Some(lease) = flow_map
+                        .get(&grant_id)
+                        .map(|lease| lease.clone())
+                        .or_else(|| reclaimed.get(&grant_id).map(|lease| lease.clone()))
+                    {
+                        warn!(
+                            node_id = %provenance.node_id(),
+                            pid = %pid,
+                            fd = %fd,
+                            owner_pid = %lease.pid,
+                            owner_fd = %lease.fd,
+                            output = %output,
+                            owner_output = %lease.output,
+                            "[p2m] IoReport on a grant issued to another process, descriptor or direction"
+                        );
+                        if lease.pid != pid {
+                            Err(TraceabilityError::GrantProcessMismatch(grant_id, pid))
+                        } else if lease.fd != fd {
+                            Err(TraceabilityError::GrantFdMismatch(grant_id, fd))
+                        } else {
+                            Err(TraceabilityError::GrantFlowMismatch(grant_id))
                         }
                     } else {
-                        Err(TraceabilityError::UndeclaredResource(pid, fd))
+                        Err(TraceabilityError::NotFoundFlow(grant_id))
                     }
                 }
-                P2mRequest::IoReport { grant_id, .. } => {
-                    if let Some((_, (source, destination))) = flow_map.remove(&grant_id) {
-                        info!(
-                            node_id = %provenance.node_id(),
-                            source = %source,
-                            destination = %destination,
-                            "[p2m] IoReport"
-                        );
-                        if let Some(remote_stream) = destination.try_into_localized_peer_stream() {
-                            match provenance
-                                .call(ProvenanceRequest::GetReferences(source.clone()))
-                                .await?
-                            {
-                                ProvenanceResponse::Provenance(references) => {
-                                    debug!(
-                                        remote_node_id = remote_stream.node_id(),
-                                        "[p2m] Updating remote provenance"
-                                    );
-                                    m2m.ready()
-                                        .await?
-                                        .call(M2mRequest::UpdateProvenance {
-                                            source_prov: references,
-                                            destination: remote_stream,
-                                        })
-                                        .await?;
-                                }
-                                _ => return Err(TraceabilityError::InternalTrace2eError),
-                            };
-                        } else {
-                            info!(
+                P2mRequest::Truncate { pid, fd } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        "[p2m] Truncate"
+                    );
+                    if !resource_map.contains_key(&(pid, fd)) {
+                        return Err(TraceabilityError::UndeclaredResource(pid, fd));
+                    }
+                    this.overwrite(pid, fd).await.map(|()| P2mResponse::Ack)
+                }
+                P2mRequest::DeclareFlow { pid, fd, sources } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        sources = ?sources,
+                        "[p2m] DeclareFlow"
+                    );
+                    if !resource_map.contains_key(&(pid, fd)) {
+                        return Err(TraceabilityError::UndeclaredResource(pid, fd));
+                    }
+                    // Sources are resolved now, the descriptors may be closed before the writes
+                    let declared = sources
+                        .into_iter()
+                        .map(|source_fd| {
+                            resource_map
+                                .get(&(pid, source_fd))
+                                .map(|resources| resources.1.clone())
+                                .ok_or(TraceabilityError::UndeclaredResource(pid, source_fd))
+                        })
+                        .collect::<Result<HashSet<_>, _>>()?;
+                    declarations.insert((pid, fd), declared);
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::ClearFlow { pid, fd } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        "[p2m] ClearFlow"
+                    );
+                    declarations.remove(&(pid, fd));
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::OpenSession { pid, name } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        name = %name,
+                        "[p2m] OpenSession"
+                    );
+                    let process =
+                        Self::track_process(&processes, &resource_map, &mut provenance, pid)
+                            .await?;
+                    let Resource::Process(process_info) = process.clone() else {
+                        return Err(TraceabilityError::InvalidProcess(pid));
+                    };
+                    let session = Resource::new_session(process_info, name);
+                    match sessions.entry(pid) {
+                        Entry::Occupied(_) => {
+                            return Err(TraceabilityError::SessionAlreadyOpen(pid));
+                        }
+                        Entry::Vacant(entry) => entry.insert(session.clone()),
+                    };
+                    // The session starts from the provenance of the process
+                    if let Err(e) = provenance
+                        .call(ProvenanceRequest::UpdateProvenance {
+                            source: process,
+                            destination: session,
+                        })
+                        .await
+                    {
+                        sessions.remove(&pid);
+                        return Err(e);
+                    }
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::EndSession { pid } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        policy = ?session_policy,
+                        "[p2m] EndSession"
+                    );
+                    let Some(session) = sessions.get(&pid).map(|session| session.clone()) else {
+                        return Err(TraceabilityError::NoOpenSession(pid));
+                    };
+                    // The taint of the pending operations of the session would be lost
+                    if flow_map
+                        .iter()
+                        .any(|lease| lease.source == session || lease.destination == session)
+                    {
+                        return Err(TraceabilityError::SessionBusy(pid));
+                    }
+                    let process =
+                        Self::track_process(&processes, &resource_map, &mut provenance, pid)
+                            .await?;
+                    // The taint of the session is only dropped if the policies of all the
+                    // sources it would remove from the process allow their declassification
+                    let retain = session_policy == SessionPolicy::Retain || {
+                        let dropped = Self::references(&mut provenance, &session)
+                            .await?
+                            .difference(&Self::references(&mut provenance, &process).await?)
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        !Self::allow_declassification(
+                            &mut compliance,
+                            &provenance.node_id(),
+                            dropped,
+                        )
+                        .await?
+                    };
+                    // The session is only closed once its taint is merged, so that a failed
+                    // merge leaves it open
+                    if retain {
+                        if session_policy == SessionPolicy::Drop {
+                            warn!(
                                 node_id = %provenance.node_id(),
-                                "[p2m] Updating local provenance"
+                                pid = %pid,
+                                "[p2m] Session sources forbid declassification, retaining taint"
                             );
-                            provenance
-                                .call(ProvenanceRequest::UpdateProvenance {
-                                    source,
-                                    destination: destination.clone(),
-                                })
-                                .await?;
                         }
-
-                        sequencer.call(SequencerRequest::ReleaseFlow { destination }).await?;
-                        Ok(P2mResponse::Ack)
-                    } else {
-                        Err(TraceabilityError::NotFoundFlow(grant_id))
+                        provenance
+                            .call(ProvenanceRequest::UpdateProvenance {
+                                source: session.clone(),
+                                destination: process,
+                            })
+                            .await?;
                     }
+                    sessions.remove_if(&pid, |_, open| *open == session);
+                    provenance.call(ProvenanceRequest::DropProvenance(session)).await?;
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::Close { pid, fd } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        "[p2m] Close"
+                    );
+                    resource_map.remove(&(pid, fd));
+                    declarations.remove(&(pid, fd));
+                    grant_cache.retain(|key, _| (key.0, key.1) != (pid, fd));
+                    Ok(P2mResponse::Ack)
+                }
+                P2mRequest::Dup { pid, fd, new_fd } => {
+                    info!(
+                        node_id = %provenance.node_id(),
+                        pid = %pid,
+                        fd = %fd,
+                        new_fd = %new_fd,
+                        "[p2m] Dup"
+                    );
+                    let resources = resource_map
+                        .get(&(pid, fd))
+                        .map(|resources| resources.clone())
+                        .ok_or(TraceabilityError::UndeclaredResource(pid, fd))?;
+                    resource_map.insert((pid, new_fd), resources);
+                    Ok(P2mResponse::Ack)
                 }
             }
         })
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use tower::Service;
+impl<S, P, C, M> P2mApiService<S, P, C, M>
+where
+    S: Service<SequencerRequest, Response = SequencerResponse, Error = TraceabilityError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    P: Service<ProvenanceRequest, Response = ProvenanceResponse, Error = TraceabilityError>
+        + Clone
+        + Send
+        + NodeId
+        + 'static,
+    P::Future: Send,
+    C: Service<ComplianceRequest, Response = ComplianceResponse, Error = TraceabilityError>
+        + Clone
+        + Send
+        + 'static,
+    C::Future: Send,
+    M: Service<M2mRequest, Response = M2mResponse, Error = TraceabilityError>
+        + Clone
+        + Send
+        + 'static,
+    M::Future: Send,
+{
+    /// Tracks the identity of a process in contact with the middleware.
+    ///
+    /// At first contact, a process descending from a known process inherits the provenance of
+    /// its nearest known ancestor, and the enrollments of the file descriptors it shares with
+    /// it. If a known process executed a new program, the new program is recorded as a new
+    /// activity derived from the previous one, and takes over the descriptors that survived
+    /// the `exec`.
+    ///
+    /// # Returns
+    /// The current process resource
+    async fn track_process(
+        processes: &ProcessMap,
+        resource_map: &ResourceMap,
+        provenance: &mut P,
+        pid: i32,
+    ) -> Result<Resource, TraceabilityError> {
+        let registry = ProcessRegistry::global();
+        let Some(current) = registry.get(pid).map(Resource::Process) else {
+            return Ok(Resource::new_process(pid));
+        };
+        match processes.insert(pid, current.clone()) {
+            Some(previous) if previous == current => {}
+            Some(Resource::Process(previous)) if matches!(&current, Resource::Process(c) if c.starttime == previous.starttime) =>
+            {
+                let previous = Resource::Process(previous);
+                info!(
+                    node_id = %provenance.node_id(),
+                    previous = %previous,
+                    current = %current,
+                    "[p2m] Process executed a new program"
+                );
+                provenance
+                    .call(ProvenanceRequest::UpdateProvenance {
+                        source: previous.clone(),
+                        destination: current.clone(),
+                    })
+                    .await?;
+                resource_map.retain(|(_, fd), (process, _)| {
+                    *process != previous || registry.is_open_fd(pid, *fd)
+                });
+                resource_map
+                    .iter_mut()
+                    .filter(|entry| entry.0 == previous)
+                    .for_each(|mut entry| entry.0 = current.clone());
+            }
+            _ => {
+                // Intermediate processes of a double fork (e.g., a shell) may not be tracked
+                if let Some(ancestor) =
+                    registry.ancestors(pid).map(Resource::Process).find(|ancestor| {
+                        matches!(ancestor, Resource::Process(process)
+                            if processes.get(&process.pid).is_some_and(|known| *known == *ancestor))
+                    })
+                    && let Resource::Process(ancestor_process) = &ancestor
+                {
+                    let apid = ancestor_process.pid;
+                    info!(
+                        node_id = %provenance.node_id(),
+                        ancestor = %ancestor,
+                        child = %current,
+                        "[p2m] Process inherits from its ancestor"
+                    );
+                    provenance
+                        .call(ProvenanceRequest::UpdateProvenance {
+                            source: ancestor.clone(),
+                            destination: current.clone(),
+                        })
+                        .await?;
+                    let inherited: Vec<(i32, Resource)> = resource_map
+                        .iter()
+                        .filter(|entry| entry.key().0 == apid && entry.0 == ancestor)
+                        .map(|entry| (entry.key().1, entry.1.clone()))
+                        .collect();
+                    for (fd, resource) in inherited {
+                        if registry.is_inherited_fd(apid, pid, fd) {
+                            resource_map.entry((pid, fd)).or_insert((current.clone(), resource));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(current)
+    }
 
-    use super::*;
-    use crate::{
-        traceability::services::{
-            compliance::ComplianceService, provenance::ProvenanceService,
-            sequencer::SequencerService,
-        },
-        transport::nop::M2mNop,
-    };
+    /// Collects the provenance of the data flowing from a source.
+    ///
+    /// Without declaration, this is the provenance of the source. The data written on a
+    /// descriptor with declared dependencies derives from the declared resources and from
+    /// the identity of the writing process, but not from the rest of the process provenance.
+    async fn flow_references(
+        provenance: &mut P,
+        source: &Resource,
+        declared: Option<&HashSet<Resource>>,
+    ) -> Result<HashSet<LocalizedResource>, TraceabilityError> {
+        let Some(declared) = declared else {
+            return match provenance.call(ProvenanceRequest::GetReferences(source.clone())).await? {
+                ProvenanceResponse::Provenance(references) => Ok(references),
+                _ => Err(TraceabilityError::InternalTrace2eError),
+            };
+        };
+        let mut references =
+            HashSet::from([LocalizedResource::new(provenance.node_id(), source.clone())]);
+        for resource in declared {
+            match provenance.call(ProvenanceRequest::GetReferences(resource.clone())).await? {
+                ProvenanceResponse::Provenance(declared_references) => {
+                    references.extend(declared_references)
+                }
+                _ => return Err(TraceabilityError::InternalTrace2eError),
+            }
+        }
+        Ok(references)
+    }
 
-    #[tokio::test]
-    async fn unit_trace2e_service_request_response() {
-        crate::trace2e_tracing::init();
-        let mut p2m_service = P2mApiService::new(
-            SequencerService::default(),
-            ProvenanceService::default(),
-            ComplianceService::default(),
-            M2mNop,
-        );
+    /// Returns the provenance of a resource.
+    async fn references(
+        provenance: &mut P,
+        resource: &Resource,
+    ) -> Result<HashSet<LocalizedResource>, TraceabilityError> {
+        match provenance.call(ProvenanceRequest::GetReferences(resource.clone())).await? {
+            ProvenanceResponse::Provenance(references) => Ok(references),
+            _ => Err(TraceabilityError::InternalTrace2eError),
+        }
+    }
 
-        assert_eq!(
-            p2m_service
-                .call(P2mRequest::LocalEnroll { pid: 1, fd: 3, path: "/tmp/test.txt".to_string() })
-                .await
-                .unwrap(),
-            P2mResponse::Ack
-        );
-        assert_eq!(
-            p2m_service
-                .call(P2mRequest::RemoteEnroll {
-                    pid: 1,
-                    fd: 3,
+    /// Checks whether the policies of all the given references allow their declassification.
+    ///
+    /// Processes are skipped, as they are activities rather than data. Remote references,
+    /// whose policies are not tracked locally, are considered to forbid it.
+    async fn allow_declassification(
+        compliance: &mut C,
+        node_id: &str,
+        references: impl IntoIterator<Item = LocalizedResource>,
+    ) -> Result<bool, TraceabilityError> {
+        let mut resources = HashSet::new();
+        for reference in references {
+            if reference.node_id() != node_id {
+                return Ok(false);
+            }
+            if !reference.resource().is_process() {
+                resources.insert(reference.resource().clone());
+            }
+        }
+        if resources.is_empty() {
+            return Ok(true);
+        }
+        match compliance.call(ComplianceRequest::GetPolicies(resources)).await? {
+            ComplianceResponse::Policies(policies) => {
+                Ok(policies.values().all(Policy::allows_declassification))
+            }
+            _ => Err(TraceabilityError::InternalTrace2eError),
+        }
+    }
+
+    /// Returns the policy epoch and the provenance epoch of the sources of a flow, or None if
+    /// they cannot be retrieved, in which case the flow is evaluated as usual.
+    async fn flow_epochs(
+        compliance: &mut C,
+        provenance: &mut P,
+        source: &Resource,
+        declared: Option<&HashSet<Resource>>,
+    ) -> Option<(u64, u64)> {
+        let ComplianceResponse::PolicyEpoch(policy_epoch) =
+            compliance.call(ComplianceRequest::GetPolicyEpoch).await.ok()?
+        else {
+            return None;
+        };
+        let mut sources = declared.cloned().unwrap_or_default();
+        sources.insert(source.clone());
+        let ProvenanceResponse::Epoch(provenance_epoch) =
+            provenance.call(ProvenanceRequest::GetEpoch(sources)).await.ok()?
+        else {
+            return None;
+        };
+        Some((policy_epoch, provenance_epoch))
+    }
+
+    /// Resolves the flow of an I/O operation of a process on a file descriptor.
+    ///
+    /// The operations on a datagram socket carry the peer socket of their datagram, which is
+    /// the resource actually read or written.
+    ///
+    /// # Returns
+    /// The lease of the flow, which starts once the flow is granted
+    ///
+    /// # Errors
+    /// `InvalidDatagram` if a peer socket is given for a descriptor that is not a datagram
+    /// socket, or missing for a datagram socket
+    async fn resolve_flow(
+        &mut self,
+        pid: i32,
+        fd: i32,
+        peer_socket: Option<String>,
+        output: bool,
+    ) -> Result<GrantLease, TraceabilityError> {
+        // A child process may use descriptors inherited from its parent without
+        // enrolling them, they are resolved at its first contact
+        if !self.resource_map.contains_key(&(pid, fd)) {
+            Self::track_process(&self.processes, &self.resource_map, &mut self.provenance, pid)
+                .await?;
+        }
+        let Some(resource) = self.resource_map.get(&(pid, fd)) else {
+            return Err(TraceabilityError::UndeclaredResource(pid, fd));
+        };
+        // The operations of a process in a session are those of the session
+        let process = self
+            .sessions
+            .get(&pid)
+            .map(|session| session.clone())
+            .unwrap_or_else(|| resource.0.to_owned());
+        let fd_resource = match peer_socket {
+            _ if resource.1.is_datagram() != peer_socket.is_some() => {
+                Err(TraceabilityError::InvalidDatagram(resource.1.to_string()))
+            }
+            Some(peer_socket) => resource
+                .1
+                .with_datagram_peer(peer_socket)
+                .ok_or_else(|| TraceabilityError::InvalidDatagram(resource.1.to_string())),
+            // The data written on a Unix socket is held by the end of its peer
+            None if output => Ok(resource
+                .1
+                .try_into_local_peer_socket(pid)
+                .unwrap_or_else(|| resource.1.to_owned())),
+            None => Ok(resource.1.to_owned()),
+        };
+        drop(resource);
+        let (source, destination) =
+            if output { (process, fd_resource?) } else { (fd_resource?, process) };
+        // Declared dependencies only apply to the writes of the process
+        let declared = output
+            .then(|| self.declarations.get(&(pid, fd)).map(|sources| sources.clone()))
+            .flatten();
+        info!(
+            node_id = %self.provenance.node_id(),
+            source = %source,
+            destination = %destination,
+            declared = ?declared,
+            "[p2m] IoRequest"
+        );
+        Ok(GrantLease { pid, fd, output, source, destination, declared, expires_at: None })
+    }
+
+    /// Returns a local socket address of the node, replacing an unspecified address (e.g., a
+    /// socket bound to `0.0.0.0`) by the address of the node.
+    ///
+    /// The datagrams of a socket are named after the address their peers receive them from,
+    /// which is assumed to be the address of the node rather than the one the socket is bound
+    /// to. The address is left unchanged if the node id is not an IP address.
+    fn node_socket(node_id: &str, local_socket: String) -> String {
+        let node_ip = node_id.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>();
+        match (local_socket.parse::<SocketAddr>(), node_ip) {
+            (Ok(socket), Ok(node_ip)) if socket.ip().is_unspecified() => {
+                SocketAddr::new(node_ip, socket.port()).to_string()
+            }
+            _ => local_socket,
+        }
+    }
+
+    /// Reserves a flow in the sequencer.
+    ///
+    /// A joined flow shares the reservation of its destination with the flows already
+    /// reserved on it by the same process.
+    async fn reserve_flow(
+        &mut self,
+        flow: &GrantLease,
+        join: bool,
+    ) -> Result<(), TraceabilityError> {
+        let (source, destination) = (flow.source.clone(), flow.destination.clone());
+        let request = if join {
+            SequencerRequest::JoinFlow { source, destination }
+        } else {
+            SequencerRequest::ReserveFlow { source, destination }
+        };
+        // The errors of the sequencer are forwarded as is, so that the process learns why
+        // the flow could not be reserved
+        match self.sequencer.call(request).await? {
+            SequencerResponse::FlowReserved => Ok(()),
+            _ => Err(TraceabilityError::InternalTrace2eError),
+        }
+    }
+
+    /// Queries the policy of a remote stream destination from its middleware, which reserves
+    /// the stream on its side.
+    ///
+    /// # Returns
+    /// The policy of the destination, None if it is local or if the query failed
+    async fn destination_policy(&mut self, destination: &Resource) -> Option<Policy> {
+        let localized_destination = destination.clone().into_localized(self.provenance.node_id());
+        if !localized_destination.resource().is_network() {
+            return None;
+        }
+        debug!(
+            node_id = %self.provenance.node_id(),
+            destination = %localized_destination,
+            "[p2m] Querying destination policy for remote stream"
+        );
+        match self
+            .m2m
+            .ready()
+            .await
+            .ok()?
+            .call(M2mRequest::GetDestinationPolicy(localized_destination))
+            .await
+        {
+            Ok(M2mResponse::DestinationPolicy(policy)) => Some(policy),
+            _ => None, // anyway, errors are handled later, but this may be improved
+        }
+    }
+
+    /// Queries the policies of remote stream destinations, with a single request for all the
+    /// destinations connected to a same remote node.
+    ///
+    /// # Returns
+    /// The policies of the destinations reserved by their middleware
+    async fn destination_policies(
+        &mut self,
+        destinations: HashSet<LocalizedResource>,
+    ) -> HashMap<LocalizedResource, Policy> {
+        let mut partitions: HashMap<String, HashSet<LocalizedResource>> = HashMap::new();
+        for destination in destinations {
+            partitions.entry(destination.node_id().clone()).or_default().insert(destination);
+        }
+        let mut policies = HashMap::new();
+        for (node_id, destinations) in partitions {
+            debug!(
+                node_id = %self.provenance.node_id(),
+                remote_node_id = %node_id,
+                destinations = %DisplayableResource::from(&destinations),
+                "[p2m] Querying destination policies for remote streams"
+            );
+            let Ok(m2m) = self.m2m.ready().await else {
+                continue;
+            };
+            if let Ok(M2mResponse::DestinationPolicies(node_policies)) =
+                m2m.call(M2mRequest::GetDestinationPolicies(destinations)).await
+            {
+                policies.extend(node_policies);
+            }
+        }
+        policies
+    }
+
+    /// Evaluates the compliance of a reserved flow.
+    ///
+    /// # Returns
+    /// Whether the flow was evaluated on local policies only, in which case the outcome of
+    /// the evaluation can be cached
+    async fn eval_flow(
+        &mut self,
+        flow: &GrantLease,
+        destination_policy: Option<Policy>,
+    ) -> Result<bool, TraceabilityError> {
+        let node_id = self.provenance.node_id();
+        let references =
+            Self::flow_references(&mut self.provenance, &flow.source, flow.declared.as_ref())
+                .await?;
+        // The declared sources may only leave out the references of the process whose policies
+        // allow their declassification
+        if flow.declared.is_some() {
+            let dropped = Self::references(&mut self.provenance, &flow.source)
+                .await?
+                .difference(&references)
+                .cloned()
+                .collect::<Vec<_>>();
+            if !Self::allow_declassification(&mut self.compliance, &node_id, dropped).await? {
+                debug!(node_id = %node_id, "[p2m] Declared sources drop a classified reference");
+                return Err(TraceabilityError::DirectPolicyViolation);
+            }
+        }
+        let (local_references, remote_references): (HashSet<_>, HashSet<_>) =
+            references.into_iter().partition(|r| *r.node_id() == node_id);
+        let localized_destination = flow.destination.clone().into_localized(node_id.clone());
+        match self
+            .compliance
+            .call(ComplianceRequest::EvalCompliance {
+                sources: local_references.iter().map(|r| r.resource().to_owned()).collect(),
+                destination: localized_destination.clone(),
+                destination_policy: destination_policy.clone(),
+            })
+            .await?
+        {
+            ComplianceResponse::Grant => (),
+            _ => return Err(TraceabilityError::InternalTrace2eError),
+        }
+        // Local compliance check passed. If we have no remote references, we can
+        // grant the flow, otherwise we need to check the compliance of the remote nodes.
+        // Destination policy is required for remote sources compliance checking.
+        if remote_references.is_empty() {
+            debug!(node_id = %node_id, "[p2m] Local compliance check passed, granting flow");
+            return Ok(!flow.destination.is_network());
+        }
+        // It the destination is not a stream, so it is a local resource, we can get the policy from the compliance service
+        // This could have been done earlier, but we do it here, to make this call only when it is really needed.
+        let destination_policy = if let Some(policy) = destination_policy {
+            policy
+        } else if !flow.destination.is_network() {
+            match self
+                .compliance
+                .call(ComplianceRequest::GetPolicy(flow.destination.clone()))
+                .await?
+            {
+                ComplianceResponse::Policy(policy) => policy,
+                _ => return Err(TraceabilityError::InternalTrace2eError),
+            }
+        } else {
+            return Err(TraceabilityError::InternalTrace2eError);
+        };
+        debug!(node_id = %node_id, "[p2m] Querying remote sources compliance");
+        match self
+            .m2m
+            .ready()
+            .await?
+            .call(M2mRequest::CheckSourceCompliance {
+                sources: remote_references,
+                destination: (localized_destination, destination_policy),
+            })
+            .await?
+        {
+            // Remote sources compliance check passed
+            M2mResponse::Ack => Ok(false),
+            _ => Err(TraceabilityError::InternalTrace2eError),
+        }
+    }
+
+    /// Grants a reserved flow if it is compliant, releases its reservations otherwise.
+    ///
+    /// The destination policy is the one returned by the middleware of a remote stream
+    /// destination, which then holds a reservation on the stream as well.
+    async fn grant_flow(
+        &mut self,
+        mut flow: GrantLease,
+        destination_policy: Option<Policy>,
+    ) -> Result<u128, TraceabilityError> {
+        // The epochs are read once the flow is reserved, so that the state they describe is
+        // not changed by a concurrent flow
+        let epochs = if self.enable_grant_cache {
+            Self::flow_epochs(
+                &mut self.compliance,
+                &mut self.provenance,
+                &flow.source,
+                flow.declared.as_ref(),
+            )
+            .await
+        } else {
+            None
+        };
+        let cached = epochs.is_some_and(|(policy_epoch, provenance_epoch)| {
+            self.grant_cache.get(&(flow.pid, flow.fd, flow.output)).is_some_and(|grant| {
+                grant.source == flow.source
+                    && grant.destination == flow.destination
+                    && grant.declared == flow.declared
+                    && grant.policy_epoch == policy_epoch
+                    && grant.provenance_epoch == provenance_epoch
+            })
+        });
+        let remote_reserved = destination_policy.is_some();
+        let evaluation = if cached {
+            debug!(
+                node_id = %self.provenance.node_id(),
+                "[p2m] Cached grant still valid, granting flow"
+            );
+            Ok(false)
+        } else {
+            self.eval_flow(&flow, destination_policy).await
+        };
+        match evaluation.and_then(|cacheable| Ok((Self::grant_token()?, cacheable))) {
+            Ok((flow_id, cacheable)) => {
+                // Only the flows evaluated on local policies are cached
+                if let Some((policy_epoch, provenance_epoch)) = epochs.filter(|_| cacheable) {
+                    self.grant_cache.insert(
+                        (flow.pid, flow.fd, flow.output),
+                        CachedGrant {
+                            source: flow.source.clone(),
+                            destination: flow.destination.clone(),
+                            declared: flow.declared.clone(),
+                            policy_epoch,
+                            provenance_epoch,
+                        },
+                    );
+                }
+                // Compliance check passed, flow can be granted, return the flow id
+                flow.expires_at = self.grant_lease.map(|lease| Instant::now() + lease);
+                self.flow_map.insert(flow_id, flow);
+                Ok(flow_id)
+            }
+            Err(e) => {
+                debug!(
+                    node_id = %self.provenance.node_id(),
+                    error = ?e,
+                    "[p2m] Compliance check failed, releasing flow"
+                );
+                // release the flow, and then forward the error
+                let remote_stream = flow.destination.try_into_localized_peer_stream();
+                self.sequencer
+                    .call(SequencerRequest::LeaveFlow {
+                        source: flow.source,
+                        destination: flow.destination,
+                    })
+                    .await?;
+                // the remote middleware reserved the stream when answering the destination
+                // policy query
+                if remote_reserved && let Some(remote_stream) = remote_stream {
+                    self.m2m.ready().await?.call(M2mRequest::ReleaseFlow(remote_stream)).await?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Evaluates an I/O operation of a process on a file descriptor, and grants it if it is
+    /// compliant.
+    ///
+    /// The operations on a datagram socket are evaluated on the datagram exchanged with the
+    /// given peer socket.
+    async fn io_request(
+        &mut self,
+        pid: i32,
+        fd: i32,
+        peer_socket: Option<String>,
+        output: bool,
+    ) -> Result<u128, TraceabilityError> {
+        let flow = self.resolve_flow(pid, fd, peer_socket, output).await?;
+        self.reserve_flow(&flow, false).await?;
+        let destination_policy = self.destination_policy(&flow.destination).await;
+        self.grant_flow(flow, destination_policy).await
+    }
+
+    /// Evaluates several I/O operations of a process, and grants those that are compliant.
+    ///
+    /// All the flows are reserved before the policies of their remote stream destinations are
+    /// queried, so that a single M2M request is sent to each remote node.
+    ///
+    /// # Returns
+    /// The outcome of each operation, in request order
+    async fn io_request_batch(
+        &mut self,
+        pid: i32,
+        requests: Vec<(i32, bool)>,
+    ) -> Vec<Result<u128, TraceabilityError>> {
+        let mut flows: Vec<Result<GrantLease, TraceabilityError>> =
+            Vec::with_capacity(requests.len());
+        for (fd, output) in requests {
+            let flow = match self.resolve_flow(pid, fd, None, output).await {
+                Ok(flow) => match flow.check_conflicts(flows.iter().flatten()) {
+                    Ok(()) => {
+                        // The process is reserved once as the destination of all its reads
+                        let join = flow.destination.is_process()
+                            && flows.iter().flatten().any(|f| f.destination == flow.destination);
+                        self.reserve_flow(&flow, join).await.map(|_| flow)
+                    }
+                    Err(e) => Err(e),
+                },
+                Err(e) => Err(e),
+            };
+            flows.push(flow);
+        }
+        let node_id = self.provenance.node_id();
+        let remote_destinations = flows
+            .iter()
+            .flatten()
+            .map(|flow| flow.destination.clone().into_localized(node_id.clone()))
+            .filter(|destination| destination.resource().is_network())
+            .collect();
+        let mut policies = self.destination_policies(remote_destinations).await;
+        let mut results = Vec::with_capacity(flows.len());
+        for flow in flows {
+            results.push(match flow {
+                Ok(flow) => {
+                    let destination_policy =
+                        policies.remove(&flow.destination.clone().into_localized(node_id.clone()));
+                    self.grant_flow(flow, destination_policy).await
+                }
+                Err(e) => Err(e),
+            });
+        }
+        results
+    }
+
+    /// Resets the provenance of a file whose content was discarded by a process.
+    ///
+    /// Discarding the content of a file is a write to it, so the reset is evaluated and
+    /// reserved as a write of the process, and ordered with the other flows on the file.
+    async fn overwrite(&mut self, pid: i32, fd: i32) -> Result<(), TraceabilityError> {
+        let flow = self.resolve_flow(pid, fd, None, true).await?;
+        if !flow.destination.is_file() {
+            return Err(TraceabilityError::InvalidRequest);
+        }
+        self.reserve_flow(&flow, false).await?;
+        let reset = match self.eval_flow(&flow, None).await {
+            Ok(_) => self
+                .provenance
+                .call(ProvenanceRequest::ResetProvenance {
+                    resource: flow.destination.clone(),
+                    process: flow.source,
+                })
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        self.sequencer
+            .call(SequencerRequest::ReleaseFlow { destination: flow.destination })
+            .await?;
+        reset
+    }
+
+    /// Records the outcome of an operation reported on a grant.
+    ///
+    /// The transfer volume of a successful operation is recorded on the flow, and the
+    /// provenance of the destination is updated, through the remote middleware if the
+    /// destination is a remote stream. The flow reservation is left to the caller.
+    async fn record_report(
+        provenance: &mut P,
+        m2m: &mut M,
+        lease: &GrantLease,
+        result: bool,
+        bytes: u64,
+        offset: Option<u64>,
+    ) -> Result<(), TraceabilityError> {
+        let GrantLease { source, destination, declared, .. } = lease;
+        if result {
+            provenance
+                .call(ProvenanceRequest::RecordTransfer {
+                    source: source.clone(),
+                    destination: destination.clone(),
+                    bytes,
+                    offset,
+                })
+                .await?;
+        }
+        if let Some(remote_stream) = destination.try_into_localized_peer_stream() {
+            let references = Self::flow_references(provenance, source, declared.as_ref()).await?;
+            debug!(remote_node_id = remote_stream.node_id(), "[p2m] Updating remote provenance");
+            m2m.ready()
+                .await?
+                .call(M2mRequest::UpdateProvenance {
+                    source_prov: references,
+                    destination: remote_stream,
+                })
+                .await?;
+        } else if declared.is_some() {
+            info!(
+                node_id = %provenance.node_id(),
+                "[p2m] Updating local provenance from declared sources"
+            );
+            let references = Self::flow_references(provenance, source, declared.as_ref()).await?;
+            provenance
+                .call(ProvenanceRequest::UpdateProvenanceRaw {
+                    source_prov: references,
+                    destination: destination.clone(),
+                })
+                .await?;
+        } else {
+            info!(node_id = %provenance.node_id(), "[p2m] Updating local provenance");
+            provenance
+                .call(ProvenanceRequest::UpdateProvenance {
+                    source: source.clone(),
+                    destination: destination.clone(),
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Releases the flow reservations held by an abandoned grant.
+    ///
+    /// The local reservation is released first. If the destination is a remote stream,
+    /// the reservation held by the remote middleware is released by an empty provenance
+    /// update, since no data is known to have been transferred.
+    async fn release_lease(
+        sequencer: &mut S,
+        m2m: &mut M,
+        lease: GrantLease,
+    ) -> Result<(), TraceabilityError> {
+        let remote_stream = lease.destination.try_into_localized_peer_stream();
+        sequencer
+            .call(SequencerRequest::LeaveFlow {
+                source: lease.source,
+                destination: lease.destination,
+            })
+            .await?;
+        if let Some(remote_stream) = remote_stream {
+            m2m.ready().await?.call(M2mRequest::ReleaseFlow(remote_stream)).await?;
+        }
+        Ok(())
+    }
+
+    /// Reclaims the grants that expired or whose owning process exited before reporting.
+    ///
+    /// Abandoned grants are removed from the flow map and their reservations are released,
+    /// so that processes waiting on the same resources can proceed. The expired grants of
+    /// running processes are kept aside until reported, so that the operations carried out
+    /// under them are still recorded, and dropped along with their process.
+    ///
+    /// # Returns
+    /// The number of reclaimed grants
+    pub async fn reap_grants(&mut self) -> usize {
+        let now = Instant::now();
+        let candidates: Vec<(u128, i32, bool)> = self
+            .flow_map
+            .iter()
+            .map(|entry| (*entry.key(), entry.pid, entry.is_expired(now)))
+            .collect();
+
+        let mut alive_processes = HashMap::new();
+        let mut reaped = 0;
+        for (grant_id, pid, expired) in candidates {
+            let abandoned = expired
+                || !*alive_processes
+                    .entry(pid)
+                    .or_insert_with(|| ResourceValidator.is_valid_process(pid));
+            if !abandoned {
+                continue;
+            }
+            // The grant may have been reported in the meantime
+            if let Some((_, lease)) = self.flow_map.remove(&grant_id) {
+                info!(
+                    node_id = %self.provenance.node_id(),
+                    grant_id = %grant_id,
+                    pid = %lease.pid,
+                    destination = %lease.destination,
+                    expired = %expired,
+                    "[p2m] Reclaiming abandoned grant"
+                );
+                self.reclaimed.insert(grant_id, lease.clone());
+                if let Err(e) = Self::release_lease(&mut self.sequencer, &mut self.m2m, lease).await
+                {
+                    warn!(grant_id = %grant_id, error = ?e, "[p2m] Failed to release abandoned grant");
+                }
+                reaped += 1;
+            }
+        }
+        // Grants kept aside are dropped once their process exited
+        self.reclaimed.retain(|_, lease| {
+            *alive_processes
+                .entry(lease.pid)
+                .or_insert_with(|| ResourceValidator.is_valid_process(lease.pid))
+        });
+        reaped
+    }
+
+    /// Releases the state held on behalf of the processes that exited.
+    ///
+    /// For each enrolled process that exited (or whose pid was reused by another process),
+    /// its file descriptor mappings are removed, its pending grants are reclaimed, and its
+    /// provenance is archived under its `(pid, starttime)` identity. This prevents a later
+    /// process reusing the same pid from inheriting stale mappings or taint.
+    ///
+    /// # Returns
+    /// The number of exited processes
+    pub async fn reap_processes(&mut self) -> usize {
+        let mut processes: HashSet<Resource> =
+            self.resource_map.iter().map(|entry| entry.value().0.clone()).collect();
+        processes.extend(self.processes.iter().map(|entry| entry.value().clone()));
+        processes.extend(self.flow_map.iter().flat_map(|entry| {
+            [entry.source.clone(), entry.destination.clone()]
+                .into_iter()
+                .filter(|resource| matches!(resource, Resource::Process(_)))
+        }));
+        let exited: Vec<Resource> = processes
+            .into_iter()
+            .filter(|resource| match resource {
+                // Sessions are released along with their process
+                Resource::Process(process) => {
+                    process.session.is_none() && ProcessRegistry::global().has_exited(process)
+                }
+                _ => false,
+            })
+            .collect();
+
+        for process in exited.iter() {
+            if let Resource::Process(Process { pid, starttime, .. }) = process {
+                self.processes.remove_if(pid, |_, known| known == process);
+                if let Some((_, session)) = self.sessions.remove_if(pid, |_, session| {
+                    matches!(session, Resource::Process(s) if s.starttime == *starttime)
+                }) && let Err(e) =
+                    self.provenance.call(ProvenanceRequest::DropProvenance(session)).await
+                {
+                    warn!(process = %process, error = ?e, "[p2m] Failed to drop exited process session");
+                }
+            }
+            self.resource_map.retain(|_, (source, _)| source != process);
+            self.declarations.retain(|key, _| self.resource_map.contains_key(key));
+            self.grant_cache.retain(|key, _| self.resource_map.contains_key(&(key.0, key.1)));
+            self.reclaimed
+                .retain(|_, lease| &lease.source != process && &lease.destination != process);
+            let grants: Vec<u128> = self
+                .flow_map
+                .iter()
+                .filter(|entry| &entry.source == process || &entry.destination == process)
+                .map(|entry| *entry.key())
+                .collect();
+            for grant_id in grants {
+                if let Some((_, lease)) = self.flow_map.remove(&grant_id)
+                    && let Err(e) =
+                        Self::release_lease(&mut self.sequencer, &mut self.m2m, lease).await
+                {
+                    warn!(grant_id = %grant_id, error = ?e, "[p2m] Failed to release exited process grant");
+                }
+            }
+            if let Err(e) =
+                self.provenance.call(ProvenanceRequest::ArchiveProcess(process.clone())).await
+            {
+                warn!(process = %process, error = ?e, "[p2m] Failed to archive exited process provenance");
+            }
+            info!(
+                node_id = %self.provenance.node_id(),
+                process = %process,
+                "[p2m] Released state of exited process"
+            );
+        }
+
+        // Record the programs executed by the processes still running
+        let running: Vec<i32> = self.processes.iter().map(|entry| *entry.key()).collect();
+        for pid in running {
+            if let Err(e) =
+                Self::track_process(&self.processes, &self.resource_map, &mut self.provenance, pid)
+                    .await
+            {
+                warn!(pid = %pid, error = ?e, "[p2m] Failed to track running process");
+            }
+        }
+        exited.len()
+    }
+
+    /// Spawns a background task reclaiming abandoned grants at the given period.
+    ///
+    /// See `reap_grants` for the reclamation rules.
+    pub fn spawn_grant_reaper(&self, period: Duration) -> JoinHandle<()> {
+        let mut this = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                let reaped = this.reap_grants().await;
+                if reaped > 0 {
+                    debug!(reaped = %reaped, "[p2m] Grant reaper pass completed");
+                }
+            }
+        })
+    }
+
+    /// Spawns a background task polling enrolled processes for termination at the given period.
+    ///
+    /// See `reap_processes` for the released state.
+    pub fn spawn_process_watcher(&self, period: Duration) -> JoinHandle<()> {
+        let mut this = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                let exited = this.reap_processes().await;
+                if exited > 0 {
+                    debug!(exited = %exited, "[p2m] Process watcher pass completed");
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower::Service;
+
+    use super::*;
+    use crate::{
+        traceability::{
+            api::types::OpenMode,
+            infrastructure::naming::LocalizedResource,
+            services::{
+                compliance::{ComplianceService, ConfidentialityPolicy},
+                provenance::ProvenanceService,
+                sequencer::SequencerService,
+            },
+        },
+        transport::nop::M2mNop,
+    };
+
+    #[tokio::test]
+    async fn unit_trace2e_service_request_response() {
+        crate::trace2e_tracing::init();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::LocalEnroll {
+                    pid: 1,
+                    fd: 3,
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default()
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::RemoteEnroll {
+                    pid: 1,
+                    fd: 3,
                     local_socket: "127.0.0.1:8080".to_string(),
                     peer_socket: "127.0.0.1:8081".to_string()
                 })
@@ -546,7 +1917,15 @@ mod tests {
         };
         assert_eq!(
             p2m_service
-                .call(P2mRequest::IoReport { pid: 1, fd: 3, grant_id: flow_id, result: true })
+                .call(P2mRequest::IoReport {
+                    pid: 1,
+                    fd: 3,
+                    grant_id: flow_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
                 .await
                 .unwrap(),
             P2mResponse::Ack
@@ -559,7 +1938,15 @@ mod tests {
         };
         assert_eq!(
             p2m_service
-                .call(P2mRequest::IoReport { pid: 1, fd: 3, grant_id: flow_id, result: true })
+                .call(P2mRequest::IoReport {
+                    pid: 1,
+                    fd: 3,
+                    grant_id: flow_id,
+                    output: false,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
                 .await
                 .unwrap(),
             P2mResponse::Ack
@@ -581,7 +1968,12 @@ mod tests {
         // This request is supposed to be filtered out by the validator
         assert_eq!(
             p2m_service
-                .call(P2mRequest::LocalEnroll { pid: 0, fd: 3, path: "/tmp/test.txt".to_string() })
+                .call(P2mRequest::LocalEnroll {
+                    pid: 0,
+                    fd: 3,
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default()
+                })
                 .await
                 .unwrap_err()
                 .to_string(),
@@ -594,7 +1986,8 @@ mod tests {
                 .call(P2mRequest::LocalEnroll {
                     pid: std::process::id() as i32,
                     fd: 3,
-                    path: "/tmp/test.txt".to_string()
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default(),
                 })
                 .await
                 .unwrap(),
@@ -631,6 +2024,7 @@ mod tests {
                 pid: std::process::id() as i32,
                 fd: 4,
                 path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
             })
             .await
             .unwrap();
@@ -663,7 +2057,15 @@ mod tests {
         // Invalid grant id
         assert_eq!(
             p2m_service
-                .call(P2mRequest::IoReport { pid: 1, fd: 3, grant_id: 0, result: true })
+                .call(P2mRequest::IoReport {
+                    pid: 1,
+                    fd: 3,
+                    grant_id: 0,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
                 .await
                 .unwrap_err()
                 .to_string(),
@@ -696,7 +2098,12 @@ mod tests {
         // Test invalid process - should fail with validation enabled
         assert_eq!(
             p2m_service_with_validation
-                .call(P2mRequest::LocalEnroll { pid: 0, fd: 3, path: "/tmp/test.txt".to_string() })
+                .call(P2mRequest::LocalEnroll {
+                    pid: 0,
+                    fd: 3,
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default()
+                })
                 .await
                 .unwrap_err()
                 .to_string(),
@@ -706,7 +2113,12 @@ mod tests {
         // Test invalid process - should succeed with validation disabled
         assert_eq!(
             p2m_service_without_validation
-                .call(P2mRequest::LocalEnroll { pid: 0, fd: 3, path: "/tmp/test.txt".to_string() })
+                .call(P2mRequest::LocalEnroll {
+                    pid: 0,
+                    fd: 3,
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default()
+                })
                 .await
                 .unwrap(),
             P2mResponse::Ack
@@ -718,7 +2130,8 @@ mod tests {
                 .call(P2mRequest::LocalEnroll {
                     pid: std::process::id() as i32,
                     fd: 3,
-                    path: "/tmp/test.txt".to_string()
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default(),
                 })
                 .await
                 .unwrap(),
@@ -731,11 +2144,1040 @@ mod tests {
                 .call(P2mRequest::LocalEnroll {
                     pid: std::process::id() as i32,
                     fd: 3,
-                    path: "/tmp/test.txt".to_string()
+                    path: "/tmp/test.txt".to_string(),
+                    mode: OpenMode::default(),
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_expired_grant_report() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        let provenance = ProvenanceService::default();
+        let file = Resource::new_file("/tmp/test.txt".to_string());
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            provenance.clone(),
+            ComplianceService::default(),
+            M2mNop,
+        )
+        .with_grant_lease(Some(Duration::from_millis(10)));
+
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let P2mResponse::Grant(flow_id) =
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        // The operation was carried out, the report is recorded despite the expired lease
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd: 3,
+                    grant_id: flow_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
                 })
                 .await
                 .unwrap(),
             P2mResponse::Ack
         );
+        assert_eq!(
+            p2m_service
+                .provenance
+                .call(ProvenanceRequest::GetReferences(file.clone()))
+                .await
+                .unwrap(),
+            ProvenanceResponse::Provenance(HashSet::from([
+                LocalizedResource::new(provenance.node_id(), file.clone()),
+                LocalizedResource::new(provenance.node_id(), Resource::new_process(pid))
+            ]))
+        );
+
+        // The reservation was released by the report
+        assert!(matches!(
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap(),
+            P2mResponse::Grant(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_failed_report() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        // The remote middleware evaluates flows, but cannot record their provenance
+        let m2m = tower::service_fn(|request: M2mRequest| async move {
+            match request {
+                M2mRequest::GetDestinationPolicy { .. } => {
+                    Ok(M2mResponse::DestinationPolicy(Policy::default()))
+                }
+                _ => Err(TraceabilityError::TransportFailedToContactRemote("10.0.0.2".to_string())),
+            }
+        });
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::new("10.0.0.1".to_string()),
+            ComplianceService::default(),
+            m2m,
+        );
+
+        p2m_service
+            .call(P2mRequest::RemoteEnroll {
+                pid,
+                fd: 3,
+                local_socket: "10.0.0.1:1234".to_string(),
+                peer_socket: "10.0.0.2:1234".to_string(),
+            })
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let P2mResponse::Grant(grant_id) =
+                p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap()
+            else {
+                panic!("Expected P2mResponse::Grant");
+            };
+            // The failed report still releases the reservation of the flow
+            assert_eq!(
+                p2m_service
+                    .call(P2mRequest::IoReport {
+                        pid,
+                        fd: 3,
+                        grant_id,
+                        output: true,
+                        result: true,
+                        bytes: 4,
+                        offset: None
+                    })
+                    .await,
+                Err(TraceabilityError::TransportFailedToContactRemote("10.0.0.2".to_string()))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_grant_token_binding() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 4,
+                path: "/tmp/test2.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let P2mResponse::Grant(grant_id) =
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+        let P2mResponse::Grant(other_grant_id) =
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 4, output: true }).await.unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+        assert_ne!(grant_id, other_grant_id);
+
+        // Reports from another process or on another descriptor are rejected
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid: pid + 1,
+                    fd: 3,
+                    grant_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "Traceability error, grant not issued to this process (id: {grant_id}, pid: {})",
+                pid + 1
+            )
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd: 4,
+                    grant_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!(
+                "Traceability error, grant not issued for this file descriptor (id: {grant_id}, fd: 4)"
+            )
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd: 3,
+                    grant_id,
+                    output: false,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
+                .await
+                .unwrap_err(),
+            TraceabilityError::GrantFlowMismatch(grant_id)
+        );
+
+        // Rejected reports do not consume the grant
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd: 3,
+                    grant_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd: 3,
+                    grant_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!("Traceability error, flow not found (id: {grant_id})")
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd: 4,
+                    grant_id: other_grant_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_reap_expired_grants() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            ComplianceService::default(),
+            M2mNop,
+        )
+        .with_grant_lease(Some(Duration::from_millis(10)));
+
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let P2mResponse::Grant(flow_id) =
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+
+        // Live grant is kept
+        assert_eq!(p2m_service.reap_grants().await, 0);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(p2m_service.reap_grants().await, 1);
+
+        // The reservation is available again
+        let P2mResponse::Grant(next_flow_id) =
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+
+        // The reclaimed grant is still recorded when reported, but only once
+        let report = |grant_id| P2mRequest::IoReport {
+            output: true,
+            pid,
+            fd: 3,
+            grant_id,
+            result: true,
+            bytes: 0,
+            offset: None,
+        };
+        assert_eq!(p2m_service.call(report(flow_id)).await.unwrap(), P2mResponse::Ack);
+        assert_eq!(
+            p2m_service.call(report(flow_id)).await.unwrap_err().to_string(),
+            format!("Traceability error, flow not found (id: {flow_id})")
+        );
+        assert!(p2m_service.reclaimed.is_empty());
+
+        // Reporting the reclaimed grant did not release the reservation of the next one
+        assert!(p2m_service.flow_map.contains_key(&next_flow_id));
+        assert_eq!(p2m_service.call(report(next_flow_id)).await.unwrap(), P2mResponse::Ack);
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_reap_exited_process_grants() {
+        crate::trace2e_tracing::init();
+        // No lease, grants are only reclaimed when their process is gone
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid: i32::MAX,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        p2m_service
+            .call(P2mRequest::IoRequest { pid: i32::MAX, fd: 3, output: false })
+            .await
+            .unwrap();
+
+        let reaper = p2m_service.spawn_grant_reaper(Duration::from_millis(5));
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        reaper.abort();
+
+        assert!(p2m_service.flow_map.is_empty());
+        // No report can come from the exited process
+        assert!(p2m_service.reclaimed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_close_and_dup() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            p2m_service.call(P2mRequest::Dup { pid, fd: 3, new_fd: 4 }).await.unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service.resource_map.get(&(pid, 4)).map(|r| r.clone()),
+            p2m_service.resource_map.get(&(pid, 3)).map(|r| r.clone())
+        );
+
+        // Closing the original descriptor keeps the duplicate usable
+        assert_eq!(
+            p2m_service.call(P2mRequest::Close { pid, fd: 3 }).await.unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoRequest { pid, fd: 3, output: true })
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!("Traceability error, undeclared resource (pid: {pid}, fd: 3)")
+        );
+        assert!(matches!(
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 4, output: true }).await.unwrap(),
+            P2mResponse::Grant(_)
+        ));
+
+        // Close is idempotent, Dup requires an enrolled descriptor
+        assert_eq!(
+            p2m_service.call(P2mRequest::Close { pid, fd: 3 }).await.unwrap(),
+            P2mResponse::Ack
+        );
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::Dup { pid, fd: 3, new_fd: 5 })
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!("Traceability error, undeclared resource (pid: {pid}, fd: 3)")
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_reap_exited_processes() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        let provenance = ProvenanceService::default();
+        let exited = Resource::new_process_mock(i32::MAX);
+        let file = Resource::new_file("/tmp/test.txt".to_string());
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            provenance.clone(),
+            ComplianceService::default(),
+            M2mNop,
+        )
+        .with_enrolled_resource(pid, 3, Resource::new_process(pid), file.clone())
+        .with_enrolled_resource(i32::MAX, 3, exited.clone(), file.clone())
+        .with_enrolled_resource(i32::MAX, 4, exited.clone(), file.clone());
+
+        // The exited process read the file, and holds a pending grant
+        let P2mResponse::Grant(flow_id) = p2m_service
+            .call(P2mRequest::IoRequest { pid: i32::MAX, fd: 3, output: false })
+            .await
+            .unwrap()
+        else {
+            panic!("Expected P2mResponse::Grant");
+        };
+        provenance.set_references(
+            exited.clone(),
+            HashSet::from([LocalizedResource::new(provenance.node_id(), file.clone())]),
+        );
+
+        assert_eq!(p2m_service.reap_processes().await, 1);
+        assert_eq!(p2m_service.resource_map.len(), 1);
+        assert!(p2m_service.resource_map.contains_key(&(pid, 3)));
+        assert!(!p2m_service.flow_map.contains_key(&flow_id));
+        assert_eq!(
+            p2m_service
+                .provenance
+                .call(ProvenanceRequest::GetArchivedReferences { pid: i32::MAX, starttime: 0 })
+                .await
+                .unwrap(),
+            ProvenanceResponse::Provenance(HashSet::from([LocalizedResource::new(
+                provenance.node_id(),
+                file.clone()
+            )]))
+        );
+
+        // The reservation of the exited process was released
+        assert!(matches!(
+            p2m_service.call(P2mRequest::IoRequest { pid, fd: 3, output: true }).await.unwrap(),
+            P2mResponse::Grant(_)
+        ));
+        assert_eq!(p2m_service.reap_processes().await, 0);
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_fork_inheritance() {
+        crate::trace2e_tracing::init();
+        let pid = std::process::id() as i32;
+        let provenance = ProvenanceService::default();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            provenance.clone(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+        let secret = LocalizedResource::new(
+            provenance.node_id(),
+            Resource::new_file("/tmp/secret.txt".to_string()),
+        );
+
+        // The parent enrolled its stdin, and read a secret file
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 0,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let parent = Resource::new_process(pid);
+        provenance.set_references(
+            parent.clone(),
+            HashSet::from([secret.clone(), LocalizedResource::new(provenance.node_id(), parent)]),
+        );
+
+        let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        let child_pid = child.id() as i32;
+
+        // The inherited stdin is usable without enrollment, and the child inherits the taint
+        assert!(matches!(
+            p2m_service
+                .call(P2mRequest::IoRequest { pid: child_pid, fd: 0, output: false })
+                .await
+                .unwrap(),
+            P2mResponse::Grant(_)
+        ));
+        let ProvenanceResponse::Provenance(references) = p2m_service
+            .provenance
+            .call(ProvenanceRequest::GetReferences(Resource::new_process(child_pid)))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::Provenance");
+        };
+        assert!(references.contains(&secret));
+
+        // Descriptors closed on exec are not inherited
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::IoRequest { pid: child_pid, fd: 3, output: false })
+                .await
+                .unwrap_err()
+                .to_string(),
+            format!("Traceability error, undeclared resource (pid: {child_pid}, fd: 3)")
+        );
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        // A grandchild forked by an untracked shell inherits from the parent, the background
+        // job of the shell inheriting its stderr but not its stdin. The job is a subshell, which
+        // does not exec, so that the process is the same once its pid is printed
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid,
+                fd: 2,
+                path: "/tmp/test2.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let mut shell = std::process::Command::new("sh")
+            .args(["-c", "(while :; do sleep 1; done) & echo $!; wait"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        std::io::BufRead::read_line(
+            &mut std::io::BufReader::new(shell.stdout.as_mut().unwrap()),
+            &mut line,
+        )
+        .unwrap();
+        let grandchild_pid: i32 = line.trim().parse().unwrap();
+        assert!(matches!(
+            p2m_service
+                .call(P2mRequest::IoRequest { pid: grandchild_pid, fd: 2, output: true })
+                .await
+                .unwrap(),
+            P2mResponse::Grant(_)
+        ));
+        let ProvenanceResponse::Provenance(references) = p2m_service
+            .provenance
+            .call(ProvenanceRequest::GetReferences(Resource::new_process(grandchild_pid)))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::Provenance");
+        };
+        assert!(references.contains(&secret));
+        assert!(
+            p2m_service
+                .call(P2mRequest::IoRequest { pid: grandchild_pid, fd: 0, output: false })
+                .await
+                .is_err()
+        );
+
+        shell.kill().unwrap();
+        shell.wait().unwrap();
+        let _ = std::process::Command::new("kill").arg(grandchild_pid.to_string()).status();
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_exec_activity() {
+        crate::trace2e_tracing::init();
+        let provenance = ProvenanceService::default();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            provenance.clone(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sleep 0.2; exec sleep 10"])
+            .spawn()
+            .unwrap();
+        let child_pid = child.id() as i32;
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid: child_pid,
+                fd: 0,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let shell = Resource::new_process(child_pid);
+
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(p2m_service.reap_processes().await, 0);
+
+        // The executed program is a new activity derived from the shell
+        let program = Resource::new_process(child_pid);
+        assert_ne!(program, shell);
+        assert!(program.to_string().ends_with("sleep"));
+        let ProvenanceResponse::Provenance(references) = p2m_service
+            .provenance
+            .call(ProvenanceRequest::GetReferences(program.clone()))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::Provenance");
+        };
+        assert!(references.contains(&LocalizedResource::new(provenance.node_id(), shell)));
+
+        // The descriptors surviving the exec now belong to the new program
+        assert_eq!(p2m_service.resource_map.get(&(child_pid, 0)).unwrap().0, program);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_truncating_enroll() {
+        crate::trace2e_tracing::init();
+        let provenance = ProvenanceService::default();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            provenance.clone(),
+            ComplianceService::default(),
+            M2mNop,
+        );
+        let file = Resource::new_file("/tmp/test.txt".to_string());
+        let secret = LocalizedResource::new(
+            provenance.node_id(),
+            Resource::new_file("/tmp/secret.txt".to_string()),
+        );
+        provenance.set_references(file.clone(), HashSet::from([secret.clone()]));
+
+        // Opening the file with truncation discards its previous provenance
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid: 1,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode { write: true, truncate: true, ..Default::default() },
+            })
+            .await
+            .unwrap();
+        let ProvenanceResponse::Provenance(references) = p2m_service
+            .provenance
+            .call(ProvenanceRequest::GetReferences(file.clone()))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::Provenance");
+        };
+        assert!(!references.contains(&secret));
+
+        // Streams cannot be truncated
+        p2m_service
+            .call(P2mRequest::RemoteEnroll {
+                pid: 1,
+                fd: 4,
+                local_socket: "127.0.0.1:8080".to_string(),
+                peer_socket: "127.0.0.1:8081".to_string(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            p2m_service.call(P2mRequest::Truncate { pid: 1, fd: 4 }).await.unwrap_err().to_string(),
+            TraceabilityError::InvalidRequest.to_string()
+        );
+        assert_eq!(
+            p2m_service.call(P2mRequest::Truncate { pid: 1, fd: 5 }).await.unwrap_err().to_string(),
+            "Traceability error, undeclared resource (pid: 1, fd: 5)"
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_grant_cache() {
+        crate::trace2e_tracing::init();
+        let provenance = ProvenanceService::default();
+        let mut compliance = ComplianceService::default();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            provenance.clone(),
+            compliance.clone(),
+            M2mNop,
+        );
+        let file = Resource::new_file("/tmp/test.txt".to_string());
+        let secret = Resource::new_file("/tmp/secret.txt".to_string());
+        compliance
+            .call(ComplianceRequest::SetConfidentiality {
+                resource: secret.clone(),
+                confidentiality: ConfidentialityPolicy::Secret,
+            })
+            .await
+            .unwrap();
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid: 1,
+                fd: 3,
+                path: "/tmp/test.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        let read = async |p2m_service: &mut P2mApiService<_, _, _, _>| {
+            let P2mResponse::Grant(grant_id) =
+                p2m_service.call(P2mRequest::IoRequest { pid: 1, fd: 3, output: false }).await?
+            else {
+                panic!("Expected P2mResponse::Grant");
+            };
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid: 1,
+                    fd: 3,
+                    grant_id,
+                    output: false,
+                    result: true,
+                    bytes: 0,
+                    offset: None,
+                })
+                .await
+        };
+
+        // The first read is evaluated and cached, the following ones reuse the evaluation
+        read(&mut p2m_service).await.unwrap();
+        let cached = p2m_service.grant_cache.get(&(1, 3, false)).unwrap().clone();
+        read(&mut p2m_service).await.unwrap();
+        assert_eq!(*p2m_service.grant_cache.get(&(1, 3, false)).unwrap(), cached);
+
+        // A change of the provenance of the source invalidates the cached grant
+        provenance.set_references(
+            file.clone(),
+            HashSet::from([LocalizedResource::new(provenance.node_id(), secret)]),
+        );
+        assert!(read(&mut p2m_service).await.is_err());
+
+        // Closing the descriptor drops its cached grants
+        p2m_service.call(P2mRequest::Close { pid: 1, fd: 3 }).await.unwrap();
+        assert!(p2m_service.grant_cache.is_empty());
+
+        // Without cache, no grant is recorded
+        let mut p2m_service = p2m_service.with_grant_cache(false);
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid: 1,
+                fd: 4,
+                path: "/tmp/other.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        p2m_service.call(P2mRequest::IoRequest { pid: 1, fd: 4, output: false }).await.unwrap();
+        assert!(p2m_service.grant_cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_io_request_batch() {
+        crate::trace2e_tracing::init();
+        let m2m_requests = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = m2m_requests.clone();
+        let m2m = tower::service_fn(move |request: M2mRequest| {
+            recorded.lock().unwrap().push(request.clone());
+            M2mNop.call(request)
+        });
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            ComplianceService::default(),
+            m2m,
+        );
+        let streams = [
+            (3, "10.0.0.1:1337", "10.0.0.2:1338"),
+            (4, "10.0.0.1:1339", "10.0.0.2:1340"),
+            (5, "10.0.0.1:1341", "10.0.0.3:1342"),
+        ];
+        for (fd, local_socket, peer_socket) in streams {
+            p2m_service
+                .call(P2mRequest::RemoteEnroll {
+                    pid: 1,
+                    fd,
+                    local_socket: local_socket.to_string(),
+                    peer_socket: peer_socket.to_string(),
+                })
+                .await
+                .unwrap();
+        }
+
+        let P2mResponse::Grants(results) = p2m_service
+            .call(P2mRequest::IoRequestBatch {
+                pid: 1,
+                requests: vec![(3, true), (4, true), (5, true), (6, true), (3, true)],
+            })
+            .await
+            .unwrap()
+        else {
+            panic!("Expected P2mResponse::Grants");
+        };
+        assert_eq!(results.len(), 5);
+        assert!(results[..3].iter().all(|result| result.is_ok()));
+        assert_eq!(results[3], Err(TraceabilityError::UndeclaredResource(1, 6)));
+        // The same stream cannot be written twice by the batch
+        assert_eq!(
+            results[4],
+            Err(TraceabilityError::UnavailableDestination(Resource::new_stream(
+                "10.0.0.1:1337".to_string(),
+                "10.0.0.2:1338".to_string()
+            )))
+        );
+
+        // A single policy query is sent to each remote node
+        let queries: Vec<HashSet<LocalizedResource>> = m2m_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| match request {
+                M2mRequest::GetDestinationPolicies(destinations) => destinations.clone(),
+                _ => panic!("Expected M2mRequest::GetDestinationPolicies"),
+            })
+            .collect();
+        assert_eq!(queries.len(), 2);
+        assert!(
+            queries.contains(&HashSet::from([
+                Resource::new_stream("10.0.0.1:1337".to_string(), "10.0.0.2:1338".to_string())
+                    .into_localized(String::new()),
+                Resource::new_stream("10.0.0.1:1339".to_string(), "10.0.0.2:1340".to_string())
+                    .into_localized(String::new()),
+            ]))
+        );
+
+        // Each grant is reported on its own
+        for ((fd, _, _), result) in streams.into_iter().zip(results) {
+            assert_eq!(
+                p2m_service
+                    .call(P2mRequest::IoReport {
+                        pid: 1,
+                        fd,
+                        grant_id: result.unwrap(),
+                        output: true,
+                        result: true,
+                        bytes: 0,
+                        offset: None,
+                    })
+                    .await
+                    .unwrap(),
+                P2mResponse::Ack
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_datagram_node_socket() {
+        crate::trace2e_tracing::init();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::new("10.0.0.1".to_string()),
+            ComplianceService::default(),
+            M2mNop,
+        );
+        let pid = std::process::id() as i32;
+        let enrolled = async |p2m_service: &mut P2mApiService<_, _, _, _>, fd, local_socket| {
+            p2m_service.call(P2mRequest::DatagramEnroll { pid, fd, local_socket }).await.unwrap();
+            p2m_service.resource_map.get(&(pid, fd)).unwrap().1.clone()
+        };
+
+        // A socket bound to an unspecified address is named after the address of the node
+        for (fd, local_socket) in [(3, "0.0.0.0:8125"), (4, "[::]:8125")] {
+            assert_eq!(
+                enrolled(&mut p2m_service, fd, local_socket.to_string()).await,
+                Resource::new_datagram("10.0.0.1:8125".to_string(), None)
+            );
+        }
+        assert_eq!(
+            enrolled(&mut p2m_service, 5, "127.0.0.1:8125".to_string()).await,
+            Resource::new_datagram("127.0.0.1:8125".to_string(), None)
+        );
+
+        // Even if its peers reach it at another address: a peer on the loopback interface
+        // names the datagrams after 127.0.0.1:8125, so that their provenance is not matched
+        let datagram = enrolled(&mut p2m_service, 3, "0.0.0.0:8125".to_string()).await;
+        assert_eq!(
+            datagram.with_datagram_peer("127.0.0.1:8126".to_string()),
+            Some(Resource::new_datagram(
+                "10.0.0.1:8125".to_string(),
+                Some("127.0.0.1:8126".to_string())
+            ))
+        );
+
+        // The address is kept if the node id is not an IP address
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::new("node1.local".to_string()),
+            ComplianceService::default(),
+            M2mNop,
+        );
+        assert_eq!(
+            enrolled(&mut p2m_service, 3, "0.0.0.0:8125".to_string()).await,
+            Resource::new_datagram("0.0.0.0:8125".to_string(), None)
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_trace2e_service_unix_socket() {
+        crate::trace2e_tracing::init();
+        let mut compliance = ComplianceService::default();
+        let mut p2m_service = P2mApiService::new(
+            SequencerService::default(),
+            ProvenanceService::default(),
+            compliance.clone(),
+            M2mNop,
+        );
+        let (writer, reader) = (std::process::id() as i32, 1);
+        let path = "/run/test.sock".to_string();
+        let secret = Resource::new_file("/tmp/secret.txt".to_string());
+        let io = async |p2m_service: &mut P2mApiService<_, _, _, _>, pid, fd, output| {
+            let P2mResponse::Grant(grant_id) =
+                p2m_service.call(P2mRequest::IoRequest { pid, fd, output }).await?
+            else {
+                panic!("Expected P2mResponse::Grant");
+            };
+            p2m_service
+                .call(P2mRequest::IoReport {
+                    pid,
+                    fd,
+                    grant_id,
+                    output,
+                    result: true,
+                    bytes: 0,
+                    offset: None,
+                })
+                .await
+        };
+
+        p2m_service
+            .call(P2mRequest::LocalEnroll {
+                pid: writer,
+                fd: 3,
+                path: "/tmp/secret.txt".to_string(),
+                mode: OpenMode::default(),
+            })
+            .await
+            .unwrap();
+        p2m_service
+            .call(P2mRequest::UnixEnroll {
+                pid: writer,
+                fd: 4,
+                path: path.clone(),
+                peer_pid: reader,
+            })
+            .await
+            .unwrap();
+        // The reader end is named from the pid of the writer
+        p2m_service
+            .call(P2mRequest::UnixEnroll {
+                pid: reader,
+                fd: 3,
+                path: path.clone(),
+                peer_pid: writer,
+            })
+            .await
+            .unwrap();
+        let reader_end = Resource::new_unix_socket(path, writer);
+
+        // The data written on the socket is held by the end of the reader
+        io(&mut p2m_service, writer, 3, false).await.unwrap();
+        io(&mut p2m_service, writer, 4, true).await.unwrap();
+        let ProvenanceResponse::Provenance(references) = p2m_service
+            .provenance
+            .call(ProvenanceRequest::GetReferences(reader_end.clone()))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::Provenance");
+        };
+        let node_id = p2m_service.provenance.node_id();
+        assert!(references.contains(&LocalizedResource::new(node_id.clone(), secret.clone())));
+        io(&mut p2m_service, reader, 3, false).await.unwrap();
+        let ProvenanceResponse::Provenance(references) = p2m_service
+            .provenance
+            .call(ProvenanceRequest::GetReferences(Resource::new_process(reader)))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::Provenance");
+        };
+        assert!(references.contains(&LocalizedResource::new(node_id, secret)));
+
+        // Writes are evaluated against the policy of the end of the reader
+        compliance
+            .call(ComplianceRequest::SetIntegrity { resource: reader_end, integrity: 5 })
+            .await
+            .unwrap();
+        assert_eq!(
+            io(&mut p2m_service, writer, 4, true).await,
+            Err(TraceabilityError::DirectPolicyViolation)
+        );
+
+        // Unix sockets cannot be truncated
+        assert_eq!(
+            p2m_service.call(P2mRequest::Truncate { pid: writer, fd: 4 }).await,
+            Err(TraceabilityError::InvalidRequest)
+        );
     }
 }
@@ -31,10 +31,14 @@ use std::collections::{HashMap, HashSet};
 use tokio::sync::broadcast;
 
 use crate::traceability::{
+    api::p2m::PendingGrant,
+    error::TraceabilityError,
     infrastructure::naming::{LocalizedResource, Resource},
     services::{
         compliance::{ConfidentialityPolicy, Policy},
         consent::Destination,
+        provenance::{EdgeVolume, LineageEvent, ResourceVolume},
+        sequencer::DeadlockEvent,
     },
 };
 
@@ -42,15 +46,18 @@ use crate::traceability::{
 ///
 /// These requests are initiated by application processes to the middleware for resource
 /// enrollment and I/O operation authorization. The workflow typically follows:
-/// 1. Enroll resources using `LocalEnroll` or `RemoteEnroll`
-/// 2. Request I/O permission using `IoRequest`  
+/// 1. Enroll resources using `LocalEnroll`, `RemoteEnroll`, `UnixEnroll` or `DatagramEnroll`
+/// 2. Request I/O permission using `IoRequest` (or `IoRequestBatch` for several descriptors,
+///    `DatagramIoRequest` for datagram sockets)
 /// 3. Report operation completion using `IoReport`
+/// 4. Release the file descriptor using `Close` (or share it using `Dup`)
 #[derive(Debug, Clone)]
 pub enum P2mRequest {
     /// Register a file resource with the middleware for traceability tracking.
     ///
     /// Must be called before any I/O operations on the file. The middleware will
     /// create appropriate resource identifiers and establish compliance policies.
+    /// If the open mode discards the previous content of the file, its provenance is reset.
     LocalEnroll {
         /// Process identifier that opened the file
         pid: i32,
@@ -58,6 +65,8 @@ pub enum P2mRequest {
         fd: i32,
         /// Absolute or relative path to the file
         path: String,
+        /// Mode in which the file was opened
+        mode: OpenMode,
     },
 
     /// Register a network stream resource with the middleware for traceability tracking.
@@ -75,6 +84,34 @@ pub enum P2mRequest {
         peer_socket: String,
     },
 
+    /// Register an end of a Unix domain socket connection with the middleware.
+    ///
+    /// Unix sockets are node-local: the data written on the socket is tracked on the end
+    /// enrolled by the peer process, without querying any remote middleware.
+    UnixEnroll {
+        /// Process identifier that holds the socket
+        pid: i32,
+        /// File descriptor assigned by the operating system
+        fd: i32,
+        /// Filesystem path of the listening socket, empty for unnamed sockets
+        path: String,
+        /// Process identifier of the peer process, as reported by the kernel
+        peer_pid: i32,
+    },
+
+    /// Register a datagram socket (e.g., UDP) with the middleware.
+    ///
+    /// Datagram sockets are connectionless, only the local socket is enrolled: the peer of
+    /// each datagram is given along with its `DatagramIoRequest`.
+    DatagramEnroll {
+        /// Process identifier that holds the socket
+        pid: i32,
+        /// File descriptor assigned by the operating system
+        fd: i32,
+        /// Local socket address (e.g., "127.0.0.1:8125")
+        local_socket: String,
+    },
+
     /// Request authorization to perform an I/O operation on a previously enrolled resource.
     ///
     /// The middleware will evaluate compliance policies, check data lineage requirements,
@@ -88,6 +125,36 @@ pub enum P2mRequest {
         output: bool,
     },
 
+    /// Request authorization to send a datagram to, or receive a datagram from, a peer on an
+    /// enrolled datagram socket.
+    ///
+    /// Evaluated as an `IoRequest` on the datagrams exchanged with the peer, whose node is
+    /// queried for its destination policy. The operation is reported with an `IoReport`.
+    DatagramIoRequest {
+        /// Process identifier requesting the operation
+        pid: i32,
+        /// File descriptor of the datagram socket
+        fd: i32,
+        /// Peer socket address the datagram is sent to or received from
+        peer_socket: String,
+        /// Direction of data flow: true for output (send), false for input (receive)
+        output: bool,
+    },
+
+    /// Request authorization to perform I/O operations on several enrolled resources at once.
+    ///
+    /// Each operation is evaluated as an `IoRequest` and granted or refused on its own, but the
+    /// destination policies of the streams connected to a same remote node are queried with a
+    /// single M2M request. Operations that conflict with each other (e.g., two reads, which both
+    /// flow into the process) are not granted concurrently.
+    IoRequestBatch {
+        /// Process identifier requesting the operations
+        pid: i32,
+        /// File descriptor and direction of each operation: true for output (write), false
+        /// for input (read)
+        requests: Vec<(i32, bool)>,
+    },
+
     /// Report the completion status of a previously authorized I/O operation.
     ///
     /// This enables the middleware to update provenance records, release flow reservations,
@@ -99,11 +166,139 @@ pub enum P2mRequest {
         fd: i32,
         /// Grant identifier returned from the corresponding `IoRequest`
         grant_id: u128,
+        /// Direction of the operation: true for output (write), false for input (read)
+        output: bool,
         /// Operation outcome: true for success, false for failure
         result: bool,
+        /// Number of bytes transferred by the operation
+        bytes: u64,
+        /// Offset of the transferred region in the file, if the resource is seekable
+        offset: Option<u64>,
+    },
+
+    /// Declare the truncation of a previously enrolled file (e.g., `set_len(0)`).
+    ///
+    /// The previous content of the file is discarded, so that its provenance is reset.
+    Truncate {
+        /// Process identifier that truncated the file
+        pid: i32,
+        /// File descriptor of the truncated file
+        fd: i32,
+    },
+
+    /// Declare the data dependencies of the writes on a file descriptor.
+    ///
+    /// Until cleared, the data written on `fd` is considered to derive from the resources
+    /// enrolled on the `sources` descriptors only, instead of everything the process has read.
+    /// Compliance is evaluated and provenance updated from these resources, fd-to-fd, without
+    /// going through the process. A new declaration replaces the previous one.
+    DeclareFlow {
+        /// Process identifier that declares the flow
+        pid: i32,
+        /// File descriptor of the destination of the writes
+        fd: i32,
+        /// File descriptors whose content the writes derive from
+        sources: Vec<i32>,
+    },
+
+    /// Clear the data dependencies declared on a file descriptor.
+    ///
+    /// The writes on `fd` derive from the whole process again.
+    ClearFlow {
+        /// Process identifier that declared the flow
+        pid: i32,
+        /// File descriptor of the destination of the writes
+        fd: i32,
+    },
+
+    /// Open a named session in a process (e.g., a request handled by a worker).
+    ///
+    /// Until the session ends, the I/O operations of the process are tracked as those of a
+    /// distinct activity, which starts from the provenance of the process.
+    OpenSession {
+        /// Process identifier that opens the session
+        pid: i32,
+        /// Name of the session, distinct from the other sessions of the process
+        name: String,
+    },
+
+    /// End the open session of a process.
+    ///
+    /// The taint of the session is merged back into the process, or dropped if the session
+    /// policy of the middleware permits it.
+    EndSession {
+        /// Process identifier that ends its session
+        pid: i32,
+    },
+
+    /// Unenroll a file descriptor closed by the process.
+    ///
+    /// Removes the resource mapping of the file descriptor, so that a later reuse of the
+    /// same descriptor number cannot be mistaken for the closed resource. Closing an
+    /// unknown file descriptor is not an error.
+    Close {
+        /// Process identifier that closed the file descriptor
+        pid: i32,
+        /// File descriptor released by the process
+        fd: i32,
+    },
+
+    /// Declare a duplicated file descriptor (e.g., `dup`, `try_clone`).
+    ///
+    /// The new file descriptor refers to the same resource as the original one.
+    Dup {
+        /// Process identifier that duplicated the file descriptor
+        pid: i32,
+        /// Previously enrolled file descriptor
+        fd: i32,
+        /// File descriptor assigned to the duplicate
+        new_fd: i32,
     },
 }
 
+impl P2mRequest {
+    /// Returns the identifier of the process that issued the request.
+    pub fn pid(&self) -> i32 {
+        match self {
+            P2mRequest::LocalEnroll { pid, .. }
+            | P2mRequest::RemoteEnroll { pid, .. }
+            | P2mRequest::UnixEnroll { pid, .. }
+            | P2mRequest::DatagramEnroll { pid, .. }
+            | P2mRequest::IoRequest { pid, .. }
+            | P2mRequest::DatagramIoRequest { pid, .. }
+            | P2mRequest::IoRequestBatch { pid, .. }
+            | P2mRequest::IoReport { pid, .. }
+            | P2mRequest::Truncate { pid, .. }
+            | P2mRequest::DeclareFlow { pid, .. }
+            | P2mRequest::ClearFlow { pid, .. }
+            | P2mRequest::OpenSession { pid, .. }
+            | P2mRequest::EndSession { pid }
+            | P2mRequest::Close { pid, .. }
+            | P2mRequest::Dup { pid, .. } => *pid,
+        }
+    }
+}
+
+/// Mode in which a file is opened by a process.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OpenMode {
+    /// The file is opened for writing
+    pub write: bool,
+    /// Writes are appended to the end of the file
+    pub append: bool,
+    /// The file is truncated when opened
+    pub truncate: bool,
+    /// The file is created, and must not exist before
+    pub create_new: bool,
+}
+
+impl OpenMode {
+    /// Checks whether the previous content of the file is discarded when opened.
+    pub fn overwrites(&self) -> bool {
+        self.truncate || self.create_new
+    }
+}
+
 /// Process-to-Middleware (P2M) response types.
 ///
 /// Responses sent from the middleware back to application processes following P2M requests.
@@ -111,14 +306,22 @@ pub enum P2mRequest {
 pub enum P2mResponse {
     /// Authorization granted for an I/O operation with a unique grant identifier.
     ///
-    /// Returned in response to `P2mRequest::IoRequest` when the operation is permitted
-    /// by current policies. The grant ID must be included in the subsequent `IoReport`.
+    /// Returned in response to `P2mRequest::IoRequest` (or `DatagramIoRequest`) when the
+    /// operation is permitted by current policies. The grant ID must be included in the
+    /// subsequent `IoReport`.
     Grant(u128),
 
+    /// Per-operation outcome of a `P2mRequest::IoRequestBatch`, in request order.
+    ///
+    /// Each granted operation carries its own grant ID, to be reported individually.
+    Grants(Vec<Result<u128, TraceabilityError>>),
+
     /// Acknowledgment of successful request processing.
     ///
-    /// Returned for `LocalEnroll`, `RemoteEnroll`, and `IoReport` requests to confirm
-    /// the operation was completed successfully.
+    /// Returned for `LocalEnroll`, `RemoteEnroll`, `UnixEnroll`, `DatagramEnroll`, `IoReport`,
+    /// `Truncate`, `DeclareFlow`, `ClearFlow`, `OpenSession`, `EndSession`, `Close` and `Dup`
+    /// requests to
+    /// confirm the operation was completed successfully.
     Ack,
 }
 
@@ -132,6 +335,12 @@ pub enum M2mRequest {
     /// Request policy for a destination resource from its authoritative middleware.
     GetDestinationPolicy(LocalizedResource),
 
+    /// Request policies for several destination resources hosted by the same middleware.
+    ///
+    /// Each destination is reserved as by `GetDestinationPolicy`, the destinations that cannot
+    /// be reserved are left out of the response.
+    GetDestinationPolicies(HashSet<LocalizedResource>),
+
     /// Request compliance policies for source resources from their authoritative middleware.
     ///
     /// Used by destination middleware to verify that incoming data flows comply with
@@ -154,6 +363,13 @@ pub enum M2mRequest {
         destination: LocalizedResource,
     },
 
+    /// Release the reservation of a destination resource taken by a destination policy query,
+    /// without updating its provenance.
+    ///
+    /// Sent when the flow is not performed after all, e.g. because it was refused or its
+    /// grant was abandoned.
+    ReleaseFlow(LocalizedResource),
+
     /// Broadcast Deletion of a resource
     ///
     /// Broadcast the deletion of a resource to all middleware instances.
@@ -171,6 +387,9 @@ pub enum M2mResponse {
     /// to the requested destination resource.
     DestinationPolicy(Policy),
 
+    /// Compliance policies of the reserved destination resources.
+    DestinationPolicies(HashMap<LocalizedResource, Policy>),
+
     /// Acknowledgment of successful request processing.
     ///
     /// Confirms that provenance updates or other operations completed successfully.
@@ -222,6 +441,14 @@ pub enum O2mRequest {
         integrity: u32,
     },
 
+    /// Allow or forbid dropping the taint of a resource from the data derived from it.
+    SetDeclassification {
+        /// Target resource to update
+        resource: Resource,
+        /// Whether the taint of the resource may be dropped
+        declassification: bool,
+    },
+
     /// Mark a resource as deleted for compliance and audit purposes.
     ///
     /// Indicates that the resource has been removed from the system while
@@ -255,6 +482,38 @@ pub enum O2mRequest {
     /// Returns all upstream resources and middleware nodes that have contributed
     /// data to the specified resource, enabling full traceability analysis.
     GetReferences(Resource),
+
+    /// Subscribe to the deadlocks detected by the sequencer.
+    ///
+    /// Returns a stream of detection events, each one describing an aborted request
+    /// and the processes involved in the circular wait.
+    WatchDeadlocks,
+
+    /// List the flow reservations currently held by the sequencer.
+    ListReservations,
+
+    /// List the grants issued to processes and not reported yet.
+    ListPendingGrants,
+
+    /// Retrieve the number of requests waiting on each resource.
+    GetQueueDepths,
+
+    /// Retrieve the volume of data read from and written to a set of resources.
+    ///
+    /// Counters are accumulated from the byte counts reported by processes, enabling
+    /// operators to distinguish a probe from a bulk transfer.
+    GetVolumes(HashSet<Resource>),
+
+    /// Retrieve the lineage events of a resource, such as overwrites.
+    ///
+    /// Events record the provenance discarded when the content of the resource was reset.
+    GetLineageEvents(Resource),
+
+    /// Forcibly release the reservation held on a destination resource.
+    ///
+    /// Pending grants on the destination are revoked, and the requests waiting
+    /// on the released resources are woken up.
+    ForceRelease(Resource),
 }
 
 /// Operator-to-Middleware (O2M) response types.
@@ -285,6 +544,26 @@ pub enum O2mResponse {
     ///
     /// A channel for receiving consent requests notifications for a specific resource.
     Notifications(broadcast::Receiver<Destination>),
+
+    /// Deadlock detection events channel.
+    ///
+    /// A channel for receiving the deadlocks detected by the sequencer.
+    DeadlockNotifications(broadcast::Receiver<DeadlockEvent>),
+
+    /// Active flow reservations, as (source, destination) pairs.
+    Reservations(Vec<(Resource, Resource)>),
+
+    /// Grants pending a report.
+    PendingGrants(Vec<PendingGrant>),
+
+    /// Number of requests waiting on each resource.
+    QueueDepths(HashMap<Resource, usize>),
+
+    /// Volume counters of the requested resources.
+    Volumes(HashMap<Resource, ResourceVolume>),
+
+    /// Lineage events of the requested resource, in chronological order.
+    LineageEvents(Vec<LineageEvent>),
 }
 
 impl PartialEq for O2mResponse {
@@ -296,7 +575,24 @@ impl PartialEq for O2mResponse {
             (O2mResponse::References(references), O2mResponse::References(other_references)) => {
                 references == other_references
             }
+            (
+                O2mResponse::Reservations(reservations),
+                O2mResponse::Reservations(other_reservations),
+            ) => reservations == other_reservations,
+            (O2mResponse::PendingGrants(grants), O2mResponse::PendingGrants(other_grants)) => {
+                grants == other_grants
+            }
+            (O2mResponse::QueueDepths(depths), O2mResponse::QueueDepths(other_depths)) => {
+                depths == other_depths
+            }
+            (O2mResponse::Volumes(volumes), O2mResponse::Volumes(other_volumes)) => {
+                volumes == other_volumes
+            }
+            (O2mResponse::LineageEvents(events), O2mResponse::LineageEvents(other_events)) => {
+                events == other_events
+            }
             (O2mResponse::Notifications(_), O2mResponse::Notifications(_))
+            | (O2mResponse::DeadlockNotifications(_), O2mResponse::DeadlockNotifications(_))
             | (O2mResponse::Ack, O2mResponse::Ack) => true,
             _ => false,
         }
@@ -321,20 +617,59 @@ pub enum SequencerRequest {
         destination: Resource,
     },
 
+    /// Reserve a data flow from source to destination, sharing the destination with the
+    /// flows already joined to it.
+    ///
+    /// The destination must not be read by another flow, but it may be written by other
+    /// joined flows, so that a process reading several resources at once holds a single
+    /// reservation of itself.
+    JoinFlow {
+        /// Source resource providing data
+        source: Resource,
+        /// Destination resource receiving data
+        destination: Resource,
+    },
+
     /// Release a previously reserved flow to allow subsequent operations.
     ///
     /// Must be called after flow completion to free the destination resource
-    /// for other operations and maintain system throughput.
+    /// for other operations and maintain system throughput. All the flows joined to the
+    /// destination are released.
     ReleaseFlow {
         /// Destination resource to release from reservation
         destination: Resource,
     },
+
+    /// Release a single flow from source to destination.
+    ///
+    /// The destination remains reserved until the other flows joined to it are released.
+    LeaveFlow {
+        /// Source resource of the flow to release
+        source: Resource,
+        /// Destination resource of the flow to release
+        destination: Resource,
+    },
+
+    /// Retrieve the active reservations involving a resource.
+    ///
+    /// Used to identify the flows holding a resource, either as the reserved
+    /// destination or as one of the sources reserved for reading.
+    GetReservations(Resource),
+
+    /// Retrieve all the active reservations.
+    ListReservations,
+
+    /// Retrieve the number of requests waiting on each resource.
+    GetQueueDepths,
+
+    /// Subscribe to the deadlocks detected in the waiting queue.
+    WatchDeadlocks,
 }
 
 /// Sequencer service response types.
 ///
 /// Responses from the sequencer service confirming flow reservation and release operations.
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub enum SequencerResponse {
     /// Confirmation that the requested flow has been successfully reserved.
     ///
@@ -344,14 +679,55 @@ pub enum SequencerResponse {
 
     /// Confirmation that the flow reservation has been successfully released.
     ///
-    /// Returns the source and destination resources that were involved in the
-    /// flow, if available, for logging and audit purposes.
+    /// Returns the source and destination resources of the flow that are no longer
+    /// reserved, so that the operations waiting on them can proceed.
     FlowReleased {
-        /// Source resource that was part of the released flow
-        source: Option<Resource>,
-        /// Destination resource that was part of the released flow
+        /// Source resources of the released flows that are no longer reserved
+        sources: Vec<Resource>,
+        /// Destination resource of the released flows, if it is no longer reserved
         destination: Option<Resource>,
     },
+
+    /// Active reservations involving the requested resource.
+    ///
+    /// Each reservation is given as a (source, destination) pair.
+    Reservations(Vec<(Resource, Resource)>),
+
+    /// Number of requests waiting on each resource with a non-empty waiting queue.
+    QueueDepths(HashMap<Resource, usize>),
+
+    /// Deadlock detection events channel.
+    ///
+    /// A channel for receiving the deadlocks detected by the waiting queue.
+    DeadlockNotifications(broadcast::Receiver<DeadlockEvent>),
+}
+
+impl PartialEq for SequencerResponse {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                SequencerResponse::FlowReleased { sources, destination },
+                SequencerResponse::FlowReleased {
+                    sources: other_sources,
+                    destination: other_destination,
+                },
+            ) => sources == other_sources && destination == other_destination,
+            (
+                SequencerResponse::Reservations(reservations),
+                SequencerResponse::Reservations(other_reservations),
+            ) => reservations == other_reservations,
+            (
+                SequencerResponse::QueueDepths(depths),
+                SequencerResponse::QueueDepths(other_depths),
+            ) => depths == other_depths,
+            (
+                SequencerResponse::DeadlockNotifications(_),
+                SequencerResponse::DeadlockNotifications(_),
+            )
+            | (SequencerResponse::FlowReserved, SequencerResponse::FlowReserved) => true,
+            _ => false,
+        }
+    }
 }
 
 /// Provenance service request types.
@@ -388,6 +764,66 @@ pub enum ProvenanceRequest {
         /// Destination resource to receive the provenance updates
         destination: Resource,
     },
+
+    /// Archive the provenance of an exited process.
+    ///
+    /// Moves the provenance of the process out of the live records, under its `(pid, starttime)`
+    /// identity, so that a later process reusing the same pid starts with a clean provenance.
+    ArchiveProcess(Resource),
+
+    /// Drop the provenance of an activity that ended, such as a process session.
+    ///
+    /// The resources written by the activity keep the provenance they derived from it.
+    DropProvenance(Resource),
+
+    /// Retrieve the archived provenance of an exited process.
+    GetArchivedReferences {
+        /// Process identifier of the exited process
+        pid: i32,
+        /// Start time of the exited process in clock ticks since boot
+        starttime: u64,
+    },
+
+    /// Record the volume of a completed data flow on the edge from source to destination.
+    ///
+    /// Accumulates the transferred bytes on the edge and on the volume counters of both
+    /// resources, along with the transferred region of the file if known.
+    RecordTransfer {
+        /// Source resource providing data
+        source: Resource,
+        /// Destination resource receiving data
+        destination: Resource,
+        /// Number of bytes transferred
+        bytes: u64,
+        /// Offset of the transferred region in the file, if the resource is seekable
+        offset: Option<u64>,
+    },
+
+    /// Retrieve the volume counters of a set of resources.
+    GetVolumes(HashSet<Resource>),
+
+    /// Retrieve the volume of the edges leading to a destination resource, by source.
+    GetEdgeVolumes(Resource),
+
+    /// Reset the provenance of a resource whose content was discarded by a process.
+    ///
+    /// The resource only derives from itself afterwards, and the discarded provenance is
+    /// kept in an overwrite lineage event.
+    ResetProvenance {
+        /// Resource whose content was discarded
+        resource: Resource,
+        /// Process that discarded the content
+        process: Resource,
+    },
+
+    /// Retrieve the lineage events of a resource.
+    GetLineageEvents(Resource),
+
+    /// Retrieve the provenance epoch of a set of resources.
+    ///
+    /// The epoch changes whenever the provenance of any of the resources changes, so that the
+    /// outcome of a computation on their provenance can be reused as long as it is unchanged.
+    GetEpoch(HashSet<Resource>),
 }
 
 /// Provenance service response types.
@@ -412,6 +848,30 @@ pub enum ProvenanceResponse {
     /// Returned when the source resource is already included in the destination's
     /// provenance, avoiding duplicate entries in the lineage records.
     ProvenanceNotUpdated,
+
+    /// Confirmation that the provenance of an exited process was archived.
+    ProvenanceArchived,
+
+    /// Confirmation that the provenance of an ended activity was dropped.
+    ProvenanceDropped,
+
+    /// Confirmation that the volume of a data flow was recorded.
+    TransferRecorded,
+
+    /// Volume counters of the requested resources.
+    Volumes(HashMap<Resource, ResourceVolume>),
+
+    /// Volume of the edges leading to the requested destination, by source.
+    EdgeVolumes(HashMap<Resource, EdgeVolume>),
+
+    /// Confirmation that the provenance of a resource was reset.
+    ProvenanceReset,
+
+    /// Lineage events of the requested resource, in chronological order.
+    LineageEvents(Vec<LineageEvent>),
+
+    /// Provenance epoch of the requested resources.
+    Epoch(u64),
 }
 
 /// Compliance service request types.
@@ -446,6 +906,13 @@ pub enum ComplianceRequest {
     /// multiple resources in a single request.
     GetPolicies(HashSet<Resource>),
 
+    /// Retrieve the current policy epoch.
+    ///
+    /// The epoch changes whenever a policy is updated or a consent decision is set, through
+    /// the O2M or M2M APIs alike, so that the outcome of a past compliance evaluation can be
+    /// reused as long as it is unchanged.
+    GetPolicyEpoch,
+
     /// Set a complete compliance policy for a specific resource.
     ///
     /// Replaces the existing policy with new configuration that defines
@@ -495,6 +962,16 @@ pub enum ComplianceRequest {
         /// Consent status: true to grant, false to revoke
         consent: bool,
     },
+
+    /// Allow or forbid dropping the taint of a resource from the data derived from it.
+    ///
+    /// Declassification is checked when a process ends a session whose taint is dropped.
+    SetDeclassification {
+        /// Target resource to update
+        resource: Resource,
+        /// Whether the taint of the resource may be dropped
+        declassification: bool,
+    },
 }
 
 /// Compliance service response types.
@@ -521,6 +998,9 @@ pub enum ComplianceResponse {
     /// for batch policy queries.
     Policies(HashMap<LocalizedResource, Policy>),
 
+    /// Current policy epoch.
+    PolicyEpoch(u64),
+
     /// Confirmation that a policy update was successfully applied.
     ///
     /// Indicates that policy modifications, consent changes, or other
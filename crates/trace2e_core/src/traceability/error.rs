@@ -23,7 +23,7 @@ use thiserror::Error;
 
 use crate::traceability::infrastructure::naming::Resource;
 
-#[derive(Debug, Error, PartialEq)]
+#[derive(Debug, Clone, Error, PartialEq)]
 pub enum TraceabilityError {
     #[error("Traceability error, internal trace2e API error")]
     InternalTrace2eError,
@@ -40,12 +40,36 @@ pub enum TraceabilityError {
     #[error("Traceability error, invalid stream (local_socket: {0}, peer_socket: {1})")]
     InvalidStream(String, String),
 
-    #[error("Traceability error, failed to instantiate flow due to system time error")]
-    SystemTimeError,
+    #[error("Traceability error, invalid unix socket (path: {0}, peer_pid: {1})")]
+    InvalidUnixSocket(String, i32),
+
+    #[error("Traceability error, invalid datagram socket (socket: {0})")]
+    InvalidDatagram(String),
 
     #[error("Traceability error, flow not found (id: {0})")]
     NotFoundFlow(u128),
 
+    #[error("Traceability error, grant lease expired (id: {0})")]
+    ExpiredGrant(u128),
+
+    #[error("Traceability error, grant not issued to this process (id: {0}, pid: {1})")]
+    GrantProcessMismatch(u128, i32),
+
+    #[error("Traceability error, grant not issued for this file descriptor (id: {0}, fd: {1})")]
+    GrantFdMismatch(u128, i32),
+
+    #[error("Traceability error, grant not issued for this direction (id: {0})")]
+    GrantFlowMismatch(u128),
+
+    #[error("Traceability error, a session is already open (pid: {0})")]
+    SessionAlreadyOpen(i32),
+
+    #[error("Traceability error, no open session (pid: {0})")]
+    NoOpenSession(i32),
+
+    #[error("Traceability error, session has pending grants (pid: {0})")]
+    SessionBusy(i32),
+
     #[error("Traceability error, destination unavailable")]
     UnavailableDestination(Resource),
 
@@ -58,6 +82,14 @@ pub enum TraceabilityError {
     #[error("Traceability error, reached max retries waiting queue")]
     ReachedMaxRetriesWaitingQueue,
 
+    #[error("Traceability error, waiting queue timeout")]
+    WaitingQueueTimeout,
+
+    #[error(
+        "Traceability error, deadlock detected in waiting queue (source: {0}, destination: {1})"
+    )]
+    DeadlockDetected(Resource, Resource),
+
     #[error("Traceability error, direct policy violation")]
     DirectPolicyViolation,
 
@@ -2,7 +2,8 @@
 //!
 //! This module provides foundational types and utilities used across all layers
 //! of the traceability system, including resource identification, naming conventions,
-//! and request validation.
+//! request validation and process lifecycle observation.
 
 pub mod naming;
+pub mod process;
 pub mod validation;
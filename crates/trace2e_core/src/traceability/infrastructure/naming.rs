@@ -7,13 +7,21 @@
 //! ## Resource Hierarchy
 //!
 //! **File Descriptors (Fd)**: Represent operating system file descriptors that can point to
-//! either files in the filesystem or network streams (sockets).
+//! files in the filesystem, network streams, datagram sockets or Unix domain sockets.
 //!
 //! **Files**: Filesystem resources identified by their path, supporting both absolute and
 //! relative path specifications.
 //!
 //! **Streams**: Network communication channels defined by local and peer socket addresses,
-//! supporting TCP connections, Unix domain sockets, and other network protocols.
+//! supporting TCP connections and other network protocols.
+//!
+//! **Datagrams**: Connectionless network channels (e.g., UDP) defined by local and peer socket
+//! addresses, like streams. A datagram socket is enrolled without peer, the peer is set for
+//! each datagram sent or received.
+//!
+//! **Unix Sockets**: Node-local communication channels defined by the path of the socket and
+//! the pid of the peer process. The data written on an end is held by the opposite end, which
+//! is named from the writer pid, so that node-local flows need no remote lookup.
 //!
 //! **Processes**: Running system processes identified by PID with additional metadata including
 //! start time and executable path for precise identification across process reuse.
@@ -29,9 +37,7 @@ use std::{
     net::SocketAddr,
 };
 
-use sysinfo::{Pid, System};
-
-use crate::traceability::error::TraceabilityError;
+use crate::traceability::{error::TraceabilityError, infrastructure::process::ProcessRegistry};
 
 /// Represents a file resource in the filesystem.
 ///
@@ -47,9 +53,9 @@ pub struct File {
 /// Represents a network stream or socket connection.
 ///
 /// Streams are bidirectional communication channels between two endpoints,
-/// typically used for TCP connections, Unix domain sockets, or other
-/// network protocols. Both endpoints must be specified to enable proper
-/// flow tracking and policy enforcement.
+/// typically used for TCP connections or other network protocols.
+/// Both endpoints must be specified to enable proper flow tracking and
+/// policy enforcement.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Stream {
     /// Local socket address (e.g., "127.0.0.1:8080")
@@ -58,17 +64,48 @@ pub struct Stream {
     pub peer_socket: String,
 }
 
-/// Represents a file descriptor that can point to either a file or a stream.
+/// Represents the datagrams exchanged between two sockets (e.g., UDP).
+///
+/// Datagram sockets are connectionless: an enrolled socket has no peer socket, while the
+/// datagrams it sends or receives are tracked with the peer of each operation.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Datagram {
+    /// Local socket address (e.g., "127.0.0.1:8125")
+    pub local_socket: String,
+    /// Remote peer socket address (e.g., "192.168.1.100:8125"), None for enrolled sockets
+    pub peer_socket: Option<Box<str>>,
+}
+
+/// Represents an end of a Unix domain socket connection.
+///
+/// Both ends of a connection share the path of the listening socket, they are told apart by
+/// the pid of the process at the other end, as reported by the kernel (`SO_PEERCRED`) to the
+/// process holding the end. Unnamed sockets (e.g., `socketpair`) have an empty path.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct UnixSocket {
+    /// Filesystem path of the listening socket (e.g., "/run/app.sock")
+    pub path: String,
+    /// Process identifier of the peer process
+    pub peer_pid: i32,
+}
+
+/// Represents a file descriptor that can point to a file, a stream, a datagram socket or a
+/// Unix socket.
 ///
 /// File descriptors are the operating system's handle for I/O operations.
-/// This enum distinguishes between filesystem-based I/O (files) and
-/// network-based I/O (streams) while maintaining a unified interface.
+/// This enum distinguishes between filesystem-based I/O (files), network-based
+/// I/O (streams and datagrams) and node-local IPC (Unix sockets) while maintaining a
+/// unified interface.
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum Fd {
     /// File descriptor pointing to a filesystem file
     File(File),
     /// File descriptor pointing to a network stream or socket
     Stream(Stream),
+    /// File descriptor pointing to a datagram socket, or datagrams exchanged with a peer
+    Datagram(Datagram),
+    /// File descriptor pointing to an end of a Unix domain socket connection
+    Unix(UnixSocket),
 }
 
 /// Represents a running system process with identifying metadata.
@@ -80,10 +117,12 @@ pub enum Fd {
 pub struct Process {
     /// Process identifier assigned by the operating system
     pub pid: i32,
-    /// Process start time in seconds since epoch for uniqueness
+    /// Process start time in clock ticks since boot for uniqueness
     pub starttime: u64,
     /// Path to the executable that created this process
     pub exe_path: String,
+    /// Name of the session of the process, if this is a session activity
+    pub session: Option<Box<str>>,
 }
 
 /// Unified resource identifier for all trackable entities in the system.
@@ -93,7 +132,7 @@ pub struct Process {
 /// to files or streams), processes, or null resources for uninitialized states.
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
 pub enum Resource {
-    /// File descriptor resource (file or stream)
+    /// File descriptor resource (file, stream, datagram or Unix socket)
     Fd(Fd),
     /// Process resource  
     Process(Process),
@@ -119,25 +158,34 @@ impl Resource {
         Self::Fd(Fd::Stream(Stream { local_socket, peer_socket }))
     }
 
+    /// Creates a new datagram resource with the specified socket addresses.
+    ///
+    /// The peer socket is None for an enrolled datagram socket, whose peer is only known
+    /// for each datagram (see `with_datagram_peer`).
+    pub fn new_datagram(local_socket: String, peer_socket: Option<String>) -> Self {
+        Self::Fd(Fd::Datagram(Datagram {
+            local_socket,
+            peer_socket: peer_socket.map(String::into_boxed_str),
+        }))
+    }
+
+    /// Creates a new Unix socket resource with the specified path and peer pid.
+    pub fn new_unix_socket(path: String, peer_pid: i32) -> Self {
+        Self::Fd(Fd::Unix(UnixSocket { path, peer_pid }))
+    }
+
     /// Creates a new process resource by querying the system for process information.
     ///
-    /// Attempts to retrieve the process start time and executable path from the
-    /// system process table. If the process is not found, creates a process
-    /// resource with default values for the metadata fields.
+    /// Retrieves the process start time and executable path from the process registry.
+    /// If the process is not found, creates a process resource with default values
+    /// for the metadata fields.
     pub fn new_process(pid: i32) -> Self {
-        let mut system = System::new();
-        system.refresh_all();
-        if let Some(process) = system.process(Pid::from(pid as usize)) {
-            let starttime = process.start_time();
-            let exe_path = if let Some(exe) = process.exe() {
-                exe.to_string_lossy().to_string()
-            } else {
-                String::new()
-            };
-            Self::Process(Process { pid, starttime, exe_path })
-        } else {
-            Self::Process(Process { pid, starttime: 0, exe_path: String::new() })
-        }
+        Self::Process(ProcessRegistry::global().get(pid).unwrap_or(Process {
+            pid,
+            starttime: 0,
+            exe_path: String::new(),
+            session: None,
+        }))
     }
 
     /// Creates a mock process resource for testing purposes.
@@ -146,7 +194,14 @@ impl Resource {
     /// for start time and executable path. Should only be used in test
     /// environments where system process queries are not needed.
     pub fn new_process_mock(pid: i32) -> Self {
-        Self::Process(Process { pid, starttime: 0, exe_path: String::new() })
+        Self::Process(Process { pid, starttime: 0, exe_path: String::new(), session: None })
+    }
+
+    /// Creates the resource of a named session of a process.
+    ///
+    /// A session is tracked as a distinct activity of the process, with its own provenance.
+    pub fn new_session(process: Process, name: String) -> Self {
+        Self::Process(Process { session: Some(name.into_boxed_str()), ..process })
     }
 
     /// Checks if this resource represents a filesystem file.
@@ -180,6 +235,44 @@ impl Resource {
         if let Resource::Fd(Fd::Stream(s)) = self { Some(&s.peer_socket) } else { None }
     }
 
+    /// Checks if this resource represents a datagram socket or datagrams exchanged with a peer.
+    pub fn is_datagram(&self) -> bool {
+        matches!(self, Resource::Fd(Fd::Datagram(_)))
+    }
+
+    /// Checks if this resource represents a network channel (stream or datagram).
+    ///
+    /// The data written on a network channel is held by the peer end, hosted by the middleware
+    /// of the peer node, and network channels have no provenance of their own.
+    pub fn is_network(&self) -> bool {
+        self.is_stream() || self.is_datagram()
+    }
+
+    /// Returns the datagrams exchanged by a datagram socket with the specified peer, or None
+    /// if this resource is not a datagram socket.
+    pub fn with_datagram_peer(&self, peer_socket: String) -> Option<Resource> {
+        let Resource::Fd(Fd::Datagram(datagram)) = self else {
+            return None;
+        };
+        Some(Resource::new_datagram(datagram.local_socket.clone(), Some(peer_socket)))
+    }
+
+    /// Checks if this resource represents an end of a Unix socket connection.
+    pub fn is_unix_socket(&self) -> bool {
+        matches!(self, Resource::Fd(Fd::Unix(_)))
+    }
+
+    /// Returns the peer end of a Unix socket resource, given the pid of the local process.
+    ///
+    /// The peer end is the resource enrolled by the peer process for the same connection, which
+    /// holds the data written on this end. Returns None for non-Unix socket resources.
+    pub fn try_into_local_peer_socket(&self, local_pid: i32) -> Option<Resource> {
+        let Resource::Fd(Fd::Unix(socket)) = self else {
+            return None;
+        };
+        Some(Resource::new_unix_socket(socket.path.clone(), local_pid))
+    }
+
     /// Converts this resource into a localized resource given the specified localization.
     ///
     /// It attempts to convert the resource into a localized stream resource, which infers
@@ -197,20 +290,26 @@ impl Resource {
     ///
     /// For stream resources, returns a new resource with the local and peer
     /// socket addresses swapped. This is useful for tracking bidirectional
-    /// flows. Returns None for non-stream resources.
+    /// flows. Datagrams exchanged with a peer are handled the same way.
+    /// Returns None for other resources, and for datagram sockets without peer.
     pub fn try_into_localized_peer_stream(&self) -> Option<LocalizedResource> {
-        let Resource::Fd(Fd::Stream(stream)) = self else {
-            return None;
+        let (local_socket, peer_socket) = match self {
+            Resource::Fd(Fd::Stream(stream)) => (&stream.local_socket, stream.peer_socket.as_str()),
+            Resource::Fd(Fd::Datagram(datagram)) => {
+                (&datagram.local_socket, datagram.peer_socket.as_deref()?)
+            }
+            _ => return None,
         };
 
-        let peer_socket = stream.peer_socket.parse::<SocketAddr>().ok()?;
-        let ip = peer_socket.ip();
+        let ip = peer_socket.parse::<SocketAddr>().ok()?.ip();
         let node_id = if ip.is_ipv6() { format!("[{}]", ip) } else { ip.to_string() };
+        let peer_resource = if self.is_stream() {
+            Resource::new_stream(peer_socket.to_string(), local_socket.clone())
+        } else {
+            Resource::new_datagram(peer_socket.to_string(), Some(local_socket.clone()))
+        };
 
-        Some(LocalizedResource::new(
-            node_id,
-            Resource::new_stream(stream.peer_socket.clone(), stream.local_socket.clone()),
-        ))
+        Some(LocalizedResource::new(node_id, peer_resource))
     }
 
     /// Checks if this resource represents a system process.
@@ -229,8 +328,23 @@ impl Display for Resource {
             Resource::Fd(Fd::Stream(stream)) => {
                 write!(f, "stream://{}::::{}", stream.local_socket, stream.peer_socket)
             }
+            Resource::Fd(Fd::Datagram(datagram)) => {
+                let peer_socket = datagram.peer_socket.as_deref().unwrap_or_default();
+                write!(f, "datagram://{}::::{}", datagram.local_socket, peer_socket)
+            }
+            Resource::Fd(Fd::Unix(socket)) => {
+                write!(f, "unix://{}::::{}", socket.path, socket.peer_pid)
+            }
             Resource::Process(process) => {
-                write!(f, "process://{}::{}::{}", process.pid, process.starttime, process.exe_path)
+                write!(
+                    f,
+                    "process://{}::{}::{}",
+                    process.pid, process.starttime, process.exe_path
+                )?;
+                if let Some(session) = &process.session {
+                    write!(f, "::{session}")?;
+                }
+                Ok(())
             }
             Resource::None => write!(f, "None"),
         }
@@ -275,6 +389,8 @@ impl TryFrom<&str> for Resource {
     /// Supports the following formats:
     /// - `file:///path` - File resource
     /// - `stream://local::::peer` - Stream resource
+    /// - `datagram://local::::peer` - Datagram resource, with an empty peer for sockets
+    /// - `unix://path::::pid` - Unix socket resource
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         if let Some(path) = s.strip_prefix("file://") {
             Ok(Resource::new_file(path.to_string()))
@@ -286,9 +402,29 @@ impl TryFrom<&str> for Resource {
                 ));
             }
             Ok(Resource::new_stream(sockets[0].to_string(), sockets[1].to_string()))
+        } else if let Some(datagram_part) = s.strip_prefix("datagram://") {
+            let Some((local_socket, peer_socket)) = datagram_part.split_once("::::") else {
+                return Err(TraceabilityError::InvalidResourceFormat(
+                    "Invalid datagram format: expected 'datagram://local::::peer'".to_string(),
+                ));
+            };
+            let peer_socket = (!peer_socket.is_empty()).then(|| peer_socket.to_string());
+            Ok(Resource::new_datagram(local_socket.to_string(), peer_socket))
+        } else if let Some(socket_part) = s.strip_prefix("unix://") {
+            let invalid = || {
+                TraceabilityError::InvalidResourceFormat(
+                    "Invalid unix socket format: expected 'unix://path::::pid'".to_string(),
+                )
+            };
+            let (path, peer_pid) = socket_part.rsplit_once("::::").ok_or_else(invalid)?;
+            Ok(Resource::new_unix_socket(
+                path.to_string(),
+                peer_pid.parse().map_err(|_| invalid())?,
+            ))
         } else {
             Err(TraceabilityError::InvalidResourceFormat(
-                "Resource must start with 'file://' or 'stream://'".to_string(),
+                "Resource must start with 'file://', 'stream://', 'datagram://' or 'unix://'"
+                    .to_string(),
             ))
         }
     }
@@ -311,6 +447,8 @@ impl TryFrom<&str> for LocalizedResource {
     /// where resource is either:
     /// - `file:///path` - File resource
     /// - `stream://local::::peer` - Stream resource
+    /// - `datagram://local::::peer` - Datagram resource
+    /// - `unix://path::::pid` - Unix socket resource
     fn try_from(s: &str) -> Result<Self, Self::Error> {
         // Split by '@' to separate resource and node_id
         let parts: Vec<&str> = s.rsplitn(2, '@').collect();
@@ -504,6 +642,61 @@ mod tests {
         assert!(resource.is_stream());
     }
 
+    #[test]
+    fn test_resource_unix_socket() {
+        let socket = Resource::new_unix_socket("/run/app.sock".to_string(), 1234);
+        assert!(socket.is_unix_socket());
+        assert!(!socket.is_stream());
+        assert_eq!(socket.to_string(), "unix:///run/app.sock::::1234");
+        assert_eq!(Resource::try_from(socket.to_string()).unwrap(), socket);
+
+        // The peer end is named from the pid of the local process
+        assert_eq!(
+            socket.try_into_local_peer_socket(5678),
+            Some(Resource::new_unix_socket("/run/app.sock".to_string(), 5678))
+        );
+        assert_eq!(
+            Resource::new_file("/tmp/test.txt".to_string()).try_into_local_peer_socket(5678),
+            None
+        );
+        // Unix sockets are node-local
+        assert_eq!(socket.clone().into_localized("127.0.0.1".to_string()).node_id(), "127.0.0.1");
+
+        assert!(matches!(
+            Resource::try_from("unix:///run/app.sock::::1234::1000::100"),
+            Err(TraceabilityError::InvalidResourceFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_resource_datagram() {
+        let socket = Resource::new_datagram("10.0.0.1:8125".to_string(), None);
+        assert!(socket.is_datagram() && socket.is_network() && !socket.is_stream());
+        assert_eq!(socket.to_string(), "datagram://10.0.0.1:8125::::");
+        assert_eq!(Resource::try_from(socket.to_string()).unwrap(), socket);
+        // A socket without peer has no peer end
+        assert_eq!(socket.try_into_localized_peer_stream(), None);
+
+        // The datagrams sent to a peer are held by the datagrams received by the peer
+        let datagram = socket.with_datagram_peer("10.0.0.2:8125".to_string()).unwrap();
+        assert_eq!(datagram.to_string(), "datagram://10.0.0.1:8125::::10.0.0.2:8125");
+        assert_eq!(
+            datagram.try_into_localized_peer_stream(),
+            Some(LocalizedResource::new(
+                "10.0.0.2".to_string(),
+                Resource::new_datagram(
+                    "10.0.0.2:8125".to_string(),
+                    Some("10.0.0.1:8125".to_string())
+                )
+            ))
+        );
+        assert_eq!(datagram.clone().into_localized("10.0.0.1".to_string()).node_id(), "10.0.0.2");
+        assert_eq!(
+            Resource::new_file("/tmp/test.txt".to_string()).with_datagram_peer(String::new()),
+            None
+        );
+    }
+
     #[test]
     fn test_resource_try_from_string() {
         let resource = Resource::try_from("file:///tmp/test.txt".to_string()).unwrap();
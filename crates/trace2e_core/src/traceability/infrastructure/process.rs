@@ -0,0 +1,408 @@
+//! Process lifecycle observation for the traceability system.
+//!
+//! Per-process state held by the middleware (file descriptor mappings, pending grants and
+//! process provenance) is keyed by process identifiers that the operating system recycles.
+//! This module detects the termination of processes, so that this state can be released
+//! before a new process reuses the same pid.
+//!
+//! ## Process Identity
+//!
+//! A process is identified by its pid and its start time. A process is considered exited
+//! when its pid is no longer present in `/proc`, or when the process currently holding the
+//! pid started at a different time (i.e., the pid was reused).
+//!
+//! Start times are expressed in clock ticks since boot, as read from `/proc/<pid>/stat`, so
+//! that two processes started within the same second are told apart.
+//!
+//! ## Process Registry
+//!
+//! Process metadata is resolved through a registry reading `/proc/<pid>/stat` and
+//! `/proc/<pid>/exe` directly, instead of scanning the whole process table. Entries are
+//! cached by pid and start time: a cached entry is only reused while the same process holds
+//! the pid and runs the same executable, and is invalidated once the process exits.
+//!
+//! ## Process Hierarchy
+//!
+//! The registry also exposes the ancestors of a process and the file descriptors a process
+//! shares with one of them, so that provenance can follow `fork` and `exec`. Descriptors are
+//! compared by the device and inode of the file or kernel object they refer to, since paths
+//! are ambiguous (e.g., a file replaced after it was opened). An `exec` keeps the pid and
+//! start time of the process but changes its command name and executable path.
+
+use std::{
+    fs::{metadata, read_link, read_to_string},
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::LazyLock,
+};
+
+use dashmap::DashMap;
+
+use crate::traceability::infrastructure::naming::Process;
+
+/// Registry shared by resource construction and validation
+static PROCESS_REGISTRY: LazyLock<ProcessRegistry> = LazyLock::new(ProcessRegistry::new);
+
+/// Observes the liveness of processes through the `/proc` filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessWatcher;
+
+impl ProcessWatcher {
+    /// Creates a new process watcher for the running system.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Returns the status of the process currently holding the given pid.
+    ///
+    /// # Returns
+    /// The process status, or None if no process holds the pid
+    pub fn stat(&self, pid: i32) -> Option<ProcessStat> {
+        let stat = read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        // The command name may contain spaces and parentheses, fields are counted after it
+        let (head, fields) = stat.rsplit_once(')')?;
+        let (_, comm) = head.split_once('(')?;
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        // Parent pid is the 4th field of the stat file, the 2nd after the command name
+        let ppid = fields.get(1)?.parse().ok()?;
+        // Start time is the 22nd field of the stat file, the 20th after the command name
+        let starttime = fields.get(19)?.parse().ok()?;
+        Some(ProcessStat { ppid, starttime, comm: comm.to_string() })
+    }
+
+    /// Returns the start time of the process currently holding the given pid.
+    ///
+    /// # Returns
+    /// The start time in clock ticks since boot, or None if no process holds the pid
+    pub fn starttime(&self, pid: i32) -> Option<u64> {
+        self.stat(pid).map(|stat| stat.starttime)
+    }
+
+    /// Returns the target of an open file descriptor of a process.
+    ///
+    /// Targets are paths for files, and kernel object names (e.g., `socket:[1234]`) otherwise.
+    ///
+    /// # Returns
+    /// The target of the file descriptor, or None if it is not open
+    pub fn fd_target(&self, pid: i32, fd: i32) -> Option<PathBuf> {
+        read_link(format!("/proc/{pid}/fd/{fd}")).ok()
+    }
+
+    /// Returns the identity of the executable run by a process.
+    ///
+    /// # Returns
+    /// The device and inode numbers of the executable, or None if it cannot be resolved
+    pub fn exe_identity(&self, pid: i32) -> Option<(u64, u64)> {
+        metadata(format!("/proc/{pid}/exe")).ok().map(|exe| (exe.dev(), exe.ino()))
+    }
+
+    /// Returns the identity of the file or kernel object an open file descriptor refers to.
+    ///
+    /// # Returns
+    /// The device and inode numbers of the target, or None if the descriptor is not open
+    pub fn fd_identity(&self, pid: i32, fd: i32) -> Option<(u64, u64)> {
+        metadata(format!("/proc/{pid}/fd/{fd}")).ok().map(|target| (target.dev(), target.ino()))
+    }
+
+    /// Checks whether a process has exited.
+    ///
+    /// A process with an unknown start time (zero) is only considered exited once its pid
+    /// is not held by any process.
+    pub fn has_exited(&self, process: &Process) -> bool {
+        match self.starttime(process.pid) {
+            Some(starttime) => process.starttime != 0 && process.starttime != starttime,
+            None => true,
+        }
+    }
+}
+
+/// Status of a process read from `/proc/<pid>/stat`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessStat {
+    /// Process identifier of the parent process
+    pub ppid: i32,
+    /// Process start time in clock ticks since boot
+    pub starttime: u64,
+    /// Command name of the process, changed by `exec`
+    pub comm: String,
+}
+
+/// Cached metadata of a process, with the identity of the executable it was resolved for.
+#[derive(Debug, Clone)]
+struct CachedProcess {
+    process: Process,
+    /// Device and inode numbers of the executable
+    exe: (u64, u64),
+}
+
+/// Cache of the metadata of running processes.
+#[derive(Debug, Default)]
+pub struct ProcessRegistry {
+    /// Observer of the processes through `/proc`
+    watcher: ProcessWatcher,
+    /// Maps process identifiers to the metadata of the process holding them
+    processes: DashMap<i32, CachedProcess>,
+}
+
+impl ProcessRegistry {
+    /// Creates a new empty process registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the registry shared across the middleware.
+    pub fn global() -> &'static Self {
+        &PROCESS_REGISTRY
+    }
+
+    /// Returns the metadata of the process currently holding the given pid.
+    ///
+    /// The status and the identity of the executable are read on each call to detect pid reuse
+    /// and `exec`, even of a program with the same command name, while the executable path is
+    /// only resolved when a process is seen for the first time or executed a new program.
+    ///
+    /// # Returns
+    /// The process metadata, or None if no process holds the pid
+    pub fn get(&self, pid: i32) -> Option<Process> {
+        let Some(ProcessStat { starttime, .. }) = self.watcher.stat(pid) else {
+            self.invalidate(pid);
+            return None;
+        };
+        let exe = self.watcher.exe_identity(pid).unwrap_or_default();
+        if let Some(cached) = self.processes.get(&pid)
+            && cached.process.starttime == starttime
+            && cached.exe == exe
+        {
+            return Some(cached.process.clone());
+        }
+        let exe_path = read_link(format!("/proc/{pid}/exe"))
+            .map(|exe| exe.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let process = Process { pid, starttime, exe_path, session: None };
+        self.processes.insert(pid, CachedProcess { process: process.clone(), exe });
+        Some(process)
+    }
+
+    /// Returns the metadata of the parent of the process currently holding the given pid.
+    ///
+    /// # Returns
+    /// The parent process metadata, or None if the process or its parent are not found
+    pub fn parent(&self, pid: i32) -> Option<Process> {
+        let ppid = self.watcher.stat(pid)?.ppid;
+        if ppid > 0 { self.get(ppid) } else { None }
+    }
+
+    /// Returns the metadata of the ancestors of the process currently holding the given pid,
+    /// from its parent up to the root of the process tree.
+    pub fn ancestors(&self, pid: i32) -> impl Iterator<Item = Process> + '_ {
+        std::iter::successors(self.parent(pid), |parent| self.parent(parent.pid))
+    }
+
+    /// Checks whether a file descriptor of a process was inherited from one of its ancestors.
+    ///
+    /// The descriptor is considered inherited if it is open in both processes and refers
+    /// to the same file or kernel object, i.e. the same device and inode.
+    pub fn is_inherited_fd(&self, ancestor: i32, pid: i32, fd: i32) -> bool {
+        match (self.watcher.fd_identity(ancestor, fd), self.watcher.fd_identity(pid, fd)) {
+            (Some(ancestor_target), Some(target)) => ancestor_target == target,
+            _ => false,
+        }
+    }
+
+    /// Checks whether a file descriptor is open in a process.
+    pub fn is_open_fd(&self, pid: i32, fd: i32) -> bool {
+        self.watcher.fd_target(pid, fd).is_some()
+    }
+
+    /// Checks whether a process has exited, invalidating its entry if so.
+    ///
+    /// See `ProcessWatcher::has_exited` for the exit conditions.
+    pub fn has_exited(&self, process: &Process) -> bool {
+        let exited = self.watcher.has_exited(process);
+        if exited {
+            self.processes.remove_if(&process.pid, |_, cached| &cached.process == process);
+        }
+        exited
+    }
+
+    /// Removes the cached entry of a pid.
+    pub fn invalidate(&self, pid: i32) {
+        self.processes.remove(&pid);
+    }
+
+    /// Returns the number of cached processes.
+    pub fn len(&self) -> usize {
+        self.processes.len()
+    }
+
+    /// Returns true if no process is cached.
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traceability::infrastructure::naming::Resource;
+
+    #[test]
+    fn unit_process_registry_cache() {
+        let registry = ProcessRegistry::new();
+        let pid = std::process::id() as i32;
+
+        let process = registry.get(pid).unwrap();
+        assert_eq!(process.pid, pid);
+        assert_eq!(
+            process.exe_path,
+            std::env::current_exe().unwrap().to_string_lossy().to_string()
+        );
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.get(pid), Some(process.clone()));
+        assert_eq!(registry.len(), 1);
+
+        // Pid reuse is detected, the cached entry of the current process is kept
+        let previous = Process { starttime: process.starttime - 1, ..process.clone() };
+        assert!(registry.has_exited(&previous));
+        assert_eq!(registry.len(), 1);
+        assert!(!registry.has_exited(&process));
+
+        // Exited processes are invalidated
+        assert_eq!(registry.get(i32::MAX), None);
+        registry.invalidate(pid);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn unit_process_registry_exec() {
+        use std::io::Write;
+
+        let registry = ProcessRegistry::new();
+        // Two distinct executables with the same command name
+        let dir = std::env::current_exe()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .join(format!("registry_exec_{}", std::process::id()));
+        let programs = ["first", "second"].map(|name| dir.join(name).join("prog"));
+        for program in programs.iter() {
+            std::fs::create_dir_all(program.parent().unwrap()).unwrap();
+            std::fs::copy("/bin/sh", program).unwrap();
+        }
+        let mut child = std::process::Command::new(&programs[0])
+            .arg("-c")
+            .arg(format!("read _; exec {} -c 'sleep 10; true'", programs[1].display()))
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let child_pid = child.id() as i32;
+        let before = registry.get(child_pid).unwrap();
+        assert_eq!(before.exe_path, programs[0].to_string_lossy());
+
+        // The program executed with the same command name is detected
+        writeln!(child.stdin.as_mut().unwrap()).unwrap();
+        while read_link(format!("/proc/{child_pid}/exe")).unwrap() != programs[1] {
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        let after = registry.get(child_pid).unwrap();
+        assert_eq!(after.exe_path, programs[1].to_string_lossy());
+        assert_eq!(after.starttime, before.starttime);
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unit_process_registry_hierarchy() {
+        let registry = ProcessRegistry::new();
+        let pid = std::process::id() as i32;
+        let file = std::fs::File::open("/proc/self/stat").unwrap();
+        let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        let child_pid = child.id() as i32;
+
+        assert_eq!(registry.parent(child_pid), registry.get(pid));
+        assert_eq!(registry.watcher.stat(child_pid).unwrap().comm, "sleep");
+
+        // Standard streams are inherited, while files opened by std are closed on exec
+        assert!(registry.is_inherited_fd(pid, child_pid, 0));
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&file);
+        assert!(registry.is_open_fd(pid, fd));
+        assert!(!registry.is_inherited_fd(pid, child_pid, fd));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert_eq!(registry.parent(child_pid), None);
+    }
+
+    #[test]
+    fn unit_process_registry_ancestors() {
+        use std::io::{BufRead, BufReader};
+
+        let registry = ProcessRegistry::new();
+        let pid = std::process::id() as i32;
+        // The shell forks a grandchild, and prints its pid
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "sleep 10 & echo $!; wait"])
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line).unwrap();
+        let grandchild_pid: i32 = line.trim().parse().unwrap();
+
+        let ancestors: Vec<i32> = registry.ancestors(grandchild_pid).map(|p| p.pid).collect();
+        assert_eq!(ancestors[..2], [child.id() as i32, pid]);
+        assert_eq!(ancestors.last(), Some(&1));
+
+        // Descriptors shared across generations are matched by device and inode
+        assert!(registry.is_inherited_fd(pid, grandchild_pid, 2));
+        assert!(registry.is_inherited_fd(child.id() as i32, grandchild_pid, 1));
+        assert!(!registry.is_inherited_fd(pid, grandchild_pid, 1));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        let _ = std::process::Command::new("kill").arg(grandchild_pid.to_string()).status();
+    }
+
+    #[test]
+    fn unit_process_watcher_current_process() {
+        let watcher = ProcessWatcher::new();
+        let pid = std::process::id() as i32;
+        let Resource::Process(process) = Resource::new_process(pid) else {
+            panic!("Expected Resource::Process");
+        };
+
+        // Start times are consistent with the system process table, which has a resolution of
+        // one second
+        let mut system = sysinfo::System::new();
+        system.refresh_process(sysinfo::Pid::from(pid as usize));
+        assert_eq!(
+            watcher.starttime(pid).map(|ticks| sysinfo::System::boot_time()
+                + ticks / rustix::param::clock_ticks_per_second()),
+            system.process(sysinfo::Pid::from(pid as usize)).map(|p| p.start_time())
+        );
+        assert_eq!(watcher.starttime(pid), Some(process.starttime));
+        assert!(!watcher.has_exited(&process));
+    }
+
+    #[test]
+    fn unit_process_watcher_exited_process() {
+        let watcher = ProcessWatcher::new();
+        let pid = std::process::id() as i32;
+        let Resource::Process(mut process) = Resource::new_process(pid) else {
+            panic!("Expected Resource::Process");
+        };
+
+        // Pid reused by another process
+        process.starttime -= 1;
+        assert!(watcher.has_exited(&process));
+
+        // Pid not held by any process
+        let Resource::Process(process) = Resource::new_process_mock(i32::MAX) else {
+            panic!("Expected Resource::Process");
+        };
+        assert_eq!(watcher.starttime(i32::MAX), None);
+        assert!(watcher.has_exited(&process));
+    }
+}
@@ -8,11 +8,17 @@
 //! ## Validation Categories
 //!
 //! **Process Validation**: Verifies that process IDs refer to actual running processes
-//! by querying the system process table.
+//! by querying the process registry.
 //!
 //! **Stream Validation**: Ensures that socket addresses are well-formed and compatible
 //! (e.g., both IPv4 or both IPv6) for network stream operations.
 //!
+//! **Datagram Validation**: Ensures that the local socket of a datagram socket, and the peer
+//! socket of each datagram, are well-formed socket addresses.
+//!
+//! **Unix Socket Validation**: Ensures that the peer of a Unix socket is a running process
+//! of the node, whose end of the connection can be tracked.
+//!
 //! ## Integration
 //!
 //! The validator is designed to work with the P2M service through the embedded validation
@@ -21,7 +27,7 @@
 
 use std::net::SocketAddr;
 
-use sysinfo::{Pid, System};
+use crate::traceability::infrastructure::process::ProcessRegistry;
 
 /// Resource validator for P2M requests.
 ///
@@ -33,6 +39,8 @@ use sysinfo::{Pid, System};
 ///
 /// - **Process IDs**: Must correspond to currently running processes
 /// - **Socket Addresses**: Must be parseable and use compatible address families
+/// - **Datagram Sockets**: Must be parseable socket addresses
+/// - **Unix Socket Peers**: Must correspond to currently running processes
 /// - **File Descriptors**: Accepted without validation (OS handles validity)
 ///
 /// ## Usage
@@ -45,7 +53,7 @@ pub struct ResourceValidator;
 impl ResourceValidator {
     /// Validates that a process ID corresponds to a currently running process.
     ///
-    /// Queries the system process table to verify that the specified PID
+    /// Queries the process registry to verify that the specified PID
     /// is associated with an active process. This prevents operations on
     /// stale or invalid process identifiers.
     ///
@@ -55,9 +63,7 @@ impl ResourceValidator {
     /// # Returns
     /// `true` if the process exists and is accessible, `false` otherwise
     pub fn is_valid_process(&self, pid: i32) -> bool {
-        let mut system = System::new();
-        system.refresh_all();
-        system.process(Pid::from(pid as usize)).is_some()
+        ProcessRegistry::global().get(pid).is_some()
     }
 
     /// Validates that socket addresses are well-formed and compatible.
@@ -81,6 +87,34 @@ impl ResourceValidator {
             _ => false,
         }
     }
+
+    /// Validates that a socket address of a datagram socket is well-formed.
+    ///
+    /// Datagram sockets are enrolled with their local socket only, and the peer socket of
+    /// each datagram is validated along with its I/O request.
+    ///
+    /// # Arguments
+    /// * `socket` - Local or peer socket address string
+    ///
+    /// # Returns
+    /// `true` if the address is valid, `false` otherwise
+    pub fn is_valid_datagram(&self, socket: &str) -> bool {
+        socket.parse::<SocketAddr>().is_ok()
+    }
+
+    /// Validates that the peer of a Unix socket is a running process of the node.
+    ///
+    /// The peer process identifier is reported by the kernel, it is zero if the peer
+    /// lives in another pid namespace, whose processes cannot be tracked.
+    ///
+    /// # Arguments
+    /// * `peer_pid` - Process identifier of the peer
+    ///
+    /// # Returns
+    /// `true` if the peer process exists and is accessible, `false` otherwise
+    pub fn is_valid_unix_socket(&self, peer_pid: i32) -> bool {
+        peer_pid > 0 && self.is_valid_process(peer_pid)
+    }
 }
 
 #[cfg(test)]
@@ -91,7 +125,7 @@ mod tests {
         traceability::{
             api::{
                 p2m::P2mApiService,
-                types::{P2mRequest, P2mResponse},
+                types::{OpenMode, P2mRequest, P2mResponse},
             },
             services::{
                 compliance::ComplianceService, provenance::ProvenanceService,
@@ -113,7 +147,12 @@ mod tests {
 
         assert_eq!(
             p2m_service
-                .call(P2mRequest::LocalEnroll { pid: 1, fd: 1, path: "test".to_string() })
+                .call(P2mRequest::LocalEnroll {
+                    pid: 1,
+                    fd: 1,
+                    path: "test".to_string(),
+                    mode: OpenMode::default()
+                })
                 .await
                 .unwrap(),
             P2mResponse::Ack
@@ -139,7 +178,15 @@ mod tests {
         };
         assert_eq!(
             p2m_service
-                .call(P2mRequest::IoReport { pid: 1, fd: 1, grant_id: flow_id, result: true })
+                .call(P2mRequest::IoReport {
+                    pid: 1,
+                    fd: 1,
+                    grant_id: flow_id,
+                    output: true,
+                    result: true,
+                    bytes: 0,
+                    offset: None
+                })
                 .await
                 .unwrap(),
             P2mResponse::Ack
@@ -147,7 +194,12 @@ mod tests {
 
         assert_eq!(
             p2m_service
-                .call(P2mRequest::LocalEnroll { pid: 0, fd: 1, path: "test".to_string() }) // pid 0 is invalid
+                .call(P2mRequest::LocalEnroll {
+                    pid: 0,
+                    fd: 1,
+                    path: "test".to_string(),
+                    mode: OpenMode::default()
+                }) // pid 0 is invalid
                 .await
                 .unwrap_err()
                 .to_string(),
@@ -167,5 +219,58 @@ mod tests {
                 .to_string(),
             "Traceability error, invalid stream (local_socket: bad_socket, peer_socket: bad_socket)"
         );
+
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::UnixEnroll {
+                    pid: 1,
+                    fd: 2,
+                    path: "/run/test.sock".to_string(),
+                    peer_pid: 1
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::UnixEnroll {
+                    pid: 1,
+                    fd: 2,
+                    path: "/run/test.sock".to_string(),
+                    peer_pid: 0
+                })
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Traceability error, invalid unix socket (path: /run/test.sock, peer_pid: 0)"
+        );
+
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::DatagramEnroll {
+                    pid: 1,
+                    fd: 3,
+                    local_socket: "127.0.0.1:8125".to_string()
+                })
+                .await
+                .unwrap(),
+            P2mResponse::Ack
+        );
+
+        assert_eq!(
+            p2m_service
+                .call(P2mRequest::DatagramIoRequest {
+                    pid: 1,
+                    fd: 3,
+                    peer_socket: "bad_socket".to_string(),
+                    output: true
+                })
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Traceability error, invalid datagram socket (socket: bad_socket)"
+        );
     }
 }
@@ -37,9 +37,10 @@ pub type P2mApiDefaultStack<M> = api::p2m::P2mApiService<
 /// Standard O2M API service stack with default component configuration.
 ///
 /// Combines provenance and compliance services for administrative operations.
-/// Does not include sequencer as O2M operations are typically read-heavy
-/// and don't require flow coordination.
+/// The sequencer is only used to monitor the flow coordination, O2M operations
+/// do not reserve flows.
 pub type O2mApiDefaultStack<M> = api::o2m::O2mApiService<
+    services::sequencer::WaitingQueueService<services::sequencer::SequencerService>,
     services::provenance::ProvenanceService,
     services::compliance::ComplianceService,
     services::consent::ConsentService,
@@ -85,6 +86,45 @@ where
             services::sequencer::WaitingQueueService::new(inner, max_retries)
         }))
         .service(services::sequencer::SequencerService::default());
+    init_middleware_with_sequencer(
+        node_id,
+        sequencer,
+        consent_timeout,
+        m2m_client,
+        enable_resource_validation,
+    )
+}
+
+/// Initialize a complete middleware stack around a configured waiting queue.
+///
+/// Same as [`init_middleware`], with the queue policy, timeout and process priorities of the
+/// waiting queue set by the caller.
+///
+/// # Arguments
+/// * `node_id` - Unique identifier for this middleware node in the distributed system
+/// * `sequencer` - Waiting queue shared by the API services to reserve flows
+/// * `m2m_client` - Client service for M2M communication with remote middleware
+/// * `enable_resource_validation` - Whether to enable resource validation for P2M requests
+///
+/// # Returns
+/// A tuple containing (M2M service, P2M service, O2M service) ready for use
+pub fn init_middleware_with_sequencer<M>(
+    node_id: String,
+    sequencer: services::sequencer::WaitingQueueService<services::sequencer::SequencerService>,
+    consent_timeout: u64,
+    m2m_client: M,
+    enable_resource_validation: bool,
+) -> (M2mApiDefaultStack, P2mApiDefaultStack<M>, O2mApiDefaultStack<M>)
+where
+    M: tower::Service<
+            api::M2mRequest,
+            Response = api::M2mResponse,
+            Error = error::TraceabilityError,
+        > + Clone
+        + Send
+        + 'static,
+    M::Future: Send,
+{
     let provenance = services::provenance::ProvenanceService::new(node_id.clone());
     let consent = services::consent::ConsentService::new(consent_timeout);
     let compliance = services::compliance::ComplianceService::new(node_id, consent.clone());
@@ -93,7 +133,7 @@ where
         api::m2m::M2mApiService::new(sequencer.clone(), provenance.clone(), compliance.clone());
 
     let p2m_service: P2mApiDefaultStack<M> = api::p2m::P2mApiService::new(
-        sequencer,
+        sequencer.clone(),
         provenance.clone(),
         compliance.clone(),
         m2m_client.clone(),
@@ -101,7 +141,8 @@ where
     .with_resource_validation(enable_resource_validation);
 
     let o2m_service: O2mApiDefaultStack<M> =
-        api::o2m::O2mApiService::new(provenance, compliance, consent, m2m_client);
+        api::o2m::O2mApiService::new(sequencer, provenance, compliance, consent, m2m_client)
+            .with_pending_grants(p2m_service.pending_grants());
 
     (m2m_service, p2m_service, o2m_service)
 }
@@ -25,7 +25,10 @@ use std::{
     collections::{HashMap, HashSet},
     future::Future,
     pin::Pin,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
     task::Poll,
 };
 
@@ -106,6 +109,8 @@ impl From<bool> for DeletionPolicy {
 /// - **`integrity`**: Numeric trust level (0 = lowest, higher = more trusted)
 /// - **`deleted`**: Tracks deletion status through a multi-phase process
 /// - **`consent`**: Whether the resource owner consent is required for flows
+/// - **`declassification`**: Whether the taint of the resource may be dropped from the data
+///   derived from it
 ///
 /// # Policy Evaluation
 ///
@@ -126,6 +131,8 @@ pub struct Policy {
     deleted: DeletionPolicy,
     /// Whether the resource owner consent is required for flows
     consent: bool,
+    /// Whether the taint of the resource may be dropped from the data derived from it
+    declassification: bool,
 }
 
 impl Default for Policy {
@@ -135,6 +142,7 @@ impl Default for Policy {
             integrity: 0,
             deleted: DeletionPolicy::NotDeleted,
             consent: false,
+            declassification: false,
         }
     }
 }
@@ -144,6 +152,8 @@ impl Policy {
     ///
     /// # Arguments
     ///
+    /// The taint of the resource cannot be dropped, see [`Policy::with_declassification`].
+    ///
     /// * `confidentiality` - The confidentiality level for the resource
     /// * `integrity` - The integrity level (0 = lowest, higher = more trusted)
     /// * `deleted` - The deletion status
@@ -154,7 +164,7 @@ impl Policy {
         deleted: DeletionPolicy,
         consent: bool,
     ) -> Self {
-        Self { confidentiality, integrity, deleted, consent }
+        Self { confidentiality, integrity, deleted, consent, declassification: false }
     }
 
     /// Returns true if the resource contains confidential data.
@@ -195,6 +205,27 @@ impl Policy {
         self.consent
     }
 
+    /// Returns true if the taint of the resource may be dropped from the data derived from it.
+    ///
+    /// When declassification is false, the resource stays in the provenance of the data
+    /// derived from it, whatever the process that derived it declares.
+    pub fn allows_declassification(&self) -> bool {
+        self.declassification
+    }
+
+    /// Updates the declassification flag for this policy.
+    ///
+    /// Returns `PolicyUpdated` if the flag was successfully changed,
+    /// or `PolicyNotUpdated` if the resource is deleted and cannot be modified.
+    pub fn with_declassification(&mut self, declassification: bool) -> ComplianceResponse {
+        if !self.is_deleted() {
+            self.declassification = declassification;
+            ComplianceResponse::PolicyUpdated
+        } else {
+            ComplianceResponse::PolicyNotUpdated
+        }
+    }
+
     /// Updates the consent flag for this policy.
     ///
     /// Returns `PolicyUpdated` if the consent was successfully changed,
@@ -298,7 +329,8 @@ impl Policy {
 /// - `EvalPolicies` - Evaluate whether a flow is permitted
 /// - `GetPolicy` / `GetPolicies` - Retrieve existing policies
 /// - `SetPolicy` - Set complete policy for a resource
-/// - `SetConfidentiality` / `SetIntegrity` / `SetConsent` - Update specific policy fields
+/// - `SetConfidentiality` / `SetIntegrity` / `SetConsent` / `SetDeclassification` - Update
+///   specific policy fields
 /// - `SetDeleted` - Mark resources for deletion
 ///
 /// # Operating Modes
@@ -321,6 +353,8 @@ pub struct ComplianceService<C = ConsentService> {
     policies: Arc<DashMap<Resource, Policy>>,
     /// Consent service
     consent: C,
+    /// Number of policy updates applied so far
+    epoch: Arc<AtomicU64>,
 }
 
 impl Default for ComplianceService {
@@ -329,6 +363,7 @@ impl Default for ComplianceService {
             node_id: String::new(),
             policies: Arc::new(DashMap::new()),
             consent: ConsentService::default(),
+            epoch: Arc::new(AtomicU64::new(0)),
         }
     }
 }
@@ -345,7 +380,14 @@ impl ComplianceService<ConsentService> {
     ///
     /// A new compliance service.
     pub fn new(node_id: String, consent: ConsentService) -> Self {
-        Self { node_id, policies: Arc::new(DashMap::new()), consent }
+        Self { node_id, policies: Arc::new(DashMap::new()), consent, epoch: Default::default() }
+    }
+
+    /// Returns the policy epoch, which changes whenever a policy is updated or a consent
+    /// decision is set, so that the outcome of a past compliance evaluation can be reused as
+    /// long as the epoch is unchanged.
+    pub fn policy_epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire) + self.consent.epoch()
     }
 
     /// Evaluates whether a data flow is compliant with the given policies.
@@ -491,6 +533,14 @@ impl ComplianceService {
         }
     }
 
+    /// Advances the policy epoch if a policy was updated, and passes the response through
+    fn advance_epoch(&self, response: ComplianceResponse) -> ComplianceResponse {
+        if response == ComplianceResponse::PolicyUpdated {
+            self.epoch.fetch_add(1, Ordering::AcqRel);
+        }
+        response
+    }
+
     /// Retrieves the policy for a specific resource, inserts a default policy and returns it if not found
     ///
     /// # Arguments
@@ -508,12 +558,12 @@ impl ComplianceService {
     ///
     /// # Returns
     ///
-    /// A map from resources to their policies, excluding stream resources.
+    /// A map from resources to their policies, excluding network resources.
     fn get_policies(&self, resources: HashSet<Resource>) -> HashMap<Resource, Policy> {
         let mut policies_set = HashMap::new();
         for resource in resources {
-            // Get the policy from the local policies, streams have no policies
-            if !resource.is_stream() {
+            // Get the policy from the local policies, streams and datagrams have no policies
+            if !resource.is_network() {
                 policies_set.insert(resource.to_owned(), self.get_policy(&resource));
             }
         }
@@ -640,6 +690,34 @@ impl ComplianceService {
         response
     }
 
+    /// Sets the declassification flag for a specific resource.
+    ///
+    /// Creates a default policy if the resource doesn't exist.
+    /// Updates are rejected if the resource is deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `resource` - The resource to update
+    /// * `declassification` - Whether the taint of the resource may be dropped
+    fn set_declassification(
+        &self,
+        resource: Resource,
+        declassification: bool,
+    ) -> ComplianceResponse {
+        let mut response = ComplianceResponse::PolicyNotUpdated;
+        self.policies
+            .entry(resource)
+            .and_modify(|policy| {
+                response = policy.with_declassification(declassification);
+            })
+            .or_insert_with(|| {
+                let mut policy = Policy::default();
+                response = policy.with_declassification(declassification);
+                policy
+            });
+        response
+    }
+
     /// Sets the consent enforcement flag for a specific resource.
     ///
     /// Creates a default policy if the resource doesn't exist.
@@ -700,6 +778,9 @@ impl Service<ComplianceRequest> for ComplianceService<ConsentService> {
                     );
                     Ok(ComplianceResponse::Policies(this.get_localized_policies(resources)))
                 }
+                ComplianceRequest::GetPolicyEpoch => {
+                    Ok(ComplianceResponse::PolicyEpoch(this.policy_epoch()))
+                }
                 ComplianceRequest::SetPolicy { resource, policy } => {
                     info!(
                         node_id = %this.node_id,
@@ -707,7 +788,7 @@ impl Service<ComplianceRequest> for ComplianceService<ConsentService> {
                         policy = ?policy,
                         "[compliance] SetPolicy"
                     );
-                    Ok(this.set_policy(resource, policy))
+                    Ok(this.advance_epoch(this.set_policy(resource, policy)))
                 }
                 ComplianceRequest::SetConfidentiality { resource, confidentiality } => {
                     info!(
@@ -716,7 +797,7 @@ impl Service<ComplianceRequest> for ComplianceService<ConsentService> {
                         confidentiality = ?confidentiality,
                         "[compliance] SetConfidentiality"
                     );
-                    Ok(this.set_confidentiality(resource, confidentiality))
+                    Ok(this.advance_epoch(this.set_confidentiality(resource, confidentiality)))
                 }
                 ComplianceRequest::SetIntegrity { resource, integrity } => {
                     info!(
@@ -725,11 +806,11 @@ impl Service<ComplianceRequest> for ComplianceService<ConsentService> {
                         integrity = ?integrity,
                         "[compliance] SetIntegrity"
                     );
-                    Ok(this.set_integrity(resource, integrity))
+                    Ok(this.advance_epoch(this.set_integrity(resource, integrity)))
                 }
                 ComplianceRequest::SetDeleted(resource) => {
                     info!(node_id = %this.node_id, resource = %resource, "[compliance] SetDeleted");
-                    Ok(this.set_deleted(resource))
+                    Ok(this.advance_epoch(this.set_deleted(resource)))
                 }
                 ComplianceRequest::EnforceConsent { resource, consent } => {
                     info!(
@@ -738,7 +819,16 @@ impl Service<ComplianceRequest> for ComplianceService<ConsentService> {
                         consent = ?consent,
                         "[compliance] EnforceConsent"
                     );
-                    Ok(this.enforce_consent(resource, consent))
+                    Ok(this.advance_epoch(this.enforce_consent(resource, consent)))
+                }
+                ComplianceRequest::SetDeclassification { resource, declassification } => {
+                    info!(
+                        node_id = %this.node_id,
+                        resource = %resource,
+                        declassification = ?declassification,
+                        "[compliance] SetDeclassification"
+                    );
+                    Ok(this.advance_epoch(this.set_declassification(resource, declassification)))
                 }
             }
         })
@@ -748,7 +838,7 @@ impl Service<ComplianceRequest> for ComplianceService<ConsentService> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::traceability::infrastructure::naming::Resource;
+    use crate::traceability::{infrastructure::naming::Resource, services::consent::Destination};
 
     // Helper functions to reduce test code duplication
     fn create_public_policy(integrity: u32) -> Policy {
@@ -913,6 +1003,39 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn unit_compliance_policy_epoch() {
+        init_tracing();
+        let mut compliance = ComplianceService::default();
+        let file = Resource::new_file("/tmp/test".to_string());
+        let epoch = compliance.policy_epoch();
+
+        // Lookups do not change the epoch, updates do
+        compliance.call(ComplianceRequest::GetPolicy(file.clone())).await.unwrap();
+        assert_eq!(compliance.policy_epoch(), epoch);
+        compliance
+            .call(ComplianceRequest::SetIntegrity { resource: file.clone(), integrity: 3 })
+            .await
+            .unwrap();
+        let epoch = compliance.policy_epoch();
+        assert_eq!(
+            compliance.call(ComplianceRequest::GetPolicyEpoch).await.unwrap(),
+            ComplianceResponse::PolicyEpoch(epoch)
+        );
+
+        // So do consent decisions
+        compliance
+            .consent
+            .call(ConsentRequest::SetConsent {
+                source: file.clone(),
+                destination: Destination::Node("10.0.0.2".to_string()),
+                consent: true,
+            })
+            .await
+            .unwrap();
+        assert_ne!(compliance.policy_epoch(), epoch);
+    }
+
     #[test]
     fn unit_compliance_get_policies_empty() {
         init_tracing();
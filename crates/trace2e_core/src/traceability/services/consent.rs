@@ -1,5 +1,13 @@
 //! Consent service for managing consent for outgoing data flows of resources.
-use std::{future::Future, pin::Pin, sync::Arc, task::Poll};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::Poll,
+};
 
 use dashmap::{DashMap, Entry};
 use tokio::{sync::broadcast, time::Duration};
@@ -168,6 +176,8 @@ pub struct ConsentService {
     notifications_channels: Arc<DashMap<Resource, broadcast::Sender<Destination>>>,
     /// Consent decision channels
     decision_channels: Arc<DashMap<ConsentKey, broadcast::Sender<bool>>>,
+    /// Number of consent decisions set so far
+    epoch: Arc<AtomicU64>,
 }
 
 impl ConsentService {
@@ -180,9 +190,15 @@ impl ConsentService {
             states: Arc::new(DashMap::new()),
             notifications_channels: Arc::new(DashMap::new()),
             decision_channels: Arc::new(DashMap::new()),
+            epoch: Arc::new(AtomicU64::new(0)),
         }
     }
 
+    /// Returns the consent epoch, which changes whenever a consent decision is set.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
     /// Check for existing consent decisions in the hierarchy.
     /// Returns the most specific consent decision if found.
     ///
@@ -302,6 +318,7 @@ impl ConsentService {
         let key = ConsentKey(source, destination);
         // Insert the consent decision into the persistent state
         self.states.insert(key.clone(), consent);
+        self.epoch.fetch_add(1, Ordering::AcqRel);
 
         // Notify the exact key's channel
         // Subscribers are already listening to both resource and node channels via dual subscription
@@ -1,7 +1,33 @@
 //! Provenance service for tracking resource references across nodes.
 //!
 //! Provides async helpers to get/update provenance and a tower::Service implementation.
-use std::{collections::HashSet, pin::Pin, sync::Arc, task::Poll};
+//!
+//! Besides the lineage of each resource, the service records the volume of data transferred
+//! along each provenance edge (from a source to a destination), and keeps per-resource volume
+//! counters, so that a small probe can be told apart from a bulk transfer. Edges are indexed by
+//! destination, and by source, so that the volumes of a process are dropped along with its
+//! provenance once it exits.
+//!
+//! When the content of a resource is discarded (e.g., a file opened with truncation), its
+//! provenance is reset, and the discarded provenance is kept in a lineage event. At most
+//! [`MAX_LINEAGE_EVENTS`] events are kept per resource, the oldest events are evicted first.
+//!
+//! Each change of the provenance of a resource is stamped with a fresh value of a node-wide
+//! clock, its epoch, so that callers can tell whether the provenance of a set of resources
+//! changed since they last looked at it.
+//!
+//! The provenance of exited processes is archived under their `(pid, starttime)` identity. At
+//! most [`MAX_ARCHIVED_PROCESSES`] processes are archived, the oldest archives are evicted first.
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::Poll,
+    time::SystemTime,
+};
 
 use dashmap::DashMap;
 use tower::Service;
@@ -16,25 +42,124 @@ use crate::traceability::{
 };
 
 type ProvenanceMap = DashMap<Resource, HashSet<LocalizedResource>>;
+/// Maps the (pid, starttime) identity of exited processes to the epoch of their archival and
+/// their provenance
+type ArchiveMap = DashMap<(i32, u64), (u64, HashSet<LocalizedResource>)>;
+/// Maps destinations to the volume of data transferred along the edges leading to them, by
+/// source
+type EdgeVolumeMap = DashMap<Resource, HashMap<Resource, EdgeVolume>>;
+/// Maps sources to the destinations of the edges leaving them
+type EdgeSourceMap = DashMap<Resource, HashSet<Resource>>;
+/// Maps resources to their volume counters
+type VolumeMap = DashMap<Resource, ResourceVolume>;
+/// Maps resources to their lineage events, in chronological order
+type LineageEventMap = DashMap<Resource, Vec<LineageEvent>>;
+/// Maps resources to the epoch of the last change of their provenance
+type EpochMap = DashMap<Resource, u64>;
+
+/// Maximum number of exited processes whose provenance is archived
+pub const MAX_ARCHIVED_PROCESSES: usize = 4096;
+
+/// Maximum number of lineage events kept per resource
+pub const MAX_LINEAGE_EVENTS: usize = 64;
+
+/// Volume of data transferred along a provenance edge.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EdgeVolume {
+    /// Total number of bytes transferred
+    pub bytes: u64,
+    /// Number of transfers reported
+    pub transfers: u64,
+    /// Smallest region of the file covering the transferred regions, as (start, end) offsets
+    pub extent: Option<(u64, u64)>,
+}
+
+impl EdgeVolume {
+    fn record(&mut self, bytes: u64, offset: Option<u64>) {
+        self.bytes = self.bytes.saturating_add(bytes);
+        self.transfers = self.transfers.saturating_add(1);
+        if let Some(offset) = offset {
+            let end = offset.saturating_add(bytes);
+            self.extent = Some(match self.extent {
+                Some((start, stop)) => (start.min(offset), stop.max(end)),
+                None => (offset, end),
+            });
+        }
+    }
+}
+
+/// Volume counters of a resource.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResourceVolume {
+    /// Number of bytes written to the resource
+    pub bytes_in: u64,
+    /// Number of bytes read from the resource
+    pub bytes_out: u64,
+    /// Number of transfers to the resource
+    pub transfers_in: u64,
+    /// Number of transfers from the resource
+    pub transfers_out: u64,
+}
+
+/// Event in the lineage of a resource, other than a data flow.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineageEvent {
+    /// The content of the resource was discarded by a process, resetting its provenance.
+    Overwrite {
+        /// Process that discarded the content
+        process: Resource,
+        /// Time of the overwrite
+        timestamp: SystemTime,
+        /// Provenance of the resource before the overwrite
+        previous: HashSet<LocalizedResource>,
+    },
+}
 
 /// Provenance service for tracking resources provenance
 #[derive(Debug, Default, Clone)]
 pub struct ProvenanceService {
     node_id: String,
     provenance: Arc<ProvenanceMap>,
+    archive: Arc<ArchiveMap>,
+    edge_volumes: Arc<EdgeVolumeMap>,
+    edge_sources: Arc<EdgeSourceMap>,
+    volumes: Arc<VolumeMap>,
+    events: Arc<LineageEventMap>,
+    epochs: Arc<EpochMap>,
+    clock: Arc<AtomicU64>,
 }
 
 impl ProvenanceService {
     pub fn new(node_id: String) -> Self {
-        Self { node_id, provenance: Arc::new(DashMap::new()) }
+        Self { node_id, ..Default::default() }
     }
 
     pub fn set_references(&self, resource: Resource, references: HashSet<LocalizedResource>) {
+        self.touch(&resource);
         self.provenance.insert(resource, references);
     }
 
+    /// Stamps a change of the provenance of a resource with a fresh epoch
+    fn touch(&self, resource: &Resource) {
+        let epoch = self.clock.fetch_add(1, Ordering::AcqRel) + 1;
+        self.epochs.insert(resource.to_owned(), epoch);
+    }
+
+    /// Get the provenance epoch of a set of resources
+    ///
+    /// Epochs are unique and increasing, so the latest epoch of the resources changes whenever
+    /// any of them changes. Resources never changed are at epoch 0.
+    fn get_epoch(&self, resources: &HashSet<Resource>) -> u64 {
+        resources
+            .iter()
+            .filter_map(|resource| self.epochs.get(resource).map(|epoch| *epoch))
+            .max()
+            .unwrap_or_default()
+    }
+
     fn init_provenance(&self, resource: &Resource) -> HashSet<LocalizedResource> {
-        if !resource.is_stream() {
+        // Network channels and Unix sockets only carry the data written by their peers
+        if !resource.is_network() && !resource.is_unix_socket() {
             HashSet::from([LocalizedResource::new(self.node_id.clone(), resource.to_owned())])
         } else {
             HashSet::new()
@@ -83,12 +208,149 @@ impl ProvenanceService {
                 destination_prov = %DisplayableResource::from(&destination_prov),
                 "[provenance-raw] Provenance updated"
             );
+            self.touch(destination);
             self.provenance.insert(destination.to_owned(), destination_prov);
             ProvenanceResponse::ProvenanceUpdated
         }
     }
 }
 
+impl ProvenanceService {
+    /// Archive the provenance of an exited process
+    ///
+    /// The provenance is removed from the live records, and kept under the process identity,
+    /// while its volumes are dropped. The oldest archive is evicted once
+    /// [`MAX_ARCHIVED_PROCESSES`] processes are archived.
+    fn archive_process(&mut self, resource: &Resource) -> ProvenanceResponse {
+        if let Resource::Process(process) = resource
+            && let Some((_, prov)) = self.provenance.remove(resource)
+        {
+            self.touch(resource);
+            self.drop_volumes(resource);
+            let epoch = self.clock.load(Ordering::Acquire);
+            self.archive.insert((process.pid, process.starttime), (epoch, prov));
+            if self.archive.len() > MAX_ARCHIVED_PROCESSES
+                && let Some(oldest) = self
+                    .archive
+                    .iter()
+                    .min_by_key(|entry| entry.value().0)
+                    .map(|entry| *entry.key())
+            {
+                self.archive.remove(&oldest);
+            }
+            ProvenanceResponse::ProvenanceArchived
+        } else {
+            ProvenanceResponse::ProvenanceNotUpdated
+        }
+    }
+
+    /// Drop the provenance and the volumes of an activity that ended
+    fn drop_prov(&mut self, resource: &Resource) -> ProvenanceResponse {
+        if self.provenance.remove(resource).is_some() {
+            self.touch(resource);
+            self.drop_volumes(resource);
+            ProvenanceResponse::ProvenanceDropped
+        } else {
+            ProvenanceResponse::ProvenanceNotUpdated
+        }
+    }
+
+    /// Get the archived provenance of an exited process
+    fn get_archived_prov(&self, pid: i32, starttime: u64) -> HashSet<LocalizedResource> {
+        self.archive.get(&(pid, starttime)).map(|entry| entry.1.to_owned()).unwrap_or_default()
+    }
+
+    /// Reset the provenance of a resource whose content was discarded by a process
+    ///
+    /// The discarded provenance is recorded in an overwrite lineage event. The oldest event of
+    /// the resource is evicted once it has [`MAX_LINEAGE_EVENTS`] events.
+    fn reset(&mut self, resource: &Resource, process: Resource) -> ProvenanceResponse {
+        let previous = self
+            .provenance
+            .remove(resource)
+            .map(|(_, prov)| prov)
+            .unwrap_or_else(|| self.init_provenance(resource));
+        self.touch(resource);
+        let mut events = self.events.entry(resource.to_owned()).or_default();
+        let evicted = (events.len() + 1).saturating_sub(MAX_LINEAGE_EVENTS);
+        events.drain(..evicted);
+        events.push(LineageEvent::Overwrite { process, timestamp: SystemTime::now(), previous });
+        ProvenanceResponse::ProvenanceReset
+    }
+
+    /// Get the lineage events of a resource
+    fn get_events(&self, resource: &Resource) -> Vec<LineageEvent> {
+        self.events.get(resource).map(|events| events.to_owned()).unwrap_or_default()
+    }
+}
+
+impl ProvenanceService {
+    /// Record the volume of a data flow on its edge and on the counters of both resources
+    fn record_transfer(
+        &mut self,
+        source: Resource,
+        destination: Resource,
+        bytes: u64,
+        offset: Option<u64>,
+    ) -> ProvenanceResponse {
+        {
+            let mut volume = self.volumes.entry(source.clone()).or_default();
+            volume.bytes_out = volume.bytes_out.saturating_add(bytes);
+            volume.transfers_out = volume.transfers_out.saturating_add(1);
+        }
+        {
+            let mut volume = self.volumes.entry(destination.clone()).or_default();
+            volume.bytes_in = volume.bytes_in.saturating_add(bytes);
+            volume.transfers_in = volume.transfers_in.saturating_add(1);
+        }
+        self.edge_sources.entry(source.clone()).or_default().insert(destination.clone());
+        self.edge_volumes
+            .entry(destination)
+            .or_default()
+            .entry(source)
+            .or_default()
+            .record(bytes, offset);
+        ProvenanceResponse::TransferRecorded
+    }
+
+    /// Drop the volume counters of a resource and the edges leading to or leaving it
+    fn drop_volumes(&self, resource: &Resource) {
+        self.volumes.remove(resource);
+        if let Some((_, sources)) = self.edge_volumes.remove(resource) {
+            for source in sources.keys() {
+                if let Some(mut destinations) = self.edge_sources.get_mut(source) {
+                    destinations.remove(resource);
+                }
+                self.edge_sources.remove_if(source, |_, destinations| destinations.is_empty());
+            }
+        }
+        if let Some((_, destinations)) = self.edge_sources.remove(resource) {
+            for destination in &destinations {
+                if let Some(mut sources) = self.edge_volumes.get_mut(destination) {
+                    sources.remove(resource);
+                }
+                self.edge_volumes.remove_if(destination, |_, sources| sources.is_empty());
+            }
+        }
+    }
+
+    /// Get the volume counters of a set of resources, resources without transfers are zeroed
+    fn get_volumes(&self, resources: HashSet<Resource>) -> HashMap<Resource, ResourceVolume> {
+        resources
+            .into_iter()
+            .map(|resource| {
+                let volume = self.volumes.get(&resource).map(|v| v.to_owned()).unwrap_or_default();
+                (resource, volume)
+            })
+            .collect()
+    }
+
+    /// Get the volume of the edges leading to a destination, by source
+    fn get_edge_volumes(&self, destination: &Resource) -> HashMap<Resource, EdgeVolume> {
+        self.edge_volumes.get(destination).map(|sources| sources.to_owned()).unwrap_or_default()
+    }
+}
+
 impl NodeId for ProvenanceService {
     fn node_id(&self) -> String {
         self.node_id.to_owned()
@@ -130,6 +392,62 @@ impl Service<ProvenanceRequest> for ProvenanceService {
                     );
                     Ok(this.update_raw(source_prov, &destination))
                 }
+                ProvenanceRequest::ArchiveProcess(resource) => {
+                    info!(node_id = %this.node_id, resource = %resource, "[provenance] ArchiveProcess");
+                    Ok(this.archive_process(&resource))
+                }
+                ProvenanceRequest::DropProvenance(resource) => {
+                    info!(node_id = %this.node_id, resource = %resource, "[provenance] DropProvenance");
+                    Ok(this.drop_prov(&resource))
+                }
+                ProvenanceRequest::GetArchivedReferences { pid, starttime } => {
+                    info!(
+                        node_id = %this.node_id,
+                        pid = %pid,
+                        starttime = %starttime,
+                        "[provenance] GetArchivedReferences"
+                    );
+                    Ok(ProvenanceResponse::Provenance(this.get_archived_prov(pid, starttime)))
+                }
+                ProvenanceRequest::RecordTransfer { source, destination, bytes, offset } => {
+                    info!(
+                        node_id = %this.node_id,
+                        source = %source,
+                        destination = %destination,
+                        bytes = %bytes,
+                        offset = ?offset,
+                        "[provenance] RecordTransfer"
+                    );
+                    Ok(this.record_transfer(source, destination, bytes, offset))
+                }
+                ProvenanceRequest::GetVolumes(resources) => {
+                    info!(node_id = %this.node_id, "[provenance] GetVolumes");
+                    Ok(ProvenanceResponse::Volumes(this.get_volumes(resources)))
+                }
+                ProvenanceRequest::GetEdgeVolumes(destination) => {
+                    info!(
+                        node_id = %this.node_id,
+                        destination = %destination,
+                        "[provenance] GetEdgeVolumes"
+                    );
+                    Ok(ProvenanceResponse::EdgeVolumes(this.get_edge_volumes(&destination)))
+                }
+                ProvenanceRequest::ResetProvenance { resource, process } => {
+                    info!(
+                        node_id = %this.node_id,
+                        resource = %resource,
+                        process = %process,
+                        "[provenance] ResetProvenance"
+                    );
+                    Ok(this.reset(&resource, process))
+                }
+                ProvenanceRequest::GetLineageEvents(resource) => {
+                    info!(node_id = %this.node_id, resource = %resource, "[provenance] GetLineageEvents");
+                    Ok(ProvenanceResponse::LineageEvents(this.get_events(&resource)))
+                }
+                ProvenanceRequest::GetEpoch(resources) => {
+                    Ok(ProvenanceResponse::Epoch(this.get_epoch(&resources)))
+                }
             }
         })
     }
@@ -157,6 +475,26 @@ mod tests {
         assert_eq!(provenance.get_prov(process.resource()), HashSet::from([file, process]));
     }
 
+    #[test]
+    fn unit_provenance_epoch() {
+        crate::trace2e_tracing::init();
+        let mut provenance = ProvenanceService::default();
+        let process = Resource::new_process_mock(0);
+        let file = Resource::new_file("/tmp/test".to_string());
+        let resources = HashSet::from([process.clone(), file.clone()]);
+        assert_eq!(provenance.get_epoch(&resources), 0);
+
+        // Only actual changes of the provenance advance the epoch
+        provenance.update(&file, &process);
+        let epoch = provenance.get_epoch(&resources);
+        assert_ne!(epoch, 0);
+        assert_eq!(provenance.get_epoch(&HashSet::from([file.clone()])), 0);
+        provenance.update(&file, &process);
+        assert_eq!(provenance.get_epoch(&resources), epoch);
+        provenance.update(&process, &file);
+        assert!(provenance.get_epoch(&resources) > epoch);
+    }
+
     #[test]
     fn unit_provenance_update_circular() {
         crate::trace2e_tracing::init();
@@ -228,4 +566,210 @@ mod tests {
             ProvenanceResponse::Provenance(HashSet::from([file, process,]))
         );
     }
+
+    #[tokio::test]
+    async fn unit_provenance_service_archive_process() {
+        crate::trace2e_tracing::init();
+        let mut provenance = ProvenanceService::default();
+        let process = LocalizedResource::new(provenance.node_id(), Resource::new_process_mock(0));
+        let file = LocalizedResource::new(
+            provenance.node_id(),
+            Resource::new_file("/tmp/test".to_string()),
+        );
+
+        provenance.update(file.resource(), process.resource());
+        assert_eq!(
+            provenance
+                .call(ProvenanceRequest::ArchiveProcess(process.resource().clone()))
+                .await
+                .unwrap(),
+            ProvenanceResponse::ProvenanceArchived
+        );
+
+        // A process reusing the pid starts with a clean provenance
+        assert_eq!(provenance.get_prov(process.resource()), HashSet::from([process.clone()]));
+        assert_eq!(
+            provenance
+                .call(ProvenanceRequest::GetArchivedReferences { pid: 0, starttime: 0 })
+                .await
+                .unwrap(),
+            ProvenanceResponse::Provenance(HashSet::from([file.clone(), process.clone()]))
+        );
+
+        // Only processes with live provenance records are archived
+        assert_eq!(
+            provenance
+                .call(ProvenanceRequest::ArchiveProcess(process.resource().clone()))
+                .await
+                .unwrap(),
+            ProvenanceResponse::ProvenanceNotUpdated
+        );
+        assert_eq!(
+            provenance
+                .call(ProvenanceRequest::ArchiveProcess(file.resource().clone()))
+                .await
+                .unwrap(),
+            ProvenanceResponse::ProvenanceNotUpdated
+        );
+
+        // The oldest archives are evicted first
+        for pid in 1..=MAX_ARCHIVED_PROCESSES as i32 {
+            let exited = Resource::new_process_mock(pid);
+            provenance.update(file.resource(), &exited);
+            provenance.archive_process(&exited);
+        }
+        assert_eq!(provenance.archive.len(), MAX_ARCHIVED_PROCESSES);
+        assert!(provenance.get_archived_prov(0, 0).is_empty());
+        assert!(!provenance.get_archived_prov(1, 0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_provenance_service_record_transfer() {
+        crate::trace2e_tracing::init();
+        let mut provenance = ProvenanceService::default();
+        let process = Resource::new_process_mock(0);
+        let file = Resource::new_file("/tmp/test".to_string());
+        let other = Resource::new_file("/tmp/other".to_string());
+
+        for (bytes, offset) in [(100, Some(0)), (50, Some(200)), (10, None)] {
+            assert_eq!(
+                provenance
+                    .call(ProvenanceRequest::RecordTransfer {
+                        source: process.clone(),
+                        destination: file.clone(),
+                        bytes,
+                        offset,
+                    })
+                    .await
+                    .unwrap(),
+                ProvenanceResponse::TransferRecorded
+            );
+        }
+        provenance.record_transfer(file.clone(), process.clone(), 20, Some(10));
+
+        // Edges accumulate bytes and cover the written regions of the file
+        assert_eq!(
+            provenance.call(ProvenanceRequest::GetEdgeVolumes(file.clone())).await.unwrap(),
+            ProvenanceResponse::EdgeVolumes(HashMap::from([(
+                process.clone(),
+                EdgeVolume { bytes: 160, transfers: 3, extent: Some((0, 250)) }
+            )]))
+        );
+        assert_eq!(
+            provenance
+                .call(ProvenanceRequest::GetVolumes(HashSet::from([
+                    process.clone(),
+                    file.clone(),
+                    other.clone()
+                ])))
+                .await
+                .unwrap(),
+            ProvenanceResponse::Volumes(HashMap::from([
+                (
+                    process.clone(),
+                    ResourceVolume {
+                        bytes_in: 20,
+                        bytes_out: 160,
+                        transfers_in: 1,
+                        transfers_out: 3
+                    }
+                ),
+                (
+                    file.clone(),
+                    ResourceVolume {
+                        bytes_in: 160,
+                        bytes_out: 20,
+                        transfers_in: 3,
+                        transfers_out: 1
+                    }
+                ),
+                (other.clone(), ResourceVolume::default()),
+            ]))
+        );
+
+        // Reports of huge transfers saturate the counters instead of overflowing them
+        for _ in 0..2 {
+            provenance.record_transfer(process.clone(), other.clone(), u64::MAX, Some(u64::MAX));
+        }
+        assert_eq!(
+            provenance.get_edge_volumes(&other),
+            HashMap::from([(
+                process.clone(),
+                EdgeVolume { bytes: u64::MAX, transfers: 2, extent: Some((u64::MAX, u64::MAX)) }
+            )])
+        );
+        assert_eq!(
+            provenance.get_volumes(HashSet::from([other.clone()]))[&other].bytes_in,
+            u64::MAX
+        );
+
+        // The volumes of an exited process are dropped along with its provenance
+        provenance.update(&file, &process);
+        provenance.archive_process(&process);
+        assert_eq!(
+            provenance.get_volumes(HashSet::from([process.clone()]))[&process],
+            ResourceVolume::default()
+        );
+        assert!(provenance.get_edge_volumes(&file).is_empty());
+        assert!(provenance.get_edge_volumes(&other).is_empty());
+        assert!(provenance.get_edge_volumes(&process).is_empty());
+        assert!(provenance.edge_volumes.is_empty() && provenance.edge_sources.is_empty());
+        assert_eq!(provenance.get_volumes(HashSet::from([file.clone()]))[&file].bytes_in, 160);
+    }
+
+    #[tokio::test]
+    async fn unit_provenance_service_reset_provenance() {
+        crate::trace2e_tracing::init();
+        let mut provenance = ProvenanceService::default();
+        let process = LocalizedResource::new(provenance.node_id(), Resource::new_process_mock(0));
+        let secret = LocalizedResource::new(
+            provenance.node_id(),
+            Resource::new_file("/tmp/secret".to_string()),
+        );
+        let file = LocalizedResource::new(
+            provenance.node_id(),
+            Resource::new_file("/tmp/test".to_string()),
+        );
+
+        provenance.update(secret.resource(), file.resource());
+        assert_eq!(
+            provenance
+                .call(ProvenanceRequest::ResetProvenance {
+                    resource: file.resource().clone(),
+                    process: process.resource().clone(),
+                })
+                .await
+                .unwrap(),
+            ProvenanceResponse::ProvenanceReset
+        );
+
+        // The overwritten file no longer derives from the secret
+        assert_eq!(provenance.get_prov(file.resource()), HashSet::from([file.clone()]));
+        let ProvenanceResponse::LineageEvents(events) = provenance
+            .call(ProvenanceRequest::GetLineageEvents(file.resource().clone()))
+            .await
+            .unwrap()
+        else {
+            panic!("Expected ProvenanceResponse::LineageEvents");
+        };
+        assert!(matches!(
+            events.as_slice(),
+            [LineageEvent::Overwrite { process: p, previous, .. }]
+                if p == process.resource() && *previous == HashSet::from([secret, file.clone()])
+        ));
+
+        // Repeated overwrites keep the most recent events only
+        for pid in 1..=MAX_LINEAGE_EVENTS as i32 {
+            provenance.reset(file.resource(), Resource::new_process_mock(pid));
+        }
+        let events = provenance.get_events(file.resource());
+        assert_eq!(events.len(), MAX_LINEAGE_EVENTS);
+        assert!(matches!(
+            (events.first(), events.last()),
+            (Some(LineageEvent::Overwrite { process: first, .. }),
+                Some(LineageEvent::Overwrite { process: last, .. }))
+                if *first == Resource::new_process_mock(1)
+                    && *last == Resource::new_process_mock(MAX_LINEAGE_EVENTS as i32)
+        ));
+    }
 }
@@ -1,47 +1,341 @@
 //! Sequencer service for reserving resources to avoid conflicts while processing flows.
 //!
 //! Includes an optional waiting queue layer and a tower::Service implementation.
-
-use std::{collections::VecDeque, pin::Pin, sync::Arc, task::Poll};
+//!
+//! ## Deadlock Detection
+//!
+//! The waiting queue maintains a wait-for graph between the processes involved in blocked
+//! requests and the processes involved in the reservations blocking them. Whenever a request
+//! is parked, including the single wait of a request without retries, the graph is checked
+//! for a path from the blocking processes back to the waiting ones. If such a cycle exists,
+//! the request is aborted with `TraceabilityError::DeadlockDetected` instead of waiting, and a
+//! `DeadlockEvent` is broadcast to the subscribers of `SequencerRequest::WatchDeadlocks`.
+//!
+//! Processes are the unit of the graph, so that resources shared by independent flows do
+//! not create spurious cycles. Reservations taken on behalf of remote middleware carry no
+//! local process, hence cycles spanning several nodes are not detected and remain bounded
+//! by the maximum number of retries.
+//!
+//! ## Queue Policies
+//!
+//! When a resource is released, its waiting requests are woken up according to the
+//! `QueuePolicy` of the waiting queue: in arrival order, readers first, writers first, or
+//! by decreasing priority of the processes involved in the requests. Regardless of the
+//! number of retries, a waiting request gives up with `TraceabilityError::WaitingQueueTimeout`
+//! once its own deadline, set on arrival from the configured timeout, is reached.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque, hash_map::RandomState},
+    hash::BuildHasher,
+    pin::Pin,
+    sync::{Arc, Mutex, MutexGuard},
+    task::Poll,
+    time::Duration,
+};
 
 use dashmap::DashMap;
-use tokio::{join, sync::oneshot};
+use tokio::{
+    sync::{broadcast, oneshot},
+    time::Instant,
+};
 use tower::Service;
 
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use crate::traceability::{
     api::types::{SequencerRequest, SequencerResponse},
     error::TraceabilityError,
-    infrastructure::naming::Resource,
+    infrastructure::naming::{Process, Resource},
 };
 
+/// Reservations held on a single resource
+///
+/// A resource is either reserved as the destination of a single flow (writer), or as the
+/// source of any number of flows (readers). Flows joined to a destination share its
+/// reservation, which then has several writers. Both sides are indexed, so that the
+/// availability of a resource is checked without scanning the active flows.
+#[derive(Debug, Default)]
+struct ResourceReservations {
+    /// Sources of the flows writing to the resource
+    writers: HashSet<Resource>,
+    /// Destinations of the flows reading from the resource
+    readers: HashSet<Resource>,
+}
+
+impl ResourceReservations {
+    fn is_empty(&self) -> bool {
+        self.writers.is_empty() && self.readers.is_empty()
+    }
+}
+
+/// Number of shards of the reservation table
+const RESERVATION_SHARDS: usize = 64;
+
+/// Reservations of the resources of a shard
+type Shard = HashMap<Resource, ResourceReservations>;
+
+/// Reservation table sharded by resource
+///
+/// Each shard has its own lock, so that flows on unrelated resources do not contend. An
+/// operation locks the shards of the resources it involves in ascending order, which keeps
+/// it atomic without risking a deadlock between concurrent operations.
+#[derive(Debug)]
+struct ReservationTable {
+    hasher: RandomState,
+    shards: Box<[Mutex<Shard>]>,
+}
+
+impl Default for ReservationTable {
+    fn default() -> Self {
+        Self {
+            hasher: RandomState::new(),
+            shards: (0..RESERVATION_SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+}
+
+/// Shards locked for an operation on a few resources
+struct LockedShards<'a> {
+    table: &'a ReservationTable,
+    guards: Vec<(usize, MutexGuard<'a, Shard>)>,
+}
+
+impl LockedShards<'_> {
+    fn shard(&self, resource: &Resource) -> &Shard {
+        let index = self.table.shard_index(resource);
+        self.guards
+            .iter()
+            .find_map(|(i, guard)| (*i == index).then_some(&**guard))
+            .expect("shard of the resource is locked")
+    }
+
+    fn shard_mut(&mut self, resource: &Resource) -> &mut Shard {
+        let index = self.table.shard_index(resource);
+        self.guards
+            .iter_mut()
+            .find_map(|(i, guard)| (*i == index).then_some(&mut **guard))
+            .expect("shard of the resource is locked")
+    }
+
+    /// Resource can be read, i.e. it is not being written
+    fn is_readable(&self, resource: &Resource) -> bool {
+        self.shard(resource).get(resource).is_none_or(|r| r.writers.is_empty())
+    }
+
+    /// Resource can be written along with the flows already writing it, i.e. it is not being read
+    fn is_joinable(&self, resource: &Resource) -> bool {
+        self.shard(resource).get(resource).is_none_or(|r| r.readers.is_empty())
+    }
+
+    /// Resource can be written, i.e. it is neither being read nor written
+    fn is_writable(&self, resource: &Resource) -> bool {
+        self.shard(resource).get(resource).is_none_or(|r| r.is_empty())
+    }
+
+    fn insert(&mut self, source: Resource, destination: Resource) {
+        self.shard_mut(&source)
+            .entry(source.clone())
+            .or_default()
+            .readers
+            .insert(destination.clone());
+        self.shard_mut(&destination).entry(destination).or_default().writers.insert(source);
+    }
+
+    /// Remove the flow from the source to the destination
+    /// Returns whether the source and the destination are no longer reserved by any flow,
+    /// None if there is no such flow
+    fn remove(&mut self, source: &Resource, destination: &Resource) -> Option<(bool, bool)> {
+        let reservations = self.shard_mut(destination).get_mut(destination)?;
+        if !reservations.writers.remove(source) {
+            return None;
+        }
+        let destination_released = reservations.writers.is_empty();
+        let source_released = match self.shard_mut(source).get_mut(source) {
+            Some(reservations) => {
+                reservations.readers.remove(destination);
+                reservations.readers.is_empty()
+            }
+            None => true,
+        };
+        self.prune(destination);
+        self.prune(source);
+        Some((source_released, destination_released))
+    }
+
+    /// Drop the entry of a resource that is no longer reserved
+    fn prune(&mut self, resource: &Resource) {
+        let shard = self.shard_mut(resource);
+        if shard.get(resource).is_some_and(|r| r.is_empty()) {
+            shard.remove(resource);
+        }
+    }
+}
+
+impl ReservationTable {
+    fn shard_index(&self, resource: &Resource) -> usize {
+        self.hasher.hash_one(resource) as usize % self.shards.len()
+    }
+
+    /// Lock the shards of the given resources, in ascending order
+    fn lock(&self, resources: &[&Resource]) -> Result<LockedShards<'_>, TraceabilityError> {
+        let mut indexes: Vec<usize> =
+            resources.iter().map(|resource| self.shard_index(resource)).collect();
+        indexes.sort_unstable();
+        indexes.dedup();
+        let guards = indexes
+            .into_iter()
+            .map(|index| {
+                self.shards[index]
+                    .lock()
+                    .map(|guard| (index, guard))
+                    .map_err(|_| TraceabilityError::InternalTrace2eError)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(LockedShards { table: self, guards })
+    }
+
+    /// Lock the shards of a destination and of the sources of the flows writing to it
+    /// Returns the sources along with the locked shards
+    fn lock_writers(
+        &self,
+        destination: &Resource,
+    ) -> Result<(Vec<Resource>, LockedShards<'_>), TraceabilityError> {
+        loop {
+            let mut locked = self.lock(&[destination])?;
+            let sources: Vec<Resource> = locked
+                .shard(destination)
+                .get(destination)
+                .map(|r| r.writers.iter().cloned().collect())
+                .unwrap_or_default();
+            let destination_index = self.shard_index(destination);
+            let mut indexes: Vec<usize> =
+                sources.iter().map(|source| self.shard_index(source)).collect();
+            indexes.sort_unstable();
+            indexes.dedup();
+            indexes.retain(|index| *index != destination_index);
+            if indexes.iter().all(|index| *index > destination_index) {
+                // Locking in ascending order, the destination can be kept locked
+                for index in indexes {
+                    let guard = self.shards[index]
+                        .lock()
+                        .map_err(|_| TraceabilityError::InternalTrace2eError)?;
+                    locked.guards.push((index, guard));
+                }
+                return Ok((sources, locked));
+            }
+            drop(locked);
+            let resources: Vec<&Resource> =
+                sources.iter().chain(std::iter::once(destination)).collect();
+            let locked = self.lock(&resources)?;
+            // The flows may have changed while no shard was locked
+            let unchanged = locked.shard(destination).get(destination).is_some_and(|r| {
+                r.writers.len() == sources.len()
+                    && sources.iter().all(|source| r.writers.contains(source))
+            });
+            if unchanged {
+                return Ok((sources, locked));
+            }
+        }
+    }
+
+    /// Remove the flows writing to the destination
+    /// Returns the sources of the removed flows that are no longer read by other flows,
+    /// None if the destination is not written
+    fn remove_writers(
+        &self,
+        destination: &Resource,
+    ) -> Result<Option<Vec<Resource>>, TraceabilityError> {
+        let (sources, mut locked) = self.lock_writers(destination)?;
+        if sources.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            sources
+                .into_iter()
+                .filter(|source| {
+                    locked.remove(source, destination).is_some_and(|(released, _)| released)
+                })
+                .collect(),
+        ))
+    }
+
+    /// All the flows, as (source, destination) pairs
+    fn all_flows(&self) -> Result<Vec<(Resource, Resource)>, TraceabilityError> {
+        let mut flows = Vec::new();
+        for shard in self.shards.iter() {
+            let shard = shard.lock().map_err(|_| TraceabilityError::InternalTrace2eError)?;
+            flows.extend(shard.iter().flat_map(|(destination, reservations)| {
+                reservations
+                    .writers
+                    .iter()
+                    .map(|source| (source.to_owned(), destination.to_owned()))
+            }));
+        }
+        Ok(flows)
+    }
+
+    /// Flows involving the resource, as (source, destination) pairs
+    fn flows(&self, resource: &Resource) -> Result<Vec<(Resource, Resource)>, TraceabilityError> {
+        let locked = self.lock(&[resource])?;
+        let Some(reservations) = locked.shard(resource).get(resource) else {
+            return Ok(Vec::new());
+        };
+        Ok(reservations
+            .writers
+            .iter()
+            .map(|source| (source.to_owned(), resource.to_owned()))
+            .chain(
+                reservations
+                    .readers
+                    .iter()
+                    .map(|destination| (resource.to_owned(), destination.to_owned())),
+            )
+            .collect())
+    }
+
+    /// No resource is reserved
+    #[cfg(test)]
+    fn is_empty(&self) -> bool {
+        self.shards.iter().all(|shard| shard.lock().is_ok_and(|shard| shard.is_empty()))
+    }
+}
+
 /// Sequencer service for managing resources reservation
 ///
 /// This service does not offer flow collision handling, it is the responsibility of the caller.
 /// `WaitingQueueService` can be set up as a layer to handle collisions.
+///
+/// Checking and reserving both ends of a flow is done atomically under the locks of their
+/// shards of the reservation table, each operation costs a constant number of lookups
+/// whatever the number of active flows.
 #[derive(Clone, Default)]
 pub struct SequencerService {
-    flows: Arc<DashMap<Resource, Resource>>,
+    reservations: Arc<ReservationTable>,
 }
 
 impl SequencerService {
     /// Make a flow
+    /// Joined flows may write a destination already written by other joined flows
     /// Returns the availability state of the source and destination before the attempt
     async fn make_flow(
         &self,
         source: Resource,
         destination: Resource,
+        join: bool,
     ) -> Result<SequencerResponse, TraceabilityError> {
+        let mut reservations = self.reservations.lock(&[&source, &destination])?;
         // source is not already reserved by a writer
-        let source_available = !self.flows.contains_key(&source);
-        // destination is not already reserved by a reader or writer
-        let destination_available = !self.flows.contains_key(&destination)
-            && !self.flows.iter().any(|entry| entry.value() == &destination);
+        let source_available = reservations.is_readable(&source);
+        // destination is not already reserved by a reader, nor by a writer unless joining
+        let destination_available = if join {
+            reservations.is_joinable(&destination)
+        } else {
+            reservations.is_writable(&destination)
+        };
 
         // if both are available, create a flow
         if source_available && destination_available {
-            self.flows.insert(destination, source);
+            reservations.insert(source, destination);
             Ok(SequencerResponse::FlowReserved)
         } else if source_available {
             Err(TraceabilityError::UnavailableDestination(destination))
@@ -52,33 +346,47 @@ impl SequencerService {
         }
     }
 
-    /// Drop a flow
+    /// Get the reservations involving a resource
+    /// Returns the (source, destination) pairs of the matching flows
+    fn get_reservations(
+        &self,
+        resource: &Resource,
+    ) -> Result<Vec<(Resource, Resource)>, TraceabilityError> {
+        self.reservations.flows(resource)
+    }
+
+    /// Drop the flows writing to a destination
     /// Returns the SequencerResponse to the caller
     async fn drop_flow(
         &self,
         destination: &Resource,
     ) -> Result<SequencerResponse, TraceabilityError> {
-        if let Some((_, source)) = self.flows.remove(destination) {
-            if self.flows.iter().any(|entry| entry.value() == &source) {
-                // Partial release of the flow
-                // source is still reserved as reader, notify the waiting queue of the destination
-                Ok(SequencerResponse::FlowReleased {
-                    source: None,
-                    destination: Some(destination.to_owned()),
-                })
-            } else {
-                // Complete release of the flow
-                // both source and destination are available again, notify both waiting queues
-                Ok(SequencerResponse::FlowReleased {
-                    source: Some(source),
-                    destination: Some(destination.to_owned()),
-                })
-            }
-        } else {
+        match self.reservations.remove_writers(destination)? {
+            // Sources still reserved as readers by other flows are not released, only the
+            // waiting queues of the released resources are notified
+            Some(sources) => Ok(SequencerResponse::FlowReleased {
+                sources,
+                destination: Some(destination.to_owned()),
+            }),
             // Destination is not reserved, nothing to do
-            Ok(SequencerResponse::FlowReleased { source: None, destination: None })
+            None => Ok(SequencerResponse::FlowReleased { sources: vec![], destination: None }),
         }
     }
+
+    /// Drop the flow from a source to a destination, the flows joined to it are kept
+    /// Returns the SequencerResponse to the caller
+    async fn leave_flow(
+        &self,
+        source: &Resource,
+        destination: &Resource,
+    ) -> Result<SequencerResponse, TraceabilityError> {
+        let released = self.reservations.lock(&[source, destination])?.remove(source, destination);
+        let (source_released, destination_released) = released.unwrap_or_default();
+        Ok(SequencerResponse::FlowReleased {
+            sources: source_released.then(|| source.to_owned()).into_iter().collect(),
+            destination: destination_released.then(|| destination.to_owned()),
+        })
+    }
 }
 
 impl Service<SequencerRequest> for SequencerService {
@@ -96,25 +404,201 @@ impl Service<SequencerRequest> for SequencerService {
             match request {
                 SequencerRequest::ReserveFlow { source, destination } => {
                     info!(source = %source, destination = %destination, "[sequencer] ReserveFlow");
-                    this.make_flow(source, destination).await
+                    this.make_flow(source, destination, false).await
+                }
+                SequencerRequest::JoinFlow { source, destination } => {
+                    info!(source = %source, destination = %destination, "[sequencer] JoinFlow");
+                    this.make_flow(source, destination, true).await
                 }
                 SequencerRequest::ReleaseFlow { destination } => {
                     info!(destination = %destination, "[sequencer] ReleaseFlow");
                     this.drop_flow(&destination).await
                 }
+                SequencerRequest::LeaveFlow { source, destination } => {
+                    info!(source = %source, destination = %destination, "[sequencer] LeaveFlow");
+                    this.leave_flow(&source, &destination).await
+                }
+                SequencerRequest::GetReservations(resource) => {
+                    debug!(resource = %resource, "[sequencer] GetReservations");
+                    Ok(SequencerResponse::Reservations(this.get_reservations(&resource)?))
+                }
+                SequencerRequest::ListReservations => {
+                    debug!("[sequencer] ListReservations");
+                    Ok(SequencerResponse::Reservations(this.reservations.all_flows()?))
+                }
+                SequencerRequest::GetQueueDepths => {
+                    // Requests never wait without a waiting queue
+                    Ok(SequencerResponse::QueueDepths(HashMap::new()))
+                }
+                SequencerRequest::WatchDeadlocks => {
+                    // Requests never wait without a waiting queue, so no deadlock can occur
+                    let (_, rx) = broadcast::channel(1);
+                    Ok(SequencerResponse::DeadlockNotifications(rx))
+                }
             }
         })
     }
 }
 
+/// Deadlock detected in the waiting queue
+///
+/// Describes the request aborted to break a circular wait.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeadlockEvent {
+    /// Source of the aborted request
+    pub source: Resource,
+    /// Destination of the aborted request
+    pub destination: Resource,
+    /// Processes involved in the circular wait, starting and ending with the waiting process
+    pub cycle: Vec<Resource>,
+}
+
+/// Wait-for graph between processes
+///
+/// Edges are counted, as several blocked requests may induce the same dependency.
+#[derive(Debug, Default)]
+struct WaitForGraph {
+    edges: HashMap<Resource, HashMap<Resource, usize>>,
+}
+
+impl WaitForGraph {
+    /// Find a path between two processes
+    /// Returns the processes along the path, both ends included
+    fn find_path(&self, from: &Resource, to: &Resource) -> Option<Vec<Resource>> {
+        let mut visited = HashSet::new();
+        let mut stack = vec![vec![from.to_owned()]];
+        while let Some(path) = stack.pop() {
+            let last = path.last()?;
+            if last == to {
+                return Some(path);
+            }
+            if !visited.insert(last.to_owned()) {
+                continue;
+            }
+            for next in self.edges.get(last).into_iter().flat_map(|edges| edges.keys()) {
+                if !visited.contains(next) {
+                    let mut next_path = path.clone();
+                    next_path.push(next.to_owned());
+                    stack.push(next_path);
+                }
+            }
+        }
+        None
+    }
+
+    /// Add the edges from waiting processes to holding processes, unless they close a cycle
+    /// Returns the cycle otherwise, the graph is left unchanged in that case
+    fn try_add(&mut self, waiters: &[Resource], holders: &[Resource]) -> Result<(), Vec<Resource>> {
+        for waiter in waiters {
+            for holder in holders.iter().filter(|holder| *holder != waiter) {
+                if let Some(path) = self.find_path(holder, waiter) {
+                    let mut cycle = vec![waiter.to_owned()];
+                    cycle.extend(path);
+                    return Err(cycle);
+                }
+            }
+        }
+        for waiter in waiters {
+            for holder in holders.iter().filter(|holder| *holder != waiter) {
+                *self
+                    .edges
+                    .entry(waiter.to_owned())
+                    .or_default()
+                    .entry(holder.to_owned())
+                    .or_default() += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the edges previously added by `try_add`
+    fn remove(&mut self, waiters: &[Resource], holders: &[Resource]) {
+        for waiter in waiters {
+            let Some(edges) = self.edges.get_mut(waiter) else {
+                continue;
+            };
+            for holder in holders.iter().filter(|holder| *holder != waiter) {
+                if let Some(count) = edges.get_mut(holder) {
+                    *count -= 1;
+                    if *count == 0 {
+                        edges.remove(holder);
+                    }
+                }
+            }
+            if edges.is_empty() {
+                self.edges.remove(waiter);
+            }
+        }
+    }
+}
+
+/// Distinct processes among the given resources
+fn processes<'a>(resources: impl IntoIterator<Item = &'a Resource>) -> Vec<Resource> {
+    resources
+        .into_iter()
+        .filter(|resource| resource.is_process())
+        .cloned()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Wait registered in the wait-for graph, removed when dropped
+///
+/// This guarantees the cleanup of the graph if the waiting request is cancelled.
+struct WaitGuard {
+    graph: Arc<Mutex<WaitForGraph>>,
+    waiters: Vec<Resource>,
+    holders: Vec<Resource>,
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        if let Ok(mut graph) = self.graph.lock() {
+            graph.remove(&self.waiters, &self.holders);
+        }
+    }
+}
+
+/// Order in which the requests waiting on a released resource are woken up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QueuePolicy {
+    /// Wake up the first waiting request, in arrival order
+    #[default]
+    Fifo,
+    /// Wake up all the requests waiting to read the resource, or the first writer if none
+    ReaderPreferring,
+    /// Wake up the first request waiting to write the resource, or all the readers if none
+    WriterPreferring,
+    /// Wake up the request with the highest process priority, in arrival order among equals
+    Priority,
+}
+
+/// Request parked in the waiting queue of a resource
+#[derive(Debug)]
+struct Waiter {
+    tx: oneshot::Sender<()>,
+    /// Request waits to read the resource, otherwise to write it
+    reader: bool,
+    priority: u32,
+}
+
 /// Waiting queue service for managing resources reservation
 ///
 /// This service is used to handle flow collisions.
 #[derive(Clone)]
 pub struct WaitingQueueService<T> {
     inner: T,
-    waiting_queue: Arc<DashMap<Resource, VecDeque<oneshot::Sender<()>>>>,
+    waiting_queue: Arc<DashMap<Resource, VecDeque<Waiter>>>,
     max_retries: u32,
+    policy: QueuePolicy,
+    /// Maximum waiting time of a request, unbounded if None
+    timeout: Option<Duration>,
+    /// Priorities of the processes, keyed by pid and start time so that a process reusing
+    /// the pid of an exited one does not inherit its priority
+    priorities: Arc<DashMap<(i32, u64), u32>>,
+    wait_for: Arc<Mutex<WaitForGraph>>,
+    deadlocks: broadcast::Sender<DeadlockEvent>,
 }
 
 impl<T> WaitingQueueService<T> {
@@ -124,27 +608,230 @@ impl<T> WaitingQueueService<T> {
             waiting_queue: Arc::new(DashMap::new()),
             // If None, so the waiting queue is not used
             max_retries: max_retries.unwrap_or_default(),
+            policy: QueuePolicy::default(),
+            timeout: None,
+            priorities: Arc::new(DashMap::new()),
+            wait_for: Arc::new(Mutex::new(WaitForGraph::default())),
+            deadlocks: broadcast::channel(100).0,
+        }
+    }
+
+    /// Sets the order in which waiting requests are woken up
+    pub fn with_policy(mut self, policy: QueuePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets the maximum waiting time of each request, from its arrival in the service
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the priority of a process, used by the `QueuePolicy::Priority` policy
+    ///
+    /// Processes default to priority 0, higher values are woken up first. The priority applies
+    /// to the sessions of the process as well. Resources other than processes are ignored.
+    pub fn with_process_priority(self, process: Resource, priority: u32) -> Self {
+        if let Resource::Process(Process { pid, starttime, .. }) = process {
+            self.priorities.insert((pid, starttime), priority);
         }
+        self
     }
 
-    async fn join_waiting_queue(&self, resource: &Resource) -> oneshot::Receiver<()> {
+    /// Priority of a request, the highest among its processes
+    fn priority(&self, request: &SequencerRequest) -> u32 {
+        let (SequencerRequest::ReserveFlow { source, destination }
+        | SequencerRequest::JoinFlow { source, destination }) = request
+        else {
+            return 0;
+        };
+        [source, destination]
+            .into_iter()
+            .filter_map(|resource| match resource {
+                Resource::Process(Process { pid, starttime, .. }) => {
+                    self.priorities.get(&(*pid, *starttime)).map(|p| *p)
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    async fn join_waiting_queue(
+        &self,
+        resource: &Resource,
+        reader: bool,
+        priority: u32,
+    ) -> oneshot::Receiver<()> {
         let (tx, rx) = oneshot::channel();
+        let waiter = Waiter { tx, reader, priority };
         if let Some(mut queue) = self.waiting_queue.get_mut(resource) {
-            queue.push_back(tx);
+            queue.push_back(waiter);
         } else {
             let mut queue = VecDeque::new();
-            queue.push_back(tx);
+            queue.push_back(waiter);
             self.waiting_queue.insert(resource.to_owned(), queue);
         }
         rx
     }
 
-    async fn notify_waiting_queue(&self, resource: &Option<Resource>) {
-        if let Some(resource) = resource
-            && let Some(mut queue) = self.waiting_queue.get_mut(resource)
-            && let Some(tx) = queue.pop_front()
-        {
-            tx.send(()).unwrap();
+    /// Number of requests still waiting on each resource
+    fn queue_depths(&self) -> HashMap<Resource, usize> {
+        self.waiting_queue
+            .iter()
+            .map(|queue| {
+                (
+                    queue.key().to_owned(),
+                    queue.iter().filter(|waiter| !waiter.tx.is_closed()).count(),
+                )
+            })
+            .filter(|(_, depth)| *depth > 0)
+            .collect()
+    }
+
+    async fn notify_waiting_queue(&self, resource: &Resource) {
+        let Some(mut queue) = self.waiting_queue.get_mut(resource) else {
+            return;
+        };
+        // Requests that timed out or were cancelled are no longer waiting
+        queue.retain(|waiter| !waiter.tx.is_closed());
+
+        let has_readers = queue.iter().any(|waiter| waiter.reader);
+        let woken: Vec<Waiter> = match self.policy {
+            QueuePolicy::Fifo => queue.pop_front().into_iter().collect(),
+            QueuePolicy::ReaderPreferring if has_readers => {
+                let (readers, writers): (Vec<_>, Vec<_>) =
+                    queue.drain(..).partition(|waiter| waiter.reader);
+                queue.extend(writers);
+                readers
+            }
+            QueuePolicy::ReaderPreferring => queue.pop_front().into_iter().collect(),
+            QueuePolicy::WriterPreferring => match queue.iter().position(|waiter| !waiter.reader) {
+                Some(position) => queue.remove(position).into_iter().collect(),
+                None => queue.drain(..).collect(),
+            },
+            QueuePolicy::Priority => {
+                // Earliest request among the highest priority ones
+                let position = queue
+                    .iter()
+                    .enumerate()
+                    .fold(None::<(usize, u32)>, |best, (position, waiter)| match best {
+                        Some((_, priority)) if priority >= waiter.priority => best,
+                        _ => Some((position, waiter.priority)),
+                    })
+                    .map(|(position, _)| position);
+                position.and_then(|position| queue.remove(position)).into_iter().collect()
+            }
+        };
+        for waiter in woken {
+            // The request may have given up in the meantime
+            let _ = waiter.tx.send(());
+        }
+    }
+}
+
+/// Wait for a notification until the deadline of the request, if any
+async fn wait_until(
+    notified: impl Future,
+    deadline: Option<Instant>,
+) -> Result<(), TraceabilityError> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, notified)
+            .await
+            .map(|_| ())
+            .map_err(|_| TraceabilityError::WaitingQueueTimeout),
+        None => {
+            notified.await;
+            Ok(())
+        }
+    }
+}
+
+impl<T> WaitingQueueService<T>
+where
+    T: Service<SequencerRequest, Response = SequencerResponse, Error = TraceabilityError>,
+{
+    /// Parks a request in the waiting queues of the resources blocking it
+    ///
+    /// Every request is checked for a deadlock before it is parked, whatever the number of
+    /// retries left. The request is woken up once all its blocking resources are released,
+    /// its wait is removed from the wait-for graph when the returned guard is dropped.
+    ///
+    /// Returns `None` if the blocking resources are no longer reserved, so the request
+    /// can be retried without waiting.
+    async fn enqueue(
+        &mut self,
+        request: &SequencerRequest,
+        blocking: &[(&Resource, bool)],
+        priority: u32,
+    ) -> Result<Option<(WaitGuard, impl Future<Output = ()> + use<T>)>, TraceabilityError> {
+        let resources = blocking.iter().map(|(resource, _)| *resource).collect::<Vec<_>>();
+        let Some(wait) = self.register_wait(request, &resources).await? else {
+            return Ok(None);
+        };
+        let mut notifications = Vec::new();
+        for (resource, reader) in blocking {
+            notifications.push(self.join_waiting_queue(resource, *reader, priority).await);
+        }
+        Ok(Some((wait, async move {
+            for notification in notifications {
+                let _ = notification.await;
+            }
+        })))
+    }
+
+    /// Register the wait of a request on the reservations holding the blocking resources
+    ///
+    /// Returns `None` if the blocking resources are no longer reserved, so the request
+    /// can be retried without waiting.
+    async fn register_wait(
+        &mut self,
+        request: &SequencerRequest,
+        blocking: &[&Resource],
+    ) -> Result<Option<WaitGuard>, TraceabilityError> {
+        let (SequencerRequest::ReserveFlow { source, destination }
+        | SequencerRequest::JoinFlow { source, destination }) = request
+        else {
+            return Err(TraceabilityError::InternalTrace2eError);
+        };
+        let mut reservations = Vec::new();
+        for resource in blocking {
+            match self.inner.call(SequencerRequest::GetReservations((*resource).to_owned())).await?
+            {
+                SequencerResponse::Reservations(r) => reservations.extend(r),
+                _ => return Err(TraceabilityError::InternalTrace2eError),
+            }
+        }
+        if reservations.is_empty() {
+            return Ok(None);
+        }
+
+        let waiters = processes([source, destination]);
+        let holders = processes(reservations.iter().flat_map(|(s, d)| [s, d]));
+
+        let added = self
+            .wait_for
+            .lock()
+            .map_err(|_| TraceabilityError::InternalTrace2eError)?
+            .try_add(&waiters, &holders);
+        match added {
+            Ok(()) => Ok(Some(WaitGuard { graph: self.wait_for.clone(), waiters, holders })),
+            Err(cycle) => {
+                warn!(
+                    source = %source,
+                    destination = %destination,
+                    cycle = ?cycle,
+                    "[sequencer] deadlock detected, aborting request"
+                );
+                // No subscriber is not an error
+                let _ = self.deadlocks.send(DeadlockEvent {
+                    source: source.to_owned(),
+                    destination: destination.to_owned(),
+                    cycle,
+                });
+                Err(TraceabilityError::DeadlockDetected(source.to_owned(), destination.to_owned()))
+            }
         }
     }
 }
@@ -168,49 +855,74 @@ where
 
     fn call(&mut self, req: SequencerRequest) -> Self::Future {
         let mut inner = self.inner.clone();
-        let max_tries = self.max_retries + 1;
-        let this = self.clone();
+        let max_tries = self.max_retries.saturating_add(1);
+        let mut this = self.clone();
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
         Box::pin(async move {
+            match req {
+                SequencerRequest::WatchDeadlocks => {
+                    return Ok(SequencerResponse::DeadlockNotifications(
+                        this.deadlocks.subscribe(),
+                    ));
+                }
+                SequencerRequest::GetQueueDepths => {
+                    return Ok(SequencerResponse::QueueDepths(this.queue_depths()));
+                }
+                _ => {}
+            }
+            let priority = this.priority(&req);
             for _ in 0..max_tries {
                 match inner.call(req.clone()).await {
                     Ok(SequencerResponse::FlowReserved) => {
                         debug!("[sequencer] FlowReserved");
                         return Ok(SequencerResponse::FlowReserved);
                     }
-                    Ok(SequencerResponse::FlowReleased { source, destination }) => {
-                        join!(
-                            this.notify_waiting_queue(&source),
-                            this.notify_waiting_queue(&destination)
-                        );
+                    Ok(SequencerResponse::FlowReleased { sources, destination }) => {
+                        for resource in sources.iter().chain(&destination) {
+                            this.notify_waiting_queue(resource).await;
+                        }
                         debug!(
-                            source = ?source,
+                            sources = ?sources,
                             destination = ?destination,
                             "[sequencer] FlowReleased"
                         );
-                        return Ok(SequencerResponse::FlowReleased { source, destination });
+                        return Ok(SequencerResponse::FlowReleased { sources, destination });
                     }
+                    Ok(response) => return Ok(response),
                     Err(TraceabilityError::UnavailableSource(source)) => {
-                        let rx = this.join_waiting_queue(&source).await;
+                        let Some((_wait, notified)) =
+                            this.enqueue(&req, &[(&source, true)], priority).await?
+                        else {
+                            continue;
+                        };
                         debug!(source = %source, "[sequencer] waiting source");
-                        let _ = rx.await;
+                        wait_until(notified, deadline).await?;
                     }
                     Err(TraceabilityError::UnavailableDestination(destination)) => {
-                        let rx = this.join_waiting_queue(&destination).await;
+                        let Some((_wait, notified)) =
+                            this.enqueue(&req, &[(&destination, false)], priority).await?
+                        else {
+                            continue;
+                        };
                         debug!(destination = %destination, "[sequencer] waiting destination");
-                        let _ = rx.await;
+                        wait_until(notified, deadline).await?;
                     }
                     Err(TraceabilityError::UnavailableSourceAndDestination(
                         source,
                         destination,
                     )) => {
-                        let rx1 = this.join_waiting_queue(&source).await;
-                        let rx2 = this.join_waiting_queue(&destination).await;
+                        let Some((_wait, notified)) = this
+                            .enqueue(&req, &[(&source, true), (&destination, false)], priority)
+                            .await?
+                        else {
+                            continue;
+                        };
                         debug!(
                             source = %source,
                             destination = %destination,
                             "[sequencer] waiting"
                         );
-                        let (_, _) = join!(rx1, rx2);
+                        wait_until(notified, deadline).await?;
                     }
                     Err(e) => return Err(e),
                 }
@@ -235,23 +947,23 @@ mod tests {
         let process = Resource::new_process_mock(0);
         let file = Resource::new_file("/tmp/test".to_string());
         assert_eq!(
-            sequencer.make_flow(process.clone(), file.clone()).await,
+            sequencer.make_flow(process.clone(), file.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
         assert_eq!(
             sequencer.drop_flow(&file).await.unwrap(),
             SequencerResponse::FlowReleased {
-                source: Some(process.clone()),
+                sources: vec![process.clone()],
                 destination: Some(file.clone())
             }
         );
         assert_eq!(
-            sequencer.make_flow(process.clone(), file.clone()).await,
+            sequencer.make_flow(process.clone(), file.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
         assert_eq!(
             sequencer.drop_flow(&file).await.unwrap(),
-            SequencerResponse::FlowReleased { source: Some(process), destination: Some(file) }
+            SequencerResponse::FlowReleased { sources: vec![process], destination: Some(file) }
         );
     }
 
@@ -262,20 +974,20 @@ mod tests {
         let process = Resource::new_process_mock(0);
         let file = Resource::new_file("/tmp/test".to_string());
         assert_eq!(
-            sequencer.make_flow(process.clone(), file.clone()).await,
+            sequencer.make_flow(process.clone(), file.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
         assert_eq!(
             sequencer.drop_flow(&file).await.unwrap(),
             SequencerResponse::FlowReleased {
-                source: Some(process),
+                sources: vec![process],
                 destination: Some(file.clone())
             }
         );
         // Already dropped, source is still available so it return true again
         assert_eq!(
             sequencer.drop_flow(&file).await.unwrap(),
-            SequencerResponse::FlowReleased { source: None, destination: None }
+            SequencerResponse::FlowReleased { sources: vec![], destination: None }
         );
     }
 
@@ -289,37 +1001,37 @@ mod tests {
         let file3 = Resource::new_file("/tmp/test3".to_string());
         let file4 = Resource::new_file("/tmp/test4".to_string());
         assert_eq!(
-            sequencer.make_flow(process.clone(), file1.clone()).await,
+            sequencer.make_flow(process.clone(), file1.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
         assert_eq!(
-            sequencer.make_flow(process.clone(), file2.clone()).await,
+            sequencer.make_flow(process.clone(), file2.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
         assert_eq!(
-            sequencer.make_flow(process.clone(), file3.clone()).await,
+            sequencer.make_flow(process.clone(), file3.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
 
         // Must fail because process is already reserved 3 times as reader
         assert_eq!(
-            sequencer.make_flow(file4.clone(), process.clone()).await,
+            sequencer.make_flow(file4.clone(), process.clone(), false).await,
             Err(TraceabilityError::UnavailableDestination(process.clone()))
         );
 
         // Drop 2 reservations
         assert_eq!(
             sequencer.drop_flow(&file2).await.unwrap(),
-            SequencerResponse::FlowReleased { source: None, destination: Some(file2) }
+            SequencerResponse::FlowReleased { sources: vec![], destination: Some(file2) }
         );
         assert_eq!(
             sequencer.drop_flow(&file1).await.unwrap(),
-            SequencerResponse::FlowReleased { source: None, destination: Some(file1) }
+            SequencerResponse::FlowReleased { sources: vec![], destination: Some(file1) }
         );
 
         // Must fail because process is still reserved once as reader
         assert_eq!(
-            sequencer.make_flow(file4.clone(), process.clone()).await,
+            sequencer.make_flow(file4.clone(), process.clone(), false).await,
             Err(TraceabilityError::UnavailableDestination(process.clone()))
         );
 
@@ -327,13 +1039,98 @@ mod tests {
         assert_eq!(
             sequencer.drop_flow(&file3).await.unwrap(),
             SequencerResponse::FlowReleased {
-                source: Some(process.clone()),
+                sources: vec![process.clone()],
                 destination: Some(file3)
             }
         );
 
         // Must succeed because process is not reserved as reader
-        assert_eq!(sequencer.make_flow(file4, process).await, Ok(SequencerResponse::FlowReserved));
+        assert_eq!(
+            sequencer.make_flow(file4, process, false).await,
+            Ok(SequencerResponse::FlowReserved)
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_sequencer_impl_reservations() {
+        crate::trace2e_tracing::init();
+        let sequencer = SequencerService::default();
+        let process = Resource::new_process_mock(0);
+        let file1 = Resource::new_file("/tmp/test1".to_string());
+        let file2 = Resource::new_file("/tmp/test2".to_string());
+        sequencer.make_flow(file1.clone(), process.clone(), false).await.unwrap();
+        sequencer.make_flow(file1.clone(), file2.clone(), false).await.unwrap();
+
+        assert_eq!(
+            sequencer.get_reservations(&process).unwrap(),
+            vec![(file1.clone(), process.clone())]
+        );
+        let mut reservations = sequencer.get_reservations(&file1).unwrap();
+        reservations.sort_by_key(|(_, destination)| destination.to_string());
+        assert_eq!(
+            reservations,
+            vec![(file1.clone(), file2.clone()), (file1.clone(), process.clone())]
+        );
+
+        sequencer.drop_flow(&process).await.unwrap();
+        sequencer.drop_flow(&file2).await.unwrap();
+        assert!(sequencer.get_reservations(&file1).unwrap().is_empty());
+        // Released resources do not leave empty entries behind
+        assert!(sequencer.reservations.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_sequencer_impl_joined_flows() {
+        crate::trace2e_tracing::init();
+        let sequencer = SequencerService::default();
+        let process = Resource::new_process_mock(0);
+        let file1 = Resource::new_file("/tmp/test1".to_string());
+        let file2 = Resource::new_file("/tmp/test2".to_string());
+        let file3 = Resource::new_file("/tmp/test3".to_string());
+        sequencer.make_flow(file1.clone(), process.clone(), false).await.unwrap();
+        assert_eq!(
+            sequencer.make_flow(file2.clone(), process.clone(), false).await,
+            Err(TraceabilityError::UnavailableDestination(process.clone()))
+        );
+        assert_eq!(
+            sequencer.make_flow(file2.clone(), process.clone(), true).await,
+            Ok(SequencerResponse::FlowReserved)
+        );
+        // A read destination cannot be joined
+        assert_eq!(
+            sequencer.make_flow(process.clone(), file3.clone(), true).await,
+            Err(TraceabilityError::UnavailableSource(process.clone()))
+        );
+
+        // The destination stays reserved until its last joined flow is released
+        assert_eq!(
+            sequencer.leave_flow(&file1, &process).await.unwrap(),
+            SequencerResponse::FlowReleased { sources: vec![file1.clone()], destination: None }
+        );
+        assert_eq!(
+            sequencer.get_reservations(&process).unwrap(),
+            vec![(file2.clone(), process.clone())]
+        );
+        assert_eq!(
+            sequencer.leave_flow(&file2, &process).await.unwrap(),
+            SequencerResponse::FlowReleased {
+                sources: vec![file2.clone()],
+                destination: Some(process.clone())
+            }
+        );
+
+        // Releasing the destination releases all its joined flows
+        sequencer.make_flow(file1.clone(), process.clone(), false).await.unwrap();
+        sequencer.make_flow(file2.clone(), process.clone(), true).await.unwrap();
+        let SequencerResponse::FlowReleased { mut sources, destination } =
+            sequencer.drop_flow(&process).await.unwrap()
+        else {
+            panic!("Expected SequencerResponse::FlowReleased");
+        };
+        sources.sort_by_key(|source| source.to_string());
+        assert_eq!(sources, vec![file1, file2]);
+        assert_eq!(destination, Some(process));
+        assert!(sequencer.reservations.is_empty());
     }
 
     #[tokio::test]
@@ -346,32 +1143,32 @@ mod tests {
         let file2 = Resource::new_file("/tmp/test2".to_string());
 
         assert_eq!(
-            sequencer.make_flow(file1.clone(), process1.clone()).await,
+            sequencer.make_flow(file1.clone(), process1.clone(), false).await,
             Ok(SequencerResponse::FlowReserved)
         );
 
         // Fails because try get write of write lock
         // (this case may be released in the future, this flow already exists)
         assert_eq!(
-            sequencer.make_flow(file1.clone(), process1.clone()).await,
+            sequencer.make_flow(file1.clone(), process1.clone(), false).await,
             Err(TraceabilityError::UnavailableDestination(process1.clone()))
         );
 
         // Fails because try get write on read lock
         assert_eq!(
-            sequencer.make_flow(process2, file1.clone()).await,
+            sequencer.make_flow(process2, file1.clone(), false).await,
             Err(TraceabilityError::UnavailableDestination(file1.clone()))
         );
 
         // Fails because try get read on write lock
         assert_eq!(
-            sequencer.make_flow(process1.clone(), file2).await,
+            sequencer.make_flow(process1.clone(), file2, false).await,
             Err(TraceabilityError::UnavailableSource(process1.clone()))
         );
 
         // Fails because circular flow (get read on write lock & get write on read lock)
         assert_eq!(
-            sequencer.make_flow(process1.clone(), file1.clone()).await,
+            sequencer.make_flow(process1.clone(), file1.clone(), false).await,
             Err(TraceabilityError::UnavailableSourceAndDestination(process1, file1))
         );
     }
@@ -400,7 +1197,7 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: file.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: Some(process), destination: Some(file) }
+            SequencerResponse::FlowReleased { sources: vec![process], destination: Some(file) }
         );
     }
 
@@ -489,7 +1286,7 @@ mod tests {
                 .await
                 .unwrap(),
             SequencerResponse::FlowReleased {
-                source: Some(file1),
+                sources: vec![file1],
                 destination: Some(process.clone())
             }
         );
@@ -510,7 +1307,7 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: process.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: Some(file2), destination: Some(process) }
+            SequencerResponse::FlowReleased { sources: vec![file2], destination: Some(process) }
         );
     }
 
@@ -631,14 +1428,14 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: file2.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: None, destination: Some(file2) }
+            SequencerResponse::FlowReleased { sources: vec![], destination: Some(file2) }
         );
         assert_eq!(
             sequencer
                 .call(SequencerRequest::ReleaseFlow { destination: file3.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: None, destination: Some(file3) }
+            SequencerResponse::FlowReleased { sources: vec![], destination: Some(file3) }
         );
 
         assert_eq!(
@@ -646,7 +1443,7 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: file1.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: Some(process), destination: Some(file1) }
+            SequencerResponse::FlowReleased { sources: vec![process], destination: Some(file1) }
         );
     }
 
@@ -752,7 +1549,7 @@ mod tests {
                 .await
                 .unwrap(),
             SequencerResponse::FlowReleased {
-                source: Some(file1),
+                sources: vec![file1],
                 destination: Some(process.clone())
             }
         );
@@ -764,7 +1561,7 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: process.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: Some(file2), destination: Some(process) }
+            SequencerResponse::FlowReleased { sources: vec![file2], destination: Some(process) }
         );
     }
     #[tokio::test]
@@ -833,7 +1630,7 @@ mod tests {
                 .await
                 .unwrap(),
             SequencerResponse::FlowReleased {
-                source: None, // here None means that the source is still reserved as reader
+                sources: vec![], // here None means that the source is still reserved as reader
                 destination: Some(process1)
             }
         );
@@ -843,7 +1640,7 @@ mod tests {
                 .await
                 .unwrap(),
             SequencerResponse::FlowReleased {
-                source: None, // here None means that the source is still reserved as reader
+                sources: vec![], // here None means that the source is still reserved as reader
                 destination: Some(process2)
             }
         );
@@ -854,7 +1651,7 @@ mod tests {
                 .unwrap(),
             SequencerResponse::FlowReleased {
                 // now source is available again
-                source: Some(file.clone()),
+                sources: vec![file.clone()],
                 destination: Some(process3)
             }
         );
@@ -867,7 +1664,7 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: file.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: Some(process4), destination: Some(file) }
+            SequencerResponse::FlowReleased { sources: vec![process4], destination: Some(file) }
         );
     }
 
@@ -913,7 +1710,7 @@ mod tests {
                 .await
                 .unwrap(),
             SequencerResponse::FlowReleased {
-                source: Some(file1),
+                sources: vec![file1],
                 destination: Some(process.clone())
             }
         );
@@ -925,7 +1722,309 @@ mod tests {
                 .call(SequencerRequest::ReleaseFlow { destination: file2.clone() })
                 .await
                 .unwrap(),
-            SequencerResponse::FlowReleased { source: Some(process), destination: Some(file2) }
+            SequencerResponse::FlowReleased { sources: vec![process], destination: Some(file2) }
+        );
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_deadlock_detection() {
+        crate::trace2e_tracing::init();
+        let mut sequencer = WaitingQueueService::new(SequencerService::default(), Some(1));
+        let SequencerResponse::DeadlockNotifications(mut deadlocks) =
+            sequencer.call(SequencerRequest::WatchDeadlocks).await.unwrap()
+        else {
+            panic!("Expected SequencerResponse::DeadlockNotifications");
+        };
+
+        let process1 = Resource::new_process_mock(1);
+        let process2 = Resource::new_process_mock(2);
+        let file1 = Resource::new_file("/tmp/test1".to_string());
+        let file2 = Resource::new_file("/tmp/test2".to_string());
+
+        // process1 writes file1, process2 writes file2
+        for (source, destination) in [(&process1, &file1), (&process2, &file2)] {
+            assert_eq!(
+                sequencer
+                    .call(SequencerRequest::ReserveFlow {
+                        source: source.clone(),
+                        destination: destination.clone(),
+                    })
+                    .await
+                    .unwrap(),
+                SequencerResponse::FlowReserved
+            );
+        }
+
+        // process1 waits for process2 to release file2
+        let mut sequencer_clone = sequencer.clone();
+        let process1_clone = process1.clone();
+        let file2_clone = file2.clone();
+        let res = tokio::spawn(async move {
+            sequencer_clone
+                .call(SequencerRequest::ReserveFlow {
+                    source: process1_clone,
+                    destination: file2_clone,
+                })
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // process2 waiting for process1 to release file1 closes the cycle
+        assert_eq!(
+            sequencer
+                .call(SequencerRequest::ReserveFlow {
+                    source: process2.clone(),
+                    destination: file1.clone(),
+                })
+                .await
+                .unwrap_err(),
+            TraceabilityError::DeadlockDetected(process2.clone(), file1.clone())
+        );
+        assert_eq!(
+            deadlocks.recv().await.unwrap(),
+            DeadlockEvent {
+                source: process2.clone(),
+                destination: file1,
+                cycle: vec![process2.clone(), process1, process2]
+            }
         );
+
+        // The surviving request proceeds once file2 is released
+        sequencer.call(SequencerRequest::ReleaseFlow { destination: file2 }).await.unwrap();
+        assert_eq!(res.await.unwrap().unwrap(), SequencerResponse::FlowReserved);
+        assert!(sequencer.wait_for.lock().unwrap().edges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_deadlock_detection_without_retries() {
+        crate::trace2e_tracing::init();
+        // Default configuration of the middleware, requests are parked once without retries
+        let mut sequencer = WaitingQueueService::new(SequencerService::default(), None);
+
+        let process1 = Resource::new_process_mock(1);
+        let process2 = Resource::new_process_mock(2);
+        let file1 = Resource::new_file("/tmp/test1".to_string());
+        let file2 = Resource::new_file("/tmp/test2".to_string());
+
+        for (source, destination) in [(&process1, &file1), (&process2, &file2)] {
+            assert_eq!(
+                sequencer
+                    .call(SequencerRequest::ReserveFlow {
+                        source: source.clone(),
+                        destination: destination.clone(),
+                    })
+                    .await
+                    .unwrap(),
+                SequencerResponse::FlowReserved
+            );
+        }
+
+        let mut sequencer_clone = sequencer.clone();
+        let (process1_clone, file2_clone) = (process1.clone(), file2.clone());
+        let res = tokio::spawn(async move {
+            sequencer_clone
+                .call(SequencerRequest::ReserveFlow {
+                    source: process1_clone,
+                    destination: file2_clone,
+                })
+                .await
+        });
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        // The first wait closing the cycle is aborted, instead of hanging both processes
+        assert_eq!(
+            sequencer
+                .call(SequencerRequest::ReserveFlow {
+                    source: process2.clone(),
+                    destination: file1.clone(),
+                })
+                .await
+                .unwrap_err(),
+            TraceabilityError::DeadlockDetected(process2, file1)
+        );
+
+        // The parked request gives up once woken up, since it has no retry left
+        sequencer.call(SequencerRequest::ReleaseFlow { destination: file2 }).await.unwrap();
+        assert_eq!(
+            res.await.unwrap().unwrap_err(),
+            TraceabilityError::ReachedMaxRetriesWaitingQueue
+        );
+        assert!(sequencer.wait_for.lock().unwrap().edges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_deadlock_same_process() {
+        crate::trace2e_tracing::init();
+        let sequencer = WaitingQueueService::new(SequencerService::default(), Some(1));
+        let mut timeout_sequencer = ServiceBuilder::new()
+            .layer(TimeoutLayer::new(Duration::from_millis(10)))
+            .service(sequencer.clone());
+
+        let process = Resource::new_process_mock(0);
+        let file1 = Resource::new_file("/tmp/test1".to_string());
+        let file2 = Resource::new_file("/tmp/test2".to_string());
+
+        assert_eq!(
+            timeout_sequencer
+                .call(SequencerRequest::ReserveFlow {
+                    source: process.clone(),
+                    destination: file1.clone(),
+                })
+                .await
+                .unwrap(),
+            SequencerResponse::FlowReserved
+        );
+
+        // Waiting on its own reservation is not a deadlock, the request only times out
+        let error = timeout_sequencer
+            .call(SequencerRequest::ReserveFlow { source: file2, destination: process })
+            .await
+            .unwrap_err();
+        assert!(error.is::<tower::timeout::error::Elapsed>());
+
+        // The cancelled wait is removed from the wait-for graph
+        assert!(sequencer.wait_for.lock().unwrap().edges.is_empty());
+    }
+
+    /// Writer holds a file while a reader, a writer and another reader wait for it,
+    /// returns which of the waiting requests are woken up when the file is released
+    async fn woken_up_waiters(sequencer: WaitingQueueService<SequencerService>) -> [bool; 3] {
+        let file = Resource::new_file("/tmp/test".to_string());
+        let mut sequencer_clone = sequencer.clone();
+        sequencer_clone
+            .call(SequencerRequest::ReserveFlow {
+                source: Resource::new_process_mock(0),
+                destination: file.clone(),
+            })
+            .await
+            .unwrap();
+
+        let mut waiters = Vec::new();
+        for (pid, reader) in [(1, true), (2, false), (3, true)] {
+            let process = Resource::new_process_mock(pid);
+            let (source, destination) =
+                if reader { (file.clone(), process) } else { (process, file.clone()) };
+            let mut sequencer_clone = sequencer.clone();
+            waiters.push(tokio::spawn(async move {
+                sequencer_clone.call(SequencerRequest::ReserveFlow { source, destination }).await
+            }));
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+
+        sequencer_clone.call(SequencerRequest::ReleaseFlow { destination: file }).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let woken_up = waiters.iter().map(|waiter| waiter.is_finished()).collect::<Vec<_>>();
+        waiters.iter().for_each(|waiter| waiter.abort());
+        woken_up.try_into().unwrap()
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_policy_fifo() {
+        crate::trace2e_tracing::init();
+        let sequencer = WaitingQueueService::new(SequencerService::default(), Some(1))
+            .with_policy(QueuePolicy::Fifo);
+        assert_eq!(woken_up_waiters(sequencer).await, [true, false, false]);
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_policy_reader_preferring() {
+        crate::trace2e_tracing::init();
+        let sequencer = WaitingQueueService::new(SequencerService::default(), Some(1))
+            .with_policy(QueuePolicy::ReaderPreferring);
+        assert_eq!(woken_up_waiters(sequencer).await, [true, false, true]);
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_policy_writer_preferring() {
+        crate::trace2e_tracing::init();
+        let sequencer = WaitingQueueService::new(SequencerService::default(), Some(1))
+            .with_policy(QueuePolicy::WriterPreferring);
+        assert_eq!(woken_up_waiters(sequencer).await, [false, true, false]);
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_policy_priority() {
+        crate::trace2e_tracing::init();
+        let sequencer = WaitingQueueService::new(SequencerService::default(), Some(1))
+            .with_policy(QueuePolicy::Priority)
+            .with_process_priority(Resource::new_process_mock(2), 5)
+            .with_process_priority(Resource::new_process_mock(3), 10);
+
+        // A later process reusing the pid does not inherit the priority
+        let reused = Resource::Process(Process {
+            pid: 3,
+            starttime: 1,
+            exe_path: String::new(),
+            session: None,
+        });
+        let file = Resource::new_file("/tmp/test".to_string());
+        assert_eq!(
+            sequencer.priority(&SequencerRequest::ReserveFlow {
+                source: reused,
+                destination: file.clone()
+            }),
+            0
+        );
+        assert_eq!(woken_up_waiters(sequencer).await, [false, false, true]);
+    }
+
+    #[tokio::test]
+    async fn unit_waiting_queue_layer_timeout() {
+        crate::trace2e_tracing::init();
+        let mut sequencer = WaitingQueueService::new(SequencerService::default(), Some(u32::MAX))
+            .with_timeout(Some(Duration::from_millis(10)));
+
+        let process0 = Resource::new_process_mock(0);
+        let process1 = Resource::new_process_mock(1);
+        let file = Resource::new_file("/tmp/test".to_string());
+
+        assert_eq!(
+            sequencer
+                .call(SequencerRequest::ReserveFlow {
+                    source: process0.clone(),
+                    destination: file.clone(),
+                })
+                .await
+                .unwrap(),
+            SequencerResponse::FlowReserved
+        );
+        assert_eq!(
+            sequencer
+                .call(SequencerRequest::ReserveFlow {
+                    source: process1.clone(),
+                    destination: file.clone(),
+                })
+                .await
+                .unwrap_err(),
+            TraceabilityError::WaitingQueueTimeout
+        );
+
+        // The timed out request is skipped, the release does not fail
+        assert_eq!(
+            sequencer
+                .call(SequencerRequest::ReleaseFlow { destination: file.clone() })
+                .await
+                .unwrap(),
+            SequencerResponse::FlowReleased { sources: vec![process0], destination: Some(file) }
+        );
+    }
+
+    #[test]
+    fn unit_wait_for_graph_cycle() {
+        let process1 = [Resource::new_process_mock(1)];
+        let process2 = [Resource::new_process_mock(2)];
+        let process3 = [Resource::new_process_mock(3)];
+        let mut graph = WaitForGraph::default();
+
+        assert!(graph.try_add(&process1, &process2).is_ok());
+        assert!(graph.try_add(&process2, &process3).is_ok());
+        assert_eq!(
+            graph.try_add(&process3, &process1),
+            Err([process3.as_slice(), &process1, &process2, &process3].concat())
+        );
+
+        // Once process2 stops waiting, the cycle is broken
+        graph.remove(&process2, &process3);
+        assert!(graph.try_add(&process3, &process1).is_ok());
     }
 }
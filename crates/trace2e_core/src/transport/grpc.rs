@@ -19,6 +19,14 @@
 //! repeated connection overhead. Connections are established on-demand and
 //! reused for subsequent requests to the same remote endpoint.
 //!
+//! ## Local Endpoint and Caller Identity
+//!
+//! Processes reach their local middleware over a Unix domain socket (see `p2m_uds_incoming`
+//! and `connect`). On this endpoint, the process identifier declared in each P2M request is
+//! checked against the credentials of the peer reported by the kernel (`SO_PEERCRED`), so
+//! that a process cannot act on behalf of another one. P2M requests received over TCP carry
+//! no such guarantee.
+//!
 //! ## Service Operations
 //!
 //! ### Process-to-Middleware (P2M)
@@ -26,6 +34,7 @@
 //! - Remote process enrollment (network connections)
 //! - I/O request authorization
 //! - I/O operation reporting
+//! - All of the above over a long-lived stream per process, with pipelined acknowledgements
 //!
 //! ### Machine-to-Machine (M2M)
 //! - Destination compliance policy retrieval
@@ -49,21 +58,120 @@
 use std::{
     collections::{HashMap, HashSet},
     future::Future,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
     pin::Pin,
     sync::Arc,
     task::Poll,
+    time::SystemTime,
 };
 
 use dashmap::DashMap;
 use futures::future::try_join_all;
-use tokio_stream::{StreamExt, wrappers::BroadcastStream};
-use tonic::{Request, Response, Status, transport::Channel};
-use tower::Service;
+use hyper_util::rt::TokioIo;
+use prost::Message;
+use tokio::net::{UnixListener, UnixStream};
+use tokio_stream::{
+    StreamExt,
+    wrappers::{BroadcastStream, UnboundedReceiverStream, UnixListenerStream},
+};
+use tonic::{
+    Code, Request, Response, Status,
+    transport::{Channel, Endpoint, Uri, server::UdsConnectInfo},
+};
+use tower::{Service, service_fn};
 use tracing::info;
 
 /// Default port for gRPC communication between trace2e middleware instances.
 pub const DEFAULT_GRPC_PORT: u16 = 50051;
 
+/// Default path of the Unix domain socket serving local processes.
+pub const DEFAULT_P2M_SOCKET: &str = "/tmp/trace2e_p2m.sock";
+
+/// Default path of the Unix domain socket serving local operators.
+pub const DEFAULT_O2M_SOCKET: &str = "/tmp/trace2e_o2m.sock";
+
+/// URL scheme selecting a Unix domain socket endpoint (e.g., `unix:///tmp/trace2e_p2m.sock`).
+const UNIX_SCHEME: &str = "unix://";
+
+/// Binds the Unix domain socket serving local processes.
+///
+/// A stale socket file left by a previous instance is replaced. The socket is made
+/// accessible to every local user, since callers are authenticated by their credentials.
+/// It must not serve the O2M API, whose callers are not authenticated.
+pub fn p2m_uds_incoming(path: impl AsRef<Path>) -> std::io::Result<UnixListenerStream> {
+    uds_incoming(path.as_ref(), 0o666)
+}
+
+/// Binds the Unix domain socket serving local operators.
+///
+/// A stale socket file left by a previous instance is replaced. The socket is only accessible
+/// to the user running the middleware (root in production deployments), since the O2M API
+/// does not authenticate its callers.
+pub fn o2m_uds_incoming(path: impl AsRef<Path>) -> std::io::Result<UnixListenerStream> {
+    uds_incoming(path.as_ref(), 0o600)
+}
+
+fn uds_incoming(path: &Path, mode: u32) -> std::io::Result<UnixListenerStream> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(UnixListenerStream::new(listener))
+}
+
+/// Connects a gRPC channel to a middleware endpoint.
+///
+/// Supports `unix://` URLs for Unix domain sockets, and `http://` URLs for TCP.
+pub async fn connect(url: &str) -> Result<Channel, tonic::transport::Error> {
+    if let Some(path) = url.strip_prefix(UNIX_SCHEME) {
+        let path = PathBuf::from(path);
+        // The URI is ignored by the connector, but required by the endpoint
+        Endpoint::from_static("http://[::]:50051")
+            .connect_with_connector(service_fn(move |_: Uri| {
+                let path = path.clone();
+                async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(path).await?)) }
+            }))
+            .await
+    } else {
+        Endpoint::from_shared(url.to_string())?.connect().await
+    }
+}
+
+/// Checks the process identifier declared in a P2M request against the peer credentials.
+///
+/// Only requests received over a Unix domain socket carry peer credentials, other requests
+/// are accepted as is.
+#[allow(clippy::result_large_err)]
+fn check_peer_pid<T>(request: &Request<T>, pid: i32) -> Result<(), Status> {
+    check_pid(peer_pid(request), pid)
+}
+
+/// Returns the process identifier of the peer of a request received over a Unix domain
+/// socket, which may be unavailable, or None for requests received over TCP.
+fn peer_pid<T>(request: &Request<T>) -> Option<Option<i32>> {
+    request
+        .extensions()
+        .get::<UdsConnectInfo>()
+        .map(|connect_info| connect_info.peer_cred.and_then(|cred| cred.pid()))
+}
+
+/// Checks a declared process identifier against the peer process identifier, if any.
+#[allow(clippy::result_large_err)]
+fn check_pid(peer_pid: Option<Option<i32>>, pid: i32) -> Result<(), Status> {
+    let Some(peer_pid) = peer_pid else {
+        return Ok(());
+    };
+    match peer_pid {
+        Some(peer_pid) if peer_pid == pid => Ok(()),
+        Some(peer_pid) => Err(Status::permission_denied(format!(
+            "declared process {pid} does not match peer process {peer_pid}"
+        ))),
+        None => Err(Status::permission_denied("peer credentials unavailable")),
+    }
+}
+
 /// Protocol Buffer definitions and descriptor sets for the trace2e gRPC service.
 pub mod proto {
     tonic::include_proto!("trace2e");
@@ -80,23 +188,83 @@ pub mod proto {
 
 use crate::{
     traceability::{
-        api::types::{M2mRequest, M2mResponse, O2mRequest, O2mResponse, P2mRequest, P2mResponse},
+        api::{
+            p2m::PendingGrant,
+            types::{
+                M2mRequest, M2mResponse, O2mRequest, O2mResponse, OpenMode, P2mRequest, P2mResponse,
+            },
+        },
         error::TraceabilityError,
         infrastructure::naming::{
-            DisplayableResource, Fd, File, LocalizedResource, Process, Resource, Stream,
+            Datagram, DisplayableResource, Fd, File, LocalizedResource, Process, Resource, Stream,
+            UnixSocket,
         },
         services::{
             compliance::{ConfidentialityPolicy, Policy},
             consent::Destination,
+            provenance::{LineageEvent, ResourceVolume},
+            sequencer::DeadlockEvent,
         },
     },
     transport::eval_remote_ip,
 };
 
+/// Returns the gRPC status code matching the class of a traceability error.
+fn status_code(error: &TraceabilityError) -> Code {
+    match error {
+        TraceabilityError::InternalTrace2eError
+        | TraceabilityError::TransportFailedToEvaluateRemote => Code::Internal,
+        TraceabilityError::InvalidRequest
+        | TraceabilityError::InvalidStream(..)
+        | TraceabilityError::InvalidUnixSocket(..)
+        | TraceabilityError::InvalidDatagram(_)
+        | TraceabilityError::GrantProcessMismatch(..)
+        | TraceabilityError::GrantFdMismatch(..)
+        | TraceabilityError::GrantFlowMismatch(_)
+        | TraceabilityError::NotLocalResource
+        | TraceabilityError::InvalidResourceFormat(_) => Code::InvalidArgument,
+        TraceabilityError::UndeclaredResource(..)
+        | TraceabilityError::InvalidProcess(_)
+        | TraceabilityError::NotFoundFlow(_)
+        | TraceabilityError::DestinationPolicyNotFound => Code::NotFound,
+        TraceabilityError::ExpiredGrant(_)
+        | TraceabilityError::NoOpenSession(_)
+        | TraceabilityError::SessionBusy(_) => Code::FailedPrecondition,
+        TraceabilityError::SessionAlreadyOpen(_) => Code::AlreadyExists,
+        TraceabilityError::UnavailableDestination(_)
+        | TraceabilityError::UnavailableSource(_)
+        | TraceabilityError::UnavailableSourceAndDestination(..)
+        | TraceabilityError::DeadlockDetected(..) => Code::Aborted,
+        TraceabilityError::ReachedMaxRetriesWaitingQueue => Code::ResourceExhausted,
+        TraceabilityError::WaitingQueueTimeout | TraceabilityError::ConsentRequestTimeout => {
+            Code::DeadlineExceeded
+        }
+        TraceabilityError::DirectPolicyViolation => Code::PermissionDenied,
+        TraceabilityError::TransportFailedToContactRemote(_) => Code::Unavailable,
+    }
+}
+
 /// Converts traceability errors to gRPC Status codes for wire transmission.
+///
+/// The code reflects the class of the error, while the details carry the error itself as an
+/// encoded `ErrorDetails` message, decoded by `TraceabilityError::try_from(&Status)`.
 impl From<TraceabilityError> for Status {
     fn from(error: TraceabilityError) -> Self {
-        Status::internal(error.to_string())
+        let code = status_code(&error);
+        let message = error.to_string();
+        let details = proto::messages::ErrorDetails::from(error).encode_to_vec();
+        Status::with_details(code, message, details.into())
+    }
+}
+
+/// Decodes the traceability error carried in the details of a gRPC Status.
+///
+/// Fails if the status carries no such details, e.g. if it was not raised by the middleware.
+impl TryFrom<&Status> for TraceabilityError {
+    type Error = ();
+
+    fn try_from(status: &Status) -> Result<Self, Self::Error> {
+        proto::messages::ErrorDetails::decode(status.details()).map_err(|_| ())?.try_into()
     }
 }
 
@@ -242,6 +410,29 @@ impl Service<M2mRequest> for M2mGrpc {
                         response.policy.map(|policy| policy.into()).unwrap_or_default(),
                     ))
                 }
+                M2mRequest::GetDestinationPolicies(destinations) => {
+                    info!(
+                        destinations = %DisplayableResource::from(&destinations),
+                        "[gRPC-client] GetDestinationPolicies"
+                    );
+                    let remote_ip = eval_remote_ip(request)?;
+                    let mut client = this.get_client_or_connect(remote_ip.clone()).await?;
+
+                    // Create the protobuf request
+                    let proto_req = proto::messages::GetDestinationPolicies {
+                        destinations: destinations.into_iter().map(|d| d.into()).collect(),
+                    };
+
+                    // Make the gRPC call
+                    let response = client
+                        .m2m_destination_policies(Request::new(proto_req))
+                        .await
+                        .map_err(|_| TraceabilityError::TransportFailedToContactRemote(remote_ip))?
+                        .into_inner();
+                    Ok(M2mResponse::DestinationPolicies(
+                        response.policies.into_iter().map(|policy| policy.into()).collect(),
+                    ))
+                }
                 M2mRequest::CheckSourceCompliance { sources, destination } => {
                     info!(
                         sources = %DisplayableResource::from(&sources),
@@ -330,6 +521,25 @@ impl Service<M2mRequest> for M2mGrpc {
 
                     Ok(M2mResponse::Ack)
                 }
+                M2mRequest::ReleaseFlow(destination) => {
+                    info!(
+                        destination = %destination,
+                        "[gRPC-client] ReleaseFlow"
+                    );
+                    let remote_ip = eval_remote_ip(request)?;
+                    let mut client = this.get_client_or_connect(remote_ip.clone()).await?;
+
+                    // Create the protobuf request
+                    let proto_req =
+                        proto::messages::ReleaseFlow { destination: Some(destination.into()) };
+
+                    // Make the gRPC call
+                    client.m2m_release_flow(Request::new(proto_req)).await.map_err(|_| {
+                        TraceabilityError::TransportFailedToContactRemote(remote_ip)
+                    })?;
+
+                    Ok(M2mResponse::Ack)
+                }
                 M2mRequest::BroadcastDeletion(resource) => {
                     info!(
                         resource = %resource,
@@ -411,16 +621,10 @@ where
         &self,
         request: Request<proto::messages::LocalCt>,
     ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
         let req = request.into_inner();
         let mut p2m = self.p2m.clone();
-        match p2m
-            .call(P2mRequest::LocalEnroll {
-                pid: req.process_id,
-                fd: req.file_descriptor,
-                path: req.path,
-            })
-            .await?
-        {
+        match p2m.call(req.into()).await? {
             P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
             _ => Err(Status::internal("Internal traceability API error")),
         }
@@ -434,17 +638,44 @@ where
         &self,
         request: Request<proto::messages::RemoteCt>,
     ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
         let req = request.into_inner();
         let mut p2m = self.p2m.clone();
-        match p2m
-            .call(P2mRequest::RemoteEnroll {
-                pid: req.process_id,
-                fd: req.file_descriptor,
-                local_socket: req.local_socket,
-                peer_socket: req.peer_socket,
-            })
-            .await?
-        {
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles Unix socket enrollment requests.
+    ///
+    /// Registers an end of a Unix domain socket connection with the middleware for tracking.
+    /// This is called when a process connects to or accepts a Unix socket.
+    async fn p2m_unix_enroll(
+        &self,
+        request: Request<proto::messages::UnixCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles datagram socket enrollment requests.
+    ///
+    /// Registers a datagram socket with the middleware for tracking, along with its local
+    /// socket only. This is called when a process binds a datagram socket.
+    async fn p2m_datagram_enroll(
+        &self,
+        request: Request<proto::messages::DatagramCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
             P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
             _ => Err(Status::internal("Internal traceability API error")),
         }
@@ -458,16 +689,29 @@ where
         &self,
         request: Request<proto::messages::IoInfo>,
     ) -> Result<Response<proto::messages::Grant>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
         let req = request.into_inner();
         let mut p2m = self.p2m.clone();
-        match p2m
-            .call(P2mRequest::IoRequest {
-                pid: req.process_id,
-                fd: req.file_descriptor,
-                output: req.flow == proto::primitives::Flow::Output as i32,
-            })
-            .await?
-        {
+        match p2m.call(req.into()).await? {
+            P2mResponse::Grant(id) => {
+                Ok(Response::new(proto::messages::Grant { id: id.to_string() }))
+            }
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles datagram I/O authorization requests from processes.
+    ///
+    /// Evaluates whether a process is authorized to send a datagram to, or receive a datagram
+    /// from, a peer socket. Returns a grant ID if authorized.
+    async fn p2m_datagram_io_request(
+        &self,
+        request: Request<proto::messages::DatagramIoInfo>,
+    ) -> Result<Response<proto::messages::Grant>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
             P2mResponse::Grant(id) => {
                 Ok(Response::new(proto::messages::Grant { id: id.to_string() }))
             }
@@ -475,6 +719,23 @@ where
         }
     }
 
+    /// Handles batched I/O operation requests from processes.
+    ///
+    /// Evaluates several operations of a process at once, and returns the outcome of each
+    /// operation in request order.
+    async fn p2m_io_request_batch(
+        &self,
+        request: Request<proto::messages::IoBatch>,
+    ) -> Result<Response<proto::messages::Grants>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Grants(results) => Ok(Response::new(results.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
     /// Handles I/O operation completion reports from processes.
     ///
     /// Records the completion and result of an I/O operation that was
@@ -483,21 +744,227 @@ where
         &self,
         request: Request<proto::messages::IoResult>,
     ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
         let req = request.into_inner();
         let mut p2m = self.p2m.clone();
-        match p2m
-            .call(P2mRequest::IoReport {
-                pid: req.process_id,
-                fd: req.file_descriptor,
-                grant_id: req.grant_id.parse::<u128>().unwrap_or_default(),
-                result: req.result,
-            })
-            .await?
-        {
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles file truncation notifications from processes.
+    ///
+    /// Resets the provenance of the truncated file.
+    async fn p2m_truncate(
+        &self,
+        request: Request<proto::messages::TruncateCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles flow declarations from processes.
+    ///
+    /// Restricts the sources of the writes on a file descriptor to the declared descriptors.
+    async fn p2m_declare_flow(
+        &self,
+        request: Request<proto::messages::DeclareFlowCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles the end of flow declarations from processes.
+    ///
+    /// The writes on the file descriptor derive from the whole process again.
+    async fn p2m_clear_flow(
+        &self,
+        request: Request<proto::messages::ClearFlowCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles session openings from processes.
+    ///
+    /// Tracks the following operations of the process as those of a distinct session.
+    async fn p2m_open_session(
+        &self,
+        request: Request<proto::messages::OpenSessionCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles session endings from processes.
+    ///
+    /// Merges or drops the taint of the session according to the session policy.
+    async fn p2m_end_session(
+        &self,
+        request: Request<proto::messages::EndSessionCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles file descriptor close notifications from processes.
+    ///
+    /// Unenrolls the file descriptor, so that its number can be reused safely.
+    async fn p2m_close(
+        &self,
+        request: Request<proto::messages::CloseCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
+            P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles file descriptor duplication notifications from processes.
+    ///
+    /// Enrolls the new file descriptor with the resources of the original one.
+    async fn p2m_dup(
+        &self,
+        request: Request<proto::messages::DupCt>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        check_peer_pid(&request, request.get_ref().process_id)?;
+        let req = request.into_inner();
+        let mut p2m = self.p2m.clone();
+        match p2m.call(req.into()).await? {
             P2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
             _ => Err(Status::internal("Internal traceability API error")),
         }
     }
+
+    type P2MStreamStream = Pin<
+        Box<
+            dyn tokio_stream::Stream<Item = Result<proto::messages::P2mStreamResponse, Status>>
+                + Send,
+        >,
+    >;
+
+    /// Handles the long-lived operation stream of a process.
+    ///
+    /// Requests are handled in the order they are received, so that a process may send its
+    /// reports without waiting for their acknowledgement. I/O requests, which may wait for the
+    /// resources to be available, are handled concurrently with the following requests. A
+    /// flush is acknowledged once all the previous requests, including the concurrent ones,
+    /// are handled. Each reply carries the sequence number of its request.
+    async fn p2m_stream(
+        &self,
+        request: Request<tonic::Streaming<proto::messages::P2mStreamRequest>>,
+    ) -> Result<Response<Self::P2MStreamStream>, Status> {
+        use proto::messages::p2m_stream_request::Request as StreamRequest;
+
+        let peer_pid = peer_pid(&request);
+        let mut inbound = request.into_inner();
+        let (replies, outbound) = tokio::sync::mpsc::unbounded_channel();
+        let p2m = self.p2m.clone();
+        tokio::spawn(async move {
+            // The requests handled concurrently hold a sender of the current generation, so that
+            // a flush waits until all the senders of its generation are dropped
+            let (mut in_flight, mut handled) = tokio::sync::mpsc::channel::<()>(1);
+            while let Ok(Some(message)) = inbound.message().await {
+                let sequence = message.sequence;
+                #[allow(clippy::result_large_err)]
+                let reply = move |result: Result<P2mResponse, Status>| {
+                    Ok(proto::messages::P2mStreamResponse {
+                        sequence,
+                        response: Some(result.into()),
+                    })
+                };
+                let request: P2mRequest = match message.request {
+                    Some(StreamRequest::LocalEnroll(req)) => req.into(),
+                    Some(StreamRequest::RemoteEnroll(req)) => req.into(),
+                    Some(StreamRequest::UnixEnroll(req)) => req.into(),
+                    Some(StreamRequest::DatagramEnroll(req)) => req.into(),
+                    Some(StreamRequest::IoRequest(req)) => req.into(),
+                    Some(StreamRequest::DatagramIoRequest(req)) => req.into(),
+                    Some(StreamRequest::IoRequestBatch(req)) => req.into(),
+                    Some(StreamRequest::IoReport(req)) => req.into(),
+                    Some(StreamRequest::Truncate(req)) => req.into(),
+                    Some(StreamRequest::DeclareFlow(req)) => req.into(),
+                    Some(StreamRequest::ClearFlow(req)) => req.into(),
+                    Some(StreamRequest::OpenSession(req)) => req.into(),
+                    Some(StreamRequest::EndSession(req)) => req.into(),
+                    Some(StreamRequest::Close(req)) => req.into(),
+                    Some(StreamRequest::Dup(req)) => req.into(),
+                    // Acknowledged once all the previous requests are handled, without
+                    // blocking the following ones, which may release what the previous ones
+                    // wait for
+                    Some(StreamRequest::Flush(_)) => {
+                        let (sender, receiver) = tokio::sync::mpsc::channel(1);
+                        let previous = std::mem::replace(&mut in_flight, sender);
+                        let mut previous_handled = std::mem::replace(&mut handled, receiver);
+                        drop(previous);
+                        // The next flush waits for this one, hence for the previous requests
+                        let in_flight = in_flight.clone();
+                        let replies = replies.clone();
+                        tokio::spawn(async move {
+                            let _ = previous_handled.recv().await;
+                            let _ = replies.send(reply(Ok(P2mResponse::Ack)));
+                            drop(in_flight);
+                        });
+                        continue;
+                    }
+                    None => {
+                        let _ = replies.send(reply(Err(Status::invalid_argument("empty request"))));
+                        continue;
+                    }
+                };
+                if let Err(status) = check_pid(peer_pid, request.pid()) {
+                    let _ = replies.send(reply(Err(status)));
+                    continue;
+                }
+                let mut p2m = p2m.clone();
+                if matches!(
+                    request,
+                    P2mRequest::IoRequest { .. }
+                        | P2mRequest::DatagramIoRequest { .. }
+                        | P2mRequest::IoRequestBatch { .. }
+                ) {
+                    let replies = replies.clone();
+                    let in_flight = in_flight.clone();
+                    tokio::spawn(async move {
+                        let _ = replies.send(reply(p2m.call(request).await.map_err(Status::from)));
+                        drop(in_flight);
+                    });
+                } else {
+                    let _ = replies.send(reply(p2m.call(request).await.map_err(Status::from)));
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(UnboundedReceiverStream::new(outbound))))
+    }
 }
 pub struct M2mHandler<M2mApi> {
     /// Machine-to-machine service handler.
@@ -542,6 +1009,22 @@ where
         }
     }
 
+    /// Handles batched destination policy requests from remote middleware.
+    ///
+    /// Returns the compliance policies of the destination resources that could be reserved.
+    async fn m2m_destination_policies(
+        &self,
+        request: Request<proto::messages::GetDestinationPolicies>,
+    ) -> Result<Response<proto::messages::DestinationPolicies>, Status> {
+        info!("[gRPC-server] m2m_destination_policies");
+        let req = request.into_inner();
+        let mut m2m = self.m2m.clone();
+        match m2m.call(req.into()).await? {
+            M2mResponse::DestinationPolicies(policies) => Ok(Response::new(policies.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
     /// Handles source compliance checking requests from remote middleware.
     ///
     /// Checks compliance policies for a set of source resources to enable
@@ -575,6 +1058,23 @@ where
         }
     }
 
+    /// Handles flow release requests from remote middleware.
+    ///
+    /// Releases the reservation of a destination resource taken by a destination policy
+    /// query, for a flow that was not performed.
+    async fn m2m_release_flow(
+        &self,
+        request: Request<proto::messages::ReleaseFlow>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        info!("[gRPC-server] m2m_release_flow");
+        let req = request.into_inner();
+        let mut m2m = self.m2m.clone();
+        match m2m.call(req.into()).await? {
+            M2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
     /// Handles deletion broadcast requests from remote middleware.
     ///
     /// Broadcasts the deletion of a resource to all middleware instances.
@@ -692,6 +1192,21 @@ where
         }
     }
 
+    /// Handles declassification setting requests from operators.
+    ///
+    /// Allows or forbids dropping the taint of a specific resource.
+    async fn o2m_set_declassification(
+        &self,
+        request: Request<proto::messages::SetDeclassificationRequest>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
     /// Handles deletion marking requests from operators.
     ///
     /// Marks a resource as deleted for compliance tracking purposes.
@@ -782,25 +1297,143 @@ where
             _ => Err(Status::internal("Internal traceability API error")),
         }
     }
-}
 
-// ========== Protocol Buffer Type Conversions ==========
-// Fundamental type conversions for serialization/deserialization
-
-/// Converts Protocol Buffer Resource to internal Resource type.
-impl From<proto::primitives::Resource> for Resource {
-    fn from(req: proto::primitives::Resource) -> Self {
-        match req.resource {
-            Some(proto::primitives::resource::Resource::Fd(fd)) => Resource::Fd(fd.into()),
-            Some(proto::primitives::resource::Resource::Process(process)) => {
-                Resource::Process(process.into())
-            }
-            None => Resource::None,
-        }
-    }
-}
+    type O2MWatchDeadlocksStream = Pin<
+        Box<
+            dyn tokio_stream::Stream<Item = Result<proto::messages::DeadlockNotification, Status>>
+                + Send,
+        >,
+    >;
 
-/// Converts internal Resource to Protocol Buffer Resource type.
+    /// Handles reservation listing requests from operators.
+    ///
+    /// Returns the flow reservations currently held by the sequencer.
+    async fn o2m_list_reservations(
+        &self,
+        request: Request<proto::messages::ListReservationsRequest>,
+    ) -> Result<Response<proto::messages::ListReservationsResponse>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::Reservations(reservations) => Ok(Response::new(reservations.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles pending grants listing requests from operators.
+    ///
+    /// Returns the grants issued to processes and not reported yet.
+    async fn o2m_list_pending_grants(
+        &self,
+        request: Request<proto::messages::ListPendingGrantsRequest>,
+    ) -> Result<Response<proto::messages::ListPendingGrantsResponse>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::PendingGrants(grants) => Ok(Response::new(grants.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles waiting queue inspection requests from operators.
+    ///
+    /// Returns the number of requests waiting on each resource.
+    async fn o2m_get_queue_depths(
+        &self,
+        request: Request<proto::messages::GetQueueDepthsRequest>,
+    ) -> Result<Response<proto::messages::GetQueueDepthsResponse>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::QueueDepths(depths) => Ok(Response::new(depths.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles volume counters requests from operators.
+    ///
+    /// Returns the volume of data read from and written to each requested resource.
+    async fn o2m_get_volumes(
+        &self,
+        request: Request<proto::messages::GetVolumesRequest>,
+    ) -> Result<Response<proto::messages::GetVolumesResponse>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::Volumes(volumes) => Ok(Response::new(volumes.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles lineage events requests from operators.
+    ///
+    /// Returns the overwrites of a resource, with the provenance they discarded.
+    async fn o2m_get_lineage_events(
+        &self,
+        request: Request<proto::messages::GetLineageEventsRequest>,
+    ) -> Result<Response<proto::messages::GetLineageEventsResponse>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::LineageEvents(events) => Ok(Response::new(events.into())),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles forced release requests from operators.
+    ///
+    /// Releases the reservation held on a destination and revokes its pending grants.
+    async fn o2m_force_release(
+        &self,
+        request: Request<proto::messages::ForceReleaseRequest>,
+    ) -> Result<Response<proto::messages::Ack>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::Ack => Ok(Response::new(proto::messages::Ack {})),
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+
+    /// Handles deadlock monitoring requests from operators.
+    ///
+    /// Returns a stream of the deadlocks detected by the sequencer waiting queue.
+    async fn o2m_watch_deadlocks(
+        &self,
+        request: Request<proto::messages::WatchDeadlocksRequest>,
+    ) -> Result<Response<Self::O2MWatchDeadlocksStream>, Status> {
+        let req = request.into_inner();
+        let mut o2m = self.o2m.clone();
+        match o2m.call(req.into()).await? {
+            O2mResponse::DeadlockNotifications(receiver) => {
+                let stream = BroadcastStream::new(receiver).map(|result| match result {
+                    Ok(event) => Ok(event.into()),
+                    Err(e) => Err(Status::internal(format!("Notification stream error: {}", e))),
+                });
+                Ok(Response::new(Box::pin(stream)))
+            }
+            _ => Err(Status::internal("Internal traceability API error")),
+        }
+    }
+}
+
+// ========== Protocol Buffer Type Conversions ==========
+// Fundamental type conversions for serialization/deserialization
+
+/// Converts Protocol Buffer Resource to internal Resource type.
+impl From<proto::primitives::Resource> for Resource {
+    fn from(req: proto::primitives::Resource) -> Self {
+        match req.resource {
+            Some(proto::primitives::resource::Resource::Fd(fd)) => Resource::Fd(fd.into()),
+            Some(proto::primitives::resource::Resource::Process(process)) => {
+                Resource::Process(process.into())
+            }
+            None => Resource::None,
+        }
+    }
+}
+
+/// Converts internal Resource to Protocol Buffer Resource type.
 impl From<Resource> for proto::primitives::Resource {
     fn from(resource: Resource) -> Self {
         match resource {
@@ -820,6 +1453,8 @@ impl From<proto::primitives::Fd> for Fd {
         match proto_fd.fd {
             Some(proto::primitives::fd::Fd::File(file)) => Fd::File(file.into()),
             Some(proto::primitives::fd::Fd::Stream(stream)) => Fd::Stream(stream.into()),
+            Some(proto::primitives::fd::Fd::UnixSocket(socket)) => Fd::Unix(socket.into()),
+            Some(proto::primitives::fd::Fd::Datagram(datagram)) => Fd::Datagram(datagram.into()),
             None => Fd::File(File { path: String::new() }),
         }
     }
@@ -834,6 +1469,12 @@ impl From<Fd> for proto::primitives::Fd {
             Fd::Stream(stream) => {
                 proto::primitives::Fd { fd: Some(proto::primitives::fd::Fd::Stream(stream.into())) }
             }
+            Fd::Unix(socket) => proto::primitives::Fd {
+                fd: Some(proto::primitives::fd::Fd::UnixSocket(socket.into())),
+            },
+            Fd::Datagram(datagram) => proto::primitives::Fd {
+                fd: Some(proto::primitives::fd::Fd::Datagram(datagram.into())),
+            },
         }
     }
 }
@@ -865,12 +1506,45 @@ impl From<Stream> for proto::primitives::Stream {
     }
 }
 
+impl From<proto::primitives::Datagram> for Datagram {
+    fn from(proto_datagram: proto::primitives::Datagram) -> Self {
+        // An empty peer socket is the one of an enrolled socket
+        let peer_socket = proto_datagram.peer_socket;
+        Datagram {
+            local_socket: proto_datagram.local_socket,
+            peer_socket: (!peer_socket.is_empty()).then(|| peer_socket.into_boxed_str()),
+        }
+    }
+}
+
+impl From<Datagram> for proto::primitives::Datagram {
+    fn from(datagram: Datagram) -> Self {
+        proto::primitives::Datagram {
+            local_socket: datagram.local_socket,
+            peer_socket: datagram.peer_socket.map(String::from).unwrap_or_default(),
+        }
+    }
+}
+
+impl From<proto::primitives::UnixSocket> for UnixSocket {
+    fn from(proto_socket: proto::primitives::UnixSocket) -> Self {
+        UnixSocket { path: proto_socket.path, peer_pid: proto_socket.peer_pid }
+    }
+}
+
+impl From<UnixSocket> for proto::primitives::UnixSocket {
+    fn from(socket: UnixSocket) -> Self {
+        proto::primitives::UnixSocket { path: socket.path, peer_pid: socket.peer_pid }
+    }
+}
+
 impl From<proto::primitives::Process> for Process {
     fn from(proto_process: proto::primitives::Process) -> Self {
         Process {
             pid: proto_process.pid,
             starttime: proto_process.starttime,
             exe_path: proto_process.exe_path,
+            session: proto_process.session.map(String::into_boxed_str),
         }
     }
 }
@@ -881,6 +1555,19 @@ impl From<Process> for proto::primitives::Process {
             pid: process.pid,
             starttime: process.starttime,
             exe_path: process.exe_path,
+            session: process.session.map(String::from),
+        }
+    }
+}
+
+/// Converts Protocol Buffer OpenMode to internal OpenMode type.
+impl From<proto::primitives::OpenMode> for OpenMode {
+    fn from(mode: proto::primitives::OpenMode) -> Self {
+        OpenMode {
+            write: mode.write,
+            append: mode.append,
+            truncate: mode.truncate,
+            create_new: mode.create_new,
         }
     }
 }
@@ -993,13 +1680,14 @@ impl From<Policy> for proto::primitives::Policy {
             integrity: policy.get_integrity(),
             deleted: policy.is_deleted(),
             consent: policy.get_consent(),
+            declassification: policy.allows_declassification(),
         }
     }
 }
 
 impl From<proto::primitives::Policy> for Policy {
     fn from(proto_policy: proto::primitives::Policy) -> Self {
-        Policy::new(
+        let mut policy = Policy::new(
             match proto_policy.confidentiality {
                 x if x == proto::primitives::Confidentiality::Secret as i32 => {
                     ConfidentialityPolicy::Secret
@@ -1009,7 +1697,9 @@ impl From<proto::primitives::Policy> for Policy {
             proto_policy.integrity,
             proto_policy.deleted.into(),
             proto_policy.consent,
-        )
+        );
+        policy.with_declassification(proto_policy.declassification);
+        policy
     }
 }
 
@@ -1055,6 +1745,368 @@ impl From<(String, HashSet<Resource>)> for proto::primitives::References {
     }
 }
 
+// ========== P2M Protocol Buffer Conversions ==========
+
+impl From<proto::messages::LocalCt> for P2mRequest {
+    fn from(req: proto::messages::LocalCt) -> Self {
+        P2mRequest::LocalEnroll {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            path: req.path,
+            mode: req.mode.map(|mode| mode.into()).unwrap_or_default(),
+        }
+    }
+}
+
+impl From<proto::messages::RemoteCt> for P2mRequest {
+    fn from(req: proto::messages::RemoteCt) -> Self {
+        P2mRequest::RemoteEnroll {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            local_socket: req.local_socket,
+            peer_socket: req.peer_socket,
+        }
+    }
+}
+
+impl From<proto::messages::UnixCt> for P2mRequest {
+    fn from(req: proto::messages::UnixCt) -> Self {
+        P2mRequest::UnixEnroll {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            path: req.path,
+            peer_pid: req.peer_pid,
+        }
+    }
+}
+
+impl From<proto::messages::IoInfo> for P2mRequest {
+    fn from(req: proto::messages::IoInfo) -> Self {
+        P2mRequest::IoRequest {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            output: req.flow == proto::primitives::Flow::Output as i32,
+        }
+    }
+}
+
+impl From<proto::messages::DatagramCt> for P2mRequest {
+    fn from(req: proto::messages::DatagramCt) -> Self {
+        P2mRequest::DatagramEnroll {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            local_socket: req.local_socket,
+        }
+    }
+}
+
+impl From<proto::messages::DatagramIoInfo> for P2mRequest {
+    fn from(req: proto::messages::DatagramIoInfo) -> Self {
+        P2mRequest::DatagramIoRequest {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            peer_socket: req.peer_socket,
+            output: req.flow == proto::primitives::Flow::Output as i32,
+        }
+    }
+}
+
+impl From<proto::messages::IoBatch> for P2mRequest {
+    fn from(req: proto::messages::IoBatch) -> Self {
+        P2mRequest::IoRequestBatch {
+            pid: req.process_id,
+            requests: req
+                .requests
+                .into_iter()
+                .map(|item| {
+                    (item.file_descriptor, item.flow == proto::primitives::Flow::Output as i32)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<proto::messages::IoResult> for P2mRequest {
+    fn from(req: proto::messages::IoResult) -> Self {
+        P2mRequest::IoReport {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            grant_id: req.grant_id.parse::<u128>().unwrap_or_default(),
+            output: req.flow == proto::primitives::Flow::Output as i32,
+            result: req.result,
+            bytes: req.bytes,
+            offset: req.offset,
+        }
+    }
+}
+
+impl From<proto::messages::TruncateCt> for P2mRequest {
+    fn from(req: proto::messages::TruncateCt) -> Self {
+        P2mRequest::Truncate { pid: req.process_id, fd: req.file_descriptor }
+    }
+}
+
+impl From<proto::messages::DeclareFlowCt> for P2mRequest {
+    fn from(req: proto::messages::DeclareFlowCt) -> Self {
+        P2mRequest::DeclareFlow {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            sources: req.sources,
+        }
+    }
+}
+
+impl From<proto::messages::ClearFlowCt> for P2mRequest {
+    fn from(req: proto::messages::ClearFlowCt) -> Self {
+        P2mRequest::ClearFlow { pid: req.process_id, fd: req.file_descriptor }
+    }
+}
+
+impl From<proto::messages::OpenSessionCt> for P2mRequest {
+    fn from(req: proto::messages::OpenSessionCt) -> Self {
+        P2mRequest::OpenSession { pid: req.process_id, name: req.name }
+    }
+}
+
+impl From<proto::messages::EndSessionCt> for P2mRequest {
+    fn from(req: proto::messages::EndSessionCt) -> Self {
+        P2mRequest::EndSession { pid: req.process_id }
+    }
+}
+
+impl From<proto::messages::CloseCt> for P2mRequest {
+    fn from(req: proto::messages::CloseCt) -> Self {
+        P2mRequest::Close { pid: req.process_id, fd: req.file_descriptor }
+    }
+}
+
+impl From<proto::messages::DupCt> for P2mRequest {
+    fn from(req: proto::messages::DupCt) -> Self {
+        P2mRequest::Dup {
+            pid: req.process_id,
+            fd: req.file_descriptor,
+            new_fd: req.new_file_descriptor,
+        }
+    }
+}
+
+/// Converts the outcome of a P2M request into a reply on a P2M stream.
+impl From<Result<P2mResponse, Status>> for proto::messages::p2m_stream_response::Response {
+    fn from(result: Result<P2mResponse, Status>) -> Self {
+        use proto::messages::p2m_stream_response::Response;
+        match result {
+            Ok(P2mResponse::Ack) => Response::Ack(proto::messages::Ack {}),
+            Ok(P2mResponse::Grant(id)) => {
+                Response::Grant(proto::messages::Grant { id: id.to_string() })
+            }
+            Ok(P2mResponse::Grants(results)) => Response::Grants(results.into()),
+            Err(status) => Response::Error(status.into()),
+        }
+    }
+}
+
+/// Converts a refused P2M request into its error report.
+impl From<Status> for proto::messages::P2mStreamError {
+    fn from(status: Status) -> Self {
+        proto::messages::P2mStreamError {
+            code: status.code() as i32,
+            message: status.message().to_string(),
+            details: proto::messages::ErrorDetails::decode(status.details()).ok(),
+        }
+    }
+}
+
+/// Converts the per-operation outcomes of a batch into a Protocol Buffer Grants response.
+impl From<Vec<Result<u128, TraceabilityError>>> for proto::messages::Grants {
+    fn from(results: Vec<Result<u128, TraceabilityError>>) -> Self {
+        use proto::messages::io_batch_result::Result as BatchResult;
+        proto::messages::Grants {
+            results: results
+                .into_iter()
+                .map(|result| proto::messages::IoBatchResult {
+                    result: Some(match result {
+                        Ok(id) => BatchResult::Grant(proto::messages::Grant { id: id.to_string() }),
+                        Err(e) => BatchResult::Error(Status::from(e).into()),
+                    }),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts a traceability error into its structured details.
+impl From<TraceabilityError> for proto::messages::ErrorDetails {
+    fn from(error: TraceabilityError) -> Self {
+        use proto::messages::ErrorReason;
+        let mut details = proto::messages::ErrorDetails::default();
+        let reason = match error {
+            TraceabilityError::InternalTrace2eError => ErrorReason::InternalTrace2eError,
+            TraceabilityError::InvalidRequest => ErrorReason::InvalidRequest,
+            TraceabilityError::UndeclaredResource(pid, fd) => {
+                details.process_id = pid;
+                details.file_descriptor = fd;
+                ErrorReason::UndeclaredResource
+            }
+            TraceabilityError::InvalidProcess(pid) => {
+                details.process_id = pid;
+                ErrorReason::InvalidProcess
+            }
+            TraceabilityError::InvalidStream(local_socket, peer_socket) => {
+                details.arguments = vec![local_socket, peer_socket];
+                ErrorReason::InvalidStream
+            }
+            TraceabilityError::InvalidUnixSocket(path, peer_pid) => {
+                details.arguments = vec![path];
+                details.process_id = peer_pid;
+                ErrorReason::InvalidUnixSocket
+            }
+            TraceabilityError::InvalidDatagram(socket) => {
+                details.arguments = vec![socket];
+                ErrorReason::InvalidDatagram
+            }
+            TraceabilityError::NotFoundFlow(id) => {
+                details.grant_id = id.to_string();
+                ErrorReason::NotFoundFlow
+            }
+            TraceabilityError::ExpiredGrant(id) => {
+                details.grant_id = id.to_string();
+                ErrorReason::ExpiredGrant
+            }
+            TraceabilityError::GrantProcessMismatch(id, pid) => {
+                details.grant_id = id.to_string();
+                details.process_id = pid;
+                ErrorReason::GrantProcessMismatch
+            }
+            TraceabilityError::GrantFdMismatch(id, fd) => {
+                details.grant_id = id.to_string();
+                details.file_descriptor = fd;
+                ErrorReason::GrantFdMismatch
+            }
+            TraceabilityError::GrantFlowMismatch(id) => {
+                details.grant_id = id.to_string();
+                ErrorReason::GrantFlowMismatch
+            }
+            TraceabilityError::SessionAlreadyOpen(pid) => {
+                details.process_id = pid;
+                ErrorReason::SessionAlreadyOpen
+            }
+            TraceabilityError::NoOpenSession(pid) => {
+                details.process_id = pid;
+                ErrorReason::NoOpenSession
+            }
+            TraceabilityError::SessionBusy(pid) => {
+                details.process_id = pid;
+                ErrorReason::SessionBusy
+            }
+            TraceabilityError::UnavailableDestination(destination) => {
+                details.resources = vec![destination.into()];
+                ErrorReason::UnavailableDestination
+            }
+            TraceabilityError::UnavailableSource(source) => {
+                details.resources = vec![source.into()];
+                ErrorReason::UnavailableSource
+            }
+            TraceabilityError::UnavailableSourceAndDestination(source, destination) => {
+                details.resources = vec![source.into(), destination.into()];
+                ErrorReason::UnavailableSourceAndDestination
+            }
+            TraceabilityError::ReachedMaxRetriesWaitingQueue => {
+                ErrorReason::ReachedMaxRetriesWaitingQueue
+            }
+            TraceabilityError::WaitingQueueTimeout => ErrorReason::WaitingQueueTimeout,
+            TraceabilityError::DeadlockDetected(source, destination) => {
+                details.resources = vec![source.into(), destination.into()];
+                ErrorReason::DeadlockDetected
+            }
+            TraceabilityError::DirectPolicyViolation => ErrorReason::DirectPolicyViolation,
+            TraceabilityError::DestinationPolicyNotFound => ErrorReason::DestinationPolicyNotFound,
+            TraceabilityError::NotLocalResource => ErrorReason::NotLocalResource,
+            TraceabilityError::TransportFailedToContactRemote(remote) => {
+                details.arguments = vec![remote];
+                ErrorReason::TransportFailedToContactRemote
+            }
+            TraceabilityError::TransportFailedToEvaluateRemote => {
+                ErrorReason::TransportFailedToEvaluateRemote
+            }
+            TraceabilityError::ConsentRequestTimeout => ErrorReason::ConsentRequestTimeout,
+            TraceabilityError::InvalidResourceFormat(format) => {
+                details.arguments = vec![format];
+                ErrorReason::InvalidResourceFormat
+            }
+        };
+        details.set_reason(reason);
+        details
+    }
+}
+
+/// Converts structured error details back into the traceability error they describe.
+///
+/// Fails if the reason is unspecified or the fields it requires are missing.
+impl TryFrom<proto::messages::ErrorDetails> for TraceabilityError {
+    type Error = ();
+
+    fn try_from(details: proto::messages::ErrorDetails) -> Result<Self, Self::Error> {
+        use proto::messages::ErrorReason;
+        let (pid, fd) = (details.process_id, details.file_descriptor);
+        let grant_id = || details.grant_id.parse::<u128>().map_err(|_| ());
+        let mut resources = details.resources.iter().cloned().map(Resource::from);
+        let mut resource = || resources.next().ok_or(());
+        let mut arguments = details.arguments.iter().cloned();
+        let mut argument = || arguments.next().ok_or(());
+        Ok(match details.reason() {
+            ErrorReason::Unspecified => return Err(()),
+            ErrorReason::InternalTrace2eError => TraceabilityError::InternalTrace2eError,
+            ErrorReason::InvalidRequest => TraceabilityError::InvalidRequest,
+            ErrorReason::UndeclaredResource => TraceabilityError::UndeclaredResource(pid, fd),
+            ErrorReason::InvalidProcess => TraceabilityError::InvalidProcess(pid),
+            ErrorReason::InvalidStream => {
+                TraceabilityError::InvalidStream(argument()?, argument()?)
+            }
+            ErrorReason::InvalidUnixSocket => {
+                TraceabilityError::InvalidUnixSocket(argument()?, pid)
+            }
+            ErrorReason::InvalidDatagram => TraceabilityError::InvalidDatagram(argument()?),
+            ErrorReason::NotFoundFlow => TraceabilityError::NotFoundFlow(grant_id()?),
+            ErrorReason::ExpiredGrant => TraceabilityError::ExpiredGrant(grant_id()?),
+            ErrorReason::GrantProcessMismatch => {
+                TraceabilityError::GrantProcessMismatch(grant_id()?, pid)
+            }
+            ErrorReason::GrantFdMismatch => TraceabilityError::GrantFdMismatch(grant_id()?, fd),
+            ErrorReason::GrantFlowMismatch => TraceabilityError::GrantFlowMismatch(grant_id()?),
+            ErrorReason::SessionAlreadyOpen => TraceabilityError::SessionAlreadyOpen(pid),
+            ErrorReason::NoOpenSession => TraceabilityError::NoOpenSession(pid),
+            ErrorReason::SessionBusy => TraceabilityError::SessionBusy(pid),
+            ErrorReason::UnavailableDestination => {
+                TraceabilityError::UnavailableDestination(resource()?)
+            }
+            ErrorReason::UnavailableSource => TraceabilityError::UnavailableSource(resource()?),
+            ErrorReason::UnavailableSourceAndDestination => {
+                TraceabilityError::UnavailableSourceAndDestination(resource()?, resource()?)
+            }
+            ErrorReason::ReachedMaxRetriesWaitingQueue => {
+                TraceabilityError::ReachedMaxRetriesWaitingQueue
+            }
+            ErrorReason::WaitingQueueTimeout => TraceabilityError::WaitingQueueTimeout,
+            ErrorReason::DeadlockDetected => {
+                TraceabilityError::DeadlockDetected(resource()?, resource()?)
+            }
+            ErrorReason::DirectPolicyViolation => TraceabilityError::DirectPolicyViolation,
+            ErrorReason::DestinationPolicyNotFound => TraceabilityError::DestinationPolicyNotFound,
+            ErrorReason::NotLocalResource => TraceabilityError::NotLocalResource,
+            ErrorReason::TransportFailedToContactRemote => {
+                TraceabilityError::TransportFailedToContactRemote(argument()?)
+            }
+            ErrorReason::TransportFailedToEvaluateRemote => {
+                TraceabilityError::TransportFailedToEvaluateRemote
+            }
+            ErrorReason::ConsentRequestTimeout => TraceabilityError::ConsentRequestTimeout,
+            ErrorReason::InvalidResourceFormat => {
+                TraceabilityError::InvalidResourceFormat(argument()?)
+            }
+        })
+    }
+}
+
 // ========== M2M Protocol Buffer Conversions ==========
 
 /// Converts Protocol Buffer GetDestinationPolicy request to internal M2M request.
@@ -1064,6 +2116,13 @@ impl From<proto::messages::GetDestinationPolicy> for M2mRequest {
     }
 }
 
+/// Converts Protocol Buffer GetDestinationPolicies request to internal M2M request.
+impl From<proto::messages::GetDestinationPolicies> for M2mRequest {
+    fn from(req: proto::messages::GetDestinationPolicies) -> Self {
+        M2mRequest::GetDestinationPolicies(req.destinations.into_iter().map(|d| d.into()).collect())
+    }
+}
+
 /// Converts Protocol Buffer UpdateProvenance request to internal M2M request.
 impl From<proto::messages::UpdateProvenance> for M2mRequest {
     fn from(req: proto::messages::UpdateProvenance) -> Self {
@@ -1085,6 +2144,13 @@ impl From<proto::messages::UpdateProvenance> for M2mRequest {
     }
 }
 
+/// Converts Protocol Buffer ReleaseFlow request to internal M2M request.
+impl From<proto::messages::ReleaseFlow> for M2mRequest {
+    fn from(req: proto::messages::ReleaseFlow) -> Self {
+        M2mRequest::ReleaseFlow(req.destination.map(|d| d.into()).unwrap_or_default())
+    }
+}
+
 /// Converts Protocol Buffer BroadcastDeletionRequest to internal M2M request.
 impl From<proto::messages::BroadcastDeletionRequest> for M2mRequest {
     fn from(req: proto::messages::BroadcastDeletionRequest) -> Self {
@@ -1114,6 +2180,21 @@ impl From<Policy> for proto::messages::DestinationPolicy {
     }
 }
 
+/// Converts internal M2M DestinationPolicies response to Protocol Buffer response.
+impl From<HashMap<LocalizedResource, Policy>> for proto::messages::DestinationPolicies {
+    fn from(policies: HashMap<LocalizedResource, Policy>) -> Self {
+        proto::messages::DestinationPolicies {
+            policies: policies
+                .into_iter()
+                .map(|(resource, policy)| proto::primitives::MappedLocalizedPolicy {
+                    resource: Some(resource.into()),
+                    policy: Some(policy.into()),
+                })
+                .collect(),
+        }
+    }
+}
+
 // ========== O2M Protocol Buffer Conversions ==========
 
 /// Converts Protocol Buffer GetPoliciesRequest to internal O2M request.
@@ -1158,6 +2239,16 @@ impl From<proto::messages::SetIntegrityRequest> for O2mRequest {
     }
 }
 
+/// Converts Protocol Buffer SetDeclassificationRequest to internal O2M request.
+impl From<proto::messages::SetDeclassificationRequest> for O2mRequest {
+    fn from(req: proto::messages::SetDeclassificationRequest) -> Self {
+        O2mRequest::SetDeclassification {
+            resource: req.resource.map(|r| r.into()).unwrap_or_default(),
+            declassification: req.declassification,
+        }
+    }
+}
+
 /// Converts Protocol Buffer SetDeletedRequest to internal O2M request.
 impl From<proto::messages::SetDeletedRequest> for O2mRequest {
     fn from(req: proto::messages::SetDeletedRequest) -> Self {
@@ -1193,8 +2284,170 @@ impl From<proto::messages::GetReferencesRequest> for O2mRequest {
     }
 }
 
+/// Converts Protocol Buffer WatchDeadlocksRequest to internal O2M request.
+impl From<proto::messages::WatchDeadlocksRequest> for O2mRequest {
+    fn from(_: proto::messages::WatchDeadlocksRequest) -> Self {
+        O2mRequest::WatchDeadlocks
+    }
+}
+
+/// Converts Protocol Buffer ListReservationsRequest to internal O2M request.
+impl From<proto::messages::ListReservationsRequest> for O2mRequest {
+    fn from(_: proto::messages::ListReservationsRequest) -> Self {
+        O2mRequest::ListReservations
+    }
+}
+
+/// Converts Protocol Buffer ListPendingGrantsRequest to internal O2M request.
+impl From<proto::messages::ListPendingGrantsRequest> for O2mRequest {
+    fn from(_: proto::messages::ListPendingGrantsRequest) -> Self {
+        O2mRequest::ListPendingGrants
+    }
+}
+
+/// Converts Protocol Buffer GetQueueDepthsRequest to internal O2M request.
+impl From<proto::messages::GetQueueDepthsRequest> for O2mRequest {
+    fn from(_: proto::messages::GetQueueDepthsRequest) -> Self {
+        O2mRequest::GetQueueDepths
+    }
+}
+
+/// Converts Protocol Buffer GetVolumesRequest to internal O2M request.
+impl From<proto::messages::GetVolumesRequest> for O2mRequest {
+    fn from(req: proto::messages::GetVolumesRequest) -> Self {
+        O2mRequest::GetVolumes(req.resources.into_iter().map(|r| r.into()).collect())
+    }
+}
+
+/// Converts Protocol Buffer GetLineageEventsRequest to internal O2M request.
+impl From<proto::messages::GetLineageEventsRequest> for O2mRequest {
+    fn from(req: proto::messages::GetLineageEventsRequest) -> Self {
+        O2mRequest::GetLineageEvents(req.resource.map(|r| r.into()).unwrap_or_default())
+    }
+}
+
+/// Converts Protocol Buffer ForceReleaseRequest to internal O2M request.
+impl From<proto::messages::ForceReleaseRequest> for O2mRequest {
+    fn from(req: proto::messages::ForceReleaseRequest) -> Self {
+        O2mRequest::ForceRelease(req.destination.map(|r| r.into()).unwrap_or_default())
+    }
+}
+
 // ========== O2M Response Conversions ==========
 
+/// Converts internal flow reservations to Protocol Buffer ListReservationsResponse.
+impl From<Vec<(Resource, Resource)>> for proto::messages::ListReservationsResponse {
+    fn from(reservations: Vec<(Resource, Resource)>) -> Self {
+        proto::messages::ListReservationsResponse {
+            reservations: reservations
+                .into_iter()
+                .map(|(source, destination)| proto::messages::Reservation {
+                    source: Some(source.into()),
+                    destination: Some(destination.into()),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts internal pending grants to Protocol Buffer ListPendingGrantsResponse.
+impl From<Vec<PendingGrant>> for proto::messages::ListPendingGrantsResponse {
+    fn from(grants: Vec<PendingGrant>) -> Self {
+        proto::messages::ListPendingGrantsResponse {
+            grants: grants
+                .into_iter()
+                .map(|grant| proto::messages::PendingGrant {
+                    grant_id: grant.grant_id.to_string(),
+                    process_id: grant.pid,
+                    source: Some(grant.source.into()),
+                    destination: Some(grant.destination.into()),
+                    expires_in_ms: grant.expires_in.map(|d| d.as_millis() as u64),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts internal waiting queue depths to Protocol Buffer GetQueueDepthsResponse.
+impl From<HashMap<Resource, usize>> for proto::messages::GetQueueDepthsResponse {
+    fn from(depths: HashMap<Resource, usize>) -> Self {
+        proto::messages::GetQueueDepthsResponse {
+            depths: depths
+                .into_iter()
+                .map(|(resource, depth)| proto::messages::QueueDepth {
+                    resource: Some(resource.into()),
+                    depth: depth as u64,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts internal volume counters to Protocol Buffer GetVolumesResponse.
+impl From<HashMap<Resource, ResourceVolume>> for proto::messages::GetVolumesResponse {
+    fn from(volumes: HashMap<Resource, ResourceVolume>) -> Self {
+        proto::messages::GetVolumesResponse {
+            volumes: volumes
+                .into_iter()
+                .map(|(resource, volume)| proto::messages::ResourceVolume {
+                    resource: Some(resource.into()),
+                    bytes_in: volume.bytes_in,
+                    bytes_out: volume.bytes_out,
+                    transfers_in: volume.transfers_in,
+                    transfers_out: volume.transfers_out,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Converts internal lineage events to Protocol Buffer GetLineageEventsResponse.
+impl From<Vec<LineageEvent>> for proto::messages::GetLineageEventsResponse {
+    fn from(events: Vec<LineageEvent>) -> Self {
+        proto::messages::GetLineageEventsResponse {
+            events: events.into_iter().map(|event| event.into()).collect(),
+        }
+    }
+}
+
+/// Converts internal lineage event to Protocol Buffer LineageEvent.
+impl From<LineageEvent> for proto::messages::LineageEvent {
+    fn from(event: LineageEvent) -> Self {
+        match event {
+            LineageEvent::Overwrite { process, timestamp, previous } => {
+                // Group LocalizedResources by node_id
+                let mut grouped: HashMap<String, HashSet<Resource>> = HashMap::default();
+                for lr in previous {
+                    grouped.entry(lr.node_id().clone()).or_default().insert(lr.resource().clone());
+                }
+                proto::messages::LineageEvent {
+                    event: Some(proto::messages::lineage_event::Event::Overwrite(
+                        proto::messages::OverwriteEvent {
+                            process: Some(process.into()),
+                            timestamp_ms: timestamp
+                                .duration_since(SystemTime::UNIX_EPOCH)
+                                .map(|d| d.as_millis() as u64)
+                                .unwrap_or_default(),
+                            previous: grouped.into_iter().map(|r| r.into()).collect(),
+                        },
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Converts internal deadlock detection event to Protocol Buffer DeadlockNotification.
+impl From<DeadlockEvent> for proto::messages::DeadlockNotification {
+    fn from(event: DeadlockEvent) -> Self {
+        proto::messages::DeadlockNotification {
+            source: Some(event.source.into()),
+            destination: Some(event.destination.into()),
+            cycle: event.cycle.into_iter().map(|r| r.into()).collect(),
+        }
+    }
+}
+
 /// Converts internal resource-policy map to Protocol Buffer GetPoliciesResponse.
 impl From<HashMap<Resource, Policy>> for proto::messages::GetPoliciesResponse {
     fn from(policies: HashMap<Resource, Policy>) -> Self {
@@ -59,7 +59,16 @@ pub mod nop;
 pub fn eval_remote_ip(req: M2mRequest) -> Result<String, TraceabilityError> {
     match req {
         M2mRequest::GetDestinationPolicy(destination)
-        | M2mRequest::UpdateProvenance { destination, .. } => Ok(destination.node_id().clone()),
+        | M2mRequest::UpdateProvenance { destination, .. }
+        | M2mRequest::ReleaseFlow(destination) => Ok(destination.node_id().clone()),
+        // The destinations of a batch must be hosted by a single node
+        M2mRequest::GetDestinationPolicies(destinations) => {
+            let mut nodes = destinations.iter().map(|destination| destination.node_id());
+            match nodes.next() {
+                Some(node) if nodes.all(|other| other == node) => Ok(node.clone()),
+                _ => Err(TraceabilityError::TransportFailedToEvaluateRemote),
+            }
+        }
         M2mRequest::BroadcastDeletion(_) => Ok("*".to_string()),
         _ => Err(TraceabilityError::TransportFailedToEvaluateRemote),
     }
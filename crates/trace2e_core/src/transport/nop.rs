@@ -12,6 +12,7 @@
 //! - **Destination Compliance**: Returns default policy (public, integrity 0, not deleted, consent given)
 //! - **Source Compliance**: Returns empty policy map (no source policies)
 //! - **Update Provenance**: Acknowledges the request without action
+//! - **Release Flow**: Acknowledges the request without action
 //!
 //! ## Use Cases
 //!
@@ -41,6 +42,7 @@ use crate::traceability::{
 /// - **GetDestinationCompliance**: Always returns a default policy
 /// - **GetSourceCompliance**: Always returns an empty policy map
 /// - **UpdateProvenance**: Always acknowledges without action
+/// - **ReleaseFlow**: Always acknowledges without action
 ///
 /// This transport is useful for single-node deployments or testing
 /// scenarios where distributed functionality is not required.
@@ -68,18 +70,29 @@ impl Service<M2mRequest> for M2mNop {
     ///
     /// - **GetDestinationPolicy**: Returns a default policy with public
     ///   confidentiality, zero integrity, not deleted, and consent given
+    /// - **GetDestinationPolicies**: Returns the default policy for each destination
     /// - **CheckSourceCompliance**: Returns an empty policy map indicating
     ///   no source policies are available
     /// - **UpdateProvenance**: Acknowledges the request without performing
     ///   any provenance updates
+    /// - **ReleaseFlow**: Acknowledges the request without releasing anything
     fn call(&mut self, request: M2mRequest) -> Self::Future {
         Box::pin(async move {
             Ok(match request {
                 M2mRequest::GetDestinationPolicy { .. } => {
                     M2mResponse::DestinationPolicy(Policy::default())
                 }
+                M2mRequest::GetDestinationPolicies(destinations) => {
+                    M2mResponse::DestinationPolicies(
+                        destinations
+                            .into_iter()
+                            .map(|destination| (destination, Policy::default()))
+                            .collect(),
+                    )
+                }
                 M2mRequest::CheckSourceCompliance { .. }
                 | M2mRequest::UpdateProvenance { .. }
+                | M2mRequest::ReleaseFlow(_)
                 | M2mRequest::BroadcastDeletion(_) => M2mResponse::Ack,
             })
         })
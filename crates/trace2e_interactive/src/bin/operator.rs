@@ -26,6 +26,29 @@
 //   --destination "node1" \
 //   --grant
 // ```
+//
+// Watch deadlocks detected by the sequencer:
+// ```bash
+// trace2e-operator watch-deadlocks
+// ```
+//
+// Inspect the sequencer state and release a stuck reservation:
+// ```bash
+// trace2e-operator list-reservations
+// trace2e-operator list-pending-grants
+// trace2e-operator get-queue-depths
+// trace2e-operator force-release "file:///path/to/file"
+// ```
+//
+// Get the volume of data read from and written to a resource:
+// ```bash
+// trace2e-operator get-volumes "file:///path/to/file"
+// ```
+//
+// List the overwrites recorded in the lineage of a resource:
+// ```bash
+// trace2e-operator get-lineage-events "file:///path/to/file"
+// ```
 
 use anyhow::{Result, anyhow};
 use clap::{Parser, Subcommand};
@@ -35,13 +58,21 @@ use trace2e_client::primitives::References;
 use trace2e_client::{o2m, primitives};
 use trace2e_core::traceability::infrastructure::naming;
 use trace2e_core::traceability::services::consent::Destination;
-use trace2e_core::transport::grpc::proto::messages::ConsentNotification;
+use trace2e_core::transport::grpc::proto::messages::{
+    ConsentNotification, DeadlockNotification, LineageEvent, OverwriteEvent, PendingGrant,
+    QueueDepth, Reservation, ResourceVolume, lineage_event,
+};
 
 /// Parse resource string into naming::Resource
 fn parse_resource(s: &str) -> Result<naming::Resource> {
     naming::Resource::try_from(s).map_err(|e| anyhow!("Failed to parse resource: {}", e))
 }
 
+/// Display an optional protobuf resource
+fn display_resource(resource: Option<primitives::Resource>) -> String {
+    naming::Resource::from(resource.unwrap_or_default()).to_string()
+}
+
 #[derive(Parser)]
 #[command(name = "trace2e-operator")]
 #[command(about = "Operator tool for trace2e middleware compliance and provenance management")]
@@ -94,6 +125,20 @@ enum Commands {
         level: u32,
     },
 
+    /// Allow or forbid dropping the taint of a resource from the data derived from it
+    SetDeclassification {
+        /// Target resource
+        resource: String,
+
+        /// Allow declassification (if not present, forbids declassification)
+        #[arg(long, conflicts_with = "forbid")]
+        allow: bool,
+
+        /// Forbid declassification
+        #[arg(long, conflicts_with = "allow")]
+        forbid: bool,
+    },
+
     /// Mark a resource as deleted
     SetDeleted {
         /// Resource to mark as deleted
@@ -136,6 +181,37 @@ enum Commands {
         /// Resource to query
         resource: String,
     },
+
+    /// Watch deadlocks detected in the sequencer waiting queue (streams notifications)
+    WatchDeadlocks,
+
+    /// List the active flow reservations
+    ListReservations,
+
+    /// List the grants pending a report
+    ListPendingGrants,
+
+    /// Get the number of requests waiting on each resource
+    GetQueueDepths,
+
+    /// Get the volume of data read from and written to resources
+    GetVolumes {
+        /// Resource URLs to query (file:///path or stream://local::peer)
+        #[arg(required = true)]
+        resources: Vec<String>,
+    },
+
+    /// Get the events recorded in the lineage of a resource, such as overwrites
+    GetLineageEvents {
+        /// Resource to query
+        resource: String,
+    },
+
+    /// Forcibly release the reservation held on a destination
+    ForceRelease {
+        /// Destination resource of the stuck flow
+        destination: String,
+    },
 }
 
 #[tokio::main]
@@ -174,6 +250,7 @@ async fn main() -> Result<()> {
                 integrity: integrity.unwrap_or(0),
                 deleted: false,
                 consent: false,
+                declassification: false,
             };
 
             match o2m::set_policy(res, policy) {
@@ -214,6 +291,19 @@ async fn main() -> Result<()> {
             }
         }
 
+        Commands::SetDeclassification { resource, allow, forbid } => {
+            let res = parse_resource(&resource)?;
+            let declassification = if forbid { false } else { allow };
+            match o2m::set_declassification(res, declassification) {
+                Ok(_) => {
+                    let state = if declassification { "ALLOWED" } else { "FORBIDDEN" };
+                    println!("✓ Declassification {} for {}", state, resource);
+                    Ok(())
+                }
+                Err(e) => Err(anyhow!("Failed to set declassification: {}", e)),
+            }
+        }
+
         Commands::SetDeleted { resource } => {
             let res = parse_resource(&resource)?;
 
@@ -305,5 +395,160 @@ async fn main() -> Result<()> {
                 Err(e) => Err(anyhow!("Failed to get references: {}", e)),
             }
         }
+
+        Commands::WatchDeadlocks => {
+            println!("Listening for deadlocks...");
+            println!("Press Ctrl+C to stop.\n");
+
+            match o2m::watch_deadlocks() {
+                Ok(mut stream) => {
+                    while let Some(notification) = stream.next().await {
+                        match notification {
+                            Ok(DeadlockNotification { source, destination, cycle }) => {
+                                let cycle: Vec<String> =
+                                    cycle.into_iter().map(|r| display_resource(Some(r))).collect();
+                                println!(
+                                    "[{:?}] Deadlock: aborted {} -> {}",
+                                    tokio::time::Instant::now(),
+                                    display_resource(source),
+                                    display_resource(destination)
+                                );
+                                println!("Cycle: {}", cycle.join(" -> "));
+                                println!();
+                            }
+                            Err(e) => {
+                                eprintln!("Stream error: {}", e);
+                                return Err(anyhow!("Deadlock stream error: {}", e));
+                            }
+                        }
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(anyhow!("Failed to watch deadlocks: {}", e)),
+            }
+        }
+
+        Commands::ListReservations => match o2m::list_reservations() {
+            Ok(reservations) => {
+                println!("Active reservations: {}", reservations.len());
+                for Reservation { source, destination } in reservations {
+                    println!("{} -> {}", display_resource(source), display_resource(destination));
+                }
+                Ok(())
+            }
+            Err(e) => Err(anyhow!("Failed to list reservations: {}", e)),
+        },
+
+        Commands::ListPendingGrants => match o2m::list_pending_grants() {
+            Ok(grants) => {
+                println!("Pending grants: {}", grants.len());
+                for PendingGrant { grant_id, process_id, source, destination, expires_in_ms } in
+                    grants
+                {
+                    let expiry = expires_in_ms
+                        .map(|ms| format!("expires in {ms} ms"))
+                        .unwrap_or_else(|| "no expiry".to_string());
+                    println!(
+                        "[{}] pid {}: {} -> {} ({})",
+                        grant_id,
+                        process_id,
+                        display_resource(source),
+                        display_resource(destination),
+                        expiry
+                    );
+                }
+                Ok(())
+            }
+            Err(e) => Err(anyhow!("Failed to list pending grants: {}", e)),
+        },
+
+        Commands::GetQueueDepths => match o2m::get_queue_depths() {
+            Ok(depths) => {
+                println!("Waiting queues: {}", depths.len());
+                for QueueDepth { resource, depth } in depths {
+                    println!("{}: {} waiting", display_resource(resource), depth);
+                }
+                Ok(())
+            }
+            Err(e) => Err(anyhow!("Failed to get queue depths: {}", e)),
+        },
+
+        Commands::GetVolumes { resources } => {
+            let parsed_resources: Result<Vec<_>> =
+                resources.iter().map(|r| parse_resource(r)).collect();
+
+            match o2m::get_volumes(parsed_resources?) {
+                Ok(volumes) => {
+                    for ResourceVolume {
+                        resource,
+                        bytes_in,
+                        bytes_out,
+                        transfers_in,
+                        transfers_out,
+                    } in volumes
+                    {
+                        println!(
+                            "{}: {} bytes in ({} transfers), {} bytes out ({} transfers)",
+                            display_resource(resource),
+                            bytes_in,
+                            transfers_in,
+                            bytes_out,
+                            transfers_out
+                        );
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(anyhow!("Failed to get volumes: {}", e)),
+            }
+        }
+
+        Commands::GetLineageEvents { resource } => {
+            let res = parse_resource(&resource)?;
+
+            match o2m::get_lineage_events(res) {
+                Ok(events) => {
+                    println!("Lineage events for {}:", resource);
+                    for LineageEvent { event } in events {
+                        if let Some(lineage_event::Event::Overwrite(OverwriteEvent {
+                            process,
+                            timestamp_ms,
+                            previous,
+                        })) = event
+                        {
+                            let previous: Vec<naming::LocalizedResource> = previous
+                                .iter()
+                                .flat_map(|References { node, resources }| {
+                                    resources.iter().map(|r| {
+                                        naming::LocalizedResource::new(
+                                            node.clone(),
+                                            r.clone().into(),
+                                        )
+                                    })
+                                })
+                                .collect();
+                            println!(
+                                "  [{}ms] overwritten by {}, discarding {}",
+                                timestamp_ms,
+                                display_resource(process),
+                                naming::DisplayableResource::from(previous.as_slice())
+                            );
+                        }
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(anyhow!("Failed to get lineage events: {}", e)),
+            }
+        }
+
+        Commands::ForceRelease { destination } => {
+            let dst = parse_resource(&destination)?;
+            match o2m::force_release(dst) {
+                Ok(_) => {
+                    println!("✓ Reservation released on {}", destination);
+                    Ok(())
+                }
+                Err(e) => Err(anyhow!("Failed to release reservation: {}", e)),
+            }
+        }
     }
 }
@@ -23,13 +23,13 @@ use std::io::{Seek, SeekFrom};
 use std::{collections::HashMap, net::SocketAddr};
 #[cfg(not(feature = "trace2e"))]
 use std::{
-    fs::OpenOptions,
+    fs::{File, OpenOptions},
     io::{Read, Write},
     net::{TcpListener, TcpStream},
 };
 #[cfg(feature = "trace2e")]
 use stde2e::{
-    fs::OpenOptions,
+    fs::{File, OpenOptions},
     io::{Read, Write},
     net::{TcpListener, TcpStream},
 };
@@ -211,8 +211,8 @@ impl TryFrom<String> for Instruction {
 /// Tracks opened file handles and network streams
 #[derive(Debug, Default)]
 pub struct Resources {
-    files: HashMap<String, std::fs::File>,
-    streams: HashMap<Resource, std::net::TcpStream>,
+    files: HashMap<String, File>,
+    streams: HashMap<Resource, TcpStream>,
     socket_cheats: HashMap<String, Resource>,
 }
 
@@ -1,10 +1,18 @@
-use clap::Parser;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
 use tonic::transport::Server;
 use tonic_reflection::server::Builder;
 use trace2e_core::{
-    traceability::init_middleware,
+    traceability::{
+        api::p2m::SessionPolicy,
+        infrastructure::naming::Resource,
+        init_middleware_with_sequencer,
+        services::sequencer::{QueuePolicy, SequencerService, WaitingQueueService},
+    },
     transport::grpc::{
-        DEFAULT_GRPC_PORT, M2mGrpc, M2mHandler, O2mHandler, P2mHandler,
+        DEFAULT_GRPC_PORT, DEFAULT_O2M_SOCKET, DEFAULT_P2M_SOCKET, M2mGrpc, M2mHandler, O2mHandler,
+        P2mHandler, o2m_uds_incoming, p2m_uds_incoming,
         proto::{
             MIDDLEWARE_DESCRIPTOR_SET, m2m_server::M2mServer, o2m_server::O2mServer,
             p2m_server::P2mServer,
@@ -34,6 +42,100 @@ struct Trace2eMiddlewareArgs {
     /// Disable resource validation for P2M requests
     #[arg(long, default_value_t = false)]
     disable_resource_validation: bool,
+
+    /// Evaluate every P2M request, instead of reusing the evaluation of the previous request
+    /// on the same descriptor while policies and provenance are unchanged
+    #[arg(long, default_value_t = false)]
+    disable_grant_cache: bool,
+
+    /// Lease duration of P2M grants in milliseconds, grants never expire if 0
+    #[arg(long, default_value_t = 0)]
+    grant_lease_ms: u64,
+
+    /// Period of the reclamation of abandoned grants in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    grant_reaper_interval_ms: u64,
+
+    /// Period of the polling of enrolled processes for termination in milliseconds
+    #[arg(long, default_value_t = 1000)]
+    process_watch_interval_ms: u64,
+
+    /// Drop the taint of the process sessions when they end, instead of merging it back, if the
+    /// policies of all its sources allow declassification
+    #[arg(long, default_value_t = false)]
+    drop_session_taint: bool,
+
+    /// Number of times a conflicting flow waits for the release of its resources before giving
+    /// up, conflicting flows are refused right away if 0
+    #[arg(long, default_value_t = 0)]
+    queue_max_retries: u32,
+
+    /// Order in which the flows waiting on a released resource are woken up
+    #[arg(long, value_enum, default_value_t = QueuePolicyArg::Fifo)]
+    queue_policy: QueuePolicyArg,
+
+    /// Maximum waiting time of a conflicting flow in milliseconds, unbounded if 0
+    #[arg(long, default_value_t = 0)]
+    queue_timeout_ms: u64,
+
+    /// Priority of a running process for the priority queue policy, as PID=PRIORITY
+    ///
+    /// The priority is bound to the process running with this pid at startup, a later process
+    /// reusing the pid does not inherit it.
+    #[arg(long, value_parser = parse_process_priority)]
+    process_priority: Vec<(i32, u32)>,
+
+    /// Path of the Unix domain socket serving local processes
+    #[arg(long, default_value = DEFAULT_P2M_SOCKET)]
+    p2m_socket: String,
+
+    /// Path of the Unix domain socket serving local operators, only accessible to the user
+    /// running the middleware
+    #[arg(long, default_value = DEFAULT_O2M_SOCKET)]
+    o2m_socket: String,
+
+    /// Also serve local processes over TCP, without verification of their identity
+    #[arg(long, default_value_t = false)]
+    p2m_tcp: bool,
+
+    /// Also serve operators over TCP, without authentication: any host reaching the port may
+    /// then change policies and consent decisions, inspect provenance, reservations and volumes,
+    /// and force the release of grants
+    #[arg(long, default_value_t = false)]
+    o2m_tcp: bool,
+}
+
+/// Order in which the flows waiting on a released resource are woken up
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum QueuePolicyArg {
+    /// In arrival order
+    Fifo,
+    /// Readers first
+    ReaderPreferring,
+    /// Writers first
+    WriterPreferring,
+    /// By decreasing process priority
+    Priority,
+}
+
+impl From<QueuePolicyArg> for QueuePolicy {
+    fn from(policy: QueuePolicyArg) -> Self {
+        match policy {
+            QueuePolicyArg::Fifo => QueuePolicy::Fifo,
+            QueuePolicyArg::ReaderPreferring => QueuePolicy::ReaderPreferring,
+            QueuePolicyArg::WriterPreferring => QueuePolicy::WriterPreferring,
+            QueuePolicyArg::Priority => QueuePolicy::Priority,
+        }
+    }
+}
+
+fn parse_process_priority(arg: &str) -> Result<(i32, u32), String> {
+    let (pid, priority) =
+        arg.split_once('=').ok_or_else(|| format!("expected PID=PRIORITY, got {arg}"))?;
+    Ok((
+        pid.parse().map_err(|e| format!("invalid pid {pid}: {e}"))?,
+        priority.parse().map_err(|e| format!("invalid priority {priority}: {e}"))?,
+    ))
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -45,18 +147,55 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let address = format!("[::]:{}", args.port).parse().unwrap();
 
-    let (m2m_service, p2m_service, o2m_service) = init_middleware(
+    let sequencer = args.process_priority.iter().fold(
+        WaitingQueueService::new(SequencerService::default(), Some(args.queue_max_retries))
+            .with_policy(args.queue_policy.into())
+            .with_timeout(
+                (args.queue_timeout_ms > 0).then(|| Duration::from_millis(args.queue_timeout_ms)),
+            ),
+        |sequencer, &(pid, priority)| {
+            sequencer.with_process_priority(Resource::new_process(pid), priority)
+        },
+    );
+    let (m2m_service, p2m_service, o2m_service) = init_middleware_with_sequencer(
         args.address.clone(),
-        None,
+        sequencer,
         args.consent_timeout,
         M2mGrpc::default(),
         !args.disable_resource_validation, // Enable validation unless disabled
     );
+    let p2m_service = p2m_service
+        .with_grant_lease(Some(Duration::from_millis(args.grant_lease_ms)))
+        .with_grant_cache(!args.disable_grant_cache)
+        .with_session_policy(if args.drop_session_taint {
+            SessionPolicy::Drop
+        } else {
+            SessionPolicy::Retain
+        });
+    // Reclaim grants that expired or whose process exited without reporting
+    p2m_service.spawn_grant_reaper(Duration::from_millis(args.grant_reaper_interval_ms.max(1)));
+    // Release the mappings, grants and provenance of exited processes
+    p2m_service.spawn_process_watcher(Duration::from_millis(args.process_watch_interval_ms.max(1)));
+
+    // Local processes are served over a Unix domain socket, where their identity is verified
+    let local_server = Server::builder()
+        .add_service(P2mServer::new(P2mHandler::new(p2m_service.clone())))
+        .serve_with_incoming(p2m_uds_incoming(&args.p2m_socket)?);
+    // Local operators are served over a distinct socket, which other users cannot connect to
+    let operator_server = Server::builder()
+        .add_service(O2mServer::new(O2mHandler::new(o2m_service.clone())))
+        .serve_with_incoming(o2m_uds_incoming(&args.o2m_socket)?);
 
-    let mut server_builder = Server::builder()
-        .add_service(P2mServer::new(P2mHandler::new(p2m_service)))
-        .add_service(M2mServer::new(M2mHandler::new(m2m_service)))
-        .add_service(O2mServer::new(O2mHandler::new(o2m_service)));
+    let mut server_builder =
+        Server::builder().add_service(M2mServer::new(M2mHandler::new(m2m_service)));
+
+    if args.o2m_tcp {
+        server_builder = server_builder.add_service(O2mServer::new(O2mHandler::new(o2m_service)));
+    }
+
+    if args.p2m_tcp {
+        server_builder = server_builder.add_service(P2mServer::new(P2mHandler::new(p2m_service)));
+    }
 
     if args.reflection {
         let reflection_service = Builder::configure()
@@ -65,7 +204,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         server_builder = server_builder.add_service(reflection_service);
     }
 
-    server_builder.serve(address).await?;
+    tokio::try_join!(local_server, operator_server, server_builder.serve(address))?;
 
     trace2e_core::trace2e_tracing::shutdown();
 
@@ -4,4 +4,5 @@ cargo build --release -p trace2e_middleware
 ./target/release/trace2e_middleware &
 TRACE2E_PID=$!
 cargo test -p stde2e
+cargo test -p tokioe2e
 kill ${TRACE2E_PID}
\ No newline at end of file